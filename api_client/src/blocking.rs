@@ -1,32 +1,64 @@
 use reqwest::blocking::Client;
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{error, model};
+use crate::{
+    encoding::Encoding,
+    error, model,
+    options::{configure_builder, ClientOptions},
+};
 
 #[derive(Clone)]
 pub struct ApiClient {
     http_client: Client,
     base_url: String,
+    resource_prefix: String,
 }
 
 impl ApiClient {
-    const API_RESOURCE_PREFIX: &str = "/api/v1";
+    const LIST_PAGE_SIZE: usize = 100;
 
     pub fn new(base_url: &str) -> Self {
-        let http_client = Client::new();
+        Self::with_options(base_url, &ClientOptions::default())
+            .expect("an HTTP client with default options")
+    }
+
+    pub fn with_options(base_url: &str, options: &ClientOptions) -> error::Result<Self> {
+        let http_client = configure_builder!(Client::builder(), options).build()?;
         // See to it that base_url does not end in /
-        Self {
+        Ok(Self {
             http_client,
             base_url: base_url.to_owned(),
-        }
+            resource_prefix: options.resource_prefix.clone(),
+        })
+    }
+
+    pub fn get_version(&self) -> error::Result<model::ServerVersion> {
+        self.request(format!("{}/version", self.base_url))
     }
 
     pub fn get_books(&self) -> error::Result<Vec<model::Book>> {
-        self.request_resource("/books")
+        self.request_every_page("/books")
+    }
+
+    pub fn get_book(&self, id: model::BookId) -> error::Result<model::Book> {
+        self.request_resource(&format!("/books/{id}"))
+    }
+
+    // Books that could not be found are reported in BookBatch::missing
+    pub fn get_books_by_ids(&self, ids: &[model::BookId]) -> error::Result<model::BookBatch> {
+        self.post_query("/books/batch-get", ids)
     }
 
     pub fn get_authors(&self) -> error::Result<Vec<model::Author>> {
-        self.request_resource("/authors")
+        self.request_every_page("/authors")
+    }
+
+    pub fn get_author(&self, id: model::AuthorId) -> error::Result<model::Author> {
+        self.request_resource(&format!("/authors/{id}"))
+    }
+
+    pub fn get_authors_with_book_counts(&self) -> error::Result<Vec<model::Author>> {
+        self.request_every_page("/authors?with_counts=true")
     }
 
     pub fn get_readers(&self) -> error::Result<Vec<model::Reader>> {
@@ -45,7 +77,10 @@ impl ApiClient {
         &self,
         book_id: model::BookId,
     ) -> error::Result<Option<model::Author>> {
-        self.request_resource(&format!("/books/{book_id}/author"))
+        match self.request_resource(&format!("/books/{book_id}/author")) {
+            Err(error) if error.is_not_found() => Ok(None),
+            author => author,
+        }
     }
 
     pub fn get_books_by_author(
@@ -55,7 +90,10 @@ impl ApiClient {
         self.request_resource(&format!("/authors/{author_id}/books"))
     }
 
-    pub fn get_books_read(&self, reader_id: model::ReaderId) -> error::Result<Vec<model::Book>> {
+    pub fn get_books_read(
+        &self,
+        reader_id: model::ReaderId,
+    ) -> error::Result<Vec<model::ReadBook>> {
         self.request_resource(&format!("/readers/{reader_id}/books"))
     }
 
@@ -71,35 +109,77 @@ impl ApiClient {
         self.post_resource("/books", info)
     }
 
+    pub fn add_book_with_author(
+        &self,
+        info: model::BookWithNewAuthor,
+    ) -> error::Result<model::CreatedBook> {
+        self.post_query("/books", info)
+    }
+
     pub fn add_reader(&self, info: model::ReaderInfo) -> error::Result<()> {
         self.post_resource("/readers", info)
     }
 
-    pub fn add_keyword_to_book(&self, id: model::BookId, keyword: String) -> error::Result<()> {
-        Ok(self.post_resource(&format!("/books/{id}/keywords"), keyword)?)
+    pub fn add_keyword_to_book(
+        &self,
+        id: model::BookId,
+        keyword: model::Keyword,
+    ) -> error::Result<()> {
+        self.post_resource(&format!("/books/{id}/keywords"), keyword)
     }
 
-    pub fn add_keyword_to_author(&self, id: model::AuthorId, keyword: String) -> error::Result<()> {
-        Ok(self.post_resource(&format!("/authors/{id}/keywords"), keyword)?)
+    pub fn add_keyword_to_author(
+        &self,
+        id: model::AuthorId,
+        keyword: model::Keyword,
+    ) -> error::Result<()> {
+        self.post_resource(&format!("/authors/{id}/keywords"), keyword)
     }
 
     pub fn get_reader_by_moniker(&self, moniker: &str) -> error::Result<Option<model::Reader>> {
         self.request_resource(&format!("/readers/moniker/{}", moniker))
     }
 
+    // Authors by exactly this name, whatever its case, of which there may be
+    // none or several
+    pub fn find_author_by_name(&self, name: &str) -> error::Result<Vec<model::Author>> {
+        self.request_resource_with("/authors/named", &[("name", name)])
+    }
+
     pub fn add_read_book(&self, info: model::BookRead) -> error::Result<()> {
         self.post_resource(&format!("/books/{}/readers", &info.book_id), info)
     }
 
     pub fn search(&self, query_text: &str) -> error::Result<Vec<model::SearchResultItem>> {
+        Ok(self.search_with(query_text, false)?.hits)
+    }
+
+    // Also carries suggestions for when nothing matched
+    pub fn search_results(&self, query_text: &str) -> error::Result<model::SearchResults> {
+        self.search_with(query_text, false)
+    }
+
+    // Hits carry the character ranges of each matched term
+    pub fn search_highlighted(
+        &self,
+        query_text: &str,
+    ) -> error::Result<Vec<model::SearchResultItem>> {
+        Ok(self.search_with(query_text, true)?.hits)
+    }
+
+    fn search_with(
+        &self,
+        query_text: &str,
+        highlight: bool,
+    ) -> error::Result<model::SearchResults> {
         let resource_uri = self.resolve_resource_uri("/search");
         let request = self
             .http_client
             .get(resource_uri)
-            .query(&[("query", query_text)])
+            .query(&[("query", query_text), ("highlight", &highlight.to_string())])
             .build()?;
         let response = self.http_client.execute(request)?;
-        Ok(serde_json::from_slice(&response.bytes()?)?)
+        Encoding::of(response.headers()).decode(&response.bytes()?)
     }
 
     fn post_resource<R>(&self, uri: &str, resource: R) -> error::Result<()>
@@ -117,7 +197,26 @@ impl ApiClient {
         if response.status().is_success() {
             Ok(())
         } else {
-            Err(error::Error::Server(response.status()))
+            let status = response.status();
+            Err(error::Error::from_response(status, &response.bytes()?))
+        }
+    }
+
+    // A POST that reads rather than creates, and so returns a body
+    fn post_query<R, S>(&self, uri: &str, query: R) -> error::Result<S>
+    where
+        R: Serialize,
+        S: DeserializeOwned,
+    {
+        let resource_uri = self.resolve_resource_uri(uri);
+        let request = self.http_client.post(resource_uri).json(&query).build()?;
+        let response = self.http_client.execute(request)?;
+
+        if response.status().is_success() {
+            Encoding::of(response.headers()).decode(&response.bytes()?)
+        } else {
+            let status = response.status();
+            Err(error::Error::from_response(status, &response.bytes()?))
         }
     }
 
@@ -127,17 +226,67 @@ impl ApiClient {
     where
         R: DeserializeOwned,
     {
-        let resource_uri = self.resolve_resource_uri(resource_uri);
-        let request = self.http_client.get(resource_uri).build()?;
+        self.request(self.resolve_resource_uri(resource_uri))
+    }
+
+    // With the parameters escaped into the query string
+    fn request_resource_with<R>(
+        &self,
+        resource_uri: &str,
+        parameters: &[(&str, &str)],
+    ) -> error::Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        self.request_with(self.resolve_resource_uri(resource_uri), parameters)
+    }
+
+    // Walks the pages until it has as many as the first said there were
+    fn request_every_page<T>(&self, resource_uri: &str) -> error::Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let separator = if resource_uri.contains('?') { '&' } else { '?' };
+        let mut items = vec![];
+        loop {
+            let model::Page { items: page, total } =
+                self.request_resource::<model::Page<T>>(&format!(
+                    "{resource_uri}{separator}offset={}&limit={}",
+                    items.len(),
+                    Self::LIST_PAGE_SIZE
+                ))?;
+            let short = page.len() < Self::LIST_PAGE_SIZE;
+            items.extend(page);
+            if short || items.len() >= total {
+                return Ok(items);
+            }
+        }
+    }
+
+    // Outside of the API's resources
+    fn request<R>(&self, uri: String) -> error::Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        self.request_with(uri, &[])
+    }
+
+    fn request_with<R>(&self, uri: String, parameters: &[(&str, &str)]) -> error::Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        let request = self.http_client.get(uri).query(parameters).build()?;
         let response = self.http_client.execute(request)?;
-        Ok(serde_json::from_slice(&response.bytes()?)?)
+
+        if response.status().is_success() {
+            Encoding::of(response.headers()).decode(&response.bytes()?)
+        } else {
+            let status = response.status();
+            Err(error::Error::from_response(status, &response.bytes()?))
+        }
     }
 
     fn resolve_resource_uri(&self, resource_uri: &str) -> String {
-        format!(
-            "{}{}{resource_uri}",
-            self.base_url,
-            Self::API_RESOURCE_PREFIX
-        )
+        format!("{}{}{resource_uri}", self.base_url, self.resource_prefix)
     }
 }
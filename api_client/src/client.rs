@@ -1,32 +1,66 @@
+use futures_util::{stream, Stream, StreamExt};
 use reqwest::Client;
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{error, model};
+use crate::{
+    encoding::Encoding,
+    error, model,
+    options::{configure_builder, ClientOptions},
+};
 
 #[derive(Clone)]
 pub struct ApiClient {
     http_client: Client,
     base_url: String,
+    resource_prefix: String,
 }
 
 impl ApiClient {
-    const API_RESOURCE_PREFIX: &str = "/api/v1";
+    const SEARCH_PAGE_SIZE: usize = 100;
+    const LIST_PAGE_SIZE: usize = 100;
 
     pub fn new(base_url: &str) -> Self {
-        let http_client = Client::new();
+        Self::with_options(base_url, &ClientOptions::default())
+            .expect("an HTTP client with default options")
+    }
+
+    pub fn with_options(base_url: &str, options: &ClientOptions) -> error::Result<Self> {
+        let http_client = configure_builder!(Client::builder(), options).build()?;
         // See to it that base_url does not end in /
-        Self {
+        Ok(Self {
             http_client,
             base_url: base_url.to_owned(),
-        }
+            resource_prefix: options.resource_prefix.clone(),
+        })
+    }
+
+    pub async fn get_version(&self) -> error::Result<model::ServerVersion> {
+        self.request(format!("{}/version", self.base_url)).await
     }
 
     pub async fn get_books(&self) -> error::Result<Vec<model::Book>> {
-        self.request_resource("/books").await
+        self.request_every_page("/books").await
+    }
+
+    pub async fn get_book(&self, id: model::BookId) -> error::Result<model::Book> {
+        self.request_resource(&format!("/books/{id}")).await
+    }
+
+    // Books that could not be found are reported in BookBatch::missing
+    pub async fn get_books_by_ids(&self, ids: &[model::BookId]) -> error::Result<model::BookBatch> {
+        self.post_resource("/books/batch-get", ids).await
     }
 
     pub async fn get_authors(&self) -> error::Result<Vec<model::Author>> {
-        self.request_resource("/authors").await
+        self.request_every_page("/authors").await
+    }
+
+    pub async fn get_author(&self, id: model::AuthorId) -> error::Result<model::Author> {
+        self.request_resource(&format!("/authors/{id}")).await
+    }
+
+    pub async fn get_authors_with_book_counts(&self) -> error::Result<Vec<model::Author>> {
+        self.request_every_page("/authors?with_counts=true").await
     }
 
     pub async fn get_readers(&self) -> error::Result<Vec<model::Reader>> {
@@ -47,8 +81,13 @@ impl ApiClient {
         &self,
         book_id: model::BookId,
     ) -> error::Result<Option<model::Author>> {
-        self.request_resource(&format!("/books/{book_id}/author"))
+        match self
+            .request_resource(&format!("/books/{book_id}/author"))
             .await
+        {
+            Err(error) if error.is_not_found() => Ok(None),
+            author => author,
+        }
     }
 
     pub async fn get_books_by_author(
@@ -62,7 +101,7 @@ impl ApiClient {
     pub async fn get_books_read(
         &self,
         reader_id: model::ReaderId,
-    ) -> error::Result<Vec<model::Book>> {
+    ) -> error::Result<Vec<model::ReadBook>> {
         self.request_resource(&format!("/readers/{reader_id}/books"))
             .await
     }
@@ -85,6 +124,13 @@ impl ApiClient {
         Ok(model::BookId(resource_id.id))
     }
 
+    pub async fn add_book_with_author(
+        &self,
+        info: model::BookWithNewAuthor,
+    ) -> error::Result<model::CreatedBook> {
+        self.post_resource("/books", info).await
+    }
+
     pub async fn add_reader(&self, info: model::ReaderInfo) -> error::Result<model::ReaderId> {
         let resource_id: model::ResourceId = self.post_resource("/readers", info).await?;
         Ok(model::ReaderId(resource_id.id))
@@ -93,21 +139,19 @@ impl ApiClient {
     pub async fn add_keyword_to_book(
         &self,
         id: model::BookId,
-        keyword: String,
+        keyword: model::Keyword,
     ) -> error::Result<()> {
-        Ok(self
-            .post_resource(&format!("/books/{id}/keywords"), keyword)
-            .await?)
+        self.post_resource(&format!("/books/{id}/keywords"), keyword)
+            .await
     }
 
     pub async fn add_keyword_to_author(
         &self,
         id: model::AuthorId,
-        keyword: String,
+        keyword: model::Keyword,
     ) -> error::Result<()> {
-        Ok(self
-            .post_resource(&format!("/authors/{id}/keywords"), keyword)
-            .await?)
+        self.post_resource(&format!("/authors/{id}/keywords"), keyword)
+            .await
     }
 
     pub async fn get_reader_by_moniker(
@@ -118,20 +162,117 @@ impl ApiClient {
             .await
     }
 
+    // Authors by exactly this name, whatever its case, of which there may be
+    // none or several
+    pub async fn find_author_by_name(&self, name: &str) -> error::Result<Vec<model::Author>> {
+        self.request_resource_with("/authors/named", &[("name", name)])
+            .await
+    }
+
     pub async fn add_read_book(&self, info: model::BookRead) -> error::Result<()> {
         self.post_resource(&format!("/books/{}/readers", &info.book_id), info)
             .await
     }
 
     pub async fn search(&self, query_text: &str) -> error::Result<Vec<model::SearchResultItem>> {
+        Ok(self.search_with(query_text, false).await?.hits)
+    }
+
+    // Also carries suggestions for when nothing matched
+    pub async fn search_results(&self, query_text: &str) -> error::Result<model::SearchResults> {
+        self.search_with(query_text, false).await
+    }
+
+    // Hits carry the character ranges of each matched term
+    pub async fn search_highlighted(
+        &self,
+        query_text: &str,
+    ) -> error::Result<Vec<model::SearchResultItem>> {
+        Ok(self.search_with(query_text, true).await?.hits)
+    }
+
+    // Every hit, a page at a time, until a page comes back short or empty.
+    // Each page asks for one hit more than it yields, which tells whether
+    // there is another page. A truncated search ends with its first page,
+    // as the server does not rank all of it: narrow it to see the rest. A
+    // failed page ends the stream with its error.
+    pub fn search_stream<'a>(
+        &'a self,
+        query_text: &'a str,
+    ) -> impl Stream<Item = error::Result<model::SearchResultItem>> + 'a {
+        stream::unfold(Some(0), move |offset| async move {
+            let offset = offset?;
+            let page = self
+                .search_page(query_text, offset, Self::SEARCH_PAGE_SIZE + 1)
+                .await;
+            Some(match page {
+                Ok(model::SearchResults {
+                    mut hits,
+                    truncated,
+                    ..
+                }) => {
+                    let more = hits.len() > Self::SEARCH_PAGE_SIZE && !truncated;
+                    hits.truncate(Self::SEARCH_PAGE_SIZE);
+                    (Ok(hits), more.then_some(offset + Self::SEARCH_PAGE_SIZE))
+                }
+                Err(error) => (Err(error), None),
+            })
+        })
+        .flat_map(|page| {
+            stream::iter(match page {
+                Ok(hits) => hits.into_iter().map(Ok).collect(),
+                Err(error) => vec![Err(error)],
+            })
+        })
+    }
+
+    async fn search_page(
+        &self,
+        query_text: &str,
+        offset: usize,
+        limit: usize,
+    ) -> error::Result<model::SearchResults> {
+        let parameters = [
+            ("query", query_text.to_owned()),
+            ("offset", offset.to_string()),
+            ("limit", limit.to_string()),
+        ];
+        self.search_by(&parameters).await
+    }
+
+    async fn search_with(
+        &self,
+        query_text: &str,
+        highlight: bool,
+    ) -> error::Result<model::SearchResults> {
+        let parameters = [
+            ("query", query_text.to_owned()),
+            ("highlight", highlight.to_string()),
+        ];
+        self.search_by(&parameters).await
+    }
+
+    async fn search_by(
+        &self,
+        parameters: &[(&str, String)],
+    ) -> error::Result<model::SearchResults> {
         let resource_uri = self.resolve_resource_uri("/search");
         let request = self
             .http_client
             .get(resource_uri)
-            .query(&[("query", query_text)])
+            .query(parameters)
             .build()?;
         let response = self.http_client.execute(request).await?;
-        Ok(serde_json::from_slice(&response.bytes().await?)?)
+
+        if response.status().is_success() {
+            Encoding::of(response.headers()).decode(&response.bytes().await?)
+        } else {
+            let status = response.status();
+            Err(error::Error::from_response(
+                status,
+                &response.bytes().await?,
+            ))
+        }
     }
 
     async fn post_resource<R, S>(&self, uri: &str, resource: R) -> error::Result<S>
@@ -148,9 +289,19 @@ impl ApiClient {
         let response = self.http_client.execute(request).await?;
 
         if response.status().is_success() {
-            Ok(response.json().await?)
+            // Accepted commands answer with no body, which reads as ()
+            let body = response.bytes().await?;
+            Ok(serde_json::from_slice(if body.is_empty() {
+                b"null"
+            } else {
+                &body
+            })?)
         } else {
-            Err(error::Error::Server(response.status()))
+            let status = response.status();
+            Err(error::Error::from_response(
+                status,
+                &response.bytes().await?,
+            ))
         }
     }
 
@@ -160,17 +311,362 @@ impl ApiClient {
     where
         R: DeserializeOwned,
     {
-        let resource_uri = self.resolve_resource_uri(resource_uri);
-        let request = self.http_client.get(resource_uri).build()?;
+        self.request(self.resolve_resource_uri(resource_uri)).await
+    }
+
+    // With the parameters escaped into the query string
+    async fn request_resource_with<R>(
+        &self,
+        resource_uri: &str,
+        parameters: &[(&str, &str)],
+    ) -> error::Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        self.request_with(self.resolve_resource_uri(resource_uri), parameters)
+            .await
+    }
+
+    // Walks the pages until it has as many as the first said there were
+    async fn request_every_page<T>(&self, resource_uri: &str) -> error::Result<Vec<T>>
+    where
+        T: DeserializeOwned,
+    {
+        let separator = if resource_uri.contains('?') { '&' } else { '?' };
+        let mut items = vec![];
+        loop {
+            let model::Page { items: page, total } = self
+                .request_resource::<model::Page<T>>(&format!(
+                    "{resource_uri}{separator}offset={}&limit={}",
+                    items.len(),
+                    Self::LIST_PAGE_SIZE
+                ))
+                .await?;
+            let short = page.len() < Self::LIST_PAGE_SIZE;
+            items.extend(page);
+            if short || items.len() >= total {
+                return Ok(items);
+            }
+        }
+    }
+
+    // Outside of the API's resources
+    async fn request<R>(&self, uri: String) -> error::Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        self.request_with(uri, &[]).await
+    }
+
+    async fn request_with<R>(&self, uri: String, parameters: &[(&str, &str)]) -> error::Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        let request = self.http_client.get(uri).query(parameters).build()?;
         let response = self.http_client.execute(request).await?;
-        Ok(serde_json::from_slice(&response.bytes().await?)?)
+
+        if response.status().is_success() {
+            Encoding::of(response.headers()).decode(&response.bytes().await?)
+        } else {
+            let status = response.status();
+            Err(error::Error::from_response(
+                status,
+                &response.bytes().await?,
+            ))
+        }
     }
 
     fn resolve_resource_uri(&self, resource_uri: &str) -> String {
+        format!("{}{}{resource_uri}", self.base_url, self.resource_prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    const EMPTY_ARRAY: &str = "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n[]";
+
+    // Answers one request with response, and hands back the request's head
+    fn mock_server(response: impl Into<Vec<u8>>) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("a listener");
+        let base_url = format!("http://{}", listener.local_addr().expect("an address"));
+        let response = response.into();
+        let server = thread::spawn(move || answer(&listener, &response));
+        (base_url, server)
+    }
+
+    // Answers one request per response, in order, on connections of their own
+    fn mock_server_answering(responses: Vec<String>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("a listener");
+        let base_url = format!("http://{}", listener.local_addr().expect("an address"));
+        let server = thread::spawn(move || {
+            responses
+                .iter()
+                .map(|response| answer(&listener, response.as_bytes()))
+                .collect()
+        });
+        (base_url, server)
+    }
+
+    fn answer(listener: &TcpListener, response: &[u8]) -> String {
+        let (mut stream, _) = listener.accept().expect("a connection");
+        let mut head = Vec::new();
+        let mut buffer = [0; 1024];
+        while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut buffer).expect("a request");
+            head.extend_from_slice(&buffer[..read]);
+        }
+        stream.write_all(response).expect("a response");
+        String::from_utf8(head).expect("a textual request head")
+    }
+
+    fn search_page(hits: usize, truncated: bool) -> String {
+        let hit = r#"{"uri":"/api/v1/authors/67e55044-10b1-426f-9247-bb680e5fe0c8","hit":{"kind":"author","name":"Sun Tzu","id":"67e55044-10b1-426f-9247-bb680e5fe0c8"}}"#;
+        let body = format!(
+            r#"{{"hits":[{}],"truncated":{truncated},"suggestions":[]}}"#,
+            vec![hit; hits].join(",")
+        );
         format!(
-            "{}{}{resource_uri}",
-            self.base_url,
-            Self::API_RESOURCE_PREFIX
+            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+             content-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    #[tokio::test]
+    async fn search_stream_pages_until_a_short_page() {
+        let page_size = ApiClient::SEARCH_PAGE_SIZE;
+        let (base_url, server) = mock_server_answering(vec![
+            search_page(page_size + 1, false),
+            search_page(3, false),
+        ]);
+        let client = ApiClient::new(&base_url);
+        let hits = client
+            .search_stream("sun")
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<error::Result<Vec<_>>>()
+            .expect("hits");
+        assert_eq!(hits.len(), page_size + 3);
+
+        let heads = server.join().expect("request heads");
+        let limit = page_size + 1;
+        assert!(heads[0].contains(&format!("offset=0&limit={limit}")));
+        assert!(heads[1].contains(&format!("offset={page_size}&limit={limit}")));
+
+        let unavailable = r#"{"code":"UNAVAILABLE","error":"later"}"#;
+        let (base_url, server) = mock_server_answering(vec![
+            search_page(page_size + 1, false),
+            format!(
+                "HTTP/1.1 503 Service Unavailable\r\ncontent-type: application/json\r\n\
+                 content-length: {}\r\nconnection: close\r\n\r\n{unavailable}",
+                unavailable.len()
+            ),
+        ]);
+        let client = ApiClient::new(&base_url);
+        let hits = client.search_stream("sun").collect::<Vec<_>>().await;
+        assert_eq!(hits.len(), page_size + 1);
+        assert!(hits[page_size]
+            .as_ref()
+            .is_err_and(|error| error.is_transient()));
+        server.join().expect("request heads");
+    }
+
+    #[tokio::test]
+    async fn search_stream_stops_without_asking_for_an_empty_page() {
+        let page_size = ApiClient::SEARCH_PAGE_SIZE;
+        for (page, hits) in [
+            // Exactly a page of hits
+            (search_page(page_size, false), page_size),
+            // More than the server ranks
+            (search_page(page_size + 1, true), page_size),
+            (search_page(0, false), 0),
+        ] {
+            let (base_url, server) = mock_server_answering(vec![page]);
+            let client = ApiClient::new(&base_url);
+            let streamed = client
+                .search_stream("sun")
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<error::Result<Vec<_>>>()
+                .expect("hits");
+            assert_eq!(streamed.len(), hits);
+            assert_eq!(server.join().expect("request heads").len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn authors_are_found_by_a_name_escaped_into_the_query() {
+        let (base_url, server) = mock_server(EMPTY_ARRAY);
+        let authors = ApiClient::new(&base_url)
+            .find_author_by_name("Sun Tzu & Co")
+            .await
+            .expect("authors");
+        assert!(authors.is_empty());
+
+        let head = server.join().expect("a request head");
+        assert!(head.starts_with("GET /api/v1/authors/named?name=Sun+Tzu+%26+Co "));
+    }
+
+    #[tokio::test]
+    async fn accepted_commands_without_a_body_succeed() {
+        let (base_url, server) =
+            mock_server("HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+        let read = model::BookRead {
+            reader_id: model::ReaderId(uuid::Uuid::new_v4()),
+            book_id: model::BookId(uuid::Uuid::new_v4()),
+            when: None,
+        };
+        ApiClient::new(&base_url)
+            .add_read_book(read)
+            .await
+            .expect("an accepted read");
+
+        let head = server.join().expect("a request head");
+        assert!(head.starts_with("POST /api/v1/books/"));
+    }
+
+    #[tokio::test]
+    async fn listings_are_requested_until_every_page_is_in() {
+        let author = r#"{"id":"67e55044-10b1-426f-9247-bb680e5fe0c8","info":{"name":"Sun Tzu"},"book_count":1}"#;
+        let page = |authors, total| {
+            let body = format!(
+                r#"{{"items":[{}],"total":{total}}}"#,
+                vec![author; authors].join(",")
+            );
+            format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                 content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        };
+        let page_size = ApiClient::LIST_PAGE_SIZE;
+        let (base_url, server) =
+            mock_server_answering(vec![page(page_size, page_size + 2), page(2, page_size + 2)]);
+        let authors = ApiClient::new(&base_url)
+            .get_authors_with_book_counts()
+            .await
+            .expect("authors");
+        assert_eq!(authors.len(), page_size + 2);
+
+        let heads = server.join().expect("request heads");
+        assert!(heads[0].contains(&format!("with_counts=true&offset=0&limit={page_size}")));
+        assert!(heads[1].contains(&format!("offset={page_size}&limit={page_size}")));
+    }
+
+    #[tokio::test]
+    async fn book_lists_can_be_had_in_message_pack() {
+        let page = serde_json::json!({
+            "items": [{
+                "id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+                "info": {
+                    "isbn": "978-1-61180-697-7",
+                    "title": "The Art of War",
+                    "author": "67e55044-10b1-426f-9247-bb680e5fe0c9",
+                },
+                "created_at": "2023-11-14T22:13:20Z",
+                "updated_at": "2023-11-14T22:13:20Z",
+            }],
+            "total": 1,
+        });
+        let body = rmp_serde::to_vec_named(&page).expect("a MessagePack body");
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: application/msgpack\r\n\
+             content-length: {}\r\nconnection: close\r\n\r\n",
+            body.len()
         )
+        .into_bytes();
+        response.extend(body);
+
+        let (base_url, server) = mock_server(response);
+        let options = ClientOptions::default().prefer_message_pack();
+        let books = ApiClient::with_options(&base_url, &options)
+            .expect("a client")
+            .get_books()
+            .await
+            .expect("books");
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].info.title, "The Art of War");
+        assert!(books[0].created_at.is_some());
+
+        let head = server.join().expect("a request head").to_lowercase();
+        assert!(head.contains("accept: application/msgpack"), "{head}");
+    }
+
+    #[tokio::test]
+    async fn user_agent_is_sent_with_requests() {
+        let (base_url, server) = mock_server(EMPTY_ARRAY.to_owned());
+        ApiClient::new(&base_url)
+            .get_readers()
+            .await
+            .expect("no readers");
+        let head = server.join().expect("a request head").to_lowercase();
+        assert!(head.contains(&format!(
+            "user-agent: {}",
+            ClientOptions::DEFAULT_USER_AGENT
+        )));
+
+        let (base_url, server) = mock_server(EMPTY_ARRAY.to_owned());
+        let options = ClientOptions::default()
+            .pool_max_idle_per_host(2)
+            .user_agent("blister-test/1");
+        ApiClient::with_options(&base_url, &options)
+            .expect("a client")
+            .get_readers()
+            .await
+            .expect("no readers");
+        let head = server.join().expect("a request head").to_lowercase();
+        assert!(head.contains("user-agent: blister-test/1"));
+    }
+
+    #[tokio::test]
+    async fn resources_are_requested_under_the_resource_prefix() {
+        for (prefix, path) in [
+            (None, "/api/v1/readers"),
+            (Some("/library/api/"), "/library/api/readers"),
+        ] {
+            let (base_url, server) = mock_server(EMPTY_ARRAY.to_owned());
+            let options = prefix.map_or_else(ClientOptions::default, |prefix| {
+                ClientOptions::default().resource_prefix(prefix)
+            });
+            ApiClient::with_options(&base_url, &options)
+                .expect("a client")
+                .get_readers()
+                .await
+                .expect("no readers");
+            let head = server.join().expect("a request head");
+            assert!(head.starts_with(&format!("GET {path} ")), "{head}");
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicate_monikers_are_a_typed_error() {
+        let body = r#"{"code":"DUPLICATE_MONIKER","error":"taken","reason":"duplicate-moniker","moniker":"suntzu"}"#;
+        let response = format!(
+            "HTTP/1.1 409 Conflict\r\ncontent-type: application/json\r\n\
+             content-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let (base_url, server) = mock_server(response);
+        let added = ApiClient::new(&base_url)
+            .add_reader(model::ReaderInfo {
+                name: "Sun Tzu".to_owned(),
+                unique_moniker: "suntzu".to_owned(),
+            })
+            .await;
+        server.join().expect("a request head");
+
+        assert!(matches!(
+            added,
+            Err(error::Error::DuplicateMoniker(moniker)) if moniker == "suntzu"
+        ));
     }
 }
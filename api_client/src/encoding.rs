@@ -0,0 +1,43 @@
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use serde::de::DeserializeOwned;
+
+use crate::error;
+
+// How a response body is encoded, going by its Content-Type. The list and
+// search endpoints answer in MessagePack when it is asked for, everything
+// else in JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    Json,
+    MessagePack,
+}
+
+impl Encoding {
+    pub(crate) const MESSAGE_PACK: &'static str = "application/msgpack";
+
+    pub(crate) fn of(headers: &HeaderMap) -> Self {
+        match headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+        {
+            Some(content_type) if content_type.starts_with(Self::MESSAGE_PACK) => Self::MessagePack,
+            _otherwise => Self::Json,
+        }
+    }
+
+    // The server writes human-readable MessagePack, in which ids and times
+    // are the same strings as in JSON
+    pub(crate) fn decode<R>(self, body: &[u8]) -> error::Result<R>
+    where
+        R: DeserializeOwned,
+    {
+        match self {
+            Self::Json => Ok(serde_json::from_slice(body)?),
+            Self::MessagePack => {
+                let mut deserializer =
+                    rmp_serde::Deserializer::from_read_ref(body).with_human_readable();
+                Ok(R::deserialize(&mut deserializer)?)
+            }
+        }
+    }
+}
@@ -2,16 +2,200 @@ use reqwest::StatusCode;
 use std::result::Result as StdResult;
 use thiserror::Error;
 
+use crate::model;
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("JSON marshalling failed {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("MessagePack unmarshalling failed {0}")]
+    MessagePack(#[from] rmp_serde::decode::Error),
+
     #[error("HTTP IO failed {0}")]
     Http(#[from] reqwest::Error),
 
     #[error("Request failed {0}")]
     Server(StatusCode),
+
+    #[error("{0} is not a valid keyword")]
+    InvalidKeyword(String),
+
+    #[error("The moniker {0} is already taken")]
+    DuplicateMoniker(String),
+
+    #[error("There is no author {0}")]
+    UnknownAuthor(model::AuthorId),
+
+    #[error("There is no book {0}")]
+    UnknownBook(model::BookId),
+
+    #[error("There is no reader {0}")]
+    UnknownReader(model::ReaderId),
+
+    #[error("No more than {0} keywords may be added")]
+    TooManyKeywords(usize),
+
+    #[error("The {0} must not be empty")]
+    EmptyField(String),
+
+    #[error("Request failed {status} {code:?}: {message}")]
+    Api {
+        status: StatusCode,
+        code: model::ErrorCode,
+        message: String,
+    },
+}
+
+impl Error {
+    // Goes by the code in the body, not by its message, which may change.
+    // Responses without one are reported by their status.
+    pub(crate) fn from_response(status: StatusCode, body: &[u8]) -> Self {
+        let Ok(model::ErrorBody { code, error }) = serde_json::from_slice(body) else {
+            return Self::Server(status);
+        };
+
+        match (code, serde_json::from_slice(body)) {
+            (
+                model::ErrorCode::DuplicateMoniker,
+                Ok(model::Rejection::DuplicateMoniker { moniker }),
+            ) => Self::DuplicateMoniker(moniker),
+            (
+                model::ErrorCode::UnknownAuthor,
+                Ok(model::Rejection::UnknownAuthor { author_id }),
+            ) => Self::UnknownAuthor(author_id),
+            (model::ErrorCode::UnknownBook, Ok(model::Rejection::UnknownBook { book_id })) => {
+                Self::UnknownBook(book_id)
+            }
+            (
+                model::ErrorCode::UnknownReader,
+                Ok(model::Rejection::UnknownReader { reader_id }),
+            ) => Self::UnknownReader(reader_id),
+            (model::ErrorCode::TooManyKeywords, Ok(model::Rejection::TooManyKeywords { max })) => {
+                Self::TooManyKeywords(max)
+            }
+            (model::ErrorCode::EmptyField, Ok(model::Rejection::EmptyField { field })) => {
+                Self::EmptyField(field)
+            }
+            _ => Self::Api {
+                status,
+                code,
+                message: error,
+            },
+        }
+    }
+
+    // Worth retrying: the request may well succeed later as it is
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Http(error) => {
+                error.is_connect()
+                    || error.is_timeout()
+                    || error
+                        .status()
+                        .is_some_and(|status| status.is_server_error())
+            }
+            Self::Server(status) | Self::Api { status, .. } => {
+                status.is_server_error()
+                    || *status == StatusCode::REQUEST_TIMEOUT
+                    || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            Self::Json(..)
+            | Self::MessagePack(..)
+            | Self::InvalidKeyword(..)
+            | Self::DuplicateMoniker(..)
+            | Self::UnknownAuthor(..)
+            | Self::UnknownBook(..)
+            | Self::UnknownReader(..)
+            | Self::TooManyKeywords(..)
+            | Self::EmptyField(..) => false,
+        }
+    }
+
+    // Will fail the same way however often it is retried
+    pub fn is_permanent(&self) -> bool {
+        !self.is_transient()
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Self::Http(error) => error.status() == Some(StatusCode::NOT_FOUND),
+            Self::Server(status) => *status == StatusCode::NOT_FOUND,
+            Self::Api { code, .. } => *code == model::ErrorCode::NotFound,
+            _ => false,
+        }
+    }
 }
 
 pub type Result<A> = StdResult<A, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn server_errors_are_transient_and_client_errors_permanent() {
+        assert!(Error::Server(StatusCode::SERVICE_UNAVAILABLE).is_transient());
+        assert!(Error::Server(StatusCode::TOO_MANY_REQUESTS).is_transient());
+        assert!(Error::Server(StatusCode::BAD_REQUEST).is_permanent());
+        assert!(Error::InvalidKeyword("".to_owned()).is_permanent());
+
+        assert!(Error::Server(StatusCode::NOT_FOUND).is_not_found());
+        assert!(Error::Server(StatusCode::NOT_FOUND).is_permanent());
+        assert!(!Error::Server(StatusCode::GONE).is_not_found());
+    }
+
+    #[test]
+    fn error_codes_decide_the_error() {
+        let error = |status, body: &str| Error::from_response(status, body.as_bytes());
+
+        assert!(matches!(
+            error(
+                StatusCode::CONFLICT,
+                r#"{"code":"DUPLICATE_MONIKER","error":"","reason":"duplicate-moniker","moniker":"suntzu"}"#
+            ),
+            Error::DuplicateMoniker(moniker) if moniker == "suntzu"
+        ));
+        assert!(matches!(
+            error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                r#"{"code":"UNKNOWN_AUTHOR","error":"","reason":"unknown-author","author_id":"67e55044-10b1-426f-9247-bb680e5fe0c8"}"#
+            ),
+            Error::UnknownAuthor(..)
+        ));
+        assert!(matches!(
+            error(
+                StatusCode::NOT_FOUND,
+                r#"{"code":"UNKNOWN_BOOK","error":"","reason":"unknown-book","book_id":"67e55044-10b1-426f-9247-bb680e5fe0c8"}"#
+            ),
+            Error::UnknownBook(..)
+        ));
+
+        let not_found = error(
+            StatusCode::NOT_FOUND,
+            r#"{"code":"NOT_FOUND","error":"Not Found"}"#,
+        );
+        assert!(not_found.is_not_found());
+        assert!(matches!(
+            error(
+                StatusCode::SERVICE_UNAVAILABLE,
+                r#"{"code":"UNAVAILABLE","error":"later"}"#
+            ),
+            Error::Api {
+                code: model::ErrorCode::Unavailable,
+                ..
+            }
+        ));
+        assert!(matches!(
+            error(StatusCode::GONE, r#"{"code":"SOMETHING_NEW","error":""}"#),
+            Error::Api {
+                code: model::ErrorCode::Unknown,
+                ..
+            }
+        ));
+        assert!(matches!(
+            error(StatusCode::BAD_GATEWAY, "<html>"),
+            Error::Server(..)
+        ));
+    }
+}
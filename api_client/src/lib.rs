@@ -1,7 +1,12 @@
-pub mod blocking; // This could be hidden behind a feature
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod client;
+mod encoding;
 pub mod error;
 pub mod model;
+pub mod options;
 
+#[cfg(feature = "blocking")]
 pub use blocking::ApiClient as BlockingApiClient;
 pub use client::ApiClient;
+pub use options::ClientOptions;
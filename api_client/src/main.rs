@@ -10,7 +10,7 @@ async fn main() {
     let authors = client.get_authors().await.expect("some authors");
     println!("Authors: {authors:?}");
 
-    for model::Book { id, info } in books {
+    for model::Book { id, info, .. } in books {
         let author = client.get_author_by_book(id).await.expect("book's author");
         println!(
             "{} by {}",
@@ -19,12 +19,12 @@ async fn main() {
         );
     }
 
-    for model::Author { id, info } in authors {
+    for model::Author { id, info, .. } in authors {
         let books = client.get_books_by_author(id).await.expect("some books");
         println!("Books by {}: {books:?}", info.name);
     }
 
-    for model::SearchResultItem { uri, hit } in client.search("Bo").await.expect("msg") {
+    for model::SearchResultItem { uri, hit, .. } in client.search("Bo").await.expect("msg") {
         match hit {
             model::SearchHit::BookTitle { title, .. } => println!("Title '{title}, at: {uri}'"),
             model::SearchHit::BookIsbn { isbn, .. } => println!("ISBN '{isbn}, at: {uri}'"),
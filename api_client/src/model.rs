@@ -1,12 +1,23 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::fmt;
-use time::UtcOffset;
+use std::{fmt, str::FromStr, sync::OnceLock};
+use time::{OffsetDateTime, UtcOffset};
 use uuid::Uuid;
 
+use crate::error;
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Author {
     pub id: AuthorId,
     pub info: AuthorInfo,
+    // Only there when asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub book_count: Option<usize>,
+    // When it was added, and when it last changed
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -21,11 +32,30 @@ pub struct AuthorInfo {
 pub struct Book {
     pub id: BookId,
     pub info: BookInfo,
+    // When it was added, and when it last changed
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+// A Book, with how many times the reader has read it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadBook {
+    #[serde(flatten)]
+    pub book: Book,
+    pub times_read: usize,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct BookId(pub Uuid);
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BookBatch {
+    pub found: Vec<Book>,
+    pub missing: Vec<BookId>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BookInfo {
     pub isbn: String,
@@ -33,6 +63,76 @@ pub struct BookInfo {
     pub author: AuthorId,
 }
 
+// Creates the author along with the book, even if one by that name exists
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BookWithNewAuthor {
+    pub isbn: String,
+    pub title: String,
+    pub author: AuthorInfo,
+}
+
+// What went wrong, as the server names it; see the server's ErrorCode for
+// what each means. Codes this client does not know of are Unknown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    NotFound,
+    MalformedRequest,
+    InvalidKeyword,
+    TooManyKeywords,
+    EmptyField,
+    UnknownAuthor,
+    UnknownBook,
+    UnknownReader,
+    DuplicateMoniker,
+    DuplicateKeyword,
+    ProgressOutOfRange,
+    VersionConflict,
+    IdempotencyKeyReused,
+    MissingKeyword,
+    UnknownKeyword,
+    SameKeyword,
+    SameAuthor,
+    ReadOnly,
+    Unavailable,
+    Internal,
+    #[serde(other)]
+    Unknown,
+}
+
+// The body of the server's error responses
+#[derive(Clone, Debug, Deserialize)]
+pub struct ErrorBody {
+    pub code: ErrorCode,
+    pub error: String,
+}
+
+// Why the server rejected a command, when it says
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum Rejection {
+    DuplicateMoniker { moniker: String },
+    UnknownAuthor { author_id: AuthorId },
+    UnknownBook { book_id: BookId },
+    UnknownReader { reader_id: ReaderId },
+    TooManyKeywords { max: usize },
+    EmptyField { field: String },
+    IdempotencyKeyReused { key: String },
+    DuplicateKeyword { keyword: String },
+    ProgressOutOfRange { percent: u8 },
+    MissingKeyword { keyword: String },
+    UnknownKeyword { keyword: String },
+    SameKeyword { keyword: String },
+    SameAuthor { author_id: AuthorId },
+    ReadOnly,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CreatedBook {
+    pub id: BookId,
+    pub author_id: AuthorId,
+}
+
 impl fmt::Display for BookId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let Self(id) = self;
@@ -51,6 +151,11 @@ impl fmt::Display for AuthorId {
 pub struct Reader {
     pub id: ReaderId,
     pub info: ReaderInfo,
+    // When it was added, and when it last changed
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -76,20 +181,46 @@ pub struct BookRead {
     pub when: Option<UtcOffset>,
 }
 
+// Books and authors are listed a page at a time; total counts them all
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub hits: Vec<SearchResultItem>,
+    // More matched than the server ranks
+    #[serde(default)]
+    pub truncated: bool,
+    // Nearby terms to try instead; only offered when there are no hits
+    #[serde(default)]
+    pub suggestions: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResultItem {
     pub uri: String,
     pub hit: SearchHit,
+    // Only present when searching with highlights
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlights: Option<Vec<Highlight>>,
 }
 
+// Character offsets into the hit's title, isbn or name; end is exclusive
 #[derive(Debug, Serialize, Deserialize)]
-#[serde(untagged)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+}
+
+// Tagged by kind, as the server sends it
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
 pub enum SearchHit {
-    #[serde(rename = "book-title")]
     BookTitle { title: String, id: BookId },
-    #[serde(rename = "book-isbn")]
     BookIsbn { isbn: String, id: BookId },
-    #[serde(rename = "author")]
     Author { name: String, id: AuthorId },
 }
 
@@ -98,9 +229,87 @@ pub struct ResourceId {
     pub id: Uuid,
 }
 
+// Servers with the same store_format_version can share an event store,
+// whatever their versions
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerVersion {
+    pub name: String,
+    pub version: String,
+    pub git_sha: String,
+    pub store_format_version: u32,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum KeywordTarget {
     Book { book_id: BookId },
     Author { author_id: AuthorId },
 }
+
+// Checked here against the same rule as the server's, to save a round trip
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct Keyword(String);
+
+static KEYWORD_REGEX: OnceLock<Regex> = OnceLock::new();
+
+fn keyword_regex() -> &'static Regex {
+    KEYWORD_REGEX.get_or_init(|| Regex::new(r"^[\p{L}_-]+$").expect("KEYWORD_REGEX is valid"))
+}
+
+impl FromStr for Keyword {
+    type Err = error::Error;
+
+    fn from_str(keyword: &str) -> error::Result<Self> {
+        if keyword_regex().is_match(keyword) {
+            Ok(Self(keyword.to_owned()))
+        } else {
+            Err(error::Error::InvalidKeyword(keyword.to_owned()))
+        }
+    }
+}
+
+impl fmt::Display for Keyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self(keyword) = self;
+        write!(f, "{keyword}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_hits_decode_by_their_kind() {
+        let id = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let hit = |json: String| serde_json::from_str::<SearchHit>(&json).expect("a search hit");
+
+        assert!(matches!(
+            hit(format!(r#"{{"kind":"book-title","title":"9781611806977","id":"{id}"}}"#)),
+            SearchHit::BookTitle { title, .. } if title == "9781611806977"
+        ));
+        assert!(matches!(
+            hit(format!(
+                r#"{{"kind":"book-isbn","isbn":"978-1-61180-697-7","id":"{id}"}}"#
+            )),
+            SearchHit::BookIsbn { .. }
+        ));
+        assert!(matches!(
+            hit(format!(
+                r#"{{"kind":"author","name":"Sun Tzu","id":"{id}"}}"#
+            )),
+            SearchHit::Author { .. }
+        ));
+        assert!(serde_json::from_str::<SearchHit>(&format!(
+            r#"{{"kind":"book-isbn","title":"The Art of War","id":"{id}"}}"#
+        ))
+        .is_err());
+
+        let title = SearchHit::BookTitle {
+            title: "The Art of War".to_owned(),
+            id: BookId(id.parse().expect("a uuid")),
+        };
+        let json = serde_json::to_string(&title).expect("a JSON hit");
+        assert!(matches!(hit(json), SearchHit::BookTitle { .. }));
+    }
+}
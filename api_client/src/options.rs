@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+// How the HTTP client underneath an ApiClient is set up. Anything left unset
+// is up to reqwest.
+#[derive(Clone, Debug)]
+pub struct ClientOptions {
+    pub(crate) pool_max_idle_per_host: Option<usize>,
+    pub(crate) pool_idle_timeout: Option<Duration>,
+    pub(crate) user_agent: String,
+    pub(crate) http2_prior_knowledge: bool,
+    pub(crate) resource_prefix: String,
+    pub(crate) message_pack: bool,
+}
+
+impl ClientOptions {
+    pub const DEFAULT_USER_AGENT: &str = concat!("blister-client/", env!("CARGO_PKG_VERSION"));
+    pub const DEFAULT_RESOURCE_PREFIX: &str = "/api/v1";
+
+    // Idle connections kept open to the server, for reuse by later calls
+    pub fn pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max_idle);
+        self
+    }
+
+    // How long an idle connection is kept before it is closed
+    pub fn pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    // Lets server logs tell the clients apart
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_owned();
+        self
+    }
+
+    // Speaks HTTP/2 from the first byte, which saves connections when many
+    // requests are made. The server must support it, even over plaintext,
+    // so HTTP/1.1 remains the default.
+    pub fn prefer_http2(mut self) -> Self {
+        self.http2_prior_knowledge = true;
+        self
+    }
+
+    // For a server whose resources are mounted somewhere other than /api/v1
+    pub fn resource_prefix(mut self, resource_prefix: &str) -> Self {
+        self.resource_prefix = format!("/{}", resource_prefix.trim_matches('/'));
+        self
+    }
+
+    // Asks for MessagePack, which is smaller than JSON on the wire. Only the
+    // list and search endpoints answer in it; the rest stay JSON.
+    pub fn prefer_message_pack(mut self) -> Self {
+        self.message_pack = true;
+        self
+    }
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: None,
+            pool_idle_timeout: None,
+            user_agent: Self::DEFAULT_USER_AGENT.to_owned(),
+            http2_prior_knowledge: false,
+            resource_prefix: Self::DEFAULT_RESOURCE_PREFIX.to_owned(),
+            message_pack: false,
+        }
+    }
+}
+
+// Both the async and the blocking builder take the same settings
+macro_rules! configure_builder {
+    ($builder:expr, $options:expr) => {{
+        let options: &$crate::options::ClientOptions = $options;
+        let mut builder = $builder.user_agent(options.user_agent.as_str());
+        if let Some(max_idle) = options.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle);
+        }
+        if let Some(timeout) = options.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if options.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        if options.message_pack {
+            let mut headers = reqwest::header::HeaderMap::new();
+            headers.insert(
+                reqwest::header::ACCEPT,
+                reqwest::header::HeaderValue::from_static($crate::encoding::Encoding::MESSAGE_PACK),
+            );
+            builder = builder.default_headers(headers);
+        }
+        builder
+    }};
+}
+
+pub(crate) use configure_builder;
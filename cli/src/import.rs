@@ -1,8 +1,9 @@
 use anyhow::{anyhow, Result};
 use core::fmt;
 use csv::ReaderBuilder;
+use futures_util::{stream, StreamExt};
 use isbn;
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize};
 use std::{
     collections::{HashMap, HashSet},
     fs::File,
@@ -10,19 +11,49 @@ use std::{
     path::PathBuf,
     str::FromStr,
 };
+use tabled::{builder::Builder, settings::Style};
 use uuid::Uuid;
 
 use api_client::{model as domain, ApiClient};
 
-pub async fn from_source(api: ApiClient, source: ImportSource) -> Result<()> {
+pub async fn from_source(
+    api: ApiClient,
+    source: ImportSource,
+    concurrency: usize,
+) -> Result<ImportSummary> {
     let csv_data = read_csv_data(source.make_reader()?);
     Importer { api }
         .compute_import_delta(&csv_data?)
         .await?
-        .import()
+        .import(concurrency)
         .await
 }
 
+// Marks books as read by readers, a moniker;isbn row at a time. Rows whose
+// reader or book cannot be found are reported, not imported.
+pub async fn reads_from_source(
+    api: ApiClient,
+    source: ImportSource,
+    dry_run: bool,
+) -> Result<ReadImportSummary> {
+    let rows = read_csv_data(source.make_reader()?)?;
+    let mut importer = ReadImporter {
+        api,
+        readers: HashMap::default(),
+        books: HashMap::default(),
+        summary: ReadImportSummary {
+            dry_run,
+            ..Default::default()
+        },
+    };
+
+    for (index, row) in rows.into_iter().enumerate() {
+        importer.import(index + 1, row).await?;
+    }
+
+    Ok(importer.summary)
+}
+
 pub enum ImportSource {
     StdIn,
     FilePath(PathBuf),
@@ -75,6 +106,8 @@ impl Importer {
                     isbn,
                     author_id,
                 });
+            } else {
+                import.books_skipped += 1;
             }
         }
 
@@ -91,6 +124,53 @@ struct ImportDelta {
     api: ApiClient,
     new_authors: HashMap<Uuid, String>,
     books: Vec<NewBook>,
+    books_skipped: usize,
+}
+
+// What an import did
+#[derive(Default)]
+pub struct ImportSummary {
+    pub authors_created: Vec<(domain::AuthorId, String)>,
+    pub books_created: Vec<(domain::BookId, String)>,
+    // Already present, so not imported
+    pub books_skipped: usize,
+    pub books_failed: Vec<(String, anyhow::Error)>,
+}
+
+impl ImportSummary {
+    pub fn table(&self) -> String {
+        let mut builder = Builder::default();
+        builder.push_record(vec!["", "Created", "Id", "Name"]);
+
+        let authors = self
+            .authors_created
+            .iter()
+            .map(|(domain::AuthorId(id), name)| ("Author", id, name));
+        let books = self
+            .books_created
+            .iter()
+            .map(|(domain::BookId(id), title)| ("Book", id, title));
+        for (index, (kind, id, name)) in authors.chain(books).enumerate() {
+            builder.push_record(vec![
+                format!("{}", index + 1),
+                kind.to_owned(),
+                id.to_string(),
+                name.to_owned(),
+            ])
+        }
+
+        let sharp = Style::sharp();
+        let mut table = format!(
+            "{}\n{} existing books skipped",
+            builder.build().with(sharp),
+            self.books_skipped
+        );
+        for (title, error) in &self.books_failed {
+            table.push_str(&format!("\nFailed to add '{title}': {error}"));
+        }
+
+        table
+    }
 }
 
 impl ImportDelta {
@@ -99,12 +179,15 @@ impl ImportDelta {
             api,
             new_authors: Default::default(),
             books: Default::default(),
+            books_skipped: 0,
         }
     }
 
     async fn get_canonical_author_ref(&mut self, author_name: &str) -> Result<AuthorId> {
         if let Some(author_id) = self.find_existing_author(author_name).await? {
             Ok(AuthorId::Existing(author_id.clone()))
+        } else if let Some(id) = self.find_new_author(author_name) {
+            Ok(AuthorId::New(id))
         } else {
             let id = Uuid::new_v4();
             self.new_authors.insert(id, author_name.to_owned());
@@ -112,6 +195,13 @@ impl ImportDelta {
         }
     }
 
+    // Several books by an author that is not there yet share one new author
+    fn find_new_author(&self, author_name: &str) -> Option<Uuid> {
+        self.new_authors
+            .iter()
+            .find_map(|(id, name)| (name == author_name).then_some(*id))
+    }
+
     async fn find_existing_author(&self, author_name: &str) -> Result<Option<domain::AuthorId>> {
         Ok(self.api.search(author_name).await?.into_iter().find_map(
             |domain::SearchResultItem { hit, .. }| match hit {
@@ -145,38 +235,253 @@ impl ImportDelta {
         self.books.push(book);
     }
 
-    async fn import(self) -> Result<()> {
+    // New authors go first, one at a time, each created along with the
+    // first of its books since the others refer to it. The remaining books
+    // are then independent of each other and are added concurrently.
+    async fn import(self, concurrency: usize) -> Result<ImportSummary> {
+        let mut summary = ImportSummary {
+            books_skipped: self.books_skipped,
+            ..Default::default()
+        };
         let mut authors = HashMap::new();
+        let mut books = Vec::with_capacity(self.books.len());
 
-        for (id, name) in self.new_authors {
-            authors.insert(id, self.api.add_author(domain::AuthorInfo { name }).await?);
+        for book in self.books {
+            match &book.author_id {
+                AuthorId::New(id) if !authors.contains_key(id) => {
+                    let name = self.new_authors[id].clone();
+                    let domain::CreatedBook {
+                        id: book_id,
+                        author_id,
+                    } = self
+                        .api
+                        .add_book_with_author(domain::BookWithNewAuthor {
+                            isbn: book.isbn.to_string(),
+                            title: book.title.clone(),
+                            author: domain::AuthorInfo { name: name.clone() },
+                        })
+                        .await?;
+                    summary.authors_created.push((author_id.clone(), name));
+                    summary.books_created.push((book_id, book.title));
+                    authors.insert(*id, author_id);
+                }
+                _otherwise => books.push(book),
+            }
         }
 
-        for NewBook {
-            title,
-            isbn,
-            author_id,
-        } in self.books
-        {
-            let author = match author_id {
-                AuthorId::New(uuid) => authors
-                    .get(&uuid)
-                    .expect("author should have been created")
-                    .clone(),
-                AuthorId::Existing(author_id) => author_id,
-            };
+        let api = &self.api;
+        let mut added = stream::iter(books)
+            .map(
+                |NewBook {
+                     title,
+                     isbn,
+                     author_id,
+                 }| {
+                    let author = match author_id {
+                        AuthorId::New(uuid) => authors
+                            .get(&uuid)
+                            .expect("author should have been created")
+                            .clone(),
+                        AuthorId::Existing(author_id) => author_id,
+                    };
+                    let book = domain::BookInfo {
+                        isbn: isbn.to_string(),
+                        title: title.clone(),
+                        author,
+                    };
+                    async move { (title, api.add_book(book).await) }
+                },
+            )
+            .buffer_unordered(concurrency);
+
+        while let Some((title, added)) = added.next().await {
+            match added {
+                Ok(book_id) => summary.books_created.push((book_id, title)),
+                Err(error) => summary.books_failed.push((title, error.into())),
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+struct ReadImporter {
+    api: ApiClient,
+    // Readers by moniker, with the books they have read, including those
+    // marked by this import
+    readers: HashMap<String, Option<(domain::ReaderId, HashSet<domain::BookId>)>>,
+    // Books by hyphenated ISBN
+    books: HashMap<String, Option<(domain::BookId, String)>>,
+    summary: ReadImportSummary,
+}
 
-            self.api
-                .add_book(domain::BookInfo {
-                    isbn: isbn.to_string(),
-                    title,
-                    author,
+impl ReadImporter {
+    async fn import(&mut self, line: usize, ReadRow { moniker, isbn }: ReadRow) -> Result<()> {
+        let unresolved = |reason: String| UnresolvedRead {
+            line,
+            moniker: moniker.clone(),
+            isbn: isbn.clone(),
+            reason,
+        };
+
+        let book = match isbn.parse::<Isbn>() {
+            Ok(parsed) => self.find_book(parsed).await?,
+            Err(error) => {
+                self.summary.unresolved.push(unresolved(format!("{error}")));
+                return Ok(());
+            }
+        };
+        let Some((book_id, title)) = book else {
+            self.summary
+                .unresolved
+                .push(unresolved("no book has this ISBN".to_owned()));
+            return Ok(());
+        };
+        let reader = Self::find_reader(&self.api, &mut self.readers, &moniker).await?;
+        let Some((reader_id, books_read)) = reader else {
+            self.summary
+                .unresolved
+                .push(unresolved("no reader has this moniker".to_owned()));
+            return Ok(());
+        };
+
+        if !books_read.insert(book_id.clone()) {
+            self.summary.reads_skipped += 1;
+        } else if self.summary.dry_run {
+            self.summary.reads_marked.push((moniker, title));
+        } else {
+            match self
+                .api
+                .add_read_book(domain::BookRead {
+                    reader_id: reader_id.clone(),
+                    book_id,
+                    when: None,
                 })
-                .await?;
+                .await
+            {
+                Ok(()) => self.summary.reads_marked.push((moniker, title)),
+                Err(error) => self.summary.reads_failed.push((line, error.into())),
+            }
         }
 
         Ok(())
     }
+
+    // Takes the fields it needs, so that the rest of self stays usable
+    // while the reader is borrowed
+    async fn find_reader<'a>(
+        api: &ApiClient,
+        readers: &'a mut HashMap<String, Option<(domain::ReaderId, HashSet<domain::BookId>)>>,
+        moniker: &str,
+    ) -> Result<Option<&'a mut (domain::ReaderId, HashSet<domain::BookId>)>> {
+        if !readers.contains_key(moniker) {
+            let reader = match api.get_reader_by_moniker(moniker).await? {
+                Some(domain::Reader { id, .. }) => {
+                    let books_read = api
+                        .get_books_read(id.clone())
+                        .await?
+                        .into_iter()
+                        .map(|read| read.book.id)
+                        .collect();
+                    Some((id, books_read))
+                }
+                None => None,
+            };
+            readers.insert(moniker.to_owned(), reader);
+        }
+
+        Ok(readers.get_mut(moniker).and_then(Option::as_mut))
+    }
+
+    // Stored ISBNs may be written differently, so they are compared in
+    // their hyphenated form
+    async fn find_book(&mut self, isbn: Isbn) -> Result<Option<(domain::BookId, String)>> {
+        let isbn = isbn.to_string();
+        if !self.books.contains_key(&isbn) {
+            let book = self.api.search(&isbn).await?.into_iter().find_map(
+                |domain::SearchResultItem { hit, .. }| match hit {
+                    domain::SearchHit::BookIsbn { isbn: found, id }
+                        if found
+                            .parse::<Isbn>()
+                            .is_ok_and(|found| found.to_string() == isbn) =>
+                    {
+                        Some(id)
+                    }
+                    _otherwise => None,
+                },
+            );
+            let book = match book {
+                Some(id) => Some((id.clone(), self.api.get_book(id).await?.info.title)),
+                None => None,
+            };
+            self.books.insert(isbn.clone(), book);
+        }
+
+        Ok(self.books[&isbn].clone())
+    }
+}
+
+// What a read import did, or with dry_run, would do
+#[derive(Default)]
+pub struct ReadImportSummary {
+    pub dry_run: bool,
+    // Monikers and titles
+    pub reads_marked: Vec<(String, String)>,
+    // Already recorded, so not marked again
+    pub reads_skipped: usize,
+    pub unresolved: Vec<UnresolvedRead>,
+    // By line
+    pub reads_failed: Vec<(usize, anyhow::Error)>,
+}
+
+pub struct UnresolvedRead {
+    pub line: usize,
+    pub moniker: String,
+    pub isbn: String,
+    pub reason: String,
+}
+
+impl ReadImportSummary {
+    pub fn table(&self) -> String {
+        let mut builder = Builder::default();
+        builder.push_record(vec!["", "Reader", "Title"]);
+        for (index, (moniker, title)) in self.reads_marked.iter().enumerate() {
+            builder.push_record(vec![
+                format!("{}", index + 1),
+                moniker.to_owned(),
+                title.to_owned(),
+            ])
+        }
+
+        let mut table = format!(
+            "{}\n{} reads {}\n{} rows resolved, {} already recorded and skipped\n\
+             {} rows unresolved",
+            builder.build().with(Style::sharp()),
+            self.reads_marked.len(),
+            if self.dry_run {
+                "would be marked"
+            } else {
+                "marked"
+            },
+            self.reads_marked.len() + self.reads_skipped + self.reads_failed.len(),
+            self.reads_skipped,
+            self.unresolved.len(),
+        );
+        for UnresolvedRead {
+            line,
+            moniker,
+            isbn,
+            reason,
+        } in &self.unresolved
+        {
+            table.push_str(&format!("\nLine {line}, {moniker};{isbn}: {reason}"));
+        }
+        for (line, error) in &self.reads_failed {
+            table.push_str(&format!("\nFailed to mark line {line}: {error}"));
+        }
+
+        table
+    }
 }
 
 struct Isbn(isbn::Isbn);
@@ -209,9 +514,16 @@ pub struct DataRow {
     author: String,
 }
 
-fn read_csv_data<R>(reader: R) -> Result<Vec<DataRow>>
+#[derive(Deserialize)]
+pub struct ReadRow {
+    moniker: String,
+    isbn: String,
+}
+
+fn read_csv_data<R, T>(reader: R) -> Result<Vec<T>>
 where
     R: BufRead,
+    T: DeserializeOwned,
 {
     let mut data = vec![];
     let mut csv = ReaderBuilder::new()
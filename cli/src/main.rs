@@ -1,14 +1,17 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::Parser;
 use import::ImportSource;
+use std::process::ExitCode;
 use uuid::Uuid;
 
-use api_client::{model as domain, ApiClient};
-use model::ImportSpec;
+use api_client::{model as domain, ApiClient, ClientOptions};
+use model::{ImportSpec, ReadImportSpec};
 
 pub mod import;
 pub mod model;
 
+const USER_AGENT: &str = concat!("blister-cli/", env!("CARGO_PKG_VERSION"));
+
 #[derive(Parser)]
 #[command(name = "blister")]
 #[command(about = "A book management CLI")]
@@ -16,6 +19,20 @@ struct CliArgs {
     #[arg(long, value_name = "base-url", help = "Base URL of the blister API")]
     base_url: String,
 
+    #[arg(
+        long,
+        help = "Talk HTTP/2 to the API, which has to support it over plaintext"
+    )]
+    http2: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        default_value = ClientOptions::DEFAULT_RESOURCE_PREFIX,
+        help = "The path the API serves its resources under"
+    )]
+    resource_prefix: String,
+
     #[command(subcommand)]
     command: model::Command,
 }
@@ -27,18 +44,73 @@ impl BookListServiceApi {
         Self(client)
     }
 
+    // Authors by exactly this name, of which there may be none or several
+    async fn find_authors_by_name(&self, name: &str) -> Result<Vec<domain::AuthorId>> {
+        let Self(client) = self;
+        Ok(client
+            .find_author_by_name(name)
+            .await?
+            .into_iter()
+            .map(|author| author.id)
+            .collect())
+    }
+
     async fn dispatch(&self, command: model::Command) -> Result<()> {
         let Self(client) = self;
         match command {
             model::Command::AddAuthor(info) => {
                 Ok(client.add_author(info.try_into()?).await.map(|_| ())?)
             }
-            model::Command::AddBook(info) => {
-                Ok(client.add_book(info.try_into()?).await.map(|_| ())?)
-            }
-            model::Command::AddReader(info) => {
-                Ok(client.add_reader(info.try_into()?).await.map(|_| ())?)
+            model::Command::AddBook(model::BookInfo {
+                title,
+                isbn,
+                author_id,
+                author_name,
+                create_author,
+            }) => {
+                let author = if let Some(name) = author_name {
+                    match self.find_authors_by_name(&name).await?.as_slice() {
+                        [author] => author.clone(),
+                        [] if create_author => {
+                            let author = domain::AuthorInfo { name };
+                            let book = domain::BookWithNewAuthor {
+                                isbn,
+                                title,
+                                author,
+                            };
+                            return Ok(client.add_book_with_author(book).await.map(|_| ())?);
+                        }
+                        [] => {
+                            return Err(anyhow!(
+                                "There is no author named {name}, --create-author adds one"
+                            ))
+                        }
+                        authors => {
+                            let ids = authors.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+                            return Err(anyhow!(
+                                "There are {} authors named {name}, pick one by --author-id: {}",
+                                authors.len(),
+                                ids.join(", ")
+                            ));
+                        }
+                    }
+                } else {
+                    let author_id = author_id.ok_or_else(|| anyhow!("No author was given"))?;
+                    domain::AuthorId(author_id.parse()?)
+                };
+                let info = domain::BookInfo {
+                    isbn,
+                    title,
+                    author,
+                };
+                Ok(client.add_book(info).await.map(|_| ())?)
             }
+            model::Command::AddReader(info) => match client.add_reader(info.try_into()?).await {
+                Err(api_client::error::Error::DuplicateMoniker(moniker)) => Err(anyhow!(
+                    "The moniker {moniker} is already taken, pick another one"
+                )),
+                added => Ok(added.map(|_| ())?),
+            },
             model::Command::ReadBook(model::BookRead {
                 reader_moniker,
                 book_id,
@@ -56,6 +128,28 @@ impl BookListServiceApi {
                     Ok(())
                 }
             }
+            model::Command::GetAuthor { id } => {
+                let author = client.get_author(domain::AuthorId(id)).await?;
+                let books = client.get_books_by_author(domain::AuthorId(id)).await?;
+                println!(
+                    "{}",
+                    model::AuthorDetail(
+                        author.into(),
+                        books.into_iter().map(model::Book::from).collect()
+                    )
+                );
+                Ok(())
+            }
+            model::Command::GetBook { id } => {
+                let book = client.get_book(domain::BookId(id)).await?;
+                let author = client.get_author(book.info.author.clone()).await?;
+                let keywords = client.get_book_keywords(domain::BookId(id)).await?;
+                println!(
+                    "{}",
+                    model::BookDetail(model::BookWithAuthor(book.into(), author.into()), keywords)
+                );
+                Ok(())
+            }
             model::Command::ListAuthors => {
                 let authors = client
                     .get_authors()
@@ -84,7 +178,12 @@ impl BookListServiceApi {
             model::Command::ListReadBooks { reader_ref } => {
                 if let Some(reader_id) = self.resolve_reader_ref(reader_ref).await? {
                     let books = model::BookWithAuthor::joined(
-                        client.get_books_read(reader_id).await?,
+                        client
+                            .get_books_read(reader_id)
+                            .await?
+                            .into_iter()
+                            .map(|read| read.book)
+                            .collect(),
                         client.get_authors().await?,
                     );
                     println!("{}", model::BookWithAuthor::table(books));
@@ -95,18 +194,28 @@ impl BookListServiceApi {
             model::Command::Search {
                 search_term: search,
             } => {
-                let results = client
-                    .search(&search)
-                    .await?
+                let domain::SearchResults {
+                    hits,
+                    truncated,
+                    suggestions,
+                } = client.search_results(&search).await?;
+                let results = hits
                     .into_iter()
                     .map(model::SearchResultItem::from)
                     .collect();
 
                 println!("{}", model::SearchResultItem::table(results));
+                if truncated {
+                    println!("There are more hits; narrow the search to see them.");
+                }
+                if !suggestions.is_empty() {
+                    println!("Did you mean: {}?", suggestions.join(", "));
+                }
 
                 Ok(())
             }
             model::Command::Import(import_spec) => Ok(self.import_data(import_spec).await?),
+            model::Command::ImportReads(import_spec) => Ok(self.import_reads(import_spec).await?),
         }
     }
 
@@ -123,19 +232,68 @@ impl BookListServiceApi {
         }
     }
 
-    async fn import_data(&self, ImportSpec { from, .. }: ImportSpec) -> Result<()> {
+    async fn import_data(
+        &self,
+        ImportSpec {
+            from, concurrency, ..
+        }: ImportSpec,
+    ) -> Result<()> {
+        let Self(api) = self;
+        let source: ImportSource = from.parse()?;
+        let summary = import::from_source(api.clone(), source, concurrency.into()).await?;
+        println!("{}", summary.table());
+
+        if summary.books_failed.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} books failed to import",
+                summary.books_failed.len()
+            ))
+        }
+    }
+
+    async fn import_reads(&self, ReadImportSpec { dry_run, from }: ReadImportSpec) -> Result<()> {
         let Self(api) = self;
         let source: ImportSource = from.parse()?;
-        import::from_source(api.clone(), source).await
+        let summary = import::reads_from_source(api.clone(), source, dry_run).await?;
+        println!("{}", summary.table());
+
+        if summary.reads_failed.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} reads failed to import",
+                summary.reads_failed.len()
+            ))
+        }
+    }
+}
+
+// Tells apart what is missing, what may work on another try, and the rest
+fn describe(error: &anyhow::Error) -> String {
+    match error.downcast_ref::<api_client::error::Error>() {
+        Some(error) if error.is_not_found() => "Not found".to_owned(),
+        Some(error) if error.is_transient() => format!("Server error, try again later: {error}"),
+        _ => format!("{error}"),
     }
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let args = CliArgs::parse();
-    let client = ApiClient::new(&args.base_url);
+    let mut options = ClientOptions::default()
+        .user_agent(USER_AGENT)
+        .resource_prefix(&args.resource_prefix);
+    if args.http2 {
+        options = options.prefer_http2();
+    }
+    let client = ApiClient::with_options(&args.base_url, &options).expect("an HTTP client");
     let api = BookListServiceApi::new(client);
-    api.dispatch(args.command)
-        .await
-        .expect("command dispatch failed");
+    if let Err(error) = api.dispatch(args.command).await {
+        eprintln!("{}", describe(&error));
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
 }
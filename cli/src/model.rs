@@ -12,6 +12,14 @@ pub enum Command {
     AddBook(BookInfo),
     AddReader(ReaderInfo),
     ReadBook(BookRead),
+    GetAuthor {
+        #[arg(value_name = "author-id")]
+        id: Uuid,
+    },
+    GetBook {
+        #[arg(value_name = "book-id")]
+        id: Uuid,
+    },
     ListAuthors,
     ListBooks,
     ListReaders,
@@ -24,6 +32,7 @@ pub enum Command {
         search_term: String,
     },
     Import(ImportSpec),
+    ImportReads(ReadImportSpec),
 }
 
 #[derive(Parser)]
@@ -31,6 +40,23 @@ pub struct ImportSpec {
     #[arg(long, value_enum)]
     pub format: ImportFormat,
 
+    #[arg(
+        long,
+        default_value_t = 4,
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Number of books to add at a time"
+    )]
+    pub concurrency: u16,
+
+    pub from: String,
+}
+
+// Rows of moniker;isbn
+#[derive(Parser)]
+pub struct ReadImportSpec {
+    #[arg(long, help = "Report what would be marked read, without marking it")]
+    pub dry_run: bool,
+
     pub from: String,
 }
 
@@ -47,26 +73,23 @@ pub struct BookInfo {
     #[arg(long, help = "ISBN of the book")]
     pub isbn: String,
 
-    #[arg(long, help = "ID of the author")]
-    pub author_id: String,
-}
-
-impl TryFrom<BookInfo> for domain::BookInfo {
-    type Error = AnyhowError;
-
-    fn try_from(
-        BookInfo {
-            title,
-            isbn,
-            author_id,
-        }: BookInfo,
-    ) -> Result<Self> {
-        Ok(Self {
-            isbn,
-            title,
-            author: domain::AuthorId(author_id.parse()?),
-        })
-    }
+    #[arg(
+        long,
+        required_unless_present = "author_name",
+        conflicts_with = "author_name",
+        help = "ID of the author"
+    )]
+    pub author_id: Option<String>,
+
+    #[arg(long, help = "Name of the author, instead of the ID")]
+    pub author_name: Option<String>,
+
+    #[arg(
+        long,
+        requires = "author_name",
+        help = "Add the author when there is none by that name"
+    )]
+    pub create_author: bool,
 }
 
 #[derive(Parser)]
@@ -109,6 +132,7 @@ impl fmt::Display for Author {
         let Self(domain::Author {
             id: domain::AuthorId(id),
             info: domain::AuthorInfo { name },
+            ..
         }) = self;
         write!(f, "[{id}]\t{name}")
     }
@@ -128,6 +152,7 @@ impl fmt::Display for Book {
         let Self(domain::Book {
             id: domain::BookId(id),
             info: domain::BookInfo { title, .. },
+            ..
         }) = self;
 
         // I would like the author name here.
@@ -186,7 +211,7 @@ impl fmt::Display for Reader {
     }
 }
 
-pub struct BookWithAuthor(Book, Author);
+pub struct BookWithAuthor(pub Book, pub Author);
 
 impl BookWithAuthor {
     pub fn joined(books: Vec<domain::Book>, authors: Vec<domain::Author>) -> Vec<BookWithAuthor> {
@@ -229,10 +254,12 @@ impl fmt::Display for BookWithAuthor {
             Book(domain::Book {
                 id: domain::BookId(book_id),
                 info: domain::BookInfo { isbn, title, .. },
+                ..
             }),
             Author(domain::Author {
                 id: domain::AuthorId(author_id),
                 info: domain::AuthorInfo { name: author_name },
+                ..
             }),
         ) = self;
         writeln!(f, "{title} [{isbn}]")?;
@@ -241,6 +268,45 @@ impl fmt::Display for BookWithAuthor {
     }
 }
 
+// A book as BookWithAuthor shows it, and its keywords
+pub struct BookDetail(pub BookWithAuthor, pub Vec<String>);
+
+impl fmt::Display for BookDetail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self(book, keywords) = self;
+        write!(f, "{book}")?;
+        if !keywords.is_empty() {
+            write!(f, "\nKeywords: {}", keywords.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+pub struct AuthorDetail(pub Author, pub Vec<Book>);
+
+impl fmt::Display for AuthorDetail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self(
+            Author(domain::Author {
+                id: domain::AuthorId(author_id),
+                info: domain::AuthorInfo { name },
+                ..
+            }),
+            books,
+        ) = self;
+        write!(f, "{name} [Author ID {author_id}]")?;
+        for Book(domain::Book {
+            id: domain::BookId(book_id),
+            info: domain::BookInfo { isbn, title, .. },
+            ..
+        }) in books
+        {
+            write!(f, "\n  {title} [{isbn}] [Book ID {book_id}]")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Parser)]
 pub struct BookRead {
     pub reader_moniker: String,
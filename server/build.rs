@@ -0,0 +1,27 @@
+use std::process::Command;
+
+// The commit the server is built from, for GET /version. Builds outside a
+// git checkout say "unknown".
+fn main() {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|output| output.trim().to_owned())
+    };
+
+    // HEAD names the branch, and the branch the commit
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]) {
+        println!("cargo:rerun-if-changed={git_dir}/HEAD");
+        if let Some(branch) = git(&["symbolic-ref", "-q", "HEAD"]) {
+            println!("cargo:rerun-if-changed={git_dir}/{branch}");
+        }
+        println!("cargo:rerun-if-changed={git_dir}/packed-refs");
+    }
+
+    let sha = git(&["rev-parse", "HEAD"]).unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=BLISTER_GIT_SHA={sha}");
+}
@@ -1,84 +1,193 @@
 use std::{
-    collections::{HashMap, HashSet},
+    any::Any,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
     fmt,
-    sync::Arc,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
+use time::OffsetDateTime;
 use tokio::{
     sync::{
-        broadcast::{self, Receiver, Sender},
-        Mutex, RwLock,
+        broadcast::{self, error::RecvError, Receiver, Sender},
+        Mutex, OnceCell, RwLock, RwLockReadGuard,
     },
     task,
+    time::{sleep, timeout},
 };
 
 use crate::{
     error::{Error, Result},
-    infrastructure::{EventDescriptor, EventStore, Termination, TerminationWaiter, UniqueId},
+    infrastructure::{
+        AggregateIdentity, AggregateVersion, Clock, Durability, EventDescriptor, EventStore,
+        IdGenerator, JournalPosition, Persisted, RandomIds, StoreStats, SystemClock, Termination,
+        TerminationWaiter, UniqueId,
+    },
+};
+use model::{
+    query, AuthorId, AuthorInfo, BookId, BookInfo, BookReadInfo, Command, Event, KeywordTarget,
+    ReaderId, ReaderInfo,
 };
-use model::{query, AuthorId, BookId, Command, Event, KeywordTarget, ReaderId};
 
 pub mod model;
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum CommandReceipt {
-    Rejected,
+    // The aggregate was changed by someone else since the expected version
+    Conflict,
     Accepted,
+    // Nothing was done, because what the command records is there already
+    AlreadyExists,
     Created(model::ResourceId),
+    // The book, and the author it was added with
+    CreatedWithAuthor(BookId, AuthorId),
+    // Rejected, for a reason that is told to the client
+    RejectedBecause(Rejection),
+    // The store did not take the events
+    Failed(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rejection {
+    DuplicateMoniker(String),
+    UnknownAuthor(AuthorId),
+    UnknownBook(BookId),
+    UnknownReader(ReaderId),
+    // The target already has the most keywords it may have
+    TooManyKeywords(usize),
+    // A name or title, by field name, that is empty or only whitespace
+    EmptyField(&'static str),
+    // The key came with another command the first time it was used
+    IdempotencyKeyReused(String),
+    // The target has this keyword already
+    DuplicateKeyword(String),
+    // Reading progress is in percent, so this is more than 100
+    ProgressOutOfRange(u8),
+    // The target does not have this keyword to remove
+    MissingKeyword(String),
+    // No book or author has this keyword to rename
+    UnknownKeyword(String),
+    // A keyword renamed to itself
+    SameKeyword(String),
+    // An author merged with itself
+    SameAuthor(AuthorId),
+    // A store-only application takes no commands
+    ReadOnly,
 }
 
 impl CommandReceipt {
     pub fn is_success(&self) -> bool {
-        !matches!(self, Self::Rejected)
+        matches!(
+            self,
+            Self::Accepted | Self::AlreadyExists | Self::Created(..) | Self::CreatedWithAuthor(..)
+        )
     }
 }
 
+// Names and titles are stored without leading and trailing whitespace, and
+// must have something left after that
+fn trim_names(command: Command) -> std::result::Result<Command, Rejection> {
+    let trim = |field, value: String| {
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            Err(Rejection::EmptyField(field))
+        } else if trimmed.len() == value.len() {
+            Ok(value)
+        } else {
+            Ok(trimmed.to_owned())
+        }
+    };
+
+    Ok(match command {
+        Command::AddBook(info) => Command::AddBook(BookInfo {
+            title: trim("title", info.title)?,
+            ..info
+        }),
+        Command::AddBookWithAuthor {
+            isbn,
+            title,
+            author: AuthorInfo { name },
+        } => Command::AddBookWithAuthor {
+            isbn,
+            title: trim("title", title)?,
+            author: AuthorInfo {
+                name: trim("name", name)?,
+            },
+        },
+        Command::AddAuthor(AuthorInfo { name }) => Command::AddAuthor(AuthorInfo {
+            name: trim("name", name)?,
+        }),
+        Command::AddReader(info) => Command::AddReader(ReaderInfo {
+            name: trim("name", info.name)?,
+            ..info
+        }),
+        Command::CorrectBookInfo(id, info, expected_version) => Command::CorrectBookInfo(
+            id,
+            BookInfo {
+                title: trim("title", info.title)?,
+                ..info
+            },
+            expected_version,
+        ),
+        command => command,
+    })
+}
+
+pub const DEFAULT_MAX_KEYWORDS_PER_TARGET: usize = 64;
+
 struct CommandDispatcher<ES> {
-    event_bus: EventBus<ES, Event>,
+    event_bus: Arc<EventBus<ES, Event>>,
     write_model: Arc<RwLock<WriteModel>>,
+    max_keywords_per_target: usize,
+    id_generator: Arc<dyn IdGenerator>,
+    // For the times that commands record, rather than those of their events
+    clock: Arc<dyn Clock>,
 }
 
 impl<ES> CommandDispatcher<ES>
 where
     ES: EventStore,
 {
-    fn new(event_bus: EventBus<ES, Event>) -> Self {
+    fn new(event_bus: Arc<EventBus<ES, Event>>) -> Self {
         Self {
             event_bus,
             write_model: Default::default(),
+            max_keywords_per_target: DEFAULT_MAX_KEYWORDS_PER_TARGET,
+            id_generator: Arc::new(RandomIds),
+            clock: Arc::new(SystemClock),
         }
     }
 
-    async fn start(&self, terminate: TerminationWaiter) -> task::JoinHandle<()> {
-        let events = self.event_bus.subscribe();
+    async fn start(&self, terminate: TerminationWaiter) -> Result<task::JoinHandle<()>>
+    where
+        ES: Send + 'static,
+    {
+        let events = Arc::new(self.event_bus.subscribe());
+        let event_bus = Arc::clone(&self.event_bus);
         let write_model = Arc::clone(&self.write_model);
 
         // Is there a race condition between this and the ReadModel subscriber?
-        self.event_bus
-            .replay_journal()
-            .await
-            .expect("a working replay");
-
-        task::spawn(async move {
-            // Can this be inverted somehow? No.
-            // The loop would  have to have a select in its body
-            // that inspects the terminate condition. Right?
-            loop {
-                tokio::select! {
-                    event = events.poll() => {
-                        if let Ok(event) = event {
-                            write_model.write().await.apply(event)
-                        } else {
-                            break
-                        }
-                    }
-                    _ = terminate.wait() => {
-                        break;
-                    }
-                }
-            }
-        })
+        self.event_bus.replay_journal().await?;
+
+        // Nothing asks how many events the write model has applied
+        Ok(task::spawn(follow_events(
+            event_bus,
+            events,
+            write_model,
+            Arc::default(),
+            terminate,
+        )))
     }
 
     async fn accept(&self, command: Command) -> CommandReceipt {
+        let command = match trim_names(command) {
+            Ok(command) => command,
+            Err(rejection) => return CommandReceipt::RejectedBecause(rejection),
+        };
+
         match command {
             Command::AddBook(info) => {
                 // Can this be transplanted onto a Book aggregate
@@ -92,24 +201,48 @@ where
                     .author_ids
                     .contains(&info.author)
                 {
-                    let id = BookId(UniqueId::fresh());
-                    self.event_bus
-                        .emit(Event::BookAdded(id, info))
-                        .await
-                        .expect("emit");
-                    CommandReceipt::Created(id.into())
+                    let id = BookId(self.id_generator.fresh());
+                    Self::receipt_of(
+                        self.event_bus.emit(Event::BookAdded(id, info)).await,
+                        CommandReceipt::Created(id.into()),
+                    )
                 } else {
-                    CommandReceipt::Rejected
+                    CommandReceipt::RejectedBecause(Rejection::UnknownAuthor(info.author))
                 }
             }
+            // The write model has not necessarily seen the author when the
+            // book is emitted, which is why this does not go through AddBook
+            Command::AddBookWithAuthor {
+                isbn,
+                title,
+                author,
+            } => {
+                let author_id = AuthorId(self.id_generator.fresh());
+                let id = BookId(self.id_generator.fresh());
+                Self::receipt_of(
+                    self.event_bus
+                        .emit_all(vec![
+                            Event::AuthorAdded(author_id, author),
+                            Event::BookAdded(
+                                id,
+                                BookInfo {
+                                    isbn,
+                                    title,
+                                    author: author_id,
+                                },
+                            ),
+                        ])
+                        .await,
+                    CommandReceipt::CreatedWithAuthor(id, author_id),
+                )
+            }
             Command::AddAuthor(info) => {
                 // This should really check to make sure that it won't accept duplicates
-                let id = AuthorId(UniqueId::fresh());
-                self.event_bus
-                    .emit(Event::AuthorAdded(id, info))
-                    .await
-                    .expect("emit");
-                CommandReceipt::Created(id.into())
+                let id = AuthorId(self.id_generator.fresh());
+                Self::receipt_of(
+                    self.event_bus.emit(Event::AuthorAdded(id, info)).await,
+                    CommandReceipt::Created(id.into()),
+                )
             }
             Command::AddReader(info) => {
                 if !self
@@ -119,242 +252,2664 @@ where
                     .reader_id_by_moniker
                     .contains_key(&info.unique_moniker)
                 {
-                    let id = ReaderId(UniqueId::fresh());
-                    self.event_bus
-                        .emit(Event::ReaderAdded(id, info))
-                        .await
-                        .expect("emit");
-                    CommandReceipt::Created(id.into())
+                    let id = ReaderId(self.id_generator.fresh());
+                    Self::receipt_of(
+                        self.event_bus.emit(Event::ReaderAdded(id, info)).await,
+                        CommandReceipt::Created(id.into()),
+                    )
                 } else {
-                    CommandReceipt::Rejected
+                    CommandReceipt::RejectedBecause(Rejection::DuplicateMoniker(
+                        info.unique_moniker,
+                    ))
                 }
             }
+            // See BookReadInfo for what counts as the same read
             Command::AddReadBook(info) => {
-                if !self
-                    .write_model
-                    .read()
-                    .await
+                let write_model = self.write_model.read().await;
+                if !write_model.reader_ids.contains(&info.reader_id) {
+                    CommandReceipt::RejectedBecause(Rejection::UnknownReader(info.reader_id))
+                } else if !write_model.books.contains_key(&info.book_id) {
+                    CommandReceipt::RejectedBecause(Rejection::UnknownBook(info.book_id))
+                } else if write_model
                     .books_read
                     .get(&info.reader_id)
-                    .is_some_and(|books| books.contains(&info.book_id))
+                    .is_some_and(|reads| reads.contains(&info))
                 {
-                    self.event_bus
-                        .emit(Event::BookRead(info.reader_id, info))
-                        .await
-                        .expect("emit");
-                    CommandReceipt::Accepted
+                    CommandReceipt::AlreadyExists
                 } else {
-                    CommandReceipt::Rejected
+                    drop(write_model);
+                    Self::receipt_of(
+                        self.event_bus
+                            .emit(Event::BookRead(info.reader_id, info))
+                            .await,
+                        CommandReceipt::Accepted,
+                    )
                 }
             }
-            Command::AddKeyword(keyword, target) => {
-                if !self
+            Command::AddKeyword(keyword, target, expected_version) => {
+                let write_model = self.write_model.read().await;
+                let target_keywords = write_model.target_keywords.get(&target);
+                if target_keywords.is_some_and(|keywords| keywords.contains(keyword.as_ref())) {
+                    CommandReceipt::RejectedBecause(Rejection::DuplicateKeyword(
+                        keyword.into_string(),
+                    ))
+                } else if target_keywords.map_or(0, HashSet::len) >= self.max_keywords_per_target {
+                    CommandReceipt::RejectedBecause(Rejection::TooManyKeywords(
+                        self.max_keywords_per_target,
+                    ))
+                } else {
+                    drop(write_model);
+                    self.emit_at(
+                        Event::KeywordAdded(target, keyword.into_string()),
+                        expected_version,
+                    )
+                    .await
+                }
+            }
+            Command::RemoveKeyword(keyword, target, expected_version) => {
+                if self
                     .write_model
                     .read()
                     .await
-                    .keyword_targets
-                    .get(keyword.as_ref())
-                    .is_some_and(|targets| targets.contains(&target))
+                    .target_keywords
+                    .get(&target)
+                    .is_some_and(|keywords| keywords.contains(keyword.as_ref()))
                 {
-                    self.event_bus
-                        .emit(Event::KeywordAdded(target, keyword.into_string()))
+                    self.emit_at(
+                        Event::KeywordRemoved(target, keyword.into_string()),
+                        expected_version,
+                    )
+                    .await
+                } else {
+                    CommandReceipt::RejectedBecause(Rejection::MissingKeyword(
+                        keyword.into_string(),
+                    ))
+                }
+            }
+            Command::CorrectBookInfo(id, info, expected_version) => {
+                let write_model = self.write_model.read().await;
+                if !write_model.books.contains_key(&id) {
+                    CommandReceipt::RejectedBecause(Rejection::UnknownBook(id))
+                } else if !write_model.author_ids.contains(&info.author) {
+                    CommandReceipt::RejectedBecause(Rejection::UnknownAuthor(info.author))
+                } else {
+                    drop(write_model);
+                    self.emit_at(Event::BookInfoCorrected(id, info), expected_version)
                         .await
-                        .expect("emit");
-                    CommandReceipt::Accepted
+                }
+            }
+            Command::DeleteBook(id, expected_version) => {
+                if self.write_model.read().await.books.contains_key(&id) {
+                    self.emit_at(Event::BookRemoved(id), expected_version).await
+                } else {
+                    CommandReceipt::RejectedBecause(Rejection::UnknownBook(id))
+                }
+            }
+            Command::RenameKeyword { from, to } => {
+                let write_model = self.write_model.read().await;
+                if from == to {
+                    CommandReceipt::RejectedBecause(Rejection::SameKeyword(from.into_string()))
+                } else if !write_model.keyword_targets.contains_key(from.as_ref()) {
+                    CommandReceipt::RejectedBecause(Rejection::UnknownKeyword(from.into_string()))
+                } else {
+                    drop(write_model);
+                    Self::receipt_of(
+                        self.event_bus
+                            .emit(Event::KeywordRenamed(from.into_string(), to.into_string()))
+                            .await,
+                        CommandReceipt::Accepted,
+                    )
+                }
+            }
+            Command::UpdateReadingProgress(reader_id, book_id, percent) => {
+                let write_model = self.write_model.read().await;
+                if percent > 100 {
+                    CommandReceipt::RejectedBecause(Rejection::ProgressOutOfRange(percent))
+                } else if !write_model.books.contains_key(&book_id) {
+                    CommandReceipt::RejectedBecause(Rejection::UnknownBook(book_id))
+                } else if !write_model.reader_ids.contains(&reader_id) {
+                    CommandReceipt::RejectedBecause(Rejection::UnknownReader(reader_id))
+                } else {
+                    drop(write_model);
+                    let mut events =
+                        vec![Event::ReadingProgressUpdated(reader_id, book_id, percent)];
+                    if percent == 100 {
+                        let info = BookReadInfo {
+                            reader_id,
+                            book_id,
+                            when: Some(self.clock.now()),
+                        };
+                        events.push(Event::BookRead(reader_id, info));
+                    }
+                    Self::receipt_of(
+                        self.event_bus.emit_all(events).await,
+                        CommandReceipt::Accepted,
+                    )
+                }
+            }
+            Command::MergeAuthors { keep, merge } => {
+                let write_model = self.write_model.read().await;
+                if keep == merge {
+                    CommandReceipt::RejectedBecause(Rejection::SameAuthor(keep))
+                } else if let Some(unknown) = [keep, merge]
+                    .into_iter()
+                    .find(|id| !write_model.author_ids.contains(id))
+                {
+                    CommandReceipt::RejectedBecause(Rejection::UnknownAuthor(unknown))
                 } else {
-                    CommandReceipt::Rejected
+                    // The merged author's books are corrected to point at the
+                    // author that is kept, so that they load that way too
+                    let mut events = write_model
+                        .books
+                        .iter()
+                        .filter(|(_, info)| info.author == merge)
+                        .map(|(id, info)| {
+                            Event::BookInfoCorrected(
+                                *id,
+                                BookInfo {
+                                    author: keep,
+                                    ..info.clone()
+                                },
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    events.push(Event::AuthorMerged(merge, keep));
+                    drop(write_model);
+                    Self::receipt_of(
+                        self.event_bus.emit_all(events).await,
+                        CommandReceipt::Accepted,
+                    )
                 }
             }
         }
     }
+
+    // Persists the event only if its aggregate is still at the expected
+    // version, when one is given; the store checks that as it writes.
+    async fn emit_at(&self, event: Event, expected: Option<AggregateVersion>) -> CommandReceipt {
+        let emitted = if let Some(expected) = expected {
+            self.event_bus.emit_expecting(event, expected).await
+        } else {
+            self.event_bus.emit(event).await
+        };
+
+        Self::receipt_of(emitted, CommandReceipt::Accepted)
+    }
+
+    // The receipt for a command whose events were emitted, or a failure
+    // for the client, rather than a panic, when the store did not take them
+    fn receipt_of(emitted: Result<()>, receipt: CommandReceipt) -> CommandReceipt {
+        match emitted {
+            Ok(()) => receipt,
+            Err(Error::ConcurrencyConflict { .. }) => CommandReceipt::Conflict,
+            Err(error) => {
+                tracing::error!("Failed to emit: {error}");
+                CommandReceipt::Failed(error.to_string())
+            }
+        }
+    }
+}
+
+// The built-in IndexSet that IndexSetQuery runs against, followed by any
+// projections registered at construction.
+struct ReadModel {
+    index_set: query::IndexSet,
+    registered: Vec<Box<dyn Projection>>,
+    projections: Projections,
+}
+
+impl ReadModel {
+    fn new(projections: Projections) -> Self {
+        Self {
+            index_set: query::IndexSet::default(),
+            registered: projections.instantiate(),
+            projections,
+        }
+    }
+
+    fn find<P>(&self) -> Option<&P>
+    where
+        P: Projection,
+    {
+        self.registered
+            .iter()
+            .find_map(|projection| (projection.as_ref() as &dyn Any).downcast_ref())
+    }
 }
 
-struct QueryHandler {
-    read_model: Arc<RwLock<query::IndexSet>>,
+struct QueryHandler<ES> {
+    read_model: Arc<RwLock<ReadModel>>,
     event_source: Arc<EventBusSubscription<Event>>,
+    event_bus: Arc<EventBus<ES, Event>>,
+    timeout: Duration,
+    max_search_hits: usize,
+    applied_events: Arc<AtomicU64>,
 }
 
-impl QueryHandler {
-    fn new(subscription: EventBusSubscription<Event>) -> Self {
+impl<ES> QueryHandler<ES>
+where
+    ES: EventStore,
+{
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn new(event_bus: Arc<EventBus<ES, Event>>, projections: Projections) -> Self {
         Self {
-            read_model: Default::default(),
-            event_source: Arc::new(subscription),
+            read_model: Arc::new(RwLock::new(ReadModel::new(projections))),
+            event_source: Arc::new(event_bus.subscribe()),
+            event_bus,
+            timeout: Self::TIMEOUT,
+            max_search_hits: query::text::DEFAULT_MAX_SEARCH_HITS,
+            applied_events: Arc::default(),
         }
     }
 
-    fn start(&self, termination: TerminationWaiter) -> task::JoinHandle<()> {
+    fn start(&self, termination: TerminationWaiter) -> task::JoinHandle<()>
+    where
+        ES: Send + 'static,
+    {
         let read_model = Arc::clone(&self.read_model);
         let event_source = Arc::clone(&self.event_source);
+        let event_bus = Arc::clone(&self.event_bus);
 
-        task::spawn(async move {
-            loop {
-                tokio::select! {
-                    // is it necessary to have this wrapper? It looks better
-                    // but causes a Mutex
-                    event = event_source.poll() => {
-                        if let Ok(event) = event {
-                            read_model.write().await.apply(event)
-                        } else {
-                            break
-                        }
-                    }
-                    _ = termination.wait() => { break }
-                }
-            }
-        })
+        task::spawn(follow_events(
+            event_bus,
+            event_source,
+            read_model,
+            Arc::clone(&self.applied_events),
+            termination,
+        ))
+    }
+
+    // Dropping a pending read() leaves the lock's wait queue, so both a
+    // timeout and a cancelled caller are safe here.
+    async fn read(&self) -> Result<RwLockReadGuard<'_, ReadModel>> {
+        timeout(self.timeout, self.read_model.read())
+            .await
+            .map_err(|_| Error::QueryTimeout)
     }
 
+    // Nothing is awaited while the read model is held, so a query that is
+    // dropped, say because its client went away, either still waits for it
+    // or is done with it.
     async fn issue<Q>(&self, query: Q) -> Result<Q::Output>
     where
         Q: query::IndexSetQuery,
     {
-        let read_model = self.read_model.read().await;
-        Ok(query.execute(&read_model))
+        Ok(query.execute(&self.read().await?.index_set))
+    }
+
+    async fn inspect<P, A>(&self, inspect: impl FnOnce(&P) -> A) -> Result<Option<A>>
+    where
+        P: Projection,
+    {
+        Ok(self.read().await?.find().map(inspect))
+    }
+
+    // The read model is held throughout, so queries wait for the new index
+    // rather than see a partial one. Events that are yet to reach the read
+    // model are in the journal already; applying them again changes nothing.
+    async fn reindex_texts(&self, term_length_threshold: Option<usize>) -> Result<usize> {
+        let mut read_model = self.read_model.write().await;
+        let journal = self.event_bus.journal_events().await?;
+        read_model
+            .index_set
+            .reindex_texts(&journal, term_length_threshold);
+
+        Ok(read_model.index_set.term_length_threshold())
     }
 }
 
-pub struct Application<ES> {
-    command_dispatcher: CommandDispatcher<ES>,
-    query_handler: QueryHandler,
+// Something that is built by folding events, such as the read- and
+// write models. Embedders register their own through Projections.
+pub trait Projection: Any + Send + Sync {
+    fn apply(&mut self, event: &Event);
+
+    // With the time the event was journaled at, for projections that keep
+    // track of when things happened
+    fn apply_at(&mut self, event: &Event, _when: OffsetDateTime) {
+        self.apply(event)
+    }
 }
 
-impl<ES> Application<ES>
-where
-    ES: EventStore,
-{
-    pub fn new(event_bus: EventBus<ES, Event>) -> Self {
-        let event_subscription = event_bus.subscribe();
-        Application {
-            command_dispatcher: CommandDispatcher::new(event_bus),
-            query_handler: QueryHandler::new(event_subscription),
+// Constructors for the projections an Application maintains in addition to
+// its own; a lagging read model uses them to start over from the journal.
+#[derive(Clone, Default)]
+pub struct Projections {
+    constructors: Vec<fn() -> Box<dyn Projection>>,
+}
+
+impl Projections {
+    pub fn register<P>(mut self) -> Self
+    where
+        P: Projection + Default,
+    {
+        self.constructors.push(|| Box::new(P::default()));
+        self
+    }
+
+    fn instantiate(&self) -> Vec<Box<dyn Projection>> {
+        self.constructors
+            .iter()
+            .map(|constructor| constructor())
+            .collect()
+    }
+}
+
+// A projection that a lagging subscriber can throw away and rebuild.
+trait Rebuild: Projection {
+    fn empty(&self) -> Self;
+}
+
+impl Projection for query::IndexSet {
+    fn apply(&mut self, event: &Event) {
+        query::IndexSet::apply(self, event.clone())
+    }
+
+    fn apply_at(&mut self, event: &Event, when: OffsetDateTime) {
+        query::IndexSet::apply_at(self, event.clone(), when)
+    }
+}
+
+impl Projection for ReadModel {
+    fn apply(&mut self, event: &Event) {
+        self.index_set.apply(event.clone());
+        for projection in &mut self.registered {
+            projection.apply(event)
         }
     }
 
-    pub async fn start(&self, termination: &Termination) {
-        let waiter = termination.waiter();
-        tokio::select! {
-            _ = self.command_dispatcher.start(termination.waiter()) => {}
-            _ = self.query_handler.start(termination.waiter()) => {}
-            _ = waiter.wait() => {}
+    fn apply_at(&mut self, event: &Event, when: OffsetDateTime) {
+        self.index_set.apply_at(event.clone(), when);
+        for projection in &mut self.registered {
+            projection.apply_at(event, when)
         }
     }
+}
 
-    pub async fn issue_query<Q>(&self, query: Q) -> Result<Q::Output>
-    where
-        Q: query::IndexSetQuery,
-    {
-        self.query_handler.issue(query).await
+impl Rebuild for ReadModel {
+    fn empty(&self) -> Self {
+        Self {
+            index_set: self.index_set.emptied(),
+            registered: self.projections.instantiate(),
+            projections: self.projections.clone(),
+        }
     }
+}
 
-    // Should be Result<(), ValidationError>
-    pub async fn submit_command(&self, command: Command) -> CommandReceipt {
-        self.command_dispatcher.accept(command).await
+impl Projection for WriteModel {
+    fn apply(&mut self, event: &Event) {
+        WriteModel::apply(self, event.clone())
     }
 }
 
-// This has to lose the EventStore.
-// But can I make this know about the concrete event type?
-pub struct EventBus<ES, E> {
-    event_store: Mutex<ES>,
-    tx: Sender<E>,
+impl Rebuild for WriteModel {
+    fn empty(&self) -> Self {
+        Self::default()
+    }
 }
 
-impl<ES, E> EventBus<ES, E>
-where
-    ES: EventStore,
-    E: EventDescriptor + Sync + Send + Clone + fmt::Debug + 'static,
+// A subscriber that lags behind has lost events, so rather than carry on
+// with gaps it resubscribes and rebuilds its projection from the journal.
+async fn follow_events<ES, P>(
+    event_bus: Arc<EventBus<ES, Event>>,
+    mut events: Arc<EventBusSubscription<Event>>,
+    projection: Arc<RwLock<P>>,
+    applied_events: Arc<AtomicU64>,
+    terminate: TerminationWaiter,
+) where
+    ES: EventStore + Send + 'static,
+    P: Rebuild,
 {
-    pub fn new(event_store: ES) -> Self {
-        let (tx, _rx) = broadcast::channel(100);
-        Self {
-            event_store: Mutex::new(event_store),
-            tx,
+    loop {
+        tokio::select! {
+            event = events.poll() => match event {
+                Ok((event, when)) => {
+                    projection.write().await.apply_at(&event, when);
+                    applied_events.fetch_add(1, Ordering::Release);
+                }
+                Err(Error::ReceiveError(RecvError::Lagged(skipped))) => {
+                    event_bus.record_lag(skipped);
+                    match event_bus.resubscribe_and_replay().await {
+                        Ok((subscription, journal)) => {
+                            let mut rebuilt = projection.read().await.empty();
+                            for (event, when) in &journal {
+                                rebuilt.apply_at(event, *when)
+                            }
+                            *projection.write().await = rebuilt;
+                            applied_events.store(journal.len() as u64, Ordering::Release);
+                            events = Arc::new(subscription);
+                        }
+                        Err(error) => {
+                            tracing::error!("Unable to re-sync lagging subscriber: {error}");
+                            break;
+                        }
+                    }
+                }
+                Err(_) => break,
+            },
+            _ = terminate.wait() => break,
         }
     }
+}
 
-    async fn replay_journal(&self) -> Result<()> {
-        for record in self.event_store.lock().await.journal().await? {
-            let event: E = EventDescriptor::from_external_representation(&record)?;
-            self.tx
-                .send(event)
-                .map_err(|broadcast::error::SendError(event)| {
-                    Error::Generic(format!("SendError {event:?}"))
-                })?;
+// A client supplied key that makes retrying a command safe: a command
+// submitted again with the same key yields the original receipt instead of
+// being accepted anew.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct IdempotencyKey(pub String);
+
+// Keys are kept for a day, and no more than CAPACITY of them; the oldest go
+// first. Each key remembers the command it came with, so that it cannot be
+// reused for another one.
+struct IdempotencyLog {
+    entries: HashMap<IdempotencyKey, IdempotencyEntry>,
+    // Oldest first, each key once
+    recorded: VecDeque<(Instant, IdempotencyKey)>,
+}
+
+struct IdempotencyEntry {
+    fingerprint: u64,
+    // Set once the first submission has been processed. Retries wait on it.
+    receipt: Arc<OnceCell<CommandReceipt>>,
+}
+
+impl IdempotencyLog {
+    const TIME_TO_LIVE: Duration = Duration::from_secs(24 * 60 * 60);
+    const CAPACITY: usize = 10_000;
+
+    fn new() -> Self {
+        Self {
+            entries: HashMap::default(),
+            recorded: VecDeque::default(),
         }
-        Ok(())
     }
 
-    // Can I do something here to force a persist to be required before issuing a send?
-    // It is not possible to
-    async fn emit(&self, event: E) -> Result<()> {
-        let mut store = self.event_store.lock().await;
-        store.persist(event.clone()).await?;
-        self.tx
-            .send(event)
-            .map_err(|broadcast::error::SendError(event)| {
-                Error::Generic(format!("Unable to send {:?} to subscribers", event).to_owned())
-            })?;
+    fn fingerprint(command: &Command) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        command.hash(&mut hasher);
+        hasher.finish()
+    }
 
-        Ok(())
+    // The receipt of the first command submitted with key, or None if that
+    // was another command
+    fn receipt(
+        &mut self,
+        key: IdempotencyKey,
+        command: &Command,
+    ) -> Option<Arc<OnceCell<CommandReceipt>>> {
+        self.evict();
+
+        let fingerprint = Self::fingerprint(command);
+        if let Some(entry) = self.entries.get(&key) {
+            return (entry.fingerprint == fingerprint).then(|| Arc::clone(&entry.receipt));
+        }
+
+        let receipt = Arc::new(OnceCell::new());
+        self.entries.insert(
+            key.clone(),
+            IdempotencyEntry {
+                fingerprint,
+                receipt: Arc::clone(&receipt),
+            },
+        );
+        self.recorded.push_back((Instant::now(), key));
+        Some(receipt)
     }
 
-    fn subscribe(&self) -> EventBusSubscription<E> {
-        EventBusSubscription::new(self.tx.subscribe())
+    fn evict(&mut self) {
+        while let Some((recorded, _)) = self.recorded.front() {
+            if recorded.elapsed() < Self::TIME_TO_LIVE && self.recorded.len() < Self::CAPACITY {
+                break;
+            }
+            if let Some((_, key)) = self.recorded.pop_front() {
+                self.entries.remove(&key);
+            }
+        }
     }
 }
 
-struct EventBusSubscription<E> {
-    rx: Mutex<Receiver<E>>,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadModelStatus {
+    pub ready: bool,
+    pub applied_events: u64,
 }
 
-impl<E> EventBusSubscription<E>
+// The command dispatcher and query handler are left out of a store-only
+// Application, which then answers only what the event store itself can.
+pub struct Application<ES> {
+    event_bus: Arc<EventBus<ES, Event>>,
+    command_dispatcher: Option<CommandDispatcher<ES>>,
+    query_handler: Option<QueryHandler<ES>>,
+    idempotency_log: Mutex<IdempotencyLog>,
+}
+
+impl<ES> Application<ES>
 where
-    E: EventDescriptor + Clone,
+    ES: EventStore,
 {
-    fn new(rx: Receiver<E>) -> Self {
-        Self { rx: Mutex::new(rx) }
+    pub fn new(event_bus: EventBus<ES, Event>) -> Self {
+        Self::with_projections(event_bus, Projections::default())
     }
 
-    async fn poll(&self) -> Result<E> {
-        Ok(self.rx.lock().await.recv().await?)
+    pub fn builder(event_store: ES) -> ApplicationBuilder<ES> {
+        ApplicationBuilder::new(event_store)
     }
-}
 
-#[derive(Default)]
-struct WriteModel {
-    author_name_ids: HashMap<String, Vec<AuthorId>>,
-    author_ids: HashSet<AuthorId>,
+    pub fn with_projections(event_bus: EventBus<ES, Event>, projections: Projections) -> Self {
+        let event_bus = Arc::new(event_bus);
+        Application {
+            query_handler: Some(QueryHandler::new(Arc::clone(&event_bus), projections)),
+            command_dispatcher: Some(CommandDispatcher::new(Arc::clone(&event_bus))),
+            event_bus,
+            idempotency_log: Mutex::new(IdempotencyLog::new()),
+        }
+    }
 
-    book_title_ids: HashMap<String, Vec<BookId>>,
+    // For tools that only read the journal or load aggregates: there is no
+    // read- or write model to build, so it starts at once however large the
+    // journal. Queries fail and commands are rejected.
+    pub fn store_only(event_bus: EventBus<ES, Event>) -> Self {
+        Application {
+            event_bus: Arc::new(event_bus),
+            command_dispatcher: None,
+            query_handler: None,
+            idempotency_log: Mutex::new(IdempotencyLog::new()),
+        }
+    }
 
-    reader_id_by_moniker: HashMap<String, ReaderId>,
-    books_read: HashMap<ReaderId, HashSet<BookId>>,
+    // Keywords added to a book or author past this many are rejected
+    pub fn with_max_keywords_per_target(mut self, max: usize) -> Self {
+        if let Some(command_dispatcher) = self.command_dispatcher.as_mut() {
+            command_dispatcher.max_keywords_per_target = max;
+        }
+        self
+    }
 
-    keyword_targets: HashMap<String, HashSet<KeywordTarget>>,
-}
+    // Indexes the trigrams of every term, so that searches can match
+    // within words; see IndexSet::with_infix_search. Has to be set before
+    // the application starts.
+    pub fn with_infix_search(mut self) -> Self {
+        if let Some(read_model) = self
+            .query_handler
+            .as_mut()
+            .and_then(|query_handler| Arc::get_mut(&mut query_handler.read_model))
+        {
+            read_model.get_mut().index_set = query::IndexSet::with_infix_search();
+        }
+        self
+    }
 
-impl WriteModel {
-    fn apply(&mut self, event: Event) {
-        match event {
-            Event::BookAdded(id, info) => {
-                self.book_title_ids.entry(info.title).or_default().push(id)
-            }
-            Event::AuthorAdded(id, info) => {
-                self.author_name_ids.entry(info.name).or_default().push(id);
-                self.author_ids.insert(id);
-            }
-            Event::ReaderAdded(id, info) => {
-                self.reader_id_by_moniker.insert(info.unique_moniker, id);
-            }
-            Event::BookRead(id, info) => {
-                self.books_read.entry(id).or_default().insert(info.book_id);
-            }
-            Event::KeywordAdded(target, keyword) => {
-                self.keyword_targets
-                    .entry(keyword)
-                    .or_default()
-                    .insert(target);
-            }
+    // Searches rank no more than this many hits
+    pub fn with_max_search_hits(mut self, max: usize) -> Self {
+        if let Some(query_handler) = self.query_handler.as_mut() {
+            query_handler.max_search_hits = max;
+        }
+        self
+    }
+
+    pub fn max_search_hits(&self) -> usize {
+        self.query_handler
+            .as_ref()
+            .map_or(query::text::DEFAULT_MAX_SEARCH_HITS, |query_handler| {
+                query_handler.max_search_hits
+            })
+    }
+
+    // Whether the read model has caught up with the journal that was
+    // replayed when the Application started, and how many events it has
+    // applied. There is no read model in a store-only Application. Only
+    // atomics are read, so this is cheap to ask on every request.
+    pub fn read_model_status(&self) -> Option<ReadModelStatus> {
+        let query_handler = self.query_handler.as_ref()?;
+        let applied_events = query_handler.applied_events.load(Ordering::Acquire);
+        Some(ReadModelStatus {
+            ready: self
+                .event_bus
+                .replayed_event_count()
+                .is_some_and(|replayed| applied_events >= replayed),
+            applied_events,
+        })
+    }
+
+    fn query_handler(&self) -> Result<&QueryHandler<ES>> {
+        self.query_handler
+            .as_ref()
+            .ok_or(Error::MissingComponent("query handler"))
+    }
+
+    // Fails if the journal cannot be replayed
+    pub async fn start(&self, termination: &Termination) -> Result<()>
+    where
+        ES: Send + 'static,
+    {
+        let waiter = termination.waiter();
+        match (&self.command_dispatcher, &self.query_handler) {
+            (Some(command_dispatcher), Some(query_handler)) => tokio::select! {
+                started = command_dispatcher.start(termination.waiter()) => started.map(|_| ()),
+                _ = query_handler.start(termination.waiter()) => Ok(()),
+                _ = waiter.wait() => Ok(()),
+            },
+            (Some(command_dispatcher), None) => command_dispatcher
+                .start(termination.waiter())
+                .await
+                .map(|_| ()),
+            (None, Some(_)) => self.start_read_only(termination).await,
+            (None, None) => Ok(()),
         }
     }
+
+    // Follows the journal with the read model only. Commands must not be
+    // submitted to an Application started this way, as it keeps no write
+    // model to validate them against.
+    pub async fn start_read_only(&self, termination: &Termination) -> Result<()>
+    where
+        ES: Send + 'static,
+    {
+        // The handle is not needed, the task stops with the termination
+        drop(self.query_handler()?.start(termination.waiter()));
+        self.event_bus.replay_journal().await
+    }
+
+    // Picks up events that other instances write to a shared store, polling
+    // for them every interval from where the replay ends. Fails once the
+    // application is started, as what was written since could be missed.
+    pub async fn follow_store(
+        &self,
+        interval: Duration,
+        termination: &Termination,
+    ) -> Result<task::JoinHandle<()>>
+    where
+        ES: Send + Sync + 'static,
+    {
+        let event_bus = Arc::clone(&self.event_bus);
+        event_bus.record_emitted_from_now().await?;
+        let waiter = termination.waiter();
+
+        Ok(task::spawn(async move {
+            event_bus.follow_journal(interval, waiter).await
+        }))
+    }
+
+    pub async fn issue_query<Q>(&self, query: Q) -> Result<Q::Output>
+    where
+        Q: query::IndexSetQuery,
+    {
+        self.query_handler()?.issue(query).await
+    }
+
+    // None if no projection of type P was registered
+    pub async fn inspect_projection<P, A>(&self, inspect: impl FnOnce(&P) -> A) -> Result<Option<A>>
+    where
+        P: Projection,
+    {
+        self.query_handler()?.inspect(inspect).await
+    }
+
+    // Should be Result<(), ValidationError>
+    pub async fn submit_command(&self, command: Command) -> CommandReceipt {
+        if let Some(command_dispatcher) = &self.command_dispatcher {
+            command_dispatcher.accept(command).await
+        } else {
+            tracing::warn!("Rejecting a command to a store-only application");
+            CommandReceipt::RejectedBecause(Rejection::ReadOnly)
+        }
+    }
+
+    // The log is only held to look the key up. A retry that arrives while
+    // the first submission is still being processed waits for its receipt,
+    // so that both cannot be accepted; other keys go ahead meanwhile.
+    pub async fn submit_idempotent_command(
+        &self,
+        key: Option<IdempotencyKey>,
+        command: Command,
+    ) -> CommandReceipt {
+        let Some(key) = key else {
+            return self.submit_command(command).await;
+        };
+
+        let receipt = self
+            .idempotency_log
+            .lock()
+            .await
+            .receipt(key.clone(), &command);
+        // A failure is not remembered, so that a retry submits the command again
+        if let Some(receipt) = receipt {
+            receipt
+                .get_or_try_init(|| async {
+                    match self.submit_command(command).await {
+                        CommandReceipt::Failed(error) => Err(error),
+                        receipt => Ok(receipt),
+                    }
+                })
+                .await
+                .map_or_else(CommandReceipt::Failed, Clone::clone)
+        } else {
+            let IdempotencyKey(key) = key;
+            CommandReceipt::RejectedBecause(Rejection::IdempotencyKeyReused(key))
+        }
+    }
+
+    // Loads the aggregate straight from the event store, bypassing the
+    // read model.
+    pub async fn load_aggregate<Aggregate>(&self, aggregate: Aggregate) -> Result<Aggregate::Root>
+    where
+        ES: Send + Sync,
+        Aggregate: AggregateIdentity + Send,
+    {
+        self.event_bus.load_aggregate(aggregate).await
+    }
+
+    pub async fn aggregate_version<Aggregate>(
+        &self,
+        aggregate: Aggregate,
+    ) -> Result<AggregateVersion>
+    where
+        Aggregate: AggregateIdentity,
+    {
+        self.event_bus.aggregate_version(*aggregate.id()).await
+    }
+
+    // Every event in the journal, in order, read straight from the store
+    pub async fn journal(&self) -> Result<Vec<Event>> {
+        self.event_bus.journal_events().await
+    }
+
+    pub async fn store_stats(&self) -> Result<StoreStats> {
+        self.event_bus.store_stats().await
+    }
+
+    pub async fn durability(&self) -> Durability {
+        self.event_bus.durability().await
+    }
+
+    pub async fn set_durability(&self, durability: Durability) -> Result<()> {
+        self.event_bus.set_durability(durability).await
+    }
+
+    // Everything persisted so far is on disk when this returns, even with
+    // buffered durability
+    pub async fn flush(&self) -> Result<()> {
+        self.event_bus.flush().await
+    }
+
+    // Rebuilds the text search index from the journal, optionally with a
+    // new term length threshold, and yields the threshold now in effect
+    pub async fn reindex_texts(&self, term_length_threshold: Option<usize>) -> Result<usize> {
+        self.query_handler()?
+            .reindex_texts(term_length_threshold)
+            .await
+    }
+
+    // Number of events that subscribers have missed because they lagged
+    // behind the event bus, and had to re-sync from the journal for.
+    pub fn lagged_event_count(&self) -> u64 {
+        self.event_bus.lagged_event_count()
+    }
+
+    // Events emitted from now on, for embedders that keep their own
+    // projections or have side effects. Dropping it affects no one else.
+    pub fn subscribe(&self) -> Subscription<ES> {
+        let event_bus = Arc::clone(&self.event_bus);
+        Subscription {
+            events: event_bus.subscribe(),
+            event_bus,
+        }
+    }
+}
+
+// Assembles an Application from its event store and whatever it should use
+// instead of the defaults that Application::new goes with.
+pub struct ApplicationBuilder<ES> {
+    event_store: ES,
+    channel_capacity: usize,
+    clock: Arc<dyn Clock>,
+    id_generator: Arc<dyn IdGenerator>,
+    durability: Option<Durability>,
+    projections: Projections,
+    max_keywords_per_target: usize,
+    max_search_hits: usize,
+    infix_search: bool,
+    store_only: bool,
+}
+
+impl<ES> ApplicationBuilder<ES>
+where
+    ES: EventStore,
+{
+    fn new(event_store: ES) -> Self {
+        Self {
+            event_store,
+            channel_capacity: EventBus::<ES, Event>::DEFAULT_CAPACITY,
+            clock: Arc::new(SystemClock),
+            id_generator: Arc::new(RandomIds),
+            durability: None,
+            projections: Projections::default(),
+            max_keywords_per_target: DEFAULT_MAX_KEYWORDS_PER_TARGET,
+            max_search_hits: query::text::DEFAULT_MAX_SEARCH_HITS,
+            infix_search: false,
+            store_only: false,
+        }
+    }
+
+    // How many events the event bus holds for subscribers that fall behind,
+    // see EventBus::with_capacity
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = capacity;
+        self
+    }
+
+    // What events are journaled at, and what commands that record a time
+    // take it from. Stores that time their events themselves only use it for
+    // the latter.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    // What the ids of new books, authors and readers are taken from
+    pub fn id_generator(mut self, id_generator: impl IdGenerator + 'static) -> Self {
+        self.id_generator = Arc::new(id_generator);
+        self
+    }
+
+    // Left as the event store has it unless given; building fails if the
+    // store cannot have it
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = Some(durability);
+        self
+    }
+
+    // Which of the optional read model projections to keep up
+    pub fn projections(mut self, projections: Projections) -> Self {
+        self.projections = projections;
+        self
+    }
+
+    // See Application::with_max_keywords_per_target
+    pub fn max_keywords_per_target(mut self, max: usize) -> Self {
+        self.max_keywords_per_target = max;
+        self
+    }
+
+    // See Application::with_max_search_hits
+    pub fn max_search_hits(mut self, max: usize) -> Self {
+        self.max_search_hits = max;
+        self
+    }
+
+    // See Application::with_infix_search
+    pub fn infix_search(mut self) -> Self {
+        self.infix_search = true;
+        self
+    }
+
+    // Neither replays the journal nor accepts commands, see
+    // Application::store_only. The clock and id generator are then only
+    // used by the store.
+    pub fn store_only(mut self) -> Self {
+        self.store_only = true;
+        self
+    }
+
+    pub fn build(self) -> Result<Application<ES>> {
+        let mut event_store = self.event_store;
+        event_store.set_clock(Arc::clone(&self.clock));
+        if let Some(durability) = self.durability {
+            event_store.set_durability(durability)?;
+        }
+
+        let event_bus = EventBus::with_capacity(event_store, self.channel_capacity);
+        let mut application = if self.store_only {
+            Application::store_only(event_bus)
+        } else {
+            Application::with_projections(event_bus, self.projections)
+        };
+        if let Some(command_dispatcher) = application.command_dispatcher.as_mut() {
+            command_dispatcher.id_generator = self.id_generator;
+            command_dispatcher.clock = self.clock;
+        }
+
+        if self.infix_search {
+            application = application.with_infix_search();
+        }
+
+        Ok(application
+            .with_max_keywords_per_target(self.max_keywords_per_target)
+            .with_max_search_hits(self.max_search_hits))
+    }
+}
+
+#[derive(Debug)]
+pub enum Notification {
+    Event(Event),
+    // The subscriber fell behind and missed events. It has been resubscribed
+    // and is handed the whole journal to rebuild from, just like the read-
+    // and write models are; events after it arrive as usual.
+    Resynced(Vec<Event>),
+}
+
+pub struct Subscription<ES> {
+    event_bus: Arc<EventBus<ES, Event>>,
+    events: EventBusSubscription<Event>,
+}
+
+impl<ES> Subscription<ES>
+where
+    ES: EventStore,
+{
+    // None once the application has shut down
+    pub async fn next(&mut self) -> Option<Notification> {
+        match self.events.poll().await {
+            Ok((event, _)) => Some(Notification::Event(event)),
+            Err(Error::ReceiveError(RecvError::Lagged(skipped))) => {
+                self.event_bus.record_lag(skipped);
+                match self.event_bus.resubscribe_and_replay().await {
+                    Ok((subscription, journal)) => {
+                        self.events = subscription;
+                        Some(Notification::Resynced(
+                            journal.into_iter().map(|(event, _)| event).collect(),
+                        ))
+                    }
+                    Err(error) => {
+                        tracing::error!("Unable to re-sync lagging subscriber: {error}");
+                        None
+                    }
+                }
+            }
+            Err(_) => None,
+        }
+    }
+}
+
+// This has to lose the EventStore.
+// But can I make this know about the concrete event type?
+pub struct EventBus<ES, E> {
+    event_store: Mutex<ES>,
+    // Events with the time they were journaled at
+    tx: Sender<(E, OffsetDateTime)>,
+    lagged_events: AtomicU64,
+    // How many events the journal replay published, once it is done
+    replayed_events: AtomicU64,
+    // Where in the journal the replay ended, for the follower to go on from
+    replayed_through: AtomicU64,
+    // Ids of events emitted here while following the journal, so that the
+    // follower does not publish them a second time.
+    emitted: Mutex<Option<HashSet<UniqueId>>>,
+}
+
+impl<ES, E> EventBus<ES, E>
+where
+    ES: EventStore,
+    E: EventDescriptor + Sync + Send + Clone + fmt::Debug + 'static,
+{
+    const NOT_REPLAYED: u64 = u64::MAX;
+
+    pub const DEFAULT_CAPACITY: usize = 100;
+
+    pub fn new(event_store: ES) -> Self {
+        Self::with_capacity(event_store, Self::DEFAULT_CAPACITY)
+    }
+
+    // How many events subscribers may fall behind by before they lag and
+    // have to catch up from the journal
+    pub fn with_capacity(event_store: ES, capacity: usize) -> Self {
+        let (tx, _rx) = broadcast::channel(capacity);
+        Self {
+            event_store: Mutex::new(event_store),
+            tx,
+            lagged_events: AtomicU64::default(),
+            replayed_events: AtomicU64::new(Self::NOT_REPLAYED),
+            replayed_through: AtomicU64::default(),
+            emitted: Mutex::default(),
+        }
+    }
+
+    async fn replay_journal(&self) -> Result<()> {
+        let store = self.event_store.lock().await;
+        let journal = store.journal().await?;
+        let replayed = journal.len() as u64;
+        let through = journal.last().map_or(0, |record| record.sequence);
+        for record in journal {
+            let event: E = EventDescriptor::from_external_representation(&record)?;
+            self.tx.send((event, record.when)).map_err(
+                |broadcast::error::SendError((event, _))| {
+                    Error::Generic(format!("SendError {event:?}"))
+                },
+            )?;
+        }
+        // Whatever was emitted before is in the replay
+        if let Some(emitted) = self.emitted.lock().await.as_mut() {
+            emitted.clear();
+        }
+        self.replayed_through.store(through, Ordering::Relaxed);
+        self.replayed_events.store(replayed, Ordering::Release);
+        Ok(())
+    }
+
+    // Where the replay ended, once it is done
+    fn replayed_position(&self) -> Option<JournalPosition> {
+        self.replayed_event_count()
+            .map(|_| JournalPosition(self.replayed_through.load(Ordering::Relaxed)))
+    }
+
+    fn replayed_event_count(&self) -> Option<u64> {
+        Some(self.replayed_events.load(Ordering::Acquire)).filter(|&n| n != Self::NOT_REPLAYED)
+    }
+
+    async fn journal_events(&self) -> Result<Vec<E>> {
+        self.event_store
+            .lock()
+            .await
+            .journal()
+            .await?
+            .iter()
+            .map(EventDescriptor::from_external_representation)
+            .collect()
+    }
+
+    // The journal is the source of truth: once an event is persisted, the
+    // emit has succeeded. Delivery to subscribers is best-effort; a subscriber
+    // that misses an event picks it up from the journal on the next replay.
+    async fn emit(&self, event: E) -> Result<()> {
+        let mut store = self.event_store.lock().await;
+        let Persisted { id, when } = store.persist(event.clone()).await?;
+        self.record_emitted(id).await;
+        self.publish(event, when);
+
+        Ok(())
+    }
+
+    async fn emit_expecting(&self, event: E, expected: AggregateVersion) -> Result<()> {
+        let mut store = self.event_store.lock().await;
+        let Persisted { id, when } = store.persist_expecting(event.clone(), expected).await?;
+        self.record_emitted(id).await;
+        self.publish(event, when);
+
+        Ok(())
+    }
+
+    // Commands that emit more than one event persist them together, so that
+    // a failure leaves none of them behind rather than a part of the command.
+    async fn emit_all(&self, events: Vec<E>) -> Result<()> {
+        let mut store = self.event_store.lock().await;
+        let persisted = store.persist_all(events.clone()).await?;
+        for (event, Persisted { id, when }) in events.into_iter().zip(persisted) {
+            self.record_emitted(id).await;
+            self.publish(event, when);
+        }
+
+        Ok(())
+    }
+
+    async fn record_emitted(&self, id: UniqueId) {
+        if let Some(emitted) = self.emitted.lock().await.as_mut() {
+            emitted.insert(id);
+        }
+    }
+
+    // From here on, emitted events are recorded so that follow_journal can
+    // tell them apart from those written by others. This has to happen
+    // before the replay, or what others write in between would be missed.
+    async fn record_emitted_from_now(&self) -> Result<()> {
+        if self.replayed_event_count().is_some() {
+            return Err(Error::Generic(
+                "The store has to be followed from before the journal is replayed".to_owned(),
+            ));
+        }
+        *self.emitted.lock().await = Some(HashSet::default());
+
+        Ok(())
+    }
+
+    // Publishes events that other writers append to a shared store, from
+    // where the replay ended. Events emitted through this bus are already
+    // published, and are skipped.
+    async fn follow_journal(&self, interval: Duration, terminate: TerminationWaiter)
+    where
+        ES: Sync,
+    {
+        let mut position = None;
+        loop {
+            tokio::select! {
+                _ = sleep(interval) => (),
+                _ = terminate.wait() => break,
+            }
+
+            let Some(after) = position.or_else(|| self.replayed_position()) else {
+                continue;
+            };
+            let store = self.event_store.lock().await;
+            match store.journal_after(after).await {
+                Ok((records, next)) => {
+                    let mut emitted = self.emitted.lock().await;
+                    let emitted = emitted.get_or_insert_with(HashSet::default);
+                    for record in records {
+                        if emitted.remove(&UniqueId(record.id)) {
+                            continue;
+                        }
+                        match E::from_external_representation(&record) {
+                            Ok(event) => self.publish(event, record.when),
+                            Err(error) => tracing::warn!("Skipping unreadable event: {error}"),
+                        }
+                    }
+                    position = Some(next);
+                }
+                Err(error) => tracing::warn!("Unable to poll the journal: {error}"),
+            }
+        }
+    }
+
+    fn publish(&self, event: E, when: OffsetDateTime) {
+        if let Err(broadcast::error::SendError((event, _))) = self.tx.send((event, when)) {
+            tracing::warn!(
+                "Unable to send {event:?} to subscribers; it is journaled and will replay"
+            );
+        }
+    }
+
+    fn subscribe(&self) -> EventBusSubscription<E> {
+        EventBusSubscription::new(self.tx.subscribe())
+    }
+
+    // The subscription is taken while holding the store, so nothing can be
+    // emitted in between: the journal covers everything up to the new
+    // subscription and the subscription everything after it.
+    async fn resubscribe_and_replay(
+        &self,
+    ) -> Result<(EventBusSubscription<E>, Vec<(E, OffsetDateTime)>)> {
+        let store = self.event_store.lock().await;
+        let subscription = self.subscribe();
+        let events = store
+            .journal()
+            .await?
+            .iter()
+            .map(|record| Ok((E::from_external_representation(record)?, record.when)))
+            .collect::<Result<_>>()?;
+
+        Ok((subscription, events))
+    }
+
+    async fn load_aggregate<Aggregate>(&self, aggregate: Aggregate) -> Result<Aggregate::Root>
+    where
+        ES: Send + Sync,
+        Aggregate: AggregateIdentity + Send,
+    {
+        self.event_store
+            .lock()
+            .await
+            .load_aggregate(aggregate)
+            .await
+    }
+
+    fn record_lag(&self, skipped: u64) {
+        tracing::warn!("Subscriber lagged behind by {skipped} events, re-syncing from the journal");
+        self.lagged_events.fetch_add(skipped, Ordering::Relaxed);
+    }
+
+    pub fn lagged_event_count(&self) -> u64 {
+        self.lagged_events.load(Ordering::Relaxed)
+    }
+
+    async fn aggregate_version(&self, id: UniqueId) -> Result<AggregateVersion> {
+        self.event_store.lock().await.aggregate_version(id).await
+    }
+
+    async fn store_stats(&self) -> Result<StoreStats> {
+        self.event_store.lock().await.stats().await
+    }
+
+    async fn durability(&self) -> Durability {
+        self.event_store.lock().await.durability()
+    }
+
+    // Waits for the event being persisted, if any, so that every event
+    // after this returns is persisted the new way
+    async fn set_durability(&self, durability: Durability) -> Result<()> {
+        self.event_store.lock().await.set_durability(durability)
+    }
+
+    // Waits for the event being persisted, if any, which is flushed too
+    async fn flush(&self) -> Result<()> {
+        self.event_store.lock().await.flush()
+    }
+}
+
+struct EventBusSubscription<E> {
+    rx: Mutex<Receiver<(E, OffsetDateTime)>>,
+}
+
+impl<E> EventBusSubscription<E>
+where
+    E: EventDescriptor + Clone,
+{
+    fn new(rx: Receiver<(E, OffsetDateTime)>) -> Self {
+        Self { rx: Mutex::new(rx) }
+    }
+
+    // Cancel safe: recv is, and the receiver is let go of with the future
+    async fn poll(&self) -> Result<(E, OffsetDateTime)> {
+        Ok(self.rx.lock().await.recv().await?)
+    }
+}
+
+#[derive(Default)]
+struct WriteModel {
+    author_name_ids: HashMap<String, Vec<AuthorId>>,
+    author_ids: HashSet<AuthorId>,
+
+    books: HashMap<BookId, BookInfo>,
+    book_title_ids: HashMap<String, Vec<BookId>>,
+
+    reader_id_by_moniker: HashMap<String, ReaderId>,
+    reader_ids: HashSet<ReaderId>,
+    books_read: HashMap<ReaderId, HashSet<BookReadInfo>>,
+
+    keyword_targets: HashMap<String, HashSet<KeywordTarget>>,
+    target_keywords: HashMap<KeywordTarget, HashSet<String>>,
+}
+
+impl WriteModel {
+    fn apply(&mut self, event: Event) {
+        match event {
+            Event::BookAdded(id, info) => {
+                self.book_title_ids
+                    .entry(info.title.clone())
+                    .or_default()
+                    .push(id);
+                self.books.insert(id, info);
+            }
+            Event::BookInfoCorrected(id, info) => {
+                for ids in self.book_title_ids.values_mut() {
+                    ids.retain(|book_id| *book_id != id);
+                }
+                self.book_title_ids
+                    .entry(info.title.clone())
+                    .or_default()
+                    .push(id);
+                self.books.insert(id, info);
+            }
+            Event::BookRemoved(id) => {
+                self.books.remove(&id);
+                for ids in self.book_title_ids.values_mut() {
+                    ids.retain(|book_id| *book_id != id);
+                }
+                for reads in self.books_read.values_mut() {
+                    reads.retain(|read| read.book_id != id);
+                }
+                if let Some(keywords) = self.target_keywords.remove(&KeywordTarget::Book(id)) {
+                    for keyword in keywords {
+                        if let Some(targets) = self.keyword_targets.get_mut(&keyword) {
+                            targets.remove(&KeywordTarget::Book(id));
+                        }
+                    }
+                }
+            }
+            Event::AuthorAdded(id, info) => {
+                self.author_name_ids.entry(info.name).or_default().push(id);
+                self.author_ids.insert(id);
+            }
+            Event::ReaderAdded(id, info) => {
+                self.reader_id_by_moniker.insert(info.unique_moniker, id);
+                self.reader_ids.insert(id);
+            }
+            Event::BookRead(id, info) => {
+                self.books_read.entry(id).or_default().insert(info);
+            }
+            Event::KeywordAdded(target, keyword) => {
+                self.target_keywords
+                    .entry(target)
+                    .or_default()
+                    .insert(keyword.clone());
+                self.keyword_targets
+                    .entry(keyword)
+                    .or_default()
+                    .insert(target);
+            }
+            Event::KeywordRemoved(target, keyword) => {
+                if let Some(keywords) = self.target_keywords.get_mut(&target) {
+                    keywords.remove(&keyword);
+                }
+                if let Some(targets) = self.keyword_targets.get_mut(&keyword) {
+                    targets.remove(&target);
+                }
+            }
+            Event::AuthorMerged(merged, into) => {
+                self.author_ids.remove(&merged);
+                for ids in self.author_name_ids.values_mut() {
+                    ids.retain(|id| *id != merged);
+                }
+                for targets in self.keyword_targets.values_mut() {
+                    if targets.remove(&KeywordTarget::Author(merged)) {
+                        targets.insert(KeywordTarget::Author(into));
+                    }
+                }
+                if let Some(keywords) = self.target_keywords.remove(&KeywordTarget::Author(merged))
+                {
+                    self.target_keywords
+                        .entry(KeywordTarget::Author(into))
+                        .or_default()
+                        .extend(keywords);
+                }
+            }
+            Event::KeywordRenamed(from, to) => {
+                let targets = self.keyword_targets.remove(&from).unwrap_or_default();
+                for target in &targets {
+                    if let Some(keywords) = self.target_keywords.get_mut(target) {
+                        keywords.remove(&from);
+                        keywords.insert(to.clone());
+                    }
+                }
+                self.keyword_targets.entry(to).or_default().extend(targets);
+            }
+            Event::ReadingProgressUpdated(..) => (),
+        }
+    }
+}
+
+// For the tests of this module and of the Api
+#[cfg(test)]
+pub(crate) mod testing {
+    use tempfile::TempDir;
+
+    use super::{Application, EventBus};
+    use crate::infrastructure::persistence::EventArchive;
+
+    // On a store of its own, which is removed when the directory is dropped
+    pub fn make_application() -> (Application<EventArchive>, TempDir) {
+        let directory = tempfile::tempdir().expect("a temporary directory");
+        let archive = EventArchive::try_new(directory.path()).expect("a valid event archive");
+        (Application::new(EventBus::new(archive)), directory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::make_application;
+    use super::*;
+    use crate::infrastructure::{
+        memory::MemoryEventStore, persistence::EventArchive, ExternalRepresentation,
+    };
+    use model::{AuthorInfo, ResourceId};
+
+    // How long a test waits for the models to catch up before it fails
+    const PATIENCE: Duration = Duration::from_secs(10);
+
+    // The models apply events in their own time, which tests wait for. A
+    // wait that takes too long fails the test instead of hanging it.
+    macro_rules! in_time {
+        ($waiting:expr) => {
+            timeout(PATIENCE, async { $waiting })
+                .await
+                .expect("the models to catch up in time")
+        };
+    }
+
+    fn add_author() -> Command {
+        Command::AddAuthor(AuthorInfo {
+            name: "Sun Tzu".to_owned(),
+        })
+    }
+
+    #[tokio::test]
+    async fn retried_command_with_same_key_yields_original_receipt() {
+        let (application, _directory) = make_application();
+        let key = || Some(IdempotencyKey("retry-me".to_owned()));
+
+        let first = application
+            .submit_idempotent_command(key(), add_author())
+            .await;
+        let retry = application
+            .submit_idempotent_command(key(), add_author())
+            .await;
+        assert!(matches!(first, CommandReceipt::Created(..)));
+        assert_eq!(first, retry);
+
+        let other = application
+            .submit_idempotent_command(Some(IdempotencyKey("another".to_owned())), add_author())
+            .await;
+        assert_ne!(first, other);
+    }
+
+    #[tokio::test]
+    async fn a_key_reused_for_another_command_is_rejected() {
+        let (application, _directory) = make_application();
+        let key = || Some(IdempotencyKey("reuse-me".to_owned()));
+
+        let first = application
+            .submit_idempotent_command(key(), add_author())
+            .await;
+        assert!(matches!(first, CommandReceipt::Created(..)));
+
+        let other_author = Command::AddAuthor(AuthorInfo {
+            name: "Lao Tzu".to_owned(),
+        });
+        assert_eq!(
+            application
+                .submit_idempotent_command(key(), other_author)
+                .await,
+            CommandReceipt::RejectedBecause(Rejection::IdempotencyKeyReused("reuse-me".to_owned()))
+        );
+    }
+
+    // Fails every write while failing is set, and otherwise keeps its
+    // events in memory
+    #[derive(Clone, Default)]
+    struct FailingStore {
+        events: MemoryEventStore,
+        failing: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl FailingStore {
+        fn failure(&self) -> Result<()> {
+            if self.failing.load(std::sync::atomic::Ordering::Relaxed) {
+                Err(Error::Generic("The store is failing".to_owned()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl EventStore for FailingStore {
+        async fn find_by_event_id(&self, id: UniqueId) -> Result<ExternalRepresentation> {
+            self.events.find_by_event_id(id).await
+        }
+
+        async fn find_by_aggregate_id(&self, id: UniqueId) -> Result<Vec<ExternalRepresentation>> {
+            self.events.find_by_aggregate_id(id).await
+        }
+
+        async fn persist<E>(&mut self, event: E) -> Result<Persisted>
+        where
+            E: EventDescriptor + Send + Sync + 'static,
+        {
+            self.failure()?;
+            self.events.persist(event).await
+        }
+
+        async fn persist_expecting<E>(
+            &mut self,
+            event: E,
+            expected: AggregateVersion,
+        ) -> Result<Persisted>
+        where
+            E: EventDescriptor + Send + Sync + 'static,
+        {
+            self.failure()?;
+            self.events.persist_expecting(event, expected).await
+        }
+
+        async fn persist_all<E>(&mut self, events: Vec<E>) -> Result<Vec<Persisted>>
+        where
+            E: EventDescriptor + Send + Sync + 'static,
+        {
+            self.failure()?;
+            self.events.persist_all(events).await
+        }
+
+        async fn aggregate_version(&self, id: UniqueId) -> Result<AggregateVersion> {
+            self.events.aggregate_version(id).await
+        }
+
+        async fn journal(&self) -> Result<Vec<ExternalRepresentation>> {
+            self.events.journal().await
+        }
+
+        async fn journal_after(
+            &self,
+            position: JournalPosition,
+        ) -> Result<(Vec<ExternalRepresentation>, JournalPosition)> {
+            self.events.journal_after(position).await
+        }
+
+        async fn stats(&self) -> Result<StoreStats> {
+            self.events.stats().await
+        }
+    }
+
+    #[tokio::test]
+    async fn a_failed_write_is_a_receipt_and_is_not_remembered() {
+        let store = FailingStore::default();
+        store
+            .failing
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+        let application = Application::new(EventBus::new(store.clone()));
+        let key = || Some(IdempotencyKey("fail-me".to_owned()));
+
+        let failed = application
+            .submit_idempotent_command(key(), add_author())
+            .await;
+        assert!(matches!(failed, CommandReceipt::Failed(..)), "{failed:?}");
+        assert!(!failed.is_success());
+
+        store
+            .failing
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+        let retry = application
+            .submit_idempotent_command(key(), add_author())
+            .await;
+        assert!(matches!(retry, CommandReceipt::Created(..)), "{retry:?}");
+    }
+
+    #[tokio::test]
+    async fn merging_an_author_into_itself_is_rejected() {
+        let (application, _directory) = make_application();
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        let CommandReceipt::Created(ResourceId::Author(keep)) =
+            application.submit_command(add_author()).await
+        else {
+            panic!("expected an author")
+        };
+        let CommandReceipt::Created(ResourceId::Author(merge)) =
+            application.submit_command(add_author()).await
+        else {
+            panic!("expected an author")
+        };
+
+        // The write model is updated from the event bus
+        let write_model = &application
+            .command_dispatcher
+            .as_ref()
+            .expect("a command dispatcher")
+            .write_model;
+        in_time!(while write_model.read().await.author_ids.len() < 2 {
+            task::yield_now().await;
+        });
+
+        let merge_self = Command::MergeAuthors { keep, merge: keep };
+        assert_eq!(
+            application.submit_command(merge_self).await,
+            CommandReceipt::RejectedBecause(Rejection::SameAuthor(keep))
+        );
+        let unknown = AuthorId(UniqueId::fresh());
+        assert_eq!(
+            application
+                .submit_command(Command::MergeAuthors {
+                    keep,
+                    merge: unknown
+                })
+                .await,
+            CommandReceipt::RejectedBecause(Rejection::UnknownAuthor(unknown))
+        );
+        assert_eq!(
+            application
+                .submit_command(Command::MergeAuthors { keep, merge })
+                .await,
+            CommandReceipt::Accepted
+        );
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn books_of_a_merged_author_can_be_corrected() {
+        let (application, _directory) = make_application();
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        let CommandReceipt::CreatedWithAuthor(book_id, merge) = application
+            .submit_command(Command::AddBookWithAuthor {
+                isbn: model::Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author: AuthorInfo {
+                    name: "Sunzi".to_owned(),
+                },
+            })
+            .await
+        else {
+            panic!("expected a book with its author")
+        };
+        let CommandReceipt::Created(ResourceId::Author(keep)) =
+            application.submit_command(add_author()).await
+        else {
+            panic!("expected an author")
+        };
+
+        let write_model = &application
+            .command_dispatcher
+            .as_ref()
+            .expect("a command dispatcher")
+            .write_model;
+        in_time!(while write_model.read().await.author_ids.len() < 2 {
+            task::yield_now().await;
+        });
+        assert_eq!(
+            application
+                .submit_command(Command::MergeAuthors { keep, merge })
+                .await,
+            CommandReceipt::Accepted
+        );
+        in_time!(while write_model.read().await.author_ids.contains(&merge) {
+            task::yield_now().await;
+        });
+
+        let corrected = model::BookInfo {
+            isbn: model::Isbn("978-1-59030-225-7".to_owned()),
+            title: "The Art of War".to_owned(),
+            author: keep,
+        };
+        assert_eq!(
+            application
+                .submit_command(Command::CorrectBookInfo(book_id, corrected.clone(), None))
+                .await,
+            CommandReceipt::Accepted
+        );
+
+        let model::Book(_, info) = eventually(
+            &application,
+            || query::BookById(book_id),
+            |book| matches!(book, Some(model::Book(_, info)) if info.isbn == corrected.isbn),
+        )
+        .await
+        .expect("a book");
+        assert_eq!(info, corrected);
+        let model::Book(_, loaded) = application
+            .load_aggregate(book_id)
+            .await
+            .expect("a book aggregate");
+        assert_eq!(loaded, corrected);
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn replaying_an_empty_journal_is_a_no_op() {
+        let (application, _directory) = make_application();
+        let termination = Termination::default();
+
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+        assert!(application
+            .issue_query(query::AllAuthors)
+            .await
+            .expect("authors")
+            .is_empty());
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn same_book_can_be_read_again_but_not_at_the_same_time() {
+        let (application, _directory) = make_application();
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        let CommandReceipt::Created(ResourceId::Author(author)) =
+            application.submit_command(add_author()).await
+        else {
+            panic!("expected an author")
+        };
+        let write_model = &application
+            .command_dispatcher
+            .as_ref()
+            .expect("a command dispatcher")
+            .write_model;
+        in_time!(
+            while !write_model.read().await.author_ids.contains(&author) {
+                task::yield_now().await;
+            }
+        );
+        let CommandReceipt::Created(ResourceId::Book(book_id)) = application
+            .submit_command(Command::AddBook(model::BookInfo {
+                isbn: model::Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author,
+            }))
+            .await
+        else {
+            panic!("expected a book")
+        };
+        in_time!(
+            while !write_model.read().await.books.contains_key(&book_id) {
+                task::yield_now().await;
+            }
+        );
+
+        let unknown_reader = ReaderId(UniqueId::fresh());
+        assert_eq!(
+            application
+                .submit_command(Command::AddReadBook(BookReadInfo {
+                    reader_id: unknown_reader,
+                    book_id,
+                    when: None,
+                }))
+                .await,
+            CommandReceipt::RejectedBecause(Rejection::UnknownReader(unknown_reader))
+        );
+
+        let CommandReceipt::Created(ResourceId::Reader(reader_id)) = application
+            .submit_command(Command::AddReader(model::ReaderInfo {
+                name: "Reader".to_owned(),
+                unique_moniker: "reader".to_owned(),
+            }))
+            .await
+        else {
+            panic!("expected a reader")
+        };
+        in_time!(
+            while !write_model.read().await.reader_ids.contains(&reader_id) {
+                task::yield_now().await;
+            }
+        );
+        let unknown_book = BookId(UniqueId::fresh());
+        assert_eq!(
+            application
+                .submit_command(Command::AddReadBook(BookReadInfo {
+                    reader_id,
+                    book_id: unknown_book,
+                    when: None,
+                }))
+                .await,
+            CommandReceipt::RejectedBecause(Rejection::UnknownBook(unknown_book))
+        );
+
+        let read_at = |when| {
+            Command::AddReadBook(BookReadInfo {
+                reader_id,
+                book_id,
+                when: Some(when),
+            })
+        };
+        let first = time::macros::datetime!(2023-01-01 0:00 UTC);
+        let second = time::macros::datetime!(2024-01-01 0:00 UTC);
+
+        for (command, receipt, reads) in [
+            (read_at(first), CommandReceipt::Accepted, 1),
+            (read_at(second), CommandReceipt::Accepted, 2),
+            (read_at(second), CommandReceipt::AlreadyExists, 2),
+        ] {
+            assert_eq!(application.submit_command(command).await, receipt);
+            // The next command must see this one in the write model
+            in_time!(while write_model
+                .read()
+                .await
+                .books_read
+                .get(&reader_id)
+                .map_or(0, HashSet::len)
+                < reads
+            {
+                task::yield_now().await;
+            });
+        }
+
+        // The read model follows the same events, in its own time
+        let history = in_time!(loop {
+            let history = application
+                .issue_query(query::BooksByReader(reader_id))
+                .await
+                .expect("a reading history");
+            if history.iter().map(|read| read.times_read).sum::<usize>() == 2 {
+                break history;
+            }
+            task::yield_now().await;
+        });
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].times_read, 2);
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn subscribers_see_events_and_are_resynced_when_lagging() {
+        let (application, _directory) = make_application();
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        let mut subscription = application.subscribe();
+        let dropped = application.subscribe();
+        drop(dropped);
+
+        application.submit_command(add_author()).await;
+        assert!(matches!(
+            subscription.next().await,
+            Some(Notification::Event(Event::AuthorAdded(..)))
+        ));
+
+        // More than the event bus holds on to for a subscriber, which is its
+        // capacity rounded up to a power of two
+        for _ in 0..150 {
+            application.submit_command(add_author()).await;
+        }
+        let Some(Notification::Resynced(journal)) = subscription.next().await else {
+            panic!("expected a re-sync")
+        };
+        assert_eq!(journal.len(), 151);
+        assert!(application.lagged_event_count() > 0);
+
+        application.submit_command(add_author()).await;
+        assert!(matches!(
+            subscription.next().await,
+            Some(Notification::Event(Event::AuthorAdded(..)))
+        ));
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn duplicate_monikers_are_rejected_saying_so() {
+        let (application, _directory) = make_application();
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        let add_reader = || {
+            Command::AddReader(model::ReaderInfo {
+                name: "Sun Tzu".to_owned(),
+                unique_moniker: "suntzu".to_owned(),
+            })
+        };
+        application.submit_command(add_reader()).await;
+        let write_model = &application
+            .command_dispatcher
+            .as_ref()
+            .expect("a command dispatcher")
+            .write_model;
+        in_time!(while !write_model
+            .read()
+            .await
+            .reader_id_by_moniker
+            .contains_key("suntzu")
+        {
+            task::yield_now().await;
+        });
+
+        assert_eq!(
+            application.submit_command(add_reader()).await,
+            CommandReceipt::RejectedBecause(Rejection::DuplicateMoniker("suntzu".to_owned()))
+        );
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn names_and_titles_are_trimmed_and_must_not_be_empty() {
+        let (application, _directory) = make_application();
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        for name in ["", " \t "] {
+            let receipt = application
+                .submit_command(Command::AddAuthor(AuthorInfo {
+                    name: name.to_owned(),
+                }))
+                .await;
+            assert_eq!(
+                receipt,
+                CommandReceipt::RejectedBecause(Rejection::EmptyField("name")),
+                "{name:?}"
+            );
+        }
+        let receipt = application
+            .submit_command(Command::AddBookWithAuthor {
+                isbn: model::Isbn("978-1-61180-697-7".to_owned()),
+                title: "  ".to_owned(),
+                author: AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            })
+            .await;
+        assert_eq!(
+            receipt,
+            CommandReceipt::RejectedBecause(Rejection::EmptyField("title"))
+        );
+
+        let CommandReceipt::CreatedWithAuthor(book_id, _) = application
+            .submit_command(Command::AddBookWithAuthor {
+                isbn: model::Isbn("978-1-61180-697-7".to_owned()),
+                title: " The Art of War\n".to_owned(),
+                author: AuthorInfo {
+                    name: "  Sun Tzu ".to_owned(),
+                },
+            })
+            .await
+        else {
+            panic!("expected a book and its author")
+        };
+        let book = in_time!(loop {
+            if let Some(book) = application
+                .issue_query(query::BookById(book_id))
+                .await
+                .expect("a query result")
+            {
+                break book;
+            }
+            tokio::task::yield_now().await;
+        });
+        assert_eq!(book.1.title, "The Art of War");
+        let authors = application
+            .issue_query(query::AllAuthors)
+            .await
+            .expect("a query result");
+        assert_eq!(authors[0].1.name, "Sun Tzu");
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn store_only_application_reads_the_journal_without_models() {
+        let directory = tempfile::tempdir().expect("a temporary directory");
+        let mut archive = EventArchive::try_new(directory.path()).expect("a valid event archive");
+        let author_id = AuthorId(UniqueId::fresh());
+        archive
+            .persist(Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ))
+            .await
+            .expect("a persisted event");
+
+        let application = Application::store_only(EventBus::new(archive));
+        application
+            .start(&Termination::default())
+            .await
+            .expect("a started application");
+
+        assert_eq!(application.journal().await.expect("a journal").len(), 1);
+        assert_eq!(
+            application
+                .load_aggregate(author_id)
+                .await
+                .expect("an author")
+                .1
+                .name,
+            "Sun Tzu"
+        );
+        assert!(matches!(
+            application.issue_query(query::AllAuthors).await,
+            Err(Error::MissingComponent(_))
+        ));
+        assert_eq!(
+            application.submit_command(add_author()).await,
+            CommandReceipt::RejectedBecause(Rejection::ReadOnly)
+        );
+    }
+
+    #[tokio::test]
+    async fn keywords_per_target_are_capped() {
+        let (application, _directory) = make_application();
+        let application = application.with_max_keywords_per_target(3);
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        let CommandReceipt::Created(ResourceId::Author(author_id)) =
+            application.submit_command(add_author()).await
+        else {
+            panic!("expected an author")
+        };
+        let target = KeywordTarget::Author(author_id);
+        let add_keyword = |keyword: &str| {
+            Command::AddKeyword(keyword.parse().expect("a valid keyword"), target, None)
+        };
+        let write_model = &application
+            .command_dispatcher
+            .as_ref()
+            .expect("a command dispatcher")
+            .write_model;
+        let keyword_count = || async {
+            write_model
+                .read()
+                .await
+                .target_keywords
+                .get(&target)
+                .map_or(0, HashSet::len)
+        };
+
+        in_time!(
+            while !write_model.read().await.author_ids.contains(&author_id) {
+                task::yield_now().await;
+            }
+        );
+        for (count, keyword) in ["war", "strategy", "classic"].into_iter().enumerate() {
+            assert_eq!(
+                application.submit_command(add_keyword(keyword)).await,
+                CommandReceipt::Accepted
+            );
+            in_time!(while keyword_count().await == count {
+                task::yield_now().await;
+            });
+        }
+
+        assert_eq!(
+            application.submit_command(add_keyword("history")).await,
+            CommandReceipt::RejectedBecause(Rejection::TooManyKeywords(3))
+        );
+        assert_eq!(
+            application.submit_command(add_keyword("war")).await,
+            CommandReceipt::RejectedBecause(Rejection::DuplicateKeyword("war".to_owned()))
+        );
+        assert_eq!(
+            application
+                .submit_command(Command::RemoveKeyword(
+                    "history".parse().expect("a valid keyword"),
+                    target,
+                    None
+                ))
+                .await,
+            CommandReceipt::RejectedBecause(Rejection::MissingKeyword("history".to_owned()))
+        );
+        let rename = |from: &str, to: &str| Command::RenameKeyword {
+            from: from.parse().expect("a valid keyword"),
+            to: to.parse().expect("a valid keyword"),
+        };
+        assert_eq!(
+            application.submit_command(rename("war", "war")).await,
+            CommandReceipt::RejectedBecause(Rejection::SameKeyword("war".to_owned()))
+        );
+        assert_eq!(
+            application.submit_command(rename("history", "war")).await,
+            CommandReceipt::RejectedBecause(Rejection::UnknownKeyword("history".to_owned()))
+        );
+
+        // Renaming onto a keyword the target already has leaves it one fewer
+        application.submit_command(rename("classic", "war")).await;
+        in_time!(while keyword_count().await == 3 {
+            task::yield_now().await;
+        });
+        assert_eq!(
+            application.submit_command(add_keyword("history")).await,
+            CommandReceipt::Accepted
+        );
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn reading_progress_is_validated_and_finishing_records_a_read() {
+        let (application, _directory) = make_application();
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        let CommandReceipt::CreatedWithAuthor(book_id, _) = application
+            .submit_command(Command::AddBookWithAuthor {
+                isbn: model::Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author: AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            })
+            .await
+        else {
+            panic!("expected a book")
+        };
+        let CommandReceipt::Created(ResourceId::Reader(reader_id)) = application
+            .submit_command(Command::AddReader(model::ReaderInfo {
+                name: "Reader".to_owned(),
+                unique_moniker: "reader".to_owned(),
+            }))
+            .await
+        else {
+            panic!("expected a reader")
+        };
+        let write_model = &application
+            .command_dispatcher
+            .as_ref()
+            .expect("a command dispatcher")
+            .write_model;
+        in_time!(while !write_model.read().await.books.contains_key(&book_id)
+            || write_model.read().await.reader_id_by_moniker.is_empty()
+        {
+            task::yield_now().await;
+        });
+
+        let progress = |percent| Command::UpdateReadingProgress(reader_id, book_id, percent);
+        assert_eq!(
+            application.submit_command(progress(101)).await,
+            CommandReceipt::RejectedBecause(Rejection::ProgressOutOfRange(101))
+        );
+        assert_eq!(
+            application.submit_command(progress(40)).await,
+            CommandReceipt::Accepted
+        );
+        in_time!(while application
+            .issue_query(query::BooksBeingRead(reader_id))
+            .await
+            .expect("books being read")
+            != Some(vec![query::BookBeingRead {
+                book: application
+                    .issue_query(query::BookById(book_id))
+                    .await
+                    .expect("a book")
+                    .expect("the book"),
+                percent: 40,
+            }]) {
+            task::yield_now().await;
+        });
+
+        // Finishing the book takes it off the reading list, and reads it
+        assert_eq!(
+            application.submit_command(progress(100)).await,
+            CommandReceipt::Accepted
+        );
+        in_time!(while application
+            .issue_query(query::BooksByReader(reader_id))
+            .await
+            .expect("books read")
+            .is_empty()
+        {
+            task::yield_now().await;
+        });
+        assert_eq!(
+            application
+                .issue_query(query::BooksBeingRead(reader_id))
+                .await
+                .expect("books being read"),
+            Some(vec![])
+        );
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn queries_time_out_while_the_read_model_is_write_locked() {
+        let (mut application, _directory) = make_application();
+        let query_handler = application.query_handler.as_mut().expect("a query handler");
+        query_handler.timeout = Duration::from_millis(50);
+
+        let read_model = Arc::clone(&query_handler.read_model);
+        let _writer = read_model.write().await;
+
+        let outcome = timeout(
+            Duration::from_secs(1),
+            application.issue_query(query::AllAuthors),
+        )
+        .await
+        .expect("the query to give up before the outer timeout");
+
+        assert!(matches!(outcome, Err(Error::QueryTimeout)));
+    }
+
+    // As when a client hangs up on a query that waits for the read model
+    #[tokio::test]
+    async fn dropped_queries_let_go_of_the_read_model() {
+        let (application, _directory) = make_application();
+        let read_model = Arc::clone(
+            &application
+                .query_handler
+                .as_ref()
+                .expect("a query handler")
+                .read_model,
+        );
+        let writer = read_model.write().await;
+
+        let mut query = Box::pin(application.issue_query(query::AllAuthors));
+        assert!(timeout(Duration::from_millis(50), &mut query)
+            .await
+            .is_err());
+        drop(query);
+        drop(writer);
+
+        assert!(read_model.try_write().is_ok());
+        let outcome = timeout(
+            Duration::from_millis(50),
+            application.issue_query(query::AllAuthors),
+        )
+        .await
+        .expect("the query not to wait");
+        assert!(outcome.expect("all authors").is_empty());
+    }
+
+    #[tokio::test]
+    async fn reindexing_applies_a_new_term_length_threshold() {
+        let (application, _directory) = make_application();
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        application
+            .submit_command(Command::AddAuthor(AuthorInfo {
+                name: "Malcolm X".to_owned(),
+            }))
+            .await;
+        in_time!(while application
+            .issue_query(query::AllAuthors)
+            .await
+            .expect("a query result")
+            .is_empty()
+        {
+            tokio::task::yield_now().await;
+        });
+
+        let search = || async {
+            application
+                .issue_query(query::text::SearchQuery::new("x".to_owned()))
+                .await
+                .map(|search| search.hits)
+        };
+        assert!(search().await.expect("hits").is_empty());
+
+        let threshold = application.reindex_texts(Some(0)).await.expect("a reindex");
+        assert_eq!(threshold, 0);
+        let hits = search().await.expect("hits");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].source, "Malcolm X");
+
+        termination.signal();
+    }
+
+    #[derive(Default)]
+    struct AuthorsAdded(usize);
+
+    impl Projection for AuthorsAdded {
+        fn apply(&mut self, event: &Event) {
+            if let Event::AuthorAdded(..) = event {
+                self.0 += 1
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn registered_projections_follow_the_event_stream() {
+        let directory = tempfile::tempdir().expect("a temporary directory");
+        let archive = EventArchive::try_new(directory.path()).expect("a valid event archive");
+        let application = Application::with_projections(
+            EventBus::new(archive),
+            Projections::default().register::<AuthorsAdded>(),
+        );
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        for _ in 0..3 {
+            application.submit_command(add_author()).await;
+        }
+
+        in_time!(while application
+            .inspect_projection(|AuthorsAdded(count)| *count)
+            .await
+            .expect("a read model")
+            != Some(3)
+        {
+            task::yield_now().await;
+        });
+        let authors = application
+            .issue_query(query::AllAuthors)
+            .await
+            .expect("all authors");
+        assert_eq!(authors.len(), 3);
+
+        // Only registered projections can be inspected
+        assert!(application
+            .inspect_projection(|_: &query::IndexSet| ())
+            .await
+            .expect("a read model")
+            .is_none());
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn read_only_application_follows_the_journal() {
+        let directory = tempfile::tempdir().expect("a temporary directory");
+        let mut archive = EventArchive::try_new(directory.path()).expect("a valid event archive");
+        archive
+            .persist(Event::AuthorAdded(
+                AuthorId(UniqueId::fresh()),
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ))
+            .await
+            .expect("a persisted event");
+
+        let application = Application::new(EventBus::new(archive));
+        let termination = Termination::default();
+        application
+            .start_read_only(&termination)
+            .await
+            .expect("a started application");
+
+        in_time!(while application
+            .issue_query(query::AllAuthors)
+            .await
+            .expect("all authors")
+            .is_empty()
+        {
+            task::yield_now().await;
+        });
+        assert!(application
+            .command_dispatcher
+            .as_ref()
+            .expect("a command dispatcher")
+            .write_model
+            .read()
+            .await
+            .author_ids
+            .is_empty());
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn followers_see_events_written_by_other_instances_once() {
+        let directory = tempfile::tempdir().expect("a temporary directory");
+        let archive = EventArchive::try_new(directory.path()).expect("a valid event archive");
+        let writer = Application::new(EventBus::new(archive.clone()));
+        let follower = Application::new(EventBus::new(archive));
+        let termination = Termination::default();
+        writer.start(&termination).await.expect("a started writer");
+        writer.submit_command(add_author()).await;
+
+        let mut subscription = follower.subscribe();
+        follower
+            .follow_store(Duration::from_millis(20), &termination)
+            .await
+            .expect("a followed store");
+        follower
+            .start(&termination)
+            .await
+            .expect("a started follower");
+        assert!(matches!(
+            subscription.next().await,
+            Some(Notification::Event(Event::AuthorAdded(..)))
+        ));
+
+        // Written after the replay, but before the follower first polls
+        writer.submit_command(add_author()).await;
+        follower.submit_command(add_author()).await;
+        writer.submit_command(add_author()).await;
+        for _ in 0..3 {
+            assert!(matches!(
+                subscription.next().await,
+                Some(Notification::Event(Event::AuthorAdded(..)))
+            ));
+        }
+        assert!(timeout(Duration::from_millis(100), subscription.next())
+            .await
+            .is_err());
+        assert!(follower
+            .follow_store(Duration::from_millis(20), &termination)
+            .await
+            .is_err());
+
+        termination.signal();
+    }
+
+    // Issues the query until its answer settles, which it does once the
+    // read model has applied what was emitted
+    async fn eventually<Q>(
+        application: &Application<EventArchive>,
+        query: impl Fn() -> Q,
+        settled: impl Fn(&Q::Output) -> bool,
+    ) -> Q::Output
+    where
+        Q: query::IndexSetQuery,
+    {
+        in_time!(loop {
+            let output = application
+                .issue_query(query())
+                .await
+                .expect("a query result");
+            if settled(&output) {
+                break output;
+            }
+            task::yield_now().await;
+        })
+    }
+
+    // Commands emit events, which the write and read models apply on their
+    // own time: a book is accepted once the write model has its author, and
+    // queries lag behind the receipts
+    #[tokio::test]
+    async fn commands_reach_queries_through_their_events() {
+        let (application, _directory) = make_application();
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+        in_time!(while !application
+            .read_model_status()
+            .is_some_and(|status| status.ready)
+        {
+            task::yield_now().await;
+        });
+
+        let CommandReceipt::Created(ResourceId::Author(author_id)) =
+            application.submit_command(add_author()).await
+        else {
+            panic!("expected an author")
+        };
+        let authors = eventually(
+            &application,
+            || query::AllAuthors,
+            |authors| !authors.is_empty(),
+        )
+        .await;
+        assert_eq!(authors[0].0, author_id);
+
+        let add_book = || {
+            Command::AddBook(BookInfo {
+                isbn: model::Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author: author_id,
+            })
+        };
+        let write_model = &application
+            .command_dispatcher
+            .as_ref()
+            .expect("a command dispatcher")
+            .write_model;
+        in_time!(
+            while !write_model.read().await.author_ids.contains(&author_id) {
+                task::yield_now().await;
+            }
+        );
+        let receipt = application.submit_command(add_book()).await;
+        let CommandReceipt::Created(ResourceId::Book(book_id)) = receipt else {
+            panic!("expected a book, not {receipt:?}")
+        };
+
+        let books = eventually(&application, || query::AllBooks, |books| !books.is_empty()).await;
+        assert_eq!(books.len(), 1);
+        assert_eq!(books[0].0, book_id);
+        assert_eq!(books[0].1.author, author_id);
+
+        let by_author = application
+            .issue_query(query::BooksByAuthorId(author_id))
+            .await
+            .expect("a query result");
+        assert_eq!(
+            by_author.iter().map(|book| book.0).collect::<Vec<_>>(),
+            vec![book_id]
+        );
+
+        let hits = application
+            .issue_query(query::text::SearchQuery::new("art of war".to_owned()))
+            .await
+            .expect("a query result")
+            .hits;
+        assert!(hits.iter().any(|hit| matches!(
+            hit.target,
+            query::text::Projection::Books(query::text::BookField::Title(id)) if id == book_id
+        )));
+        let hits = application
+            .issue_query(query::text::SearchQuery::new("tzu".to_owned()))
+            .await
+            .expect("a query result")
+            .hits;
+        assert!(hits.iter().any(|hit| matches!(
+            hit.target,
+            query::text::Projection::Authors(query::text::AuthorField::Name(id)) if id == author_id
+        )));
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn only_books_that_are_there_can_be_deleted() {
+        let (application, _directory) = make_application();
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        let unknown = BookId(UniqueId::fresh());
+        assert_eq!(
+            application
+                .submit_command(Command::DeleteBook(unknown, None))
+                .await,
+            CommandReceipt::RejectedBecause(Rejection::UnknownBook(unknown))
+        );
+
+        let CommandReceipt::CreatedWithAuthor(book_id, _) = application
+            .submit_command(Command::AddBookWithAuthor {
+                isbn: model::Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author: AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            })
+            .await
+        else {
+            panic!("expected a book")
+        };
+        eventually(&application, || query::AllBooks, |books| !books.is_empty()).await;
+
+        // The write model may not have seen the book yet
+        let write_model = &application
+            .command_dispatcher
+            .as_ref()
+            .expect("a command dispatcher")
+            .write_model;
+        in_time!(
+            while !write_model.read().await.books.contains_key(&book_id) {
+                task::yield_now().await;
+            }
+        );
+        assert_eq!(
+            application
+                .submit_command(Command::DeleteBook(book_id, None))
+                .await,
+            CommandReceipt::Accepted
+        );
+        eventually(&application, || query::AllBooks, Vec::is_empty).await;
+
+        termination.signal();
+    }
+
+    struct SequentialIds(std::sync::atomic::AtomicU64);
+
+    impl IdGenerator for SequentialIds {
+        fn fresh(&self) -> UniqueId {
+            let n = self.0.fetch_add(1, Ordering::Relaxed) + 1;
+            UniqueId(uuid::Uuid::from_u128(n.into()))
+        }
+    }
+
+    struct FixedClock(OffsetDateTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> OffsetDateTime {
+            self.0
+        }
+    }
+
+    #[tokio::test]
+    async fn built_application_takes_ids_and_times_from_what_it_is_given() {
+        let directory = tempfile::tempdir().expect("a temporary directory");
+        let mut archive = EventArchive::try_new(directory.path()).expect("a valid event archive");
+        // Seeded the day before the application's clock stands at
+        let (seeded_at, now) = (
+            time::macros::datetime!(2024-04-30 12:00 UTC),
+            time::macros::datetime!(2024-05-01 12:00 UTC),
+        );
+        archive.set_clock(Arc::new(FixedClock(seeded_at)));
+        let seeded_author = AuthorId(UniqueId::fresh());
+        archive
+            .persist(Event::AuthorAdded(
+                seeded_author,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ))
+            .await
+            .expect("a persisted event");
+
+        let application = Application::builder(archive.clone())
+            .channel_capacity(16)
+            .clock(FixedClock(now))
+            .id_generator(SequentialIds(Default::default()))
+            .build()
+            .expect("a built application");
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        let add_book = || {
+            Command::AddBook(BookInfo {
+                isbn: model::Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author: seeded_author,
+            })
+        };
+        let write_model = &application
+            .command_dispatcher
+            .as_ref()
+            .expect("a command dispatcher")
+            .write_model;
+        in_time!(
+            while !write_model.read().await.author_ids.contains(&seeded_author) {
+                task::yield_now().await;
+            }
+        );
+        let receipt = application.submit_command(add_book()).await;
+        let book_id = BookId(UniqueId(uuid::Uuid::from_u128(1)));
+        assert_eq!(receipt, CommandReceipt::Created(book_id.into()));
+        assert_eq!(
+            application.submit_command(add_author()).await,
+            CommandReceipt::Created(AuthorId(UniqueId(uuid::Uuid::from_u128(2))).into())
+        );
+
+        // The journal is in the order the events were persisted in
+        let journal = archive.journal().await.expect("the journal");
+        let times = journal
+            .iter()
+            .map(|event| (event.aggregate_id, event.when))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            times,
+            [
+                (*seeded_author.0.uuid(), seeded_at),
+                (uuid::Uuid::from_u128(1), now),
+                (uuid::Uuid::from_u128(2), now)
+            ]
+        );
+
+        termination.signal();
+    }
 }
@@ -1,14 +1,15 @@
 use core::fmt;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::{str::FromStr, sync::OnceLock, time::SystemTime};
+use std::{str::FromStr, sync::OnceLock};
 use time::OffsetDateTime;
+use uuid::Uuid;
 
 use crate::{
     error::{Error, Result},
     infrastructure::{
-        AggregateIdentity, AggregateRoot, AggregateStream, EventDescriptor, ExternalRepresentation,
-        UniqueId,
+        AggregateIdentity, AggregateRoot, AggregateStream, AggregateVersion, EventDescriptor,
+        ExternalRepresentation, UniqueId,
     },
 };
 
@@ -21,6 +22,19 @@ pub enum Event {
     ReaderAdded(ReaderId, ReaderInfo),
     BookRead(ReaderId, BookReadInfo),
     KeywordAdded(KeywordTarget, String),
+    KeywordRemoved(KeywordTarget, String),
+    // The first author is merged into, and replaced by, the second
+    AuthorMerged(AuthorId, AuthorId),
+    // Supersedes the info the book was added with, or last corrected to
+    BookInfoCorrected(BookId, BookInfo),
+    // The first keyword is renamed to the second, or merged into it if the
+    // second is already in use
+    KeywordRenamed(String, String),
+    // How far into the book the reader is, in percent
+    ReadingProgressUpdated(ReaderId, BookId, u8),
+    // The book is gone from the read model, but its events stay in the
+    // journal
+    BookRemoved(BookId),
 }
 
 impl Event {
@@ -29,6 +43,16 @@ impl Event {
     const READER_ADDED: &str = "reader-added";
     const BOOK_READ: &str = "book-read";
     const KEYWORD_ADDED: &str = "keyword-added";
+    const KEYWORD_REMOVED: &str = "keyword-removed";
+    const AUTHOR_MERGED: &str = "author-merged";
+    const BOOK_INFO_CORRECTED: &str = "book-info-corrected";
+    const KEYWORD_RENAMED: &str = "keyword-renamed";
+    const READING_PROGRESS_UPDATED: &str = "reading-progress-updated";
+    const BOOK_REMOVED: &str = "book-removed";
+
+    // Keywords are not aggregates of their own, so every rename is recorded
+    // against this one
+    const KEYWORDS_AGGREGATE_ID: Uuid = Uuid::from_u128(0x6b657977_6f726473_00000000_00000000);
 
     fn name(&self) -> &str {
         match self {
@@ -37,66 +61,137 @@ impl Event {
             Event::ReaderAdded(..) => Self::READER_ADDED,
             Event::BookRead(..) => Self::BOOK_READ,
             Event::KeywordAdded(..) => Self::KEYWORD_ADDED,
+            Event::KeywordRemoved(..) => Self::KEYWORD_REMOVED,
+            Event::AuthorMerged(..) => Self::AUTHOR_MERGED,
+            Event::BookInfoCorrected(..) => Self::BOOK_INFO_CORRECTED,
+            Event::KeywordRenamed(..) => Self::KEYWORD_RENAMED,
+            Event::ReadingProgressUpdated(..) => Self::READING_PROGRESS_UPDATED,
+            Event::BookRemoved(..) => Self::BOOK_REMOVED,
+        }
+    }
+}
+
+// What an event's aggregate_id refers to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResourceKind {
+    Book,
+    Author,
+    Reader,
+    // Every keyword rename shares the one aggregate
+    Keywords,
+}
+
+impl ExternalRepresentation {
+    // Goes by `what`, except for keyword-added and keyword-removed which are
+    // recorded against whichever book or author the keyword was added to or
+    // removed from. None for events
+    // that are not known, or whose data does not say.
+    pub fn resource_kind(&self) -> Option<ResourceKind> {
+        match self.what.as_str() {
+            Event::BOOK_ADDED | Event::BOOK_INFO_CORRECTED | Event::BOOK_REMOVED => {
+                Some(ResourceKind::Book)
+            }
+            Event::AUTHOR_ADDED | Event::AUTHOR_MERGED => Some(ResourceKind::Author),
+            Event::READER_ADDED | Event::BOOK_READ | Event::READING_PROGRESS_UPDATED => {
+                Some(ResourceKind::Reader)
+            }
+            Event::KEYWORD_RENAMED => Some(ResourceKind::Keywords),
+            Event::KEYWORD_ADDED | Event::KEYWORD_REMOVED => {
+                serde_json::from_value::<KeywordSurrogate>(self.data.clone())
+                    .ok()
+                    .map(|KeywordSurrogate { target, .. }| match target {
+                        KeywordTarget::Book(..) => ResourceKind::Book,
+                        KeywordTarget::Author(..) => ResourceKind::Author,
+                    })
+            }
+            _ => None,
         }
     }
 }
 
 #[derive(Serialize, Deserialize)]
-struct KeywordAddedSurrogate {
+struct KeywordSurrogate {
     keyword: String,
     target: KeywordTarget,
 }
 
+#[derive(Serialize, Deserialize)]
+struct KeywordRenamedSurrogate {
+    from: String,
+    to: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReadingProgressSurrogate {
+    book_id: BookId,
+    percent: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthorMergedSurrogate {
+    into: AuthorId,
+}
+
 impl EventDescriptor for Event {
     fn external_representation(
         &self,
         UniqueId(id): UniqueId,
-        when: SystemTime,
+        when: OffsetDateTime,
     ) -> Result<ExternalRepresentation> {
-        match self {
-            Event::BookAdded(BookId(UniqueId(aggregate_id)), info) => Ok(ExternalRepresentation {
-                id,
-                when,
-                aggregate_id: *aggregate_id,
-                what: self.name().to_owned(),
-                data: serde_json::to_value(info)?,
-            }),
+        let (aggregate_id, data) = match self {
+            Event::BookAdded(BookId(UniqueId(aggregate_id)), info)
+            | Event::BookInfoCorrected(BookId(UniqueId(aggregate_id)), info) => {
+                (*aggregate_id, serde_json::to_value(info)?)
+            }
             Event::AuthorAdded(AuthorId(UniqueId(aggregate_id)), info) => {
-                Ok(ExternalRepresentation {
-                    id,
-                    when,
-                    aggregate_id: *aggregate_id,
-                    what: self.name().to_owned(),
-                    data: serde_json::to_value(info)?,
-                })
+                (*aggregate_id, serde_json::to_value(info)?)
             }
             Event::ReaderAdded(ReaderId(UniqueId(aggregate_id)), info) => {
-                Ok(ExternalRepresentation {
-                    id,
-                    when,
-                    aggregate_id: *aggregate_id,
-                    what: self.name().to_owned(),
-                    data: serde_json::to_value(info)?,
-                })
+                (*aggregate_id, serde_json::to_value(info)?)
             }
-            Event::BookRead(ReaderId(UniqueId(aggregate_id)), info) => Ok(ExternalRepresentation {
-                id,
-                when,
-                aggregate_id: *aggregate_id,
-                what: self.name().to_owned(),
-                data: serde_json::to_value(info)?,
-            }),
-            Event::KeywordAdded(target, keyword) => Ok(ExternalRepresentation {
-                id,
-                when,
-                aggregate_id: *target.aggregate_id().uuid(),
-                what: self.name().to_owned(),
-                data: serde_json::to_value(KeywordAddedSurrogate {
+            Event::BookRead(ReaderId(UniqueId(aggregate_id)), info) => {
+                (*aggregate_id, serde_json::to_value(info)?)
+            }
+            Event::KeywordAdded(target, keyword) | Event::KeywordRemoved(target, keyword) => (
+                *target.aggregate_id().uuid(),
+                serde_json::to_value(KeywordSurrogate {
                     keyword: keyword.to_owned(),
                     target: *target,
                 })?,
-            }),
-        }
+            ),
+            // Recorded with the author that goes away
+            Event::AuthorMerged(AuthorId(UniqueId(aggregate_id)), into) => (
+                *aggregate_id,
+                serde_json::to_value(AuthorMergedSurrogate { into: *into })?,
+            ),
+            Event::KeywordRenamed(from, to) => (
+                Self::KEYWORDS_AGGREGATE_ID,
+                serde_json::to_value(KeywordRenamedSurrogate {
+                    from: from.to_owned(),
+                    to: to.to_owned(),
+                })?,
+            ),
+            Event::ReadingProgressUpdated(ReaderId(UniqueId(aggregate_id)), book_id, percent) => (
+                *aggregate_id,
+                serde_json::to_value(ReadingProgressSurrogate {
+                    book_id: *book_id,
+                    percent: *percent,
+                })?,
+            ),
+            Event::BookRemoved(BookId(UniqueId(aggregate_id))) => {
+                (*aggregate_id, serde_json::Value::Null)
+            }
+        };
+
+        Ok(ExternalRepresentation {
+            id,
+            when,
+            aggregate_id,
+            what: self.name().to_owned(),
+            data,
+            // The store numbers the event when it persists it
+            sequence: 0,
+        })
     }
 
     fn from_external_representation(
@@ -116,6 +211,10 @@ impl EventDescriptor for Event {
                 BookId(UniqueId(*aggregate_id)),
                 serde_json::from_value(data.clone())?,
             )),
+            Event::BOOK_INFO_CORRECTED => Ok(Event::BookInfoCorrected(
+                BookId(UniqueId(*aggregate_id)),
+                serde_json::from_value(data.clone())?,
+            )),
             Event::READER_ADDED => Ok(Event::ReaderAdded(
                 ReaderId(UniqueId(*aggregate_id)),
                 serde_json::from_value(data.clone())?,
@@ -125,10 +224,31 @@ impl EventDescriptor for Event {
                 serde_json::from_value(data.clone())?,
             )),
             Event::KEYWORD_ADDED => {
-                let KeywordAddedSurrogate { keyword, target }: KeywordAddedSurrogate =
-                    serde_json::from_value(data.clone())?;
+                let KeywordSurrogate { keyword, target } = serde_json::from_value(data.clone())?;
                 Ok(Event::KeywordAdded(target, keyword))
             }
+            Event::KEYWORD_REMOVED => {
+                let KeywordSurrogate { keyword, target } = serde_json::from_value(data.clone())?;
+                Ok(Event::KeywordRemoved(target, keyword))
+            }
+            Event::AUTHOR_MERGED => {
+                let AuthorMergedSurrogate { into } = serde_json::from_value(data.clone())?;
+                Ok(Event::AuthorMerged(AuthorId(UniqueId(*aggregate_id)), into))
+            }
+            Event::KEYWORD_RENAMED => {
+                let KeywordRenamedSurrogate { from, to } = serde_json::from_value(data.clone())?;
+                Ok(Event::KeywordRenamed(from, to))
+            }
+            Event::READING_PROGRESS_UPDATED => {
+                let ReadingProgressSurrogate { book_id, percent } =
+                    serde_json::from_value(data.clone())?;
+                Ok(Event::ReadingProgressUpdated(
+                    ReaderId(UniqueId(*aggregate_id)),
+                    book_id,
+                    percent,
+                ))
+            }
+            Event::BOOK_REMOVED => Ok(Event::BookRemoved(BookId(UniqueId(*aggregate_id)))),
             otherwise => Err(Error::UnknownEventType(otherwise.to_owned())),
         }
     }
@@ -149,22 +269,51 @@ impl KeywordTarget {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct Author(pub AuthorId, pub AuthorInfo);
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct Book(pub BookId, pub BookInfo);
 
-#[derive(Clone)]
+#[derive(Clone, Hash)]
 pub enum Command {
     AddBook(BookInfo),
+    // Creates the author first, even if there is one by that name already
+    AddBookWithAuthor {
+        isbn: Isbn,
+        title: String,
+        author: AuthorInfo,
+    },
     AddAuthor(AuthorInfo),
     AddReader(ReaderInfo),
     AddReadBook(BookReadInfo),
-    AddKeyword(Keyword, KeywordTarget),
+    // The writes to an existing aggregate optionally apply only if it is
+    // still at the expected version
+    AddKeyword(Keyword, KeywordTarget, Option<AggregateVersion>),
+    // Only a keyword that the target has can be removed from it
+    RemoveKeyword(Keyword, KeywordTarget, Option<AggregateVersion>),
+    // Re-points the books of merge to keep, and removes merge
+    MergeAuthors {
+        keep: AuthorId,
+        merge: AuthorId,
+    },
+    // Appends a correction; the original BookAdded stays in the journal
+    CorrectBookInfo(BookId, BookInfo, Option<AggregateVersion>),
+    // Re-points the targets of from to to, merging with those it already has
+    RenameKeyword {
+        from: Keyword,
+        to: Keyword,
+    },
+    // In percent, so at most 100. Reaching 100 finishes the book, which is
+    // then also recorded as read, as of now.
+    UpdateReadingProgress(ReaderId, BookId, u8),
+    // Only books that are there can be deleted
+    DeleteBook(BookId, Option<AggregateVersion>),
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+// Arrives on the wire as a JSON string, validated by FromStr
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
 pub struct Keyword(String);
 
 impl Keyword {
@@ -177,7 +326,7 @@ impl Keyword {
 impl AsRef<str> for Keyword {
     fn as_ref(&self) -> &str {
         let Self(inner) = self;
-        &inner
+        inner
     }
 }
 
@@ -191,7 +340,7 @@ impl fmt::Display for Keyword {
 static KEYWORD_REGEX: OnceLock<Regex> = OnceLock::new();
 
 fn keyword_regex() -> &'static Regex {
-    KEYWORD_REGEX.get_or_init(|| Regex::new(r"[\p{L}_-]+").expect("KEYWORD_REGEX is valid"))
+    KEYWORD_REGEX.get_or_init(|| Regex::new(r"^[\p{L}_-]+$").expect("KEYWORD_REGEX is valid"))
 }
 
 impl FromStr for Keyword {
@@ -208,21 +357,57 @@ impl FromStr for Keyword {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl TryFrom<String> for Keyword {
+    type Error = Error;
+
+    fn try_from(keyword: String) -> Result<Self> {
+        keyword.parse()
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BookInfo {
     pub isbn: Isbn,
     pub title: String,
     pub author: AuthorId,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AuthorInfo {
     pub name: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// ISBNs are normalized to their hyphenated form whenever they are read,
+// so that "9781611806977" and "978-1-61180-697-7" are the same book. Strings
+// that are not valid ISBNs are kept as they are.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String")]
 pub struct Isbn(pub String);
 
+impl Isbn {
+    pub fn canonical(isbn: &str) -> String {
+        isbn.parse::<isbn::Isbn>()
+            .ok()
+            .and_then(|isbn| isbn.hyphenate().ok())
+            .map(|isbn| isbn.to_string())
+            .unwrap_or_else(|| isbn.to_owned())
+    }
+
+    pub fn digits(&self) -> String {
+        let Self(isbn) = self;
+        isbn.chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_uppercase())
+            .collect()
+    }
+}
+
+impl From<String> for Isbn {
+    fn from(isbn: String) -> Self {
+        Self(Self::canonical(&isbn))
+    }
+}
+
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AuthorId(pub UniqueId);
 
@@ -241,7 +426,7 @@ impl From<ReaderId> for uuid::Uuid {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, Serialize, Deserialize)]
 pub struct ReaderInfo {
     pub name: String,
     pub unique_moniker: String,
@@ -250,6 +435,10 @@ pub struct ReaderInfo {
 #[derive(Debug)]
 pub struct Reader(pub ReaderId, pub ReaderInfo);
 
+// A read is identified by all of its fields. Reading the same book again is
+// a new read as long as its when differs; a read with the same reader, book
+// and when is the same read and is rejected. Both the write model and the
+// read model key reads on this.
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BookReadInfo {
     // This thing should not be needed here. Move into the event
@@ -261,14 +450,19 @@ pub struct BookReadInfo {
 impl AggregateRoot for Author {
     type Id = AuthorId;
 
-    fn try_load(stream: AggregateStream) -> Result<Self> {
-        if let Event::AuthorAdded(id, info) = stream.peek()? {
-            Ok(Author(id, info))
-        } else {
-            Err(Error::AggregateParseError(
-                "expected an AuthorAdded".to_owned(),
-            ))
-        }
+    fn try_load(stream: AggregateStream<'_>) -> Result<Self> {
+        stream
+            .fold(None, |author, event| match (author, event) {
+                (None, Event::AuthorAdded(id, info)) => Ok(Some(Author(id, info))),
+                (None, _) => Err(Error::AggregateParseError(
+                    "expected an AuthorAdded".to_owned(),
+                )),
+                // A merged author no longer exists on its own
+                (Some(_), Event::AuthorMerged(..)) => Ok(None),
+                // Keywords are stored with the author but are not a part of it
+                (author, _) => Ok(author),
+            })?
+            .ok_or(Error::NoSuchAggregate)
     }
 }
 
@@ -290,6 +484,7 @@ impl From<BookId> for uuid::Uuid {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ResourceId {
     Book(BookId),
     Author(AuthorId),
@@ -317,15 +512,20 @@ impl From<ReaderId> for ResourceId {
 impl AggregateRoot for Book {
     type Id = BookId;
 
-    fn try_load(stream: AggregateStream) -> Result<Self> {
-        // This can be simplified
-        if let Event::BookAdded(id, info) = stream.peek()? {
-            Ok(Book(id, info))
-        } else {
-            Err(Error::AggregateParseError(
-                "Expected a BookAdded".to_owned(),
-            ))
-        }
+    fn try_load(stream: AggregateStream<'_>) -> Result<Self> {
+        stream
+            .fold(None, |book, event| match (book, event) {
+                (None, Event::BookAdded(id, info)) => Ok(Some(Book(id, info))),
+                (None, _) => Err(Error::AggregateParseError(
+                    "Expected a BookAdded".to_owned(),
+                )),
+                // The latest correction wins
+                (Some(Book(id, _)), Event::BookInfoCorrected(_, info)) => Ok(Some(Book(id, info))),
+                (Some(_), Event::BookRemoved(_)) => Ok(None),
+                // Keywords are stored with the book but are not a part of it
+                (book, _) => Ok(book),
+            })?
+            .ok_or(Error::NoSuchAggregate)
     }
 }
 
@@ -337,3 +537,161 @@ impl AggregateIdentity for BookId {
         id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords_are_whole_words() {
+        assert!("science-fiction".parse::<Keyword>().is_ok());
+        assert!("Ålandsö".parse::<Keyword>().is_ok());
+        assert!("science fiction".parse::<Keyword>().is_err());
+        assert!("\"fiction\"".parse::<Keyword>().is_err());
+        assert!("".parse::<Keyword>().is_err());
+    }
+
+    #[test]
+    fn every_event_has_a_resource_kind() -> Result<()> {
+        let book_id = BookId(UniqueId::fresh());
+        let author_id = AuthorId(UniqueId::fresh());
+        let reader_id = ReaderId(UniqueId::fresh());
+        let book_info = BookInfo {
+            isbn: Isbn("978-1-61180-697-7".to_owned()),
+            title: "The Art of War".to_owned(),
+            author: author_id,
+        };
+
+        for (event, kind) in [
+            (
+                Event::BookAdded(book_id, book_info.clone()),
+                ResourceKind::Book,
+            ),
+            (
+                Event::BookInfoCorrected(book_id, book_info),
+                ResourceKind::Book,
+            ),
+            (Event::BookRemoved(book_id), ResourceKind::Book),
+            (
+                Event::AuthorAdded(
+                    author_id,
+                    AuthorInfo {
+                        name: "Sun Tzu".to_owned(),
+                    },
+                ),
+                ResourceKind::Author,
+            ),
+            (
+                Event::AuthorMerged(author_id, AuthorId(UniqueId::fresh())),
+                ResourceKind::Author,
+            ),
+            (
+                Event::ReaderAdded(
+                    reader_id,
+                    ReaderInfo {
+                        name: "Reader".to_owned(),
+                        unique_moniker: "reader".to_owned(),
+                    },
+                ),
+                ResourceKind::Reader,
+            ),
+            (
+                Event::BookRead(
+                    reader_id,
+                    BookReadInfo {
+                        reader_id,
+                        book_id,
+                        when: None,
+                    },
+                ),
+                ResourceKind::Reader,
+            ),
+            (
+                Event::ReadingProgressUpdated(reader_id, book_id, 50),
+                ResourceKind::Reader,
+            ),
+            (
+                Event::KeywordAdded(KeywordTarget::Book(book_id), "war".to_owned()),
+                ResourceKind::Book,
+            ),
+            (
+                Event::KeywordAdded(KeywordTarget::Author(author_id), "war".to_owned()),
+                ResourceKind::Author,
+            ),
+            (
+                Event::KeywordRemoved(KeywordTarget::Author(author_id), "war".to_owned()),
+                ResourceKind::Author,
+            ),
+            (
+                Event::KeywordRenamed("war".to_owned(), "warfare".to_owned()),
+                ResourceKind::Keywords,
+            ),
+        ] {
+            let external =
+                event.external_representation(UniqueId::fresh(), OffsetDateTime::now_utc())?;
+            assert_eq!(external.resource_kind(), Some(kind), "{}", external.what);
+        }
+
+        let unknown = ExternalRepresentation {
+            what: "book-burned".to_owned(),
+            ..Event::KeywordRenamed("a".to_owned(), "b".to_owned())
+                .external_representation(UniqueId::fresh(), OffsetDateTime::now_utc())?
+        };
+        assert_eq!(unknown.resource_kind(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn book_added_normalizes_isbn() -> Result<()> {
+        let record = ExternalRepresentation {
+            id: uuid::Uuid::new_v4(),
+            when: OffsetDateTime::now_utc(),
+            aggregate_id: uuid::Uuid::new_v4(),
+            what: Event::BOOK_ADDED.to_owned(),
+            data: serde_json::json!({
+                "author": uuid::Uuid::new_v4(),
+                "isbn": "9781611806977",
+                "title": "The Art of War",
+            }),
+            sequence: 0,
+        };
+
+        let Event::BookAdded(id, info) = Event::from_external_representation(&record)? else {
+            panic!("expected a BookAdded")
+        };
+        assert_eq!(info.isbn.0, "978-1-61180-697-7");
+        assert_eq!(info.isbn.digits(), "9781611806977");
+
+        let stored =
+            Event::BookAdded(id, info).external_representation(UniqueId::fresh(), record.when)?;
+        assert_eq!(stored.data["isbn"], "978-1-61180-697-7");
+
+        Ok(())
+    }
+
+    #[test]
+    fn book_aggregate_takes_the_latest_correction() -> Result<()> {
+        let id = BookId(UniqueId::fresh());
+        let info = BookInfo {
+            isbn: Isbn("978-1-61180-697-7".to_owned()),
+            title: "The Art of Wor".to_owned(),
+            author: AuthorId(UniqueId::fresh()),
+        };
+        let corrected = BookInfo {
+            title: "The Art of War".to_owned(),
+            ..info.clone()
+        };
+        let events = [
+            Event::BookAdded(id, info),
+            Event::BookInfoCorrected(id, corrected.clone()),
+        ]
+        .iter()
+        .map(|event| event.external_representation(UniqueId::fresh(), OffsetDateTime::now_utc()))
+        .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(Book::try_load(events.into())?, Book(id, corrected));
+
+        Ok(())
+    }
+}
@@ -1,4 +1,10 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use time::OffsetDateTime;
+
+#[cfg(any(test, feature = "debug"))]
+use serde::Serialize;
+#[cfg(any(test, feature = "debug"))]
+use uuid::Uuid;
 
 use crate::core::model::{
     Author, AuthorId, AuthorInfo, Book, BookId, BookInfo, BookReadInfo, Event, Keyword,
@@ -15,19 +21,202 @@ pub struct IndexSet {
     books_by_reader_id: HashMap<ReaderId, HashSet<BookReadInfo>>,
     books_by_author_id: HashMap<AuthorId, Vec<BookId>>,
 
+    // Books still being read, so short of 100 percent
+    reading_progress: HashMap<ReaderId, HashMap<BookId, u8>>,
+
+    // In the order they were added, which is the order of the journal
+    books_added: Vec<BookId>,
+    authors_added: Vec<AuthorId>,
+
     texts: text::SearchIndex,
     keywords: keywords::Index,
+
+    // Only for events applied with apply_at
+    book_times: HashMap<BookId, Timestamps>,
+    author_times: HashMap<AuthorId, Timestamps>,
+    reader_times: HashMap<ReaderId, Timestamps>,
+
+    // Applied events by name, including those that leave no other trace here
+    event_counts: BTreeMap<String, usize>,
+}
+
+// Everything an IndexSet holds, in a stable order so that equal states
+// serialize equally. This is for asserting on a whole projection at once in
+// tests, and is not available otherwise.
+#[cfg(any(test, feature = "debug"))]
+#[derive(Debug, Serialize)]
+pub struct IndexSnapshot {
+    pub authors: BTreeMap<Uuid, AuthorInfo>,
+    pub books: BTreeMap<Uuid, BookInfo>,
+    pub readers: BTreeMap<Uuid, ReaderInfo>,
+    pub books_by_author: BTreeMap<Uuid, Vec<Uuid>>,
+    pub books_read: BTreeMap<Uuid, Vec<BookReadInfo>>,
+    pub keywords: BTreeMap<String, Vec<KeywordTarget>>,
+    pub counts: SnapshotCounts,
+}
+
+#[cfg(any(test, feature = "debug"))]
+#[derive(Debug, Serialize)]
+pub struct SnapshotCounts {
+    pub authors: usize,
+    pub books: usize,
+    pub readers: usize,
+    pub keywords: usize,
+    pub search_terms: usize,
+}
+
+#[cfg(any(test, feature = "debug"))]
+impl IndexSet {
+    pub fn snapshot(&self) -> IndexSnapshot {
+        let books_by_author = self
+            .books_by_author_id
+            .iter()
+            .map(|(AuthorId(author_id), book_ids)| {
+                let mut book_ids = book_ids
+                    .iter()
+                    .map(|BookId(id)| *id.uuid())
+                    .collect::<Vec<_>>();
+                book_ids.sort();
+                (*author_id.uuid(), book_ids)
+            })
+            .collect();
+        let books_read = self
+            .books_by_reader_id
+            .iter()
+            .map(|(ReaderId(reader_id), reads)| {
+                let mut reads = reads.iter().cloned().collect::<Vec<_>>();
+                reads.sort_by_key(|BookReadInfo { book_id, when, .. }| (*book_id.0.uuid(), *when));
+                (*reader_id.uuid(), reads)
+            })
+            .collect();
+        let keywords = self.keywords.snapshot();
+
+        IndexSnapshot {
+            authors: self
+                .authors
+                .iter()
+                .map(|(AuthorId(id), info)| (*id.uuid(), info.clone()))
+                .collect(),
+            books: self
+                .books
+                .iter()
+                .map(|(BookId(id), info)| (*id.uuid(), info.clone()))
+                .collect(),
+            readers: self
+                .readers
+                .iter()
+                .map(|(ReaderId(id), info)| (*id.uuid(), info.clone()))
+                .collect(),
+            books_by_author,
+            books_read,
+            counts: SnapshotCounts {
+                authors: self.authors.len(),
+                books: self.books.len(),
+                readers: self.readers.len(),
+                keywords: keywords.len(),
+                search_terms: self.texts.term_count(),
+            },
+            keywords,
+        }
+    }
 }
 
 impl IndexSet {
+    // With the trigram index that InfixQuery needs
+    pub fn with_infix_search() -> Self {
+        Self {
+            texts: text::SearchIndex::with_trigrams(),
+            ..Default::default()
+        }
+    }
+
+    // Empty, but indexing text the way this one does
+    pub fn emptied(&self) -> Self {
+        Self {
+            texts: self.texts.emptied(),
+            ..Default::default()
+        }
+    }
+
+    pub fn term_length_threshold(&self) -> usize {
+        self.texts.term_length_threshold()
+    }
+
+    // Rebuilds the search index from the journal, so that every title and
+    // name is indexed the way terms are indexed now, or with a new term
+    // length threshold. The other indices are left as they are.
+    pub fn reindex_texts(&mut self, journal: &[Event], term_length_threshold: Option<usize>) {
+        let mut texts = self.texts.emptied();
+        if let Some(threshold) = term_length_threshold {
+            texts = texts.with_term_length_threshold(threshold);
+        }
+        for event in journal {
+            texts.apply(event)
+        }
+        self.texts = texts;
+    }
+
+    fn book_count(&self, author_id: &AuthorId) -> usize {
+        self.books_by_author_id.get(author_id).map_or(0, Vec::len)
+    }
+
     pub fn apply(&mut self, event: Event) {
         self.texts.apply(&event);
         self.apply_event(event)
     }
 
+    // Also keeps when books, authors and readers were added and last
+    // changed, going by when is the time the event was journaled at
+    pub fn apply_at(&mut self, event: Event, when: OffsetDateTime) {
+        self.stamp(&event, when);
+        self.apply(event)
+    }
+
+    // Before the event is applied, so that a merge still knows the books
+    // of the author that goes away
+    fn stamp(&mut self, event: &Event, when: OffsetDateTime) {
+        let added = Timestamps {
+            created_at: when,
+            updated_at: when,
+        };
+        let touch = |timestamps: Option<&mut Timestamps>| {
+            if let Some(timestamps) = timestamps {
+                timestamps.updated_at = when
+            }
+        };
+
+        match event {
+            Event::BookAdded(id, _) => {
+                self.book_times.insert(*id, added);
+            }
+            Event::AuthorAdded(id, _) => {
+                self.author_times.insert(*id, added);
+            }
+            Event::ReaderAdded(id, _) => {
+                self.reader_times.insert(*id, added);
+            }
+            Event::BookInfoCorrected(id, _) => touch(self.book_times.get_mut(id)),
+            // The books move to into, which gains them
+            Event::AuthorMerged(merged, into) => {
+                for book_id in self.books_by_author_id.get(merged).into_iter().flatten() {
+                    touch(self.book_times.get_mut(book_id))
+                }
+                touch(self.author_times.get_mut(into));
+                self.author_times.remove(merged);
+            }
+            _otherwise => (),
+        }
+    }
+
     fn apply_event(&mut self, event: Event) {
+        *self
+            .event_counts
+            .entry(event.name().to_owned())
+            .or_default() += 1;
+
         match event {
             Event::BookAdded(id, info) => {
+                self.books_added.push(id);
                 self.books.insert(id, info.clone());
                 self.books_by_author_id
                     .entry(info.author)
@@ -35,6 +224,7 @@ impl IndexSet {
                     .push(id);
             }
             Event::AuthorAdded(id, info) => {
+                self.authors_added.push(id);
                 self.authors.insert(id, info);
             }
             Event::ReaderAdded(id, info) => {
@@ -48,6 +238,66 @@ impl IndexSet {
             Event::KeywordAdded(target, keyword) => {
                 self.keywords.add_keyword_to_target(keyword, target)
             }
+            Event::KeywordRemoved(target, keyword) => {
+                self.keywords.remove_keyword_from_target(&keyword, target)
+            }
+            Event::BookInfoCorrected(id, info) => {
+                if let Some(previous) = self.books.insert(id, info.clone()) {
+                    if let Some(books) = self.books_by_author_id.get_mut(&previous.author) {
+                        books.retain(|book_id| *book_id != id);
+                    }
+                }
+                self.books_by_author_id
+                    .entry(info.author)
+                    .or_default()
+                    .push(id);
+            }
+            Event::AuthorMerged(merged, into) => {
+                let books = self.books_by_author_id.remove(&merged).unwrap_or_default();
+                for book_id in &books {
+                    if let Some(book) = self.books.get_mut(book_id) {
+                        book.author = into;
+                    }
+                }
+                self.books_by_author_id
+                    .entry(into)
+                    .or_default()
+                    .extend(books);
+                self.authors.remove(&merged);
+                self.authors_added.retain(|id| *id != merged);
+                self.keywords
+                    .retarget(KeywordTarget::Author(merged), KeywordTarget::Author(into));
+            }
+            Event::KeywordRenamed(from, to) => self.keywords.rename(&from, to),
+            // Reads of it go too, so that it is not counted as read
+            Event::BookRemoved(id) => {
+                if let Some(info) = self.books.remove(&id) {
+                    if let Some(books) = self.books_by_author_id.get_mut(&info.author) {
+                        books.retain(|book_id| *book_id != id);
+                        if books.is_empty() {
+                            self.books_by_author_id.remove(&info.author);
+                        }
+                    }
+                }
+                self.books_added.retain(|book_id| *book_id != id);
+                self.book_times.remove(&id);
+                for progress in self.reading_progress.values_mut() {
+                    progress.remove(&id);
+                }
+                self.books_by_reader_id.retain(|_, reads| {
+                    reads.retain(|read| read.book_id != id);
+                    !reads.is_empty()
+                });
+                self.keywords.remove_target(KeywordTarget::Book(id));
+            }
+            Event::ReadingProgressUpdated(reader_id, book_id, percent) => {
+                let progress = self.reading_progress.entry(reader_id).or_default();
+                if percent < 100 {
+                    progress.insert(book_id, percent);
+                } else {
+                    progress.remove(&book_id);
+                }
+            }
         }
     }
 }
@@ -58,422 +308,3095 @@ pub trait IndexSetQuery {
     fn execute(&self, index: &IndexSet) -> Self::Output;
 }
 
-pub struct AllBooks;
+// When a book, author or reader was added, and when it last changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timestamps {
+    pub created_at: OffsetDateTime,
+    pub updated_at: OffsetDateTime,
+}
 
-impl IndexSetQuery for AllBooks {
-    type Output = Vec<Book>;
+// What the read model keeps Timestamps for
+pub trait Timestamped {
+    fn timestamps(&self, index: &IndexSet) -> Option<Timestamps>;
+}
 
-    fn execute(&self, index: &IndexSet) -> Self::Output {
-        index
-            .books
-            .iter()
-            .map(|(id, info)| Book(*id, info.clone()))
+impl Timestamped for Book {
+    fn timestamps(&self, index: &IndexSet) -> Option<Timestamps> {
+        index.book_times.get(&self.0).copied()
+    }
+}
+
+impl Timestamped for Author {
+    fn timestamps(&self, index: &IndexSet) -> Option<Timestamps> {
+        index.author_times.get(&self.0).copied()
+    }
+}
+
+impl Timestamped for Reader {
+    fn timestamps(&self, index: &IndexSet) -> Option<Timestamps> {
+        index.reader_times.get(&self.0).copied()
+    }
+}
+
+// Authors with their book counts
+impl<T> Timestamped for (T, usize)
+where
+    T: Timestamped,
+{
+    fn timestamps(&self, index: &IndexSet) -> Option<Timestamps> {
+        self.0.timestamps(index)
+    }
+}
+
+// Query outputs that WithTimestamps can pair up with timestamps
+pub trait Stamp {
+    type Stamped;
+
+    fn stamp(self, index: &IndexSet) -> Self::Stamped;
+}
+
+impl<T> Stamp for Option<T>
+where
+    T: Timestamped,
+{
+    type Stamped = Option<(T, Option<Timestamps>)>;
+
+    fn stamp(self, index: &IndexSet) -> Self::Stamped {
+        self.map(|found| {
+            let timestamps = found.timestamps(index);
+            (found, timestamps)
+        })
+    }
+}
+
+impl<T> Stamp for Vec<T>
+where
+    T: Timestamped,
+{
+    type Stamped = Vec<(T, Option<Timestamps>)>;
+
+    fn stamp(self, index: &IndexSet) -> Self::Stamped {
+        self.into_iter()
+            .map(|found| {
+                let timestamps = found.timestamps(index);
+                (found, timestamps)
+            })
             .collect()
     }
 }
 
-pub struct BookById(pub BookId);
+impl<T> Stamp for Option<Vec<T>>
+where
+    T: Timestamped,
+{
+    type Stamped = Option<Vec<(T, Option<Timestamps>)>>;
 
-impl IndexSetQuery for BookById {
-    type Output = Option<Book>;
+    fn stamp(self, index: &IndexSet) -> Self::Stamped {
+        self.map(|found| found.stamp(index))
+    }
+}
 
-    fn execute(&self, index: &IndexSet) -> Self::Output {
-        let Self(id) = self;
-        index.books.get(id).map(|info| Book(*id, info.clone()))
+impl<T> Stamp for Page<T>
+where
+    T: Timestamped,
+{
+    type Stamped = Page<(T, Option<Timestamps>)>;
+
+    fn stamp(self, index: &IndexSet) -> Self::Stamped {
+        let Self { items, total } = self;
+        Page {
+            items: items.stamp(index),
+            total,
+        }
     }
 }
 
-pub struct AuthorById(pub AuthorId);
+impl<T, E> Stamp for std::result::Result<T, E>
+where
+    T: Timestamped,
+{
+    type Stamped = std::result::Result<(T, Option<Timestamps>), E>;
 
-impl IndexSetQuery for AuthorById {
-    type Output = Option<Author>;
+    fn stamp(self, index: &IndexSet) -> Self::Stamped {
+        self.map(|found| {
+            let timestamps = found.timestamps(index);
+            (found, timestamps)
+        })
+    }
+}
+
+// Runs the query, and pairs each book, author or reader it finds with its
+// timestamps, if the read model has them
+pub struct WithTimestamps<Q>(pub Q);
+
+impl<Q> IndexSetQuery for WithTimestamps<Q>
+where
+    Q: IndexSetQuery,
+    Q::Output: Stamp,
+{
+    type Output = <Q::Output as Stamp>::Stamped;
 
     fn execute(&self, index: &IndexSet) -> Self::Output {
-        let Self(id) = self;
-        index.authors.get(id).map(|info| Author(*id, info.clone()))
+        let Self(query) = self;
+        query.execute(index).stamp(index)
     }
 }
 
-pub struct AuthorByBookId(pub BookId);
+// A page of what the query finds, and how many it found in all
+pub struct Paged<Q> {
+    pub query: Q,
+    pub limit: usize,
+    pub offset: usize,
+}
 
-impl IndexSetQuery for AuthorByBookId {
-    type Output = Option<Author>;
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+}
+
+// A query that finds the ids of what it answers with first, in the order it
+// answers in, so that a page need only look up what is on it
+pub trait PageableQuery {
+    type Id;
+    type Item;
+
+    fn ids(&self, index: &IndexSet) -> Vec<Self::Id>;
+
+    fn item(&self, index: &IndexSet, id: &Self::Id) -> Option<Self::Item>;
+
+    // Everything, for the query unpaged
+    fn items(&self, index: &IndexSet) -> Vec<Self::Item> {
+        self.ids(index)
+            .iter()
+            .filter_map(|id| self.item(index, id))
+            .collect()
+    }
+}
+
+impl<Q> IndexSetQuery for Paged<Q>
+where
+    Q: PageableQuery,
+{
+    type Output = Page<Q::Item>;
 
     fn execute(&self, index: &IndexSet) -> Self::Output {
-        let Self(id) = self;
-        index.books.get(id).and_then(|BookInfo { author, .. }| {
-            index
-                .authors
-                .get(author)
-                .map(|info| Author(*author, info.clone())) // this pattern repeats.
-        })
+        let ids = self.query.ids(index);
+        let start = self.offset.min(ids.len());
+        let end = start.saturating_add(self.limit).min(ids.len());
+        Page {
+            total: ids.len(),
+            items: ids[start..end]
+                .iter()
+                .filter_map(|id| self.query.item(index, id))
+                .collect(),
+        }
     }
 }
 
-pub struct BooksByReader(pub ReaderId);
+// By id, so that they page the same way every time
+pub struct AllBooks;
 
-impl IndexSetQuery for BooksByReader {
+impl IndexSetQuery for AllBooks {
     type Output = Vec<Book>;
 
     fn execute(&self, index: &IndexSet) -> Self::Output {
-        let Self(id) = self;
-
-        index
-            .books_by_reader_id
-            .get(id)
-            .and_then(|read_books| {
-                read_books
-                    .iter()
-                    .map(|BookReadInfo { book_id, .. }| {
-                        index
-                            .books
-                            .get(book_id)
-                            .map(|info| Book(*book_id, info.clone()))
-                    })
-                    .collect::<Option<Vec<_>>>()
-            })
-            .unwrap_or_default()
+        self.items(index)
     }
 }
 
-pub struct AllAuthors;
+impl PageableQuery for AllBooks {
+    type Id = BookId;
+    type Item = Book;
 
-impl IndexSetQuery for AllAuthors {
-    type Output = Vec<Author>;
+    fn ids(&self, index: &IndexSet) -> Vec<BookId> {
+        let mut ids = index.books.keys().copied().collect::<Vec<_>>();
+        ids.sort_by_key(|BookId(id)| *id.uuid());
+        ids
+    }
 
-    fn execute(&self, index: &IndexSet) -> Self::Output {
-        index
-            .authors
-            .iter()
-            .map(|(id, info)| Author(*id, info.clone()))
-            .collect()
+    fn item(&self, index: &IndexSet, id: &BookId) -> Option<Book> {
+        index.books.get(id).map(|info| Book(*id, info.clone()))
     }
 }
 
-pub struct BooksByAuthorId(pub AuthorId);
+// Books added at or after the one time and before the other, in the order
+// they were added; either end may be left open. Only books added with
+// apply_at have a time to go by.
+pub struct BooksAddedBetween(pub Option<OffsetDateTime>, pub Option<OffsetDateTime>);
 
-impl IndexSetQuery for BooksByAuthorId {
+impl IndexSetQuery for BooksAddedBetween {
     type Output = Vec<Book>;
 
     fn execute(&self, index: &IndexSet) -> Self::Output {
-        let Self(author_id) = self;
-        if let Some(book_ids) = index.books_by_author_id.get(author_id) {
-            book_ids
-                .iter()
-                .filter_map(|id| index.books.get(id).map(|info| Book(*id, info.clone())))
-                .collect::<Vec<_>>()
-        } else {
-            vec![]
-        }
+        self.items(index)
     }
 }
 
-pub struct AllReaders;
+impl PageableQuery for BooksAddedBetween {
+    type Id = BookId;
+    type Item = Book;
 
-impl IndexSetQuery for AllReaders {
-    type Output = Vec<Reader>;
+    fn ids(&self, index: &IndexSet) -> Vec<BookId> {
+        let Self(after, before) = self;
+        index
+            .books_added
+            .iter()
+            .filter(|id| {
+                index.book_times.get(id).is_some_and(|times| {
+                    after.is_none_or(|after| times.created_at >= after)
+                        && before.is_none_or(|before| times.created_at < before)
+                })
+            })
+            .copied()
+            .collect()
+    }
+
+    fn item(&self, index: &IndexSet, id: &BookId) -> Option<Book> {
+        AllBooks.item(index, id)
+    }
+}
+
+// The most recently added books, newest first, at most this many
+pub struct RecentBooks(pub usize);
+
+impl IndexSetQuery for RecentBooks {
+    type Output = Vec<Book>;
 
     fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(limit) = self;
         index
-            .readers
+            .books_added
             .iter()
-            .map(|(id, info)| Reader(*id, info.clone()))
+            .rev()
+            .filter_map(|id| index.books.get(id).map(|info| Book(*id, info.clone())))
+            .take(*limit)
             .collect()
     }
 }
 
-pub struct ReaderById(pub ReaderId);
+pub struct BookById(pub BookId);
 
-impl IndexSetQuery for ReaderById {
-    type Output = Option<Reader>;
+impl IndexSetQuery for BookById {
+    type Output = Option<Book>;
 
     fn execute(&self, index: &IndexSet) -> Self::Output {
         let Self(id) = self;
-        index.readers.get(id).map(|info| Reader(*id, info.clone()))
+        index.books.get(id).map(|info| Book(*id, info.clone()))
     }
 }
 
-pub struct UniqueReaderByMoniker(pub String);
+pub struct BooksByIds(pub Vec<BookId>);
 
-impl IndexSetQuery for UniqueReaderByMoniker {
-    type Output = Option<Reader>;
+#[derive(Debug, Default)]
+pub struct BooksFound {
+    pub found: Vec<Book>,
+    pub missing: Vec<BookId>,
+}
+
+impl IndexSetQuery for BooksByIds {
+    type Output = BooksFound;
 
     fn execute(&self, index: &IndexSet) -> Self::Output {
-        let Self(moniker) = self;
-        index
-            .reader_by_moniker
-            .get(moniker.as_str())
-            .and_then(|reader_id| {
-                index
-                    .readers
-                    .get(reader_id)
-                    .map(|info| Reader(*reader_id, info.clone()))
-            })
+        let Self(ids) = self;
+        ids.iter().fold(BooksFound::default(), |mut books, id| {
+            if let Some(info) = index.books.get(id) {
+                books.found.push(Book(*id, info.clone()))
+            } else {
+                books.missing.push(*id)
+            }
+            books
+        })
     }
 }
 
-pub struct AllKeywords;
+pub struct AuthorById(pub AuthorId);
 
-impl IndexSetQuery for AllKeywords {
-    type Output = Vec<String>;
+impl IndexSetQuery for AuthorById {
+    type Output = Option<Author>;
 
     fn execute(&self, index: &IndexSet) -> Self::Output {
-        index.keywords.all_keywords()
+        let Self(id) = self;
+        index.authors.get(id).map(|info| Author(*id, info.clone()))
     }
 }
 
-pub struct TargetKeywords(pub KeywordTarget);
+// The author along with how many books it has
+pub struct AuthorWithBookCount(pub AuthorId);
 
-impl IndexSetQuery for TargetKeywords {
-    type Output = Vec<String>;
+impl IndexSetQuery for AuthorWithBookCount {
+    type Output = Option<(Author, usize)>;
 
     fn execute(&self, index: &IndexSet) -> Self::Output {
-        let Self(target) = self;
-        index.keywords.get_keywords(target)
+        let Self(id) = self;
+        index
+            .authors
+            .get(id)
+            .map(|info| (Author(*id, info.clone()), index.book_count(id)))
     }
 }
 
-pub struct KeywordTargets(pub Keyword);
+pub struct AuthorByBookId(pub BookId);
 
-impl IndexSetQuery for KeywordTargets {
-    type Output = Vec<KeywordTarget>;
+#[derive(Debug, PartialEq, Eq)]
+pub enum BookAuthorError {
+    NoSuchBook,
+    // The book refers to an author that is not in the read model. This
+    // means that the indices are inconsistent.
+    AuthorMissing(AuthorId),
+}
+
+impl IndexSetQuery for AuthorByBookId {
+    type Output = Result<Author, BookAuthorError>;
 
     fn execute(&self, index: &IndexSet) -> Self::Output {
-        let Self(Keyword(keyword)) = self;
-        index.keywords.get_targets_of_keyword(keyword)
+        let Self(id) = self;
+        let BookInfo { author, .. } = index.books.get(id).ok_or(BookAuthorError::NoSuchBook)?;
+        index
+            .authors
+            .get(author)
+            .map(|info| Author(*author, info.clone())) // this pattern repeats.
+            .ok_or(BookAuthorError::AuthorMissing(*author))
     }
 }
 
-pub mod keywords {
-    use bimap::BiHashMap;
-    use std::collections::{HashMap, HashSet};
+// Diagnostic: references in the read model that point at nothing.
+pub struct DanglingReferences;
 
-    use crate::core::model::KeywordTarget;
+#[derive(Debug, PartialEq, Eq)]
+pub enum DanglingReference {
+    BookAuthor(BookId, AuthorId),
+}
 
-    #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+impl IndexSetQuery for DanglingReferences {
+    type Output = Vec<DanglingReference>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        index
+            .books
+            .iter()
+            .filter(|(_, BookInfo { author, .. })| !index.authors.contains_key(author))
+            .map(|(id, BookInfo { author, .. })| DanglingReference::BookAuthor(*id, *author))
+            .collect()
+    }
+}
+
+pub struct BooksByReader(pub ReaderId);
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReadBook {
+    pub book: Book,
+    pub times_read: usize,
+}
+
+impl IndexSetQuery for BooksByReader {
+    type Output = Vec<ReadBook>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(id) = self;
+
+        index
+            .books_by_reader_id
+            .get(id)
+            .and_then(|read_books| {
+                let mut times_read = HashMap::<BookId, usize>::new();
+                for BookReadInfo { book_id, .. } in read_books {
+                    *times_read.entry(*book_id).or_default() += 1;
+                }
+
+                times_read
+                    .into_iter()
+                    .map(|(book_id, times_read)| {
+                        index.books.get(&book_id).map(|info| ReadBook {
+                            book: Book(book_id, info.clone()),
+                            times_read,
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()
+            })
+            .unwrap_or_default()
+    }
+}
+
+// The books a reader has not read, optionally only those by an author, in
+// the order they were added. None if there is no such reader.
+pub struct UnreadBooks(pub ReaderId, pub Option<AuthorId>);
+
+impl IndexSetQuery for UnreadBooks {
+    type Output = Option<Vec<Book>>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(id, author_id) = self;
+        index.readers.get(id)?;
+
+        let read = index
+            .books_by_reader_id
+            .get(id)
+            .into_iter()
+            .flatten()
+            .map(|BookReadInfo { book_id, .. }| *book_id)
+            .collect::<HashSet<_>>();
+        let candidates = if let Some(author_id) = author_id {
+            index
+                .books_by_author_id
+                .get(author_id)
+                .map_or(&[][..], Vec::as_slice)
+        } else {
+            &index.books_added
+        };
+
+        Some(
+            candidates
+                .iter()
+                .filter(|book_id| !read.contains(book_id))
+                .filter_map(|id| index.books.get(id).map(|info| Book(*id, info.clone())))
+                .collect(),
+        )
+    }
+}
+
+// None if there is no such reader
+pub struct ReaderStats(pub ReaderId);
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReaderStatistics {
+    // Distinct books, however many times each was read
+    pub books_read: usize,
+    pub distinct_authors: usize,
+    // Only reads that say when they happened are considered here
+    pub first_read: Option<OffsetDateTime>,
+    pub last_read: Option<OffsetDateTime>,
+}
+
+impl IndexSetQuery for ReaderStats {
+    type Output = Option<ReaderStatistics>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(id) = self;
+        index.readers.get(id)?;
+
+        let reads = index.books_by_reader_id.get(id);
+        let reads = || reads.into_iter().flatten();
+        let books = reads()
+            .map(|BookReadInfo { book_id, .. }| *book_id)
+            .collect::<HashSet<_>>();
+        let authors = books
+            .iter()
+            .filter_map(|book_id| index.books.get(book_id))
+            .map(|BookInfo { author, .. }| *author)
+            .collect::<HashSet<_>>();
+
+        Some(ReaderStatistics {
+            books_read: books.len(),
+            distinct_authors: authors.len(),
+            first_read: reads().filter_map(|read| read.when).min(),
+            last_read: reads().filter_map(|read| read.when).max(),
+        })
+    }
+}
+
+// Readers by how many distinct books they have read, most first and then by
+// name. Readers who have read nothing are left out.
+pub struct TopReaders(pub usize);
+
+impl IndexSetQuery for TopReaders {
+    type Output = Vec<(Reader, usize)>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(limit) = self;
+        let mut readers = index
+            .books_by_reader_id
+            .iter()
+            .filter_map(|(id, reads)| {
+                let books_read = reads
+                    .iter()
+                    .map(|BookReadInfo { book_id, .. }| book_id)
+                    .collect::<HashSet<_>>()
+                    .len();
+                index
+                    .readers
+                    .get(id)
+                    .filter(|_| books_read > 0)
+                    .map(|info| (Reader(*id, info.clone()), books_read))
+            })
+            .collect::<Vec<_>>();
+        readers.sort_by(|(lhs, lhs_count), (rhs, rhs_count)| {
+            rhs_count
+                .cmp(lhs_count)
+                .then_with(|| lhs.1.name.cmp(&rhs.1.name))
+                .then_with(|| lhs.0 .0.uuid().cmp(rhs.0 .0.uuid()))
+        });
+        readers.truncate(*limit);
+        readers
+    }
+}
+
+// The books a reader has started but not finished, by title. None if there
+// is no such reader.
+pub struct BooksBeingRead(pub ReaderId);
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct BookBeingRead {
+    pub book: Book,
+    pub percent: u8,
+}
+
+impl IndexSetQuery for BooksBeingRead {
+    type Output = Option<Vec<BookBeingRead>>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(id) = self;
+        index.readers.get(id)?;
+
+        let mut books = index
+            .reading_progress
+            .get(id)
+            .into_iter()
+            .flatten()
+            .filter_map(|(book_id, percent)| {
+                index.books.get(book_id).map(|info| BookBeingRead {
+                    book: Book(*book_id, info.clone()),
+                    percent: *percent,
+                })
+            })
+            .collect::<Vec<_>>();
+        books.sort_by(
+            |BookBeingRead { book: lhs, .. }, BookBeingRead { book: rhs, .. }| {
+                lhs.1.title.cmp(&rhs.1.title)
+            },
+        );
+
+        Some(books)
+    }
+}
+
+// By id, like AllBooks
+pub struct AllAuthors;
+
+impl IndexSetQuery for AllAuthors {
+    type Output = Vec<Author>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        self.items(index)
+    }
+}
+
+impl PageableQuery for AllAuthors {
+    type Id = AuthorId;
+    type Item = Author;
+
+    fn ids(&self, index: &IndexSet) -> Vec<AuthorId> {
+        let mut ids = index.authors.keys().copied().collect::<Vec<_>>();
+        ids.sort_by_key(|AuthorId(id)| *id.uuid());
+        ids
+    }
+
+    fn item(&self, index: &IndexSet, id: &AuthorId) -> Option<Author> {
+        index.authors.get(id).map(|info| Author(*id, info.clone()))
+    }
+}
+
+// Authors by this name, whatever its case, by id. Names are not unique, so
+// there may be several.
+pub struct AuthorsByName(pub String);
+
+impl IndexSetQuery for AuthorsByName {
+    type Output = Vec<Author>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(name) = self;
+        let name = name.trim().to_lowercase();
+        let mut authors = index
+            .authors
+            .iter()
+            .filter(|(_, info)| info.name.to_lowercase() == name)
+            .map(|(id, info)| Author(*id, info.clone()))
+            .collect::<Vec<_>>();
+        authors.sort_by_key(|Author(AuthorId(id), _)| *id.uuid());
+        authors
+    }
+}
+
+pub struct AllAuthorsWithBookCounts;
+
+impl IndexSetQuery for AllAuthorsWithBookCounts {
+    type Output = Vec<(Author, usize)>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        self.items(index)
+    }
+}
+
+impl PageableQuery for AllAuthorsWithBookCounts {
+    type Id = AuthorId;
+    type Item = (Author, usize);
+
+    fn ids(&self, index: &IndexSet) -> Vec<AuthorId> {
+        AllAuthors.ids(index)
+    }
+
+    fn item(&self, index: &IndexSet, id: &AuthorId) -> Option<(Author, usize)> {
+        AllAuthors
+            .item(index, id)
+            .map(|author| (author, index.book_count(id)))
+    }
+}
+
+// Authors no book refers to, by name: import leftovers, or candidates
+// for deleting or merging
+pub struct AuthorsWithoutBooks;
+
+impl IndexSetQuery for AuthorsWithoutBooks {
+    type Output = Vec<Author>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let mut authors = index
+            .authors
+            .iter()
+            .filter(|(id, _)| index.book_count(id) == 0)
+            .map(|(id, info)| Author(*id, info.clone()))
+            .collect::<Vec<_>>();
+        authors.sort_by(|Author(lhs_id, lhs), Author(rhs_id, rhs)| {
+            lhs.name
+                .cmp(&rhs.name)
+                .then_with(|| lhs_id.0.uuid().cmp(rhs_id.0.uuid()))
+        });
+        authors
+    }
+}
+
+// The most recently added authors that are still around, newest first
+pub struct RecentAuthors(pub usize);
+
+impl IndexSetQuery for RecentAuthors {
+    type Output = Vec<Author>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(limit) = self;
+        index
+            .authors_added
+            .iter()
+            .rev()
+            .filter_map(|id| index.authors.get(id).map(|info| Author(*id, info.clone())))
+            .take(*limit)
+            .collect()
+    }
+}
+
+pub struct BooksByAuthorId(pub AuthorId);
+
+impl IndexSetQuery for BooksByAuthorId {
+    type Output = Vec<Book>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(author_id) = self;
+        if let Some(book_ids) = index.books_by_author_id.get(author_id) {
+            book_ids
+                .iter()
+                .filter_map(|id| index.books.get(id).map(|info| Book(*id, info.clone())))
+                .collect::<Vec<_>>()
+        } else {
+            vec![]
+        }
+    }
+}
+
+pub struct AllReaders;
+
+impl IndexSetQuery for AllReaders {
+    type Output = Vec<Reader>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        index
+            .readers
+            .iter()
+            .map(|(id, info)| Reader(*id, info.clone()))
+            .collect()
+    }
+}
+
+pub struct ReaderById(pub ReaderId);
+
+impl IndexSetQuery for ReaderById {
+    type Output = Option<Reader>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(id) = self;
+        index.readers.get(id).map(|info| Reader(*id, info.clone()))
+    }
+}
+
+pub struct UniqueReaderByMoniker(pub String);
+
+impl IndexSetQuery for UniqueReaderByMoniker {
+    type Output = Option<Reader>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(moniker) = self;
+        index
+            .reader_by_moniker
+            .get(moniker.as_str())
+            .and_then(|reader_id| {
+                index
+                    .readers
+                    .get(reader_id)
+                    .map(|info| Reader(*reader_id, info.clone()))
+            })
+    }
+}
+
+// Alphabetically, as the index keeps them in no order
+pub struct AllKeywords;
+
+impl IndexSetQuery for AllKeywords {
+    type Output = Vec<String>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let mut keywords = index.keywords.all_keywords();
+        keywords.sort();
+        keywords
+    }
+}
+
+// Keywords with how many targets carry them, most used first
+pub struct KeywordsWithCounts;
+
+impl IndexSetQuery for KeywordsWithCounts {
+    type Output = Vec<(String, usize)>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let mut counts = index.keywords.keyword_counts();
+        counts.sort_by(|(lhs, lhs_count), (rhs, rhs_count)| {
+            rhs_count.cmp(lhs_count).then_with(|| lhs.cmp(rhs))
+        });
+        counts
+    }
+}
+
+// How many of each kind of event the read model has applied, by event name
+pub struct EventCounts;
+
+impl IndexSetQuery for EventCounts {
+    type Output = BTreeMap<String, usize>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        index.event_counts.clone()
+    }
+}
+
+// Alphabetically, like AllKeywords
+pub struct TargetKeywords(pub KeywordTarget);
+
+impl IndexSetQuery for TargetKeywords {
+    type Output = Vec<String>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(target) = self;
+        let mut keywords = index.keywords.get_keywords(target);
+        keywords.sort();
+        keywords
+    }
+}
+
+pub struct KeywordTargets(pub Keyword);
+
+impl IndexSetQuery for KeywordTargets {
+    type Output = Vec<KeywordTarget>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let Self(Keyword(keyword)) = self;
+        index.keywords.get_targets_of_keyword(keyword)
+    }
+}
+
+// Whether a target must carry any or all of the keywords asked for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeywordMatch {
+    #[default]
+    Any,
+    All,
+}
+
+pub struct TargetsByKeywords {
+    pub keywords: Vec<Keyword>,
+    pub mode: KeywordMatch,
+}
+
+impl IndexSetQuery for TargetsByKeywords {
+    type Output = Vec<KeywordTarget>;
+
+    fn execute(&self, index: &IndexSet) -> Self::Output {
+        let keywords = self
+            .keywords
+            .iter()
+            .map(Keyword::as_ref)
+            .collect::<Vec<_>>();
+        let mut targets = index.keywords.get_targets_of_keywords(&keywords, self.mode);
+        targets.sort_by_key(|target| *target.aggregate_id().uuid());
+        targets
+    }
+}
+
+pub mod keywords {
+    use bimap::BiHashMap;
+    #[cfg(any(test, feature = "debug"))]
+    use std::collections::BTreeMap;
+    use std::collections::{HashMap, HashSet};
+
+    use super::KeywordMatch;
+    use crate::core::model::KeywordTarget;
+
+    #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
     struct KeywordId(u16);
 
-    #[derive(Debug, Default)]
-    struct KeywordMap {
-        next_id: u16,
-        inner: BiHashMap<String, KeywordId>,
+    #[derive(Debug, Default)]
+    struct KeywordMap {
+        next_id: u16,
+        inner: BiHashMap<String, KeywordId>,
+    }
+
+    impl KeywordMap {
+        fn get_or_reserve_id(&mut self, keyword: String) -> KeywordId {
+            self.inner
+                .get_by_left(&keyword)
+                .copied()
+                .unwrap_or_else(|| {
+                    let id = KeywordId(self.next_id);
+                    self.inner.insert(keyword, id);
+                    self.next_id += 1;
+                    id
+                })
+        }
+
+        fn keyword_id(&self, keyword: &str) -> Option<&KeywordId> {
+            self.inner.get_by_left(keyword)
+        }
+
+        fn keyword(&self, id: KeywordId) -> Option<&str> {
+            self.inner.get_by_right(&id).map(|s| s.as_str())
+        }
+
+        fn all(&self) -> Vec<String> {
+            self.inner.left_values().cloned().collect()
+        }
+
+        // The id stays the same, only its keyword changes
+        fn rename(&mut self, id: KeywordId, keyword: String) {
+            self.inner.remove_by_right(&id);
+            self.inner.insert(keyword, id);
+        }
+
+        fn remove(&mut self, id: KeywordId) {
+            self.inner.remove_by_right(&id);
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct Index {
+        keyword_map: KeywordMap,
+
+        target_keywords: HashMap<KeywordTarget, HashSet<KeywordId>>,
+        keyword_targets: HashMap<KeywordId, HashSet<KeywordTarget>>,
+    }
+
+    impl Index {
+        pub fn all_keywords(&self) -> Vec<String> {
+            self.keyword_map.all()
+        }
+
+        pub fn keyword_counts(&self) -> Vec<(String, usize)> {
+            self.keyword_map
+                .all()
+                .into_iter()
+                .map(|keyword| {
+                    let count = self.get_targets_of_keyword(&keyword).len();
+                    (keyword, count)
+                })
+                .collect()
+        }
+
+        pub fn add_keyword_to_target(&mut self, keyword: String, target: KeywordTarget) {
+            let id = self.keyword_map.get_or_reserve_id(keyword);
+            self.target_keywords.entry(target).or_default().insert(id);
+            self.keyword_targets.entry(id).or_default().insert(target);
+        }
+
+        // The keyword stays, even if no target has it anymore
+        pub fn remove_keyword_from_target(&mut self, keyword: &str, target: KeywordTarget) {
+            if let Some(id) = self.keyword_map.keyword_id(keyword) {
+                if let Some(targets) = self.keyword_targets.get_mut(id) {
+                    targets.remove(&target);
+                }
+                if let Some(keywords) = self.target_keywords.get_mut(&target) {
+                    keywords.remove(id);
+                }
+            }
+        }
+
+        // The keywords stay, even those that no target has anymore
+        pub fn remove_target(&mut self, target: KeywordTarget) {
+            for id in self.target_keywords.remove(&target).unwrap_or_default() {
+                if let Some(targets) = self.keyword_targets.get_mut(&id) {
+                    targets.remove(&target);
+                }
+            }
+        }
+
+        pub fn retarget(&mut self, from: KeywordTarget, to: KeywordTarget) {
+            for id in self.target_keywords.remove(&from).unwrap_or_default() {
+                if let Some(targets) = self.keyword_targets.get_mut(&id) {
+                    targets.remove(&from);
+                    targets.insert(to);
+                }
+                self.target_keywords.entry(to).or_default().insert(id);
+            }
+        }
+
+        // If to is already in use, the targets of from join its targets
+        pub fn rename(&mut self, from: &str, to: String) {
+            let Some(&from_id) = self.keyword_map.keyword_id(from) else {
+                return;
+            };
+
+            if let Some(&to_id) = self.keyword_map.keyword_id(&to) {
+                for target in self.keyword_targets.remove(&from_id).unwrap_or_default() {
+                    if let Some(keywords) = self.target_keywords.get_mut(&target) {
+                        keywords.remove(&from_id);
+                        keywords.insert(to_id);
+                    }
+                    self.keyword_targets
+                        .entry(to_id)
+                        .or_default()
+                        .insert(target);
+                }
+                self.keyword_map.remove(from_id);
+            } else {
+                self.keyword_map.rename(from_id, to);
+            }
+        }
+
+        #[cfg(any(test, feature = "debug"))]
+        pub fn snapshot(&self) -> BTreeMap<String, Vec<KeywordTarget>> {
+            self.keyword_map
+                .all()
+                .into_iter()
+                .map(|keyword| {
+                    let mut targets = self.get_targets_of_keyword(&keyword);
+                    targets.sort_by_key(|target| *target.aggregate_id().uuid());
+                    (keyword, targets)
+                })
+                .collect()
+        }
+
+        pub fn get_keywords(&self, target: &KeywordTarget) -> Vec<String> {
+            self.target_keywords
+                .get(target)
+                .map(|keywords| {
+                    keywords
+                        .iter()
+                        .map(|id| {
+                            self.keyword_map
+                                .keyword(*id)
+                                .map(|s| s.to_owned())
+                                .expect("consistent maps")
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+
+        // No keywords at all match no targets, in either mode
+        pub fn get_targets_of_keywords(
+            &self,
+            keywords: &[&str],
+            mode: KeywordMatch,
+        ) -> Vec<KeywordTarget> {
+            let mut target_sets = keywords.iter().map(|keyword| {
+                self.keyword_map
+                    .keyword_id(keyword)
+                    .and_then(|id| self.keyword_targets.get(id))
+            });
+            let Some(first) = target_sets.next() else {
+                return vec![];
+            };
+
+            let targets = match mode {
+                KeywordMatch::Any => target_sets
+                    .flatten()
+                    .fold(first.cloned().unwrap_or_default(), |union, targets| {
+                        &union | targets
+                    }),
+                KeywordMatch::All => target_sets.fold(
+                    first.cloned().unwrap_or_default(),
+                    |intersection, targets| {
+                        targets.map_or_else(HashSet::new, |targets| &intersection & targets)
+                    },
+                ),
+            };
+            targets.into_iter().collect()
+        }
+
+        pub fn get_targets_of_keyword(&self, keyword: &str) -> Vec<KeywordTarget> {
+            self.keyword_map
+                .keyword_id(keyword)
+                .and_then(|id| self.keyword_targets.get(id))
+                .map(|xs| xs.iter().cloned().collect::<Vec<_>>())
+                .unwrap_or_default()
+        }
+    }
+}
+
+pub mod text {
+    use icu_normalizer::DecomposingNormalizer;
+    use std::{
+        cmp::{Eq, Ordering},
+        collections::{BinaryHeap, HashMap, HashSet},
+        iter,
+        ops::{BitOr, Range},
+        str::FromStr,
+    };
+    use unicode_segmentation::UnicodeSegmentation;
+
+    use crate::{
+        core::model::{
+            query::{IndexSet, IndexSetQuery},
+            AuthorId, AuthorInfo, BookId, BookInfo, Event, Isbn,
+        },
+        error::{Error, Result},
+    };
+
+    const SEARCH_TERM_LENGTH_THRESHOLD: usize = 1;
+    const MAX_SUGGESTIONS: usize = 5;
+    const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+    // Splits on Unicode word boundaries, so that punctuation of any kind
+    // separates terms and non-Latin scripts are split sensibly.
+    fn tokenize(phrase: &str) -> Vec<&str> {
+        terms_longer_than(phrase, SEARCH_TERM_LENGTH_THRESHOLD)
+    }
+
+    // Lengths are in bytes
+    fn terms_longer_than(phrase: &str, threshold: usize) -> Vec<&str> {
+        phrase
+            .unicode_words()
+            .filter(|term| term.len() > threshold)
+            .collect()
+    }
+
+    // The terms that a title or name is indexed under, and that a search
+    // for it looks up, exactly as they are in an index with the default
+    // term length threshold
+    pub fn tokenize_phrase(phrase: &str) -> Vec<String> {
+        tokenize(phrase).into_iter().map(fold_term).collect()
+    }
+
+    // Terms are indexed and looked up in their folded form: trimmed, lower
+    // case, with diacritics stripped, so that " BRONTE" finds "Brontë".
+    pub fn fold_term(term: &str) -> String {
+        DecomposingNormalizer::new_nfd()
+            .normalize(&term.trim().to_lowercase())
+            .chars()
+            .filter(|c| !is_combining_mark(*c))
+            .collect()
+    }
+
+    fn is_combining_mark(c: char) -> bool {
+        matches!(
+            c,
+            '\u{0300}'..='\u{036F}'
+                | '\u{1AB0}'..='\u{1AFF}'
+                | '\u{1DC0}'..='\u{1DFF}'
+                | '\u{20D0}'..='\u{20FF}'
+                | '\u{FE20}'..='\u{FE2F}'
+        )
+    }
+
+    // Move to super-module - this must not be publically
+    // accessible from the http module
+    #[derive(Debug)]
+    pub struct SearchIndex {
+        term_projections: HashMap<String, HashSet<Projection>>,
+        // The terms that contain each trigram, when infix search is enabled
+        trigram_terms: Option<HashMap<String, HashSet<String>>>,
+        // Terms no longer than this, in bytes, are neither indexed nor
+        // looked up
+        term_length_threshold: usize,
+    }
+
+    impl Default for SearchIndex {
+        fn default() -> Self {
+            Self {
+                term_projections: HashMap::default(),
+                trigram_terms: None,
+                term_length_threshold: SEARCH_TERM_LENGTH_THRESHOLD,
+            }
+        }
+    }
+
+    impl SearchIndex {
+        // Also indexes the trigrams of every term, for InfixQuery. This
+        // holds on to each term once per trigram in it, so several times
+        // what the index holds otherwise.
+        pub fn with_trigrams() -> Self {
+            Self {
+                trigram_terms: Some(HashMap::default()),
+                ..Default::default()
+            }
+        }
+
+        pub fn with_term_length_threshold(self, term_length_threshold: usize) -> Self {
+            Self {
+                term_length_threshold,
+                ..self
+            }
+        }
+
+        pub fn term_length_threshold(&self) -> usize {
+            self.term_length_threshold
+        }
+
+        // Empty, but indexing terms the way this one does
+        pub fn emptied(&self) -> Self {
+            Self {
+                term_projections: HashMap::default(),
+                trigram_terms: self.trigram_terms.as_ref().map(|_| HashMap::default()),
+                term_length_threshold: self.term_length_threshold,
+            }
+        }
+
+        // The folded terms of phrase that this index would hold
+        fn tokenize_phrase(&self, phrase: &str) -> Vec<String> {
+            terms_longer_than(phrase, self.term_length_threshold)
+                .into_iter()
+                .map(fold_term)
+                .collect()
+        }
+
+        pub fn apply(&mut self, event: &Event) {
+            match event {
+                Event::BookAdded(id, BookInfo { isbn, title, .. }) => {
+                    let this_book = Projection::Books(BookField::Isbn(*id));
+                    let Isbn(canonical) = isbn;
+                    self.bind_term(canonical, this_book);
+                    self.bind_term(&isbn.digits(), this_book);
+                    self.index_phrase(title, Projection::Books(BookField::Title(*id)));
+                }
+                Event::AuthorAdded(id, AuthorInfo { name }) => {
+                    self.index_phrase(name, Projection::Authors(AuthorField::Name(*id)));
+                }
+                // Don't index these
+                Event::ReaderAdded(..) => (),
+                Event::BookRead(..) => (),
+                // Think about this.
+                Event::KeywordAdded(..) => (),
+                Event::KeywordRemoved(..) => (),
+                Event::KeywordRenamed(..) => (),
+                Event::ReadingProgressUpdated(..) => (),
+                Event::AuthorMerged(merged, _) => {
+                    self.unbind(Projection::Authors(AuthorField::Name(*merged)))
+                }
+                // The corrected title and ISBN must replace the old terms,
+                // or searches would keep finding the book by what it was.
+                Event::BookInfoCorrected(id, info) => {
+                    self.unbind(Projection::Books(BookField::Isbn(*id)));
+                    self.unbind(Projection::Books(BookField::Title(*id)));
+                    self.apply(&Event::BookAdded(*id, info.clone()));
+                }
+                Event::BookRemoved(id) => {
+                    self.unbind(Projection::Books(BookField::Isbn(*id)));
+                    self.unbind(Projection::Books(BookField::Title(*id)));
+                }
+            }
+        }
+
+        #[cfg(any(test, feature = "debug"))]
+        pub fn term_count(&self) -> usize {
+            self.term_projections.len()
+        }
+
+        fn unbind(&mut self, target: Projection) {
+            let mut unbound = vec![];
+            self.term_projections.retain(|term, targets| {
+                targets.remove(&target);
+                if targets.is_empty() {
+                    unbound.push(term.clone());
+                }
+                !targets.is_empty()
+            });
+
+            if let Some(trigram_terms) = &mut self.trigram_terms {
+                for term in unbound {
+                    for trigram in trigrams(&term) {
+                        if let Some(terms) = trigram_terms.get_mut(&trigram) {
+                            terms.remove(&term);
+                        }
+                    }
+                }
+                trigram_terms.retain(|_, terms| !terms.is_empty());
+            }
+        }
+
+        fn index_phrase(&mut self, phrase: &str, target: Projection) {
+            for term in self.tokenize_phrase(phrase) {
+                self.bind_folded_term(term, target)
+            }
+        }
+
+        fn bind_term(&mut self, term: &str, target: Projection) {
+            self.bind_folded_term(fold_term(term), target)
+        }
+
+        fn bind_folded_term(&mut self, term: String, target: Projection) {
+            if let Some(trigram_terms) = &mut self.trigram_terms {
+                for trigram in trigrams(&term) {
+                    trigram_terms
+                        .entry(trigram)
+                        .or_default()
+                        .insert(term.clone());
+                }
+            }
+            self.term_projections
+                .entry(term)
+                .or_default()
+                .insert(target);
+        }
+
+        // Terms that have every trigram of the folded fragment. That they
+        // also contain the fragment is likely, but not certain. Fragments
+        // shorter than a trigram, and indices without trigrams, match
+        // nothing.
+        fn infix(&self, fragment: &str) -> HashSet<Projection> {
+            self.infix_terms(fragment)
+                .iter()
+                .filter_map(|term| self.term_projections.get(term))
+                .flatten()
+                .copied()
+                .collect()
+        }
+
+        fn infix_terms(&self, fragment: &str) -> HashSet<String> {
+            let Some(trigram_terms) = &self.trigram_terms else {
+                return HashSet::default();
+            };
+            let mut term_sets = trigrams(&fold_term(fragment))
+                .into_iter()
+                .map(|trigram| trigram_terms.get(&trigram));
+            let Some(Some(first)) = term_sets.next() else {
+                return HashSet::default();
+            };
+            term_sets.fold(first.clone(), |terms, next| {
+                next.map_or_else(HashSet::default, |next| &terms & next)
+            })
+        }
+
+        pub fn lookup(&self, term: &str) -> Vec<Projection> {
+            if let Some(xs) = self.term_projections.get(&fold_term(term)) {
+                xs.iter().copied().collect()
+            } else {
+                vec![]
+            }
+        }
+
+        // Indexed terms closest to term by edit distance, nearest first.
+        // This walks the whole vocabulary.
+        pub fn suggest(&self, term: &str) -> Vec<String> {
+            let term = fold_term(term);
+            let mut candidates = self
+                .near(&term)
+                .map(|(distance, candidate, _)| (distance, candidate))
+                .collect::<Vec<_>>();
+            candidates.sort();
+
+            candidates
+                .into_iter()
+                .take(MAX_SUGGESTIONS)
+                .map(|(_, candidate)| candidate.to_owned())
+                .collect()
+        }
+
+        // Indexed terms other than the folded term, but within edit distance
+        // of it. This walks the whole vocabulary.
+        fn near<'a>(
+            &'a self,
+            term: &'a str,
+        ) -> impl Iterator<Item = (usize, &'a String, &'a HashSet<Projection>)> + 'a {
+            let length = term.chars().count();
+
+            self.term_projections
+                .iter()
+                .filter(move |(candidate, _)| {
+                    candidate.chars().count().abs_diff(length) <= MAX_SUGGESTION_DISTANCE
+                })
+                .map(|(candidate, targets)| (edit_distance(term, candidate), candidate, targets))
+                .filter(|(distance, ..)| (1..=MAX_SUGGESTION_DISTANCE).contains(distance))
+        }
+    }
+
+    // Overlapping runs of three characters
+    fn trigrams(term: &str) -> Vec<String> {
+        let chars = term.chars().collect::<Vec<_>>();
+        chars
+            .windows(3)
+            .map(|trigram| trigram.iter().collect())
+            .collect()
+    }
+
+    // Levenshtein distance, in characters
+    fn edit_distance(lhs: &str, rhs: &str) -> usize {
+        let rhs = rhs.chars().collect::<Vec<_>>();
+        let mut previous = (0..=rhs.len()).collect::<Vec<_>>();
+
+        for (i, l) in lhs.chars().enumerate() {
+            let mut current = vec![i + 1; rhs.len() + 1];
+            for (j, r) in rhs.iter().enumerate() {
+                let substitution = previous[j] + usize::from(l != *r);
+                current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            }
+            previous = current;
+        }
+
+        previous[rhs.len()]
+    }
+
+    // Titles, ISBNs and names with a term that has the fragment somewhere
+    // in it, such as "rink" in "Drinking". Needs an IndexSet made with
+    // IndexSet::with_infix_search.
+    pub struct InfixQuery(pub String);
+
+    impl IndexSetQuery for InfixQuery {
+        type Output = Vec<SearchHit>;
+
+        fn execute(&self, index: &IndexSet) -> Self::Output {
+            let Self(fragment) = self;
+            let mut hits = index
+                .texts
+                .infix(fragment)
+                .into_iter()
+                .filter_map(|projection| resolve_projection(projection, index))
+                .collect::<Vec<_>>();
+            hits.sort_by(|lhs, rhs| lhs.source.cmp(&rhs.source));
+            hits
+        }
+    }
+
+    // Close terms to offer when a SearchQuery comes up empty
+    pub struct Suggestions(pub String);
+
+    impl IndexSetQuery for Suggestions {
+        type Output = Vec<String>;
+
+        fn execute(&self, index: &IndexSet) -> Self::Output {
+            let Self(term) = self;
+            index.texts.suggest(term)
+        }
+    }
+
+    // The fields that a SearchQuery looks in
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct FieldMask(u8);
+
+    impl FieldMask {
+        pub const TITLE: Self = Self(0b001);
+        pub const ISBN: Self = Self(0b010);
+        pub const AUTHOR_NAME: Self = Self(0b100);
+        pub const ALL: Self = Self(0b111);
+
+        pub fn contains(self, Self(other): Self) -> bool {
+            let Self(this) = self;
+            this & other == other
+        }
+
+        fn of(target: &Projection) -> Self {
+            match target {
+                Projection::Books(BookField::Title(..)) => Self::TITLE,
+                Projection::Books(BookField::Isbn(..)) => Self::ISBN,
+                Projection::Authors(AuthorField::Name(..)) => Self::AUTHOR_NAME,
+            }
+        }
+    }
+
+    impl Default for FieldMask {
+        fn default() -> Self {
+            Self::ALL
+        }
+    }
+
+    impl BitOr for FieldMask {
+        type Output = Self;
+
+        fn bitor(self, Self(rhs): Self) -> Self {
+            let Self(lhs) = self;
+            Self(lhs | rhs)
+        }
+    }
+
+    // A comma separated list of title, isbn and author
+    impl FromStr for FieldMask {
+        type Err = Error;
+
+        fn from_str(fields: &str) -> Result<Self> {
+            fields
+                .split(',')
+                .map(|field| match field.trim() {
+                    "title" => Ok(Self::TITLE),
+                    "isbn" => Ok(Self::ISBN),
+                    "author" => Ok(Self::AUTHOR_NAME),
+                    otherwise => Err(Error::Generic(format!(
+                        "{otherwise} is not a searchable field"
+                    ))),
+                })
+                .try_fold(Self(0), |mask, field| Ok(mask | field?))
+        }
+    }
+
+    // ISBNs only ever match whole, so such a hit is all but certain. A title
+    // says more about a book than a name does about an author.
+    fn field_weight(target: &Projection) -> usize {
+        match target {
+            Projection::Books(BookField::Isbn(..)) => 4,
+            Projection::Books(BookField::Title(..)) => 3,
+            Projection::Authors(AuthorField::Name(..)) => 2,
+        }
+    }
+
+    const EXACT_MATCH_SCORE: usize = 2;
+    const FUZZY_MATCH_SCORE: usize = 1;
+    const INFIX_MATCH_SCORE: usize = 1;
+
+    // Looks up the text as a whole, which is how ISBNs match, and each of
+    // its terms. A hit has to match the text as a whole or every one of its
+    // terms. Hits are ranked by how well they matched, weighted by the field
+    // they matched in. A hit that matched in several ways is still returned
+    // once.
+    pub struct SearchQuery {
+        pub text: String,
+        pub fields: FieldMask,
+        // Also match indexed terms within a small edit distance
+        pub fuzzy: bool,
+        // Also match indexed terms that have a term of the text in them,
+        // such as "drinking" for "rink". Matches nothing more unless the
+        // IndexSet was made with IndexSet::with_infix_search.
+        pub infix: bool,
+        pub limit: Option<usize>,
+        pub offset: usize,
+        // Compute highlights, which costs a second pass over each hit
+        pub highlight: bool,
+        // Hits ranked past this many are dropped, whatever the limit and
+        // offset, so that a very common term cannot make a huge response
+        pub max_hits: usize,
+    }
+
+    pub const DEFAULT_MAX_SEARCH_HITS: usize = 1000;
+
+    impl SearchQuery {
+        pub fn new(text: String) -> Self {
+            Self {
+                text,
+                fields: FieldMask::ALL,
+                fuzzy: false,
+                infix: false,
+                limit: None,
+                offset: 0,
+                highlight: false,
+                max_hits: DEFAULT_MAX_SEARCH_HITS,
+            }
+        }
+
+        // Scores and the indexed terms that each target matched on, of the
+        // targets that matched the whole text or every one of its terms
+        fn score(&self, index: &SearchIndex) -> HashMap<Projection, (usize, HashSet<String>)> {
+            let whole = fold_term(&self.text);
+            let terms = index
+                .tokenize_phrase(&self.text)
+                .into_iter()
+                .collect::<HashSet<_>>();
+
+            let mut scores = HashMap::<Projection, (usize, HashSet<String>)>::new();
+            // The terms of the text, rather than those in the index, that
+            // each target matched; None is the text as a whole
+            let mut matched = HashMap::<Projection, HashSet<Option<&String>>>::new();
+            let mut record = |of_text, term: &str, targets: &HashSet<Projection>, score: usize| {
+                for target in targets {
+                    if self.fields.contains(FieldMask::of(target)) {
+                        let (total, terms) = scores.entry(*target).or_default();
+                        if terms.insert(term.to_owned()) {
+                            *total += score * field_weight(target);
+                        }
+                        matched.entry(*target).or_default().insert(of_text);
+                    }
+                }
+            };
+
+            if !terms.contains(&whole) {
+                if let Some(targets) = index.term_projections.get(&whole) {
+                    record(None, &whole, targets, EXACT_MATCH_SCORE);
+                }
+            }
+
+            for term in &terms {
+                if let Some(targets) = index.term_projections.get(term) {
+                    record(Some(term), term, targets, EXACT_MATCH_SCORE);
+                }
+                if self.fuzzy {
+                    for (_, near, targets) in index.near(term) {
+                        record(Some(term), near, targets, FUZZY_MATCH_SCORE);
+                    }
+                }
+                if self.infix {
+                    for containing in index.infix_terms(term) {
+                        if containing != *term && containing.contains(term.as_str()) {
+                            if let Some(targets) = index.term_projections.get(&containing) {
+                                record(Some(term), &containing, targets, INFIX_MATCH_SCORE);
+                            }
+                        }
+                    }
+                }
+            }
+
+            scores.retain(|target, _| {
+                matched
+                    .get(target)
+                    .is_some_and(|of_text| of_text.contains(&None) || of_text.len() == terms.len())
+            });
+            scores
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct SearchHit {
+        pub target: Projection,
+        pub source: String,
+        // Character (not byte) ranges in source that matched a query term
+        pub highlights: Option<Vec<Range<usize>>>,
+    }
+
+    #[derive(Debug, Default)]
+    pub struct SearchHits {
+        pub hits: Vec<SearchHit>,
+        // There were more than max_hits matches
+        pub truncated: bool,
+    }
+
+    impl IndexSetQuery for SearchQuery {
+        type Output = SearchHits;
+
+        // Matches are ranked by what the index already holds, so that only
+        // the hits that are returned are made into SearchHits. No more than
+        // max_hits of them are kept while ranking, the worst of those on top
+        // of the heap to be dropped for a better one.
+        fn execute(&self, index: &IndexSet) -> Self::Output {
+            let mut ranked = BinaryHeap::new();
+            let mut matches = 0;
+            for (projection, (score, terms)) in self.score(&index.texts) {
+                let Some(source) = indexed_source(&projection, index) else {
+                    panic!("Text index has data that is not reflected in the field indices.")
+                };
+                matches += 1;
+                ranked.push(Ranked {
+                    score,
+                    source,
+                    projection,
+                    terms,
+                });
+                if ranked.len() > self.max_hits {
+                    ranked.pop();
+                }
+            }
+            let truncated = matches > self.max_hits;
+
+            let hits = ranked
+                .into_sorted_vec()
+                .into_iter()
+                .skip(self.offset)
+                .take(self.limit.unwrap_or(usize::MAX))
+                .filter_map(
+                    |Ranked {
+                         projection, terms, ..
+                     }| {
+                        let mut hit = resolve_projection(projection, index)?;
+                        if self.highlight {
+                            hit.highlights = Some(highlight_terms(&hit, &terms));
+                        }
+                        Some(hit)
+                    },
+                )
+                .collect();
+
+            SearchHits { hits, truncated }
+        }
+    }
+
+    // A match that is ordered by rank, so that better matches are less: by
+    // higher score, then by source
+    struct Ranked<'a> {
+        score: usize,
+        source: &'a str,
+        projection: Projection,
+        terms: HashSet<String>,
+    }
+
+    impl Ord for Ranked<'_> {
+        fn cmp(&self, other: &Self) -> Ordering {
+            other
+                .score
+                .cmp(&self.score)
+                .then_with(|| self.source.cmp(other.source))
+        }
+    }
+
+    impl PartialOrd for Ranked<'_> {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl PartialEq for Ranked<'_> {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+
+    impl Eq for Ranked<'_> {}
+
+    fn indexed_source<'a>(target: &Projection, index: &'a IndexSet) -> Option<&'a str> {
+        match target {
+            Projection::Books(BookField::Isbn(id)) => index.books.get(id).map(
+                |BookInfo {
+                     isbn: Isbn(isbn), ..
+                 }| isbn.as_str(),
+            ),
+            Projection::Books(BookField::Title(id)) => {
+                index.books.get(id).map(|x| x.title.as_str())
+            }
+            Projection::Authors(AuthorField::Name(id)) => {
+                index.authors.get(id).map(|x| x.name.as_str())
+            }
+        }
+    }
+
+    // It would look good to have this on IndexSet, but ... what?
+    fn resolve_projection(target: Projection, index: &IndexSet) -> Option<SearchHit> {
+        let source = indexed_source(&target, index)?;
+        let source = if let Projection::Books(BookField::Isbn(..)) = target {
+            // Hyphenated, whichever form the search matched
+            Isbn::canonical(source)
+        } else {
+            source.to_owned()
+        };
+
+        Some(SearchHit {
+            target,
+            source,
+            highlights: None,
+        })
+    }
+
+    #[cfg(test)]
+    fn highlight_ranges(hit: &SearchHit, terms: &str) -> Vec<Range<usize>> {
+        let terms = tokenize_phrase(terms).into_iter().collect::<HashSet<_>>();
+        highlight_terms(hit, &terms)
+    }
+
+    // Terms are folded, as they are in the index
+    fn highlight_terms(hit: &SearchHit, terms: &HashSet<String>) -> Vec<Range<usize>> {
+        let SearchHit { target, source, .. } = hit;
+        let char_offset = |byte_offset: usize| source[..byte_offset].chars().count();
+
+        if let Projection::Books(BookField::Isbn(..)) = target {
+            // ISBNs match as a whole, hyphens and all
+            iter::once(0..char_offset(source.len())).collect()
+        } else {
+            source
+                .unicode_word_indices()
+                .filter(|(_, word)| terms.contains(&fold_term(word)))
+                .map(|(start, word)| char_offset(start)..char_offset(start + word.len()))
+                .collect()
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+    pub enum Projection {
+        Books(BookField),
+        Authors(AuthorField),
+    }
+
+    #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+    pub enum BookField {
+        Title(BookId),
+        Isbn(BookId),
     }
 
-    impl KeywordMap {
-        fn get_or_reserve_id(&mut self, keyword: String) -> KeywordId {
-            self.inner
-                .get_by_left(&keyword)
-                .copied()
-                .unwrap_or_else(|| {
-                    let id = KeywordId(self.next_id);
-                    self.inner.insert(keyword, id);
-                    self.next_id += 1;
-                    id
-                })
+    #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
+    pub enum AuthorField {
+        Name(AuthorId),
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::infrastructure::UniqueId;
+
+        #[test]
+        fn tokenize_splits_on_colons_and_ampersands() {
+            assert_eq!(
+                tokenize("Crime & Punishment: A Novel"),
+                vec!["Crime", "Punishment", "Novel"]
+            );
         }
 
-        fn keyword_id(&self, keyword: &str) -> Option<&KeywordId> {
-            self.inner.get_by_left(keyword)
+        #[test]
+        fn tokenize_keeps_accented_terms_whole() {
+            assert_eq!(
+                tokenize("Émile Zola — Thérèse Raquin"),
+                vec!["Émile", "Zola", "Thérèse", "Raquin"]
+            );
         }
 
-        fn keyword(&self, id: KeywordId) -> Option<&str> {
-            self.inner.get_by_right(&id).map(|s| s.as_str())
+        #[test]
+        fn tokenize_phrase_is_what_the_index_stores() {
+            for name in [
+                "Crime & Punishment: A Novel",
+                "Émile Zola — Thérèse Raquin",
+                "ÅNGSTRÖM's  Résumé",
+                "C++ & C#, 2nd ed.",
+                "Ngũgĩ wa Thiong'o",
+                "東京物語",
+                "a",
+            ] {
+                let mut index = SearchIndex::default();
+                let author_id = AuthorId(UniqueId::fresh());
+                index.apply(&Event::AuthorAdded(
+                    author_id,
+                    AuthorInfo {
+                        name: name.to_owned(),
+                    },
+                ));
+
+                let stored = index.term_projections.into_keys().collect::<HashSet<_>>();
+                let tokenized = tokenize_phrase(name).into_iter().collect::<HashSet<_>>();
+                assert_eq!(stored, tokenized, "{name}");
+            }
+
+            assert_eq!(
+                tokenize_phrase("ÅNGSTRÖM's  Résumé"),
+                vec!["angstrom's", "resume"]
+            );
         }
 
-        fn all(&self) -> Vec<String> {
-            self.inner.left_values().cloned().collect()
+        #[test]
+        fn infix_search_needs_trigrams() {
+            let title = "Drinking with Strangers";
+            let add_book = |index: &mut IndexSet| {
+                let author_id = AuthorId(UniqueId::fresh());
+                index.apply(Event::AuthorAdded(
+                    author_id,
+                    AuthorInfo {
+                        name: "Butch Walker".to_owned(),
+                    },
+                ));
+                index.apply(Event::BookAdded(
+                    BookId(UniqueId::fresh()),
+                    BookInfo {
+                        isbn: Isbn("978-1-61180-697-7".to_owned()),
+                        title: title.to_owned(),
+                        author: author_id,
+                    },
+                ));
+            };
+            let sources = |index: &IndexSet, fragment: &str| {
+                InfixQuery(fragment.to_owned())
+                    .execute(index)
+                    .into_iter()
+                    .map(|hit| hit.source)
+                    .collect::<Vec<_>>()
+            };
+
+            let mut with_trigrams = IndexSet::with_infix_search();
+            add_book(&mut with_trigrams);
+            assert_eq!(sources(&with_trigrams, "rink"), vec![title]);
+            assert_eq!(sources(&with_trigrams, "RANGE"), vec![title]);
+            assert_eq!(sources(&with_trigrams, "alk"), vec!["Butch Walker"]);
+            assert!(sources(&with_trigrams, "rinks").is_empty());
+            assert!(sources(&with_trigrams, "ri").is_empty());
+
+            let mut without_trigrams = IndexSet::default();
+            add_book(&mut without_trigrams);
+            assert!(sources(&without_trigrams, "rink").is_empty());
+
+            // Searches match within words only when asked to
+            let search = |index: &IndexSet, text: &str, infix| {
+                SearchQuery {
+                    infix,
+                    ..SearchQuery::new(text.to_owned())
+                }
+                .execute(index)
+                .hits
+                .into_iter()
+                .map(|hit| hit.source)
+                .collect::<Vec<_>>()
+            };
+            assert_eq!(search(&with_trigrams, "rink strange", true), vec![title]);
+            assert_eq!(search(&with_trigrams, "drinking", true), vec![title]);
+            assert!(search(&with_trigrams, "rink strange", false).is_empty());
+            assert!(search(&with_trigrams, "rink bogus", true).is_empty());
+            assert!(search(&without_trigrams, "rink", true).is_empty());
         }
-    }
 
-    #[derive(Debug, Default)]
-    pub struct Index {
-        keyword_map: KeywordMap,
+        #[test]
+        fn search_ignores_accents_but_shows_the_original() {
+            let mut index = IndexSet::default();
+            let author_id = AuthorId(UniqueId::fresh());
+            index.apply(Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: "Emily Brontë".to_owned(),
+                },
+            ));
+
+            let hits = SearchQuery::new("bronte".to_owned()).execute(&index).hits;
+            assert_eq!(hits.len(), 1);
+            assert_eq!(
+                hits[0].target,
+                Projection::Authors(AuthorField::Name(author_id))
+            );
+            assert_eq!(hits[0].source, "Emily Brontë");
+            assert_eq!(hits[0].highlights, None);
+        }
 
-        target_keywords: HashMap<KeywordTarget, HashSet<KeywordId>>,
-        keyword_targets: HashMap<KeywordId, HashSet<KeywordTarget>>,
-    }
+        #[test]
+        fn search_ignores_case_but_shows_the_original() {
+            let mut index = IndexSet::default();
+            let book_id = BookId(UniqueId::fresh());
+            index.apply(Event::BookAdded(
+                book_id,
+                BookInfo {
+                    isbn: Isbn("978-1-61180-697-7".to_owned()),
+                    title: "The Art of War".to_owned(),
+                    author: AuthorId(UniqueId::fresh()),
+                },
+            ));
+
+            for text in ["ART", "art", " Art "] {
+                let hits = SearchQuery::new(text.to_owned()).execute(&index).hits;
+                assert_eq!(hits.len(), 1, "{text}");
+                assert_eq!(hits[0].target, Projection::Books(BookField::Title(book_id)));
+                assert_eq!(hits[0].source, "The Art of War");
+                assert_eq!(index.texts.lookup(text), vec![hits[0].target], "{text}");
+            }
+        }
 
-    impl Index {
-        pub fn all_keywords(&self) -> Vec<String> {
-            self.keyword_map.all()
+        #[test]
+        fn edit_distance_counts_characters() {
+            assert_eq!(edit_distance("bronte", "bronte"), 0);
+            assert_eq!(edit_distance("bronte", "bronze"), 1);
+            assert_eq!(edit_distance("brnote", "bronte"), 2);
+            assert_eq!(edit_distance("", "zola"), 4);
         }
 
-        pub fn add_keyword_to_target(&mut self, keyword: String, target: KeywordTarget) {
-            let id = self.keyword_map.get_or_reserve_id(keyword);
-            self.target_keywords.entry(target).or_default().insert(id);
-            self.keyword_targets.entry(id).or_default().insert(target);
+        #[test]
+        fn suggestions_are_nearby_indexed_terms() {
+            let mut index = IndexSet::default();
+            index.apply(Event::AuthorAdded(
+                AuthorId(UniqueId::fresh()),
+                AuthorInfo {
+                    name: "Emily Brontë".to_owned(),
+                },
+            ));
+
+            assert!(SearchQuery::new("brotne".to_owned())
+                .execute(&index)
+                .hits
+                .is_empty());
+            assert_eq!(
+                Suggestions("brotne".to_owned()).execute(&index),
+                vec!["bronte"]
+            );
+            assert!(Suggestions("dickens".to_owned()).execute(&index).is_empty());
         }
 
-        pub fn remove_keyword_from_target(&mut self, keyword: &str, target: KeywordTarget) {
-            if let Some(id) = self.keyword_map.keyword_id(keyword) {
-                if let Some(targets) = self.keyword_targets.get_mut(id) {
-                    targets.remove(&target);
-                    self.target_keywords.remove(&target);
+        fn search_fixture() -> IndexSet {
+            let mut index = IndexSet::default();
+            let author = AuthorId(UniqueId::fresh());
+            index.apply(Event::AuthorAdded(
+                author,
+                AuthorInfo {
+                    name: "Emily Brontë".to_owned(),
+                },
+            ));
+            for (isbn, title) in [
+                ("978-1-61180-697-7", "Wuthering Heights"),
+                ("978-0-7710-6143-1", "Emily of New Moon"),
+            ] {
+                index.apply(Event::BookAdded(
+                    BookId(UniqueId::fresh()),
+                    BookInfo {
+                        isbn: Isbn(isbn.to_owned()),
+                        title: title.to_owned(),
+                        author,
+                    },
+                ));
+            }
+            index
+        }
+
+        #[test]
+        fn isbn_hits_show_the_canonical_isbn() {
+            for indexed in ["978-1-61180-697-7", "9781611806977"] {
+                let mut index = IndexSet::default();
+                let book_id = BookId(UniqueId::fresh());
+                index.apply(Event::BookAdded(
+                    book_id,
+                    BookInfo {
+                        isbn: Isbn(indexed.to_owned()),
+                        title: "The Art of War".to_owned(),
+                        author: AuthorId(UniqueId::fresh()),
+                    },
+                ));
+
+                let hits = SearchQuery::new("9781611806977".to_owned())
+                    .execute(&index)
+                    .hits;
+                assert_eq!(hits.len(), 1, "{indexed}");
+                assert_eq!(hits[0].target, Projection::Books(BookField::Isbn(book_id)));
+                assert_eq!(hits[0].source, "978-1-61180-697-7", "{indexed}");
+            }
+        }
+
+        #[test]
+        fn search_parameters_select_rank_and_page_hits() {
+            let index = search_fixture();
+            let search = |text: &str, fields, fuzzy, limit, offset| {
+                SearchQuery {
+                    text: text.to_owned(),
+                    fields,
+                    fuzzy,
+                    infix: false,
+                    limit,
+                    offset,
+                    highlight: false,
+                    max_hits: DEFAULT_MAX_SEARCH_HITS,
                 }
+                .execute(&index)
+                .hits
+                .into_iter()
+                .map(|hit| hit.source)
+                .collect::<Vec<_>>()
+            };
+            let all = FieldMask::ALL;
+            let emilys = vec!["Emily of New Moon", "Emily Brontë"];
+
+            // Titles outrank names
+            assert_eq!(search("emily", all, false, None, 0), emilys);
+            assert_eq!(
+                search("emily", FieldMask::TITLE, false, None, 0),
+                vec!["Emily of New Moon"]
+            );
+            assert_eq!(
+                search("emily", FieldMask::AUTHOR_NAME, false, None, 0),
+                vec!["Emily Brontë"]
+            );
+            assert!(search("emily", FieldMask::ISBN, false, None, 0).is_empty());
+
+            // Only fuzzy searches forgive typos
+            assert!(search("emilt", all, false, None, 0).is_empty());
+            assert_eq!(search("emilt", all, true, None, 0), emilys);
+            assert!(search("wutherign", all, false, None, 0).is_empty());
+            assert_eq!(
+                search("wutherign", all, true, None, 0),
+                vec!["Wuthering Heights"]
+            );
+
+            // Hits match every term, and are returned once however many
+            // they matched
+            assert_eq!(
+                search("emily bronte", all, false, None, 0),
+                vec!["Emily Brontë"]
+            );
+            assert_eq!(search("emily emily", all, false, None, 0), emilys);
+            assert!(search("emily dickens", all, false, None, 0).is_empty());
+            // The terms have to be in the same field
+            assert!(search("bronte heights", all, false, None, 0).is_empty());
+            assert_eq!(
+                search("emilt bronte", all, true, None, 0),
+                vec!["Emily Brontë"]
+            );
+
+            // ISBNs match whole, and only in the ISBN field
+            assert_eq!(
+                search("978-1-61180-697-7", all, false, None, 0),
+                vec!["978-1-61180-697-7"]
+            );
+            assert_eq!(
+                search("9781611806977", FieldMask::ISBN, true, None, 0),
+                vec!["978-1-61180-697-7"]
+            );
+            assert!(search("978-1-61180-697-7", FieldMask::TITLE, false, None, 0).is_empty());
+
+            // Paging applies to the ranked hits
+            assert_eq!(
+                search("emily", all, false, Some(1), 0),
+                vec!["Emily of New Moon"]
+            );
+            assert_eq!(
+                search("emily", all, false, Some(1), 1),
+                vec!["Emily Brontë"]
+            );
+            assert_eq!(search("emily", all, false, None, 1), vec!["Emily Brontë"]);
+            assert!(search("emily", all, false, Some(0), 0).is_empty());
+            assert!(search("emily", all, false, None, 2).is_empty());
+        }
+
+        #[test]
+        fn common_terms_are_capped_and_flagged_as_truncated() {
+            let mut index = IndexSet::default();
+            let author = AuthorId(UniqueId::fresh());
+            for volume in 0..DEFAULT_MAX_SEARCH_HITS + 5 {
+                index.apply(Event::BookAdded(
+                    BookId(UniqueId::fresh()),
+                    BookInfo {
+                        isbn: Isbn(format!("isbn-{volume}")),
+                        title: format!("War, volume {volume}"),
+                        author,
+                    },
+                ));
             }
+
+            let search = SearchQuery::new("war".to_owned()).execute(&index);
+            assert_eq!(search.hits.len(), DEFAULT_MAX_SEARCH_HITS);
+            assert!(search.truncated);
+
+            let capped = |max_hits, offset| SearchQuery {
+                max_hits,
+                offset,
+                ..SearchQuery::new("war".to_owned())
+            };
+            let search = capped(10, 0).execute(&index);
+            assert_eq!(search.hits.len(), 10);
+            assert!(search.truncated);
+            assert!(capped(10, 10).execute(&index).hits.is_empty());
+
+            let search = capped(DEFAULT_MAX_SEARCH_HITS + 5, 0).execute(&index);
+            assert_eq!(search.hits.len(), DEFAULT_MAX_SEARCH_HITS + 5);
+            assert!(!search.truncated);
         }
 
-        pub fn get_keywords(&self, target: &KeywordTarget) -> Vec<String> {
-            self.target_keywords
-                .get(target)
-                .map(|keywords| {
-                    keywords
-                        .iter()
-                        .map(|id| {
-                            self.keyword_map
-                                .keyword(*id)
-                                .map(|s| s.to_owned())
-                                .expect("consistent maps")
-                        })
-                        .collect()
-                })
-                .unwrap_or_default()
+        #[test]
+        fn fuzzy_hits_highlight_the_terms_they_matched() {
+            let index = search_fixture();
+            let hits = SearchQuery {
+                fuzzy: true,
+                highlight: true,
+                ..SearchQuery::new("wutherign heights".to_owned())
+            }
+            .execute(&index)
+            .hits;
+
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].highlights, Some(vec![0..9, 10..17]));
         }
 
-        pub fn get_targets_of_keyword(&self, keyword: &str) -> Vec<KeywordTarget> {
-            self.keyword_map
-                .keyword_id(keyword)
-                .and_then(|id| self.keyword_targets.get(id))
-                .map(|xs| xs.iter().cloned().collect::<Vec<_>>())
-                .unwrap_or_default()
+        #[test]
+        fn field_masks_parse_from_field_names() {
+            assert_eq!(
+                "title, author".parse::<FieldMask>().expect("a field mask"),
+                FieldMask::TITLE | FieldMask::AUTHOR_NAME
+            );
+            assert_eq!(
+                "isbn".parse::<FieldMask>().expect("a field mask"),
+                FieldMask::ISBN
+            );
+            assert!("genre".parse::<FieldMask>().is_err());
+        }
+
+        #[test]
+        fn highlights_are_character_ranges_of_each_matched_term() {
+            let hit = SearchHit {
+                target: Projection::Authors(AuthorField::Name(AuthorId(UniqueId::fresh()))),
+                source: "Émile Zola — Thérèse Raquin".to_owned(),
+                highlights: None,
+            };
+
+            assert_eq!(highlight_ranges(&hit, "therese emile"), vec![0..5, 13..20]);
         }
     }
 }
 
-pub mod text {
-    use std::{
-        cmp::Eq,
-        collections::{HashMap, HashSet},
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::model::{AuthorInfo, Isbn},
+        infrastructure::UniqueId,
     };
 
-    use crate::core::model::{
-        query::{IndexSet, IndexSetQuery},
-        AuthorId, AuthorInfo, BookId, BookInfo, Event, Isbn,
-    };
+    // Set UPDATE_GOLDEN_FILES to write the snapshot instead of comparing to it
+    #[test]
+    fn snapshot_matches_golden_file() {
+        const GOLDEN_FILE: &str = "src/core/model/snapshots/index_set.json";
+        let id = |n| UniqueId(uuid::Uuid::from_u128(n));
+        let (sun_tzu, laozi) = (AuthorId(id(1)), AuthorId(id(2)));
+        let (art_of_war, tao_te_ching) = (BookId(id(10)), BookId(id(11)));
+        let reader = ReaderId(id(20));
+
+        let mut index = IndexSet::default();
+        for event in [
+            Event::AuthorAdded(
+                sun_tzu,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ),
+            Event::AuthorAdded(
+                laozi,
+                AuthorInfo {
+                    name: "Laozi".to_owned(),
+                },
+            ),
+            Event::BookAdded(
+                art_of_war,
+                BookInfo {
+                    isbn: Isbn("978-1-61180-697-7".to_owned()),
+                    title: "The Art of War".to_owned(),
+                    author: sun_tzu,
+                },
+            ),
+            Event::BookAdded(
+                tao_te_ching,
+                BookInfo {
+                    isbn: Isbn("978-0-06-081245-1".to_owned()),
+                    title: "Tao Te Ching".to_owned(),
+                    author: laozi,
+                },
+            ),
+            Event::ReaderAdded(
+                reader,
+                ReaderInfo {
+                    name: "Reader".to_owned(),
+                    unique_moniker: "reader".to_owned(),
+                },
+            ),
+            Event::BookRead(
+                reader,
+                BookReadInfo {
+                    reader_id: reader,
+                    book_id: art_of_war,
+                    when: Some(time::macros::datetime!(2024-01-01 0:00 UTC)),
+                },
+            ),
+            Event::BookRead(
+                reader,
+                BookReadInfo {
+                    reader_id: reader,
+                    book_id: art_of_war,
+                    when: Some(time::macros::datetime!(2023-01-01 0:00 UTC)),
+                },
+            ),
+            Event::KeywordAdded(KeywordTarget::Book(art_of_war), "strategy".to_owned()),
+            Event::KeywordAdded(KeywordTarget::Author(sun_tzu), "strategy".to_owned()),
+            Event::KeywordAdded(KeywordTarget::Book(tao_te_ching), "classic".to_owned()),
+        ] {
+            index.apply(event)
+        }
 
-    const SEARCH_TERM_LENGTH_THRESHOLD: usize = 1;
+        let snapshot = serde_json::to_value(index.snapshot()).expect("a serializable snapshot");
+        let golden_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_FILE);
+        if std::env::var_os("UPDATE_GOLDEN_FILES").is_some() {
+            let pretty = serde_json::to_string_pretty(&snapshot).expect("a snapshot");
+            std::fs::write(&golden_path, pretty + "\n").expect("a written golden file");
+        }
+        let golden: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&golden_path).expect("a golden file"))
+                .expect("a JSON golden file");
 
-    fn tokenize(phrase: &str) -> Vec<&str> {
-        phrase
-            .split(&[' ', ',', '.', '-', '(', ')'])
-            .filter(|term| term.len() > SEARCH_TERM_LENGTH_THRESHOLD)
-            .collect()
+        assert_eq!(snapshot, golden);
     }
 
-    // Move to super-module - this must not be publically
-    // accessible from the http module
-    #[derive(Debug, Default)]
-    pub struct SearchIndex {
-        term_projections: HashMap<String, HashSet<Projection>>,
+    #[test]
+    fn removed_books_leave_nothing_behind() {
+        let book_id = BookId(UniqueId::fresh());
+        let journal = [
+            Event::BookAdded(
+                book_id,
+                BookInfo {
+                    isbn: Isbn("978-1-61180-697-7".to_owned()),
+                    title: "The Art of War".to_owned(),
+                    author: AuthorId(UniqueId::fresh()),
+                },
+            ),
+            Event::BookRemoved(book_id),
+        ];
+
+        let mut index = IndexSet::with_infix_search();
+        for event in journal {
+            index.apply(event)
+        }
+
+        let snapshot =
+            |index: &IndexSet| serde_json::to_value(index.snapshot()).expect("a snapshot");
+        assert_eq!(snapshot(&index), snapshot(&IndexSet::default()));
+        assert!(index.texts.lookup("war").is_empty());
     }
 
-    impl SearchIndex {
-        pub fn apply(&mut self, event: &Event) {
-            match event {
+    #[test]
+    fn removed_books_no_longer_count_as_read() {
+        let reader_id = ReaderId(UniqueId::fresh());
+        let book_id = BookId(UniqueId::fresh());
+        let mut index = IndexSet::default();
+        for event in [
+            Event::ReaderAdded(
+                reader_id,
+                ReaderInfo {
+                    name: "Reader".to_owned(),
+                    unique_moniker: "reader".to_owned(),
+                },
+            ),
+            Event::BookAdded(
+                book_id,
+                BookInfo {
+                    isbn: Isbn("978-1-61180-697-7".to_owned()),
+                    title: "The Art of War".to_owned(),
+                    author: AuthorId(UniqueId::fresh()),
+                },
+            ),
+            Event::BookRead(
+                reader_id,
+                BookReadInfo {
+                    reader_id,
+                    book_id,
+                    when: None,
+                },
+            ),
+        ] {
+            index.apply(event)
+        }
+        assert_eq!(TopReaders(10).execute(&index).len(), 1);
+
+        index.apply(Event::BookRemoved(book_id));
+        assert!(TopReaders(10).execute(&index).is_empty());
+        assert!(index.books_by_reader_id.is_empty());
+    }
+
+    #[test]
+    fn books_are_found_by_when_they_were_added() {
+        let author_id = AuthorId(UniqueId::fresh());
+        let mut index = IndexSet::default();
+        let book_ids = [
+            time::macros::datetime!(2023-12-31 23:59 UTC),
+            time::macros::datetime!(2024-01-01 0:00 UTC),
+            time::macros::datetime!(2024-06-01 0:00 UTC),
+            time::macros::datetime!(2025-01-01 0:00 UTC),
+        ]
+        .map(|added| {
+            let book_id = BookId(UniqueId::fresh());
+            index.apply_at(
                 Event::BookAdded(
-                    id,
+                    book_id,
                     BookInfo {
-                        isbn: Isbn(isbn),
-                        title,
-                        ..
+                        isbn: Isbn("978-1-61180-697-7".to_owned()),
+                        title: format!("Added {added}"),
+                        author: author_id,
                     },
-                ) => {
-                    let this_book = Projection::Books(BookField::Isbn(*id));
-                    self.bind_term(isbn, this_book);
-                    self.index_phrase(title, Projection::Books(BookField::Title(*id)));
-                }
-                Event::AuthorAdded(id, AuthorInfo { name }) => {
-                    self.index_phrase(name, Projection::Authors(AuthorField::Name(*id)));
-                }
-                // Don't index these
-                Event::ReaderAdded(..) => (),
-                Event::BookRead(..) => (),
-                // Think about this.
-                Event::KeywordAdded(..) => (),
+                ),
+                added,
+            );
+            book_id
+        });
+        // Without a time, which apply_at would have given it
+        index.apply(Event::BookAdded(
+            BookId(UniqueId::fresh()),
+            BookInfo {
+                isbn: Isbn("978-1-61180-697-7".to_owned()),
+                title: "Untimed".to_owned(),
+                author: author_id,
+            },
+        ));
+
+        let between = |after, before| {
+            BooksAddedBetween(after, before)
+                .execute(&index)
+                .into_iter()
+                .map(|Book(id, _)| id)
+                .collect::<Vec<_>>()
+        };
+        let (new_year, next_new_year) = (
+            Some(time::macros::datetime!(2024-01-01 0:00 UTC)),
+            Some(time::macros::datetime!(2025-01-01 0:00 UTC)),
+        );
+        assert_eq!(
+            between(new_year, next_new_year),
+            vec![book_ids[1], book_ids[2]]
+        );
+        assert_eq!(between(new_year, None), book_ids[1..].to_vec());
+        assert_eq!(between(None, new_year), vec![book_ids[0]]);
+        assert_eq!(between(None, None), book_ids.to_vec());
+    }
+
+    #[test]
+    fn books_page_the_same_way_every_time() {
+        let author_id = AuthorId(UniqueId::fresh());
+        let mut index = IndexSet::default();
+        let mut book_ids = (0..7)
+            .map(|n| {
+                let book_id = BookId(UniqueId::fresh());
+                index.apply(Event::BookAdded(
+                    book_id,
+                    BookInfo {
+                        isbn: Isbn("978-1-61180-697-7".to_owned()),
+                        title: format!("Volume {n}"),
+                        author: author_id,
+                    },
+                ));
+                book_id
+            })
+            .collect::<Vec<_>>();
+        book_ids.sort_by_key(|BookId(id)| *id.uuid());
+
+        let page = |limit, offset| {
+            let Page { items, total } = Paged {
+                query: AllBooks,
+                limit,
+                offset,
             }
+            .execute(&index);
+            (
+                items.into_iter().map(|Book(id, _)| id).collect::<Vec<_>>(),
+                total,
+            )
+        };
+        assert_eq!(page(3, 0), (book_ids[..3].to_vec(), 7));
+        assert_eq!(page(3, 3), (book_ids[3..6].to_vec(), 7));
+        assert_eq!(page(3, 6), (book_ids[6..].to_vec(), 7));
+        assert_eq!(page(3, 9), (vec![], 7));
+        assert_eq!(page(50, 0), (book_ids, 7));
+    }
+
+    #[test]
+    fn corrections_touch_updated_at_only() {
+        let (added, corrected) = (
+            time::macros::datetime!(2024-01-01 0:00 UTC),
+            time::macros::datetime!(2024-02-01 0:00 UTC),
+        );
+        let author_id = AuthorId(UniqueId::fresh());
+        let book_id = BookId(UniqueId::fresh());
+        let info = |title: &str| BookInfo {
+            isbn: Isbn("978-1-61180-697-7".to_owned()),
+            title: title.to_owned(),
+            author: author_id,
+        };
+
+        let mut index = IndexSet::default();
+        index.apply_at(
+            Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ),
+            added,
+        );
+        index.apply_at(Event::BookAdded(book_id, info("Art of War")), added);
+        index.apply_at(
+            Event::BookInfoCorrected(book_id, info("The Art of War")),
+            corrected,
+        );
+
+        let (_, book_times) = WithTimestamps(BookById(book_id))
+            .execute(&index)
+            .expect("a book");
+        assert_eq!(
+            book_times,
+            Some(Timestamps {
+                created_at: added,
+                updated_at: corrected,
+            })
+        );
+        let (_, author_times) = WithTimestamps(AuthorById(author_id))
+            .execute(&index)
+            .expect("an author");
+        assert_eq!(
+            author_times,
+            Some(Timestamps {
+                created_at: added,
+                updated_at: added,
+            })
+        );
+    }
+
+    #[test]
+    fn keywords_are_listed_alphabetically() {
+        let mut index = IndexSet::default();
+        let book = KeywordTarget::Book(BookId(UniqueId::fresh()));
+        let author = KeywordTarget::Author(AuthorId(UniqueId::fresh()));
+        for keyword in [
+            "war",
+            "strategy",
+            "classic",
+            "philosophy",
+            "ancient",
+            "china",
+        ] {
+            index.apply(Event::KeywordAdded(book, keyword.to_owned()));
+        }
+        index.apply(Event::KeywordAdded(author, "general".to_owned()));
+
+        for _ in 0..3 {
+            assert_eq!(
+                TargetKeywords(book).execute(&index),
+                [
+                    "ancient",
+                    "china",
+                    "classic",
+                    "philosophy",
+                    "strategy",
+                    "war"
+                ]
+            );
+            assert_eq!(
+                AllKeywords.execute(&index),
+                [
+                    "ancient",
+                    "china",
+                    "classic",
+                    "general",
+                    "philosophy",
+                    "strategy",
+                    "war"
+                ]
+            );
         }
+    }
 
-        fn index_phrase(&mut self, phrase: &str, target: Projection) {
-            for token in tokenize(phrase) {
-                self.bind_term(token, target)
+    #[test]
+    fn keyword_counts_are_most_used_first() {
+        let mut index = IndexSet::default();
+        let books = [(); 3].map(|_| KeywordTarget::Book(BookId(UniqueId::fresh())));
+        let author = KeywordTarget::Author(AuthorId(UniqueId::fresh()));
+
+        for (keyword, targets) in [
+            ("war", &books[..1]),
+            ("strategy", &books[..]),
+            ("classic", &books[1..]),
+            ("philosophy", &books[2..]),
+        ] {
+            for target in targets {
+                index.apply(Event::KeywordAdded(*target, keyword.to_owned()));
             }
         }
+        index.apply(Event::KeywordAdded(author, "classic".to_owned()));
+        // Tagging the same target twice does not count twice
+        index.apply(Event::KeywordAdded(books[0], "war".to_owned()));
+
+        assert_eq!(
+            KeywordsWithCounts.execute(&index),
+            vec![
+                ("classic".to_owned(), 3),
+                ("strategy".to_owned(), 3),
+                ("philosophy".to_owned(), 1),
+                ("war".to_owned(), 1),
+            ]
+        );
+    }
 
-        fn bind_term(&mut self, term: &str, target: Projection) {
-            self.term_projections
-                .entry(term.to_owned())
-                .or_default()
-                .insert(target);
-        }
+    #[test]
+    fn unread_books_leave_out_what_the_reader_has_read() {
+        let mut index = IndexSet::default();
+        let (sun_tzu, laozi) = (AuthorId(UniqueId::fresh()), AuthorId(UniqueId::fresh()));
+        let books = [sun_tzu, sun_tzu, laozi].map(|author| {
+            let id = BookId(UniqueId::fresh());
+            index.apply(Event::BookAdded(
+                id,
+                BookInfo {
+                    isbn: Isbn("978-1-61180-697-7".to_owned()),
+                    title: "The Art of War".to_owned(),
+                    author,
+                },
+            ));
+            id
+        });
+        let reader_id = ReaderId(UniqueId::fresh());
+        index.apply(Event::ReaderAdded(
+            reader_id,
+            ReaderInfo {
+                name: "Reader".to_owned(),
+                unique_moniker: "reader".to_owned(),
+            },
+        ));
+        index.apply(Event::BookRead(
+            reader_id,
+            BookReadInfo {
+                reader_id,
+                book_id: books[0],
+                when: None,
+            },
+        ));
+
+        let unread = |author| {
+            UnreadBooks(reader_id, author)
+                .execute(&index)
+                .map(|books| books.into_iter().map(|Book(id, _)| id).collect::<Vec<_>>())
+        };
+        assert_eq!(unread(None), Some(vec![books[1], books[2]]));
+        assert_eq!(unread(Some(sun_tzu)), Some(vec![books[1]]));
+        assert_eq!(unread(Some(AuthorId(UniqueId::fresh()))), Some(vec![]));
+        assert_eq!(
+            UnreadBooks(ReaderId(UniqueId::fresh()), None).execute(&index),
+            None
+        );
+    }
 
-        pub fn lookup(&self, term: &str) -> Vec<Projection> {
-            if let Some(xs) = self.term_projections.get(term) {
-                xs.iter().copied().collect()
-            } else {
-                vec![]
-            }
+    #[test]
+    fn reader_stats_join_reads_through_books_to_authors() {
+        let mut index = IndexSet::default();
+        let (sun_tzu, laozi) = (AuthorId(UniqueId::fresh()), AuthorId(UniqueId::fresh()));
+        let books = [sun_tzu, sun_tzu, laozi].map(|author| {
+            let id = BookId(UniqueId::fresh());
+            index.apply(Event::BookAdded(
+                id,
+                BookInfo {
+                    isbn: Isbn("978-1-61180-697-7".to_owned()),
+                    title: "The Art of War".to_owned(),
+                    author,
+                },
+            ));
+            id
+        });
+        let reader_id = ReaderId(UniqueId::fresh());
+        index.apply(Event::ReaderAdded(
+            reader_id,
+            ReaderInfo {
+                name: "Reader".to_owned(),
+                unique_moniker: "reader".to_owned(),
+            },
+        ));
+        assert_eq!(
+            ReaderStats(reader_id).execute(&index),
+            Some(ReaderStatistics::default())
+        );
+
+        let day = |n| OffsetDateTime::UNIX_EPOCH + time::Duration::days(n);
+        for (book_id, when) in [
+            (books[0], Some(day(3))),
+            (books[1], Some(day(1))),
+            (books[1], Some(day(7))),
+            (books[2], None),
+        ] {
+            index.apply(Event::BookRead(
+                reader_id,
+                BookReadInfo {
+                    reader_id,
+                    book_id,
+                    when,
+                },
+            ));
         }
+
+        assert_eq!(
+            ReaderStats(reader_id).execute(&index),
+            Some(ReaderStatistics {
+                books_read: 3,
+                distinct_authors: 2,
+                first_read: Some(day(1)),
+                last_read: Some(day(7)),
+            })
+        );
+        assert_eq!(
+            ReaderStats(ReaderId(UniqueId::fresh())).execute(&index),
+            None
+        );
     }
 
-    // SearchQuery with multiple terms that return intersection(hits*)
-    pub struct SearchQuery(pub String);
+    #[test]
+    fn event_counts_tally_every_kind_of_event() {
+        let mut index = IndexSet::default();
+        let author = AuthorId(UniqueId::fresh());
+        let book = BookId(UniqueId::fresh());
+
+        for event in [
+            Event::AuthorAdded(
+                author,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ),
+            Event::KeywordAdded(KeywordTarget::Author(author), "strategy".to_owned()),
+            Event::KeywordAdded(KeywordTarget::Book(book), "strategy".to_owned()),
+            Event::KeywordRenamed("strategy".to_owned(), "tactics".to_owned()),
+        ] {
+            index.apply(event)
+        }
 
-    pub struct SearchHit {
-        pub target: Projection,
-        pub source: String,
+        assert_eq!(
+            EventCounts.execute(&index),
+            BTreeMap::from([
+                ("author-added".to_owned(), 1),
+                ("keyword-added".to_owned(), 2),
+                ("keyword-renamed".to_owned(), 1),
+            ])
+        );
     }
 
-    impl IndexSetQuery for SearchQuery {
-        type Output = Vec<SearchHit>;
+    #[test]
+    fn removing_a_keyword_leaves_the_others_of_the_target() {
+        let mut index = IndexSet::default();
+        let (book, other) = (
+            KeywordTarget::Book(BookId(UniqueId::fresh())),
+            KeywordTarget::Book(BookId(UniqueId::fresh())),
+        );
+        for (target, keyword) in [(book, "war"), (book, "strategy"), (other, "war")] {
+            index.apply(Event::KeywordAdded(target, keyword.to_owned()));
+        }
 
-        fn execute(&self, index: &IndexSet) -> Self::Output {
-            let mut hits = vec![];
+        index.apply(Event::KeywordRemoved(book, "war".to_owned()));
 
-            let SearchQuery(search_term) = self;
-            for projection in index.texts.lookup(search_term) {
-                if let Some(hit) = resolve_projection(projection, index) {
-                    hits.push(hit)
-                } else {
-                    panic!("Text index has data that is not reflected in the field indices.")
-                }
+        assert_eq!(TargetKeywords(book).execute(&index), vec!["strategy"]);
+        assert_eq!(
+            KeywordTargets("war".parse().expect("a valid keyword")).execute(&index),
+            vec![other]
+        );
+    }
+
+    #[test]
+    fn targets_by_keywords_are_the_union_or_intersection() {
+        let mut index = IndexSet::default();
+        let books = [(); 4].map(|_| KeywordTarget::Book(BookId(UniqueId::fresh())));
+        for (book, keyword) in [
+            (0, "war"),
+            (0, "strategy"),
+            (1, "war"),
+            (2, "strategy"),
+            (3, "poetry"),
+        ] {
+            index.apply(Event::KeywordAdded(books[book], keyword.to_owned()));
+        }
+        let targets = |names: &[&str], mode| {
+            TargetsByKeywords {
+                keywords: names
+                    .iter()
+                    .map(|name| name.parse().expect("a valid keyword"))
+                    .collect(),
+                mode,
             }
+            .execute(&index)
+            .into_iter()
+            .collect::<HashSet<_>>()
+        };
 
-            hits
+        // Overlapping
+        assert_eq!(
+            targets(&["war", "strategy"], KeywordMatch::Any),
+            HashSet::from([books[0], books[1], books[2]])
+        );
+        assert_eq!(
+            targets(&["war", "strategy"], KeywordMatch::All),
+            HashSet::from([books[0]])
+        );
+
+        // Disjoint
+        assert_eq!(
+            targets(&["war", "poetry"], KeywordMatch::Any),
+            HashSet::from([books[0], books[1], books[3]])
+        );
+        assert!(targets(&["war", "poetry"], KeywordMatch::All).is_empty());
+
+        // Keywords in use by nothing
+        assert_eq!(
+            targets(&["poetry", "unheard-of"], KeywordMatch::Any),
+            HashSet::from([books[3]])
+        );
+        assert!(targets(&["poetry", "unheard-of"], KeywordMatch::All).is_empty());
+        assert!(targets(&[], KeywordMatch::Any).is_empty());
+    }
+
+    #[test]
+    fn renamed_keywords_keep_their_targets() {
+        let mut index = IndexSet::default();
+        let books = [(); 3].map(|_| KeywordTarget::Book(BookId(UniqueId::fresh())));
+        let keyword = |name: &str| name.parse::<Keyword>().expect("a valid keyword");
+        let targets = |index: &IndexSet, name| {
+            KeywordTargets(keyword(name))
+                .execute(index)
+                .into_iter()
+                .collect::<HashSet<_>>()
+        };
+
+        index.apply(Event::KeywordAdded(books[0], "scifi".to_owned()));
+        index.apply(Event::KeywordAdded(books[1], "scifi".to_owned()));
+        index.apply(Event::KeywordRenamed(
+            "scifi".to_owned(),
+            "sci-fi".to_owned(),
+        ));
+        assert_eq!(
+            targets(&index, "sci-fi"),
+            HashSet::from([books[0], books[1]])
+        );
+        assert!(targets(&index, "scifi").is_empty());
+        assert_eq!(
+            TargetKeywords(books[0]).execute(&index),
+            vec!["sci-fi".to_owned()]
+        );
+
+        // Renaming onto a keyword in use merges the two
+        index.apply(Event::KeywordAdded(books[2], "science-fiction".to_owned()));
+        index.apply(Event::KeywordRenamed(
+            "sci-fi".to_owned(),
+            "science-fiction".to_owned(),
+        ));
+        assert_eq!(targets(&index, "science-fiction"), HashSet::from(books));
+        assert_eq!(
+            AllKeywords.execute(&index),
+            vec!["science-fiction".to_owned()]
+        );
+        assert_eq!(
+            TargetKeywords(books[1]).execute(&index),
+            vec!["science-fiction".to_owned()]
+        );
+    }
+
+    #[test]
+    fn recent_entities_are_newest_first() {
+        let mut index = IndexSet::default();
+        let author_ids = ["Sun Tzu", "Laozi", "Confucius"].map(|name| {
+            let id = AuthorId(UniqueId::fresh());
+            index.apply(Event::AuthorAdded(
+                id,
+                AuthorInfo {
+                    name: name.to_owned(),
+                },
+            ));
+            id
+        });
+        for title in ["The Art of War", "Tao Te Ching", "Analects"] {
+            index.apply(Event::BookAdded(
+                BookId(UniqueId::fresh()),
+                BookInfo {
+                    isbn: Isbn("978-1-61180-697-7".to_owned()),
+                    title: title.to_owned(),
+                    author: author_ids[0],
+                },
+            ));
         }
+
+        let names = |index: &IndexSet, limit| {
+            RecentAuthors(limit)
+                .execute(index)
+                .into_iter()
+                .map(|Author(_, info)| info.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&index, 2), vec!["Confucius", "Laozi"]);
+        assert_eq!(
+            RecentBooks(5)
+                .execute(&index)
+                .into_iter()
+                .map(|Book(_, info)| info.title)
+                .collect::<Vec<_>>(),
+            vec!["Analects", "Tao Te Ching", "The Art of War"]
+        );
+
+        index.apply(Event::AuthorMerged(author_ids[2], author_ids[0]));
+        assert_eq!(names(&index, 2), vec!["Laozi", "Sun Tzu"]);
     }
 
-    // It would look good to have this on IndexSet, but ... what?
-    fn resolve_projection(target: Projection, index: &IndexSet) -> Option<SearchHit> {
-        let source = match &target {
-            Projection::Books(BookField::Isbn(id)) => index.books.get(id).map(
-                |BookInfo {
-                     isbn: Isbn(isbn), ..
-                 }| isbn,
-            ),
-            Projection::Books(BookField::Title(id)) => index.books.get(id).map(|x| &x.title),
-            Projection::Authors(AuthorField::Name(id)) => index.authors.get(id).map(|x| &x.name),
+    #[test]
+    fn author_by_book_id_tells_missing_book_from_missing_author() {
+        let mut index = IndexSet::default();
+        let author_id = AuthorId(UniqueId::fresh());
+        let book_id = BookId(UniqueId::fresh());
+        index.apply(Event::BookAdded(
+            book_id,
+            BookInfo {
+                isbn: Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author: author_id,
+            },
+        ));
+
+        assert_eq!(
+            AuthorByBookId(BookId(UniqueId::fresh()))
+                .execute(&index)
+                .unwrap_err(),
+            BookAuthorError::NoSuchBook
+        );
+        assert_eq!(
+            AuthorByBookId(book_id).execute(&index).unwrap_err(),
+            BookAuthorError::AuthorMissing(author_id)
+        );
+
+        index.apply(Event::AuthorAdded(
+            author_id,
+            AuthorInfo {
+                name: "Sun Tzu".to_owned(),
+            },
+        ));
+        assert!(AuthorByBookId(book_id).execute(&index).is_ok());
+    }
+
+    #[test]
+    fn dangling_references_reports_books_without_authors() {
+        let mut index = IndexSet::default();
+        let author_id = AuthorId(UniqueId::fresh());
+        let book_id = BookId(UniqueId::fresh());
+        index.apply(Event::BookAdded(
+            book_id,
+            BookInfo {
+                isbn: Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author: author_id,
+            },
+        ));
+
+        assert_eq!(
+            DanglingReferences.execute(&index),
+            vec![DanglingReference::BookAuthor(book_id, author_id)]
+        );
+
+        index.apply(Event::AuthorAdded(
+            author_id,
+            AuthorInfo {
+                name: "Sun Tzu".to_owned(),
+            },
+        ));
+        assert!(DanglingReferences.execute(&index).is_empty());
+    }
+
+    #[test]
+    fn authors_are_found_by_their_whole_name_in_any_case() {
+        let mut index = IndexSet::default();
+        let [sun_tzu, other_sun_tzu, sun] = [(); 3].map(|_| AuthorId(UniqueId::fresh()));
+        for (id, name) in [
+            (sun_tzu, "Sun Tzu"),
+            (other_sun_tzu, "sun tzu"),
+            (sun, "Sun"),
+        ] {
+            index.apply(Event::AuthorAdded(
+                id,
+                AuthorInfo {
+                    name: name.to_owned(),
+                },
+            ));
+        }
+
+        let mut found = AuthorsByName("SUN TZU".to_owned())
+            .execute(&index)
+            .into_iter()
+            .map(|Author(id, _)| id)
+            .collect::<Vec<_>>();
+        found.sort_by_key(|AuthorId(id)| *id.uuid());
+        let mut expected = vec![sun_tzu, other_sun_tzu];
+        expected.sort_by_key(|AuthorId(id)| *id.uuid());
+        assert_eq!(found, expected);
+        assert!(AuthorsByName("Tzu".to_owned()).execute(&index).is_empty());
+    }
+
+    #[test]
+    fn authors_without_books_are_listed_by_name() {
+        let mut index = IndexSet::default();
+        let mut add_author = |name: &str| {
+            let author_id = AuthorId(UniqueId::fresh());
+            index.apply(Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: name.to_owned(),
+                },
+            ));
+            author_id
         };
+        let sun_tzu = add_author("Sun Tzu");
+        let machiavelli = add_author("Niccolò Machiavelli");
+        let clausewitz = add_author("Carl von Clausewitz");
+        index.apply(Event::BookAdded(
+            BookId(UniqueId::fresh()),
+            BookInfo {
+                isbn: Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author: sun_tzu,
+            },
+        ));
+
+        let orphans = AuthorsWithoutBooks
+            .execute(&index)
+            .into_iter()
+            .map(|Author(id, _)| id)
+            .collect::<Vec<_>>();
+        assert_eq!(orphans, vec![clausewitz, machiavelli]);
+    }
 
-        source.map(|source| SearchHit {
-            target,
-            source: source.to_owned(),
-        })
+    #[test]
+    fn merged_author_hands_over_books_and_leaves_the_search_index() {
+        let mut index = IndexSet::default();
+        let keep = AuthorId(UniqueId::fresh());
+        let merge = AuthorId(UniqueId::fresh());
+        for (id, name) in [(keep, "J.R.R. Tolkien"), (merge, "JRR Tolkien")] {
+            index.apply(Event::AuthorAdded(
+                id,
+                AuthorInfo {
+                    name: name.to_owned(),
+                },
+            ));
+        }
+        let book_id = BookId(UniqueId::fresh());
+        index.apply(Event::BookAdded(
+            book_id,
+            BookInfo {
+                isbn: Isbn("978-0-261-10320-7".to_owned()),
+                title: "The Hobbit".to_owned(),
+                author: merge,
+            },
+        ));
+
+        index.apply(Event::AuthorMerged(merge, keep));
+
+        assert_eq!(AuthorById(merge).execute(&index), None);
+        assert_eq!(
+            BooksByAuthorId(keep)
+                .execute(&index)
+                .into_iter()
+                .map(|Book(id, info)| (id, info.author))
+                .collect::<Vec<_>>(),
+            vec![(book_id, keep)]
+        );
+        assert!(BooksByAuthorId(merge).execute(&index).is_empty());
+        assert!(DanglingReferences.execute(&index).is_empty());
+        assert_eq!(
+            text::SearchQuery::new("jrr".to_owned())
+                .execute(&index)
+                .hits
+                .len(),
+            0
+        );
     }
 
-    #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
-    pub enum Projection {
-        Books(BookField),
-        Authors(AuthorField),
+    #[test]
+    fn top_readers_are_ranked_by_distinct_books_read() {
+        let mut index = IndexSet::default();
+        let author_id = AuthorId(UniqueId::fresh());
+        let book_ids = [(); 3].map(|_| BookId(UniqueId::fresh()));
+        for (n, book_id) in book_ids.iter().enumerate() {
+            index.apply(Event::BookAdded(
+                *book_id,
+                BookInfo {
+                    isbn: Isbn(format!("978-1-61180-697-{n}")),
+                    title: format!("Volume {n}"),
+                    author: author_id,
+                },
+            ));
+        }
+        let mut add_reader = |name: &str, read: &[BookId]| {
+            let reader_id = ReaderId(UniqueId::fresh());
+            index.apply(Event::ReaderAdded(
+                reader_id,
+                ReaderInfo {
+                    name: name.to_owned(),
+                    unique_moniker: name.to_lowercase(),
+                },
+            ));
+            for book_id in read {
+                index.apply(Event::BookRead(
+                    reader_id,
+                    BookReadInfo {
+                        reader_id,
+                        book_id: *book_id,
+                        when: None,
+                    },
+                ));
+            }
+            reader_id
+        };
+        let [first, second, third] = book_ids;
+        let sun_tzu = add_reader("Sun Tzu", &[first, first, first]);
+        let clausewitz = add_reader("Clausewitz", &[first, second, third]);
+        let machiavelli = add_reader("Machiavelli", &[second, third]);
+        let boyd = add_reader("Boyd", &[first, third]);
+        add_reader("Liddell Hart", &[]);
+
+        let ranking = |limit| {
+            TopReaders(limit)
+                .execute(&index)
+                .into_iter()
+                .map(|(Reader(id, _), books_read)| (id, books_read))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ranking(10),
+            vec![(clausewitz, 3), (boyd, 2), (machiavelli, 2), (sun_tzu, 1)]
+        );
+        assert_eq!(ranking(2), vec![(clausewitz, 3), (boyd, 2)]);
     }
 
-    #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
-    pub enum BookField {
-        Title(BookId),
-        Isbn(BookId),
+    #[test]
+    fn books_by_reader_counts_re_reads() {
+        let mut index = IndexSet::default();
+        let author_id = AuthorId(UniqueId::fresh());
+        let book_id = BookId(UniqueId::fresh());
+        let reader_id = ReaderId(UniqueId::fresh());
+        index.apply(Event::BookAdded(
+            book_id,
+            BookInfo {
+                isbn: Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author: author_id,
+            },
+        ));
+        for when in [
+            time::macros::datetime!(2023-01-01 0:00 UTC),
+            time::macros::datetime!(2024-01-01 0:00 UTC),
+        ] {
+            index.apply(Event::BookRead(
+                reader_id,
+                BookReadInfo {
+                    reader_id,
+                    book_id,
+                    when: Some(when),
+                },
+            ));
+        }
+
+        let history = BooksByReader(reader_id).execute(&index);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].book.0, book_id);
+        assert_eq!(history[0].times_read, 2);
     }
 
-    #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
-    pub enum AuthorField {
-        Name(AuthorId),
+    #[test]
+    fn corrected_book_info_replaces_the_original_in_queries_and_search() {
+        let mut index = IndexSet::default();
+        let author_id = AuthorId(UniqueId::fresh());
+        let book_id = BookId(UniqueId::fresh());
+        let info = BookInfo {
+            isbn: Isbn("978-1-61180-697-7".to_owned()),
+            title: "The Art of Wor".to_owned(),
+            author: author_id,
+        };
+        index.apply(Event::BookAdded(book_id, info.clone()));
+
+        let corrected = BookInfo {
+            title: "The Art of War".to_owned(),
+            ..info
+        };
+        index.apply(Event::BookInfoCorrected(book_id, corrected.clone()));
+
+        assert_eq!(
+            BookById(book_id).execute(&index),
+            Some(Book(book_id, corrected))
+        );
+        assert_eq!(BooksByAuthorId(author_id).execute(&index).len(), 1);
+        assert_eq!(
+            text::SearchQuery::new("war".to_owned())
+                .execute(&index)
+                .hits
+                .len(),
+            1
+        );
+        assert!(text::SearchQuery::new("wor".to_owned())
+            .execute(&index)
+            .hits
+            .is_empty());
+        assert_eq!(
+            text::SearchQuery::new("9781611806977".to_owned())
+                .execute(&index)
+                .hits
+                .len(),
+            1
+        );
     }
 }
@@ -9,12 +9,21 @@ pub enum Error {
     #[error("Failed to marshall json data {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("Failed to marshall MessagePack data {0}")]
+    MessagePack(#[from] rmp_serde::encode::Error),
+
     #[error("Unknown event-type `{0}`")]
     UnknownEventType(String),
 
     #[error("Failed to parse event data {0}")]
     AggregateParseError(String),
 
+    #[error("No such aggregate")]
+    NoSuchAggregate,
+
+    #[error("Aggregate was expected at version {expected}, but is at {actual}")]
+    ConcurrencyConflict { expected: u64, actual: u64 },
+
     #[error("IO error {0}")]
     IoError(#[from] io::Error),
 
@@ -24,14 +33,30 @@ pub enum Error {
     #[error("Error receving an event {0}")]
     ReceiveError(#[from] RecvError),
 
+    #[error("Query timed out waiting for the read model")]
+    QueryTimeout,
+
+    #[error("`{0}` is not a path that the resources can be served under")]
+    InvalidResourcePrefix(String),
+
+    #[error("The application was built without a {0}")]
+    MissingComponent(&'static str),
+
     #[error("Generic error {0}")]
     Generic(String),
 
     #[error("Regex error {0}")]
     Regex(#[from] regex::Error),
 
+    #[error("The event store is corrupt: {0}")]
+    CorruptStore(String),
+
     #[error("Fjall persistence error {0}")]
     EventArchive(#[from] fjall::Error),
+
+    #[cfg(feature = "postgres")]
+    #[error("Postgres persistence error {0}")]
+    Postgres(#[from] sqlx::Error),
 }
 
 pub type Result<A> = std::result::Result<A, Error>;
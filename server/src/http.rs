@@ -1,104 +1,339 @@
 use axum::{
-    extract::Path,
-    extract::Query,
-    extract::State,
+    async_trait,
+    extract::{
+        self, DefaultBodyLimit, FromRequest, FromRequestParts, Query, RawPathParams, Request, State,
+    },
+    http::request::Parts,
     http::StatusCode,
-    http::{HeaderMap, HeaderValue},
+    http::{
+        header::{ACCEPT, CONTENT_TYPE, LOCATION},
+        HeaderMap, HeaderValue,
+    },
+    middleware,
     response::{IntoResponse, Response},
-    routing::{get, post},
-    Json, Router,
+    routing::{delete, get, post},
+    Extension, Json, Router,
 };
-use serde::Serialize;
-use std::{result::Result as StdResult, sync::Arc};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{convert::Infallible, result::Result as StdResult, sync::Arc};
 use tokio::net::TcpListener;
+use tower_http::limit::RequestBodyLimitLayer;
 use uuid::Uuid;
 
 use crate::{
     core::{
         model::{self as domain},
-        Application, CommandReceipt,
+        Application, CommandReceipt, IdempotencyKey, Rejection,
     },
     error::{Error, Result},
-    infrastructure::EventStore,
+    infrastructure::{AggregateVersion, EventStore},
 };
 
 pub mod model;
 
-const API_RESOURCE_PREFIX: &str = "/api/v1";
+pub const API_RESOURCE_PREFIX: &str = "/api/v1";
 
 type ApiResult<A> = StdResult<A, ApiError>;
 
 // The Api type can go away and become just a function:
 // http::start_api(application)
 type ApplicationInner<ES> = Arc<Application<ES>>;
-pub struct Api<ES>(ApplicationInner<ES>);
+pub struct Api<ES> {
+    application: ApplicationInner<ES>,
+    mode: Mode,
+    public_base_url: Option<String>,
+    resource_prefix: String,
+    body_limits: BodyLimits,
+}
+
+// The largest request bodies taken, in bytes; larger ones are answered
+// with 413 before they are read. Batch-get takes a list of ids, and so
+// more than the commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BodyLimits {
+    pub commands: usize,
+    pub batch_get: usize,
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            commands: 1024 * 1024,
+            batch_get: 4 * 1024 * 1024,
+        }
+    }
+}
+
+// Where the resources are mounted, for the URIs that refer to them
+#[derive(Clone)]
+struct ResourcePrefix(Arc<str>);
+
+// A read-only Api serves queries only; the routes of its commands are left
+// out, so they are answered with 405 or 404.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    ReadWrite,
+    ReadOnly,
+}
 
 impl<ES> Api<ES>
 where
     ES: EventStore + Send + Sync + Clone + 'static,
 {
     pub fn new(application: Application<ES>) -> Self {
-        Self(Arc::new(application))
+        Self::with_mode(application, Mode::ReadWrite)
+    }
+
+    pub fn with_mode(application: Application<ES>, mode: Mode) -> Self {
+        Self {
+            application: Arc::new(application),
+            mode,
+            public_base_url: None,
+            resource_prefix: API_RESOURCE_PREFIX.to_owned(),
+            body_limits: BodyLimits::default(),
+        }
+    }
+
+    // Location headers are relative, unless given the URL that clients
+    // reach the server at, such as https://books.example.com behind a proxy
+    pub fn with_public_base_url(self, base_url: &str) -> Self {
+        Self {
+            public_base_url: Some(base_url.to_owned()),
+            ..self
+        }
+    }
+
+    // The resources are under /api/v1 unless mounted elsewhere, such as
+    // behind a proxy that rewrites paths. The prefix is a path, like
+    // /books/api, and must not be the root or have captures in it.
+    pub fn with_resource_prefix(self, resource_prefix: &str) -> Result<Self> {
+        let trimmed = resource_prefix.trim_end_matches('/');
+        if !trimmed.starts_with('/') || trimmed.contains([':', '*']) {
+            Err(Error::InvalidResourcePrefix(resource_prefix.to_owned()))
+        } else {
+            Ok(Self {
+                resource_prefix: trimmed.to_owned(),
+                ..self
+            })
+        }
+    }
+
+    pub fn with_body_limits(self, body_limits: BodyLimits) -> Self {
+        Self {
+            body_limits,
+            ..self
+        }
+    }
+
+    // The routes with their state, for mounting in a larger app, adding
+    // middleware or calling without a socket
+    pub fn into_router(self) -> Router {
+        let Self {
+            application,
+            mode,
+            public_base_url,
+            resource_prefix,
+            body_limits,
+        } = self;
+        absolute_locations(
+            routing_configuration(mode, &resource_prefix, body_limits),
+            public_base_url,
+        )
+        .with_state(application)
     }
 
     pub async fn start(self, listener: TcpListener) -> Result<()> {
-        let Self(application) = self;
-        let routes = routing_configuration().with_state(application);
-        Ok(axum::serve(listener, routes).await?)
+        // Serves HTTP/1.1 and, to clients that start with it, HTTP/2
+        Ok(axum::serve(listener, self.into_router()).await?)
+    }
+}
+
+// Locations of created resources are made beneath the resource prefix
+fn routing_configuration<ES>(
+    mode: Mode,
+    resource_prefix: &str,
+    body_limits: BodyLimits,
+) -> Router<ApplicationInner<ES>>
+where
+    ES: EventStore + Send + Sync + Clone + 'static,
+{
+    let resources = match mode {
+        Mode::ReadWrite => {
+            query_routes(body_limits).merge(limit_body(command_routes(), body_limits.commands))
+        }
+        Mode::ReadOnly => query_routes(body_limits),
+    };
+    let resources = absolute_locations(resources, Some(resource_prefix.to_owned()))
+        .layer(Extension(ResourcePrefix(resource_prefix.into())));
+
+    let routes = Router::new()
+        .route("/", get(system_root))
+        .route("/version", get(system_version))
+        .nest("/admin", admin_query_routes())
+        .nest(resource_prefix, resources);
+
+    match mode {
+        Mode::ReadWrite => routes.nest(
+            "/admin",
+            limit_body(admin_command_routes(), body_limits.commands),
+        ),
+        Mode::ReadOnly => routes,
+    }
+}
+
+// Bodies larger than the limit are answered with 413, before they are read
+// when they say how large they are. Axum's own limit is lifted, or it would
+// cap the larger limits at its default.
+fn limit_body<S>(routes: Router<S>, limit: usize) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    routes
+        .layer(RequestBodyLimitLayer::new(limit))
+        .layer(DefaultBodyLimit::disable())
+}
+
+fn absolute_locations<S>(routes: Router<S>, base_url: Option<String>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    if let Some(base_url) = base_url {
+        let base_url = base_url.trim_end_matches('/').to_owned();
+        routes.layer(middleware::map_response(move |response| {
+            let base_url = base_url.clone();
+            async move { absolute_location(response, &base_url) }
+        }))
+    } else {
+        routes
+    }
+}
+
+fn absolute_location(mut response: Response, base_url: &str) -> Response {
+    let absolute = response
+        .headers()
+        .get(LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .filter(|location| location.starts_with('/'))
+        .and_then(|location| HeaderValue::from_str(&format!("{base_url}{location}")).ok());
+    if let Some(absolute) = absolute {
+        response.headers_mut().insert(LOCATION, absolute);
     }
+    response
 }
 
-fn routing_configuration<ES>() -> Router<ApplicationInner<ES>>
+// Everything that does not change anything. Batch-get is a POST only
+// because its ids go in the body.
+fn query_routes<ES>(body_limits: BodyLimits) -> Router<ApplicationInner<ES>>
 where
     ES: EventStore + Send + Sync + Clone + 'static,
 {
+    let batch_get = Router::new().route("/batch-get", post(books::batch_get));
     let books = Router::new()
         .route("/", get(books::list))
-        .route("/", post(books::create))
+        .merge(limit_body(batch_get, body_limits.batch_get))
+        .route("/recent", get(books::recent))
         .route("/:id", get(books::get))
+        .route("/:id/state", get(books::state))
         .route("/:id/keywords", get(keywords::by_book))
-        .route("/:id/keywords", post(keywords::add_to_book))
-        .route("/:id/readers", post(books::add_reader))
         .route("/:id/author", get(authors::by_book)); // todo: 'authors' and change the
                                                       // model tor reflect this
 
     let authors = Router::new()
         .route("/", get(authors::list))
-        .route("/", post(authors::create))
+        .route("/recent", get(authors::recent))
+        .route("/named", get(authors::by_name))
         .route("/:id", get(authors::get))
+        .route("/:id/state", get(authors::state))
         .route("/:id/keywords", get(keywords::by_author))
-        .route("/:id/keywords", post(keywords::add_to_author))
         .route("/:id/books", get(books::by_author));
 
     let readers = Router::new()
         .route("/", get(readers::list))
-        .route("/", post(readers::create))
+        .route("/leaderboard", get(readers::leaderboard))
         .route("/moniker/:moniker", get(readers::by_unique_moniker))
         .route("/:id", get(readers::get))
-        .route("/:id/books", get(books::by_reader));
+        .route("/:id/books", get(books::by_reader))
+        .route("/:id/unread", get(books::unread_by_reader))
+        .route("/:id/stats", get(readers::stats))
+        .route("/:id/reading", get(readers::reading));
 
     let keywords = Router::new()
         .route("/", get(keywords::list))
-        // Would like to be able to supply multiple keywords
+        .route("/targets", get(keywords::targets_of_several))
         .route("/:keyword/targets", get(keywords::targets));
 
     let search = get(search::text);
 
-    let api = Router::new()
+    Router::new()
         .nest("/books", books)
         .nest("/authors", authors)
         .nest("/readers", readers)
         .route("/search", search)
-        .nest("/keywords", keywords);
+        .nest("/keywords", keywords)
+}
+
+fn command_routes<ES>() -> Router<ApplicationInner<ES>>
+where
+    ES: EventStore + Send + Sync + Clone + 'static,
+{
+    let books = Router::new()
+        .route("/", post(books::create))
+        .route("/:id/keywords", post(keywords::add_to_book))
+        .route("/:id/keywords/:keyword", delete(keywords::remove_from_book))
+        .route("/:id/readers", post(books::add_reader));
+
+    let authors = Router::new()
+        .route("/", post(authors::create))
+        .route("/:id/keywords", post(keywords::add_to_author))
+        .route(
+            "/:id/keywords/:keyword",
+            delete(keywords::remove_from_author),
+        )
+        .route("/:keep/merge/:merge", post(authors::merge));
+
+    let readers = Router::new()
+        .route("/", post(readers::create))
+        .route("/:id/reading", post(readers::update_reading_progress));
+
+    let keywords = Router::new().route("/:keyword/rename", post(keywords::rename));
 
     Router::new()
-        .route("/", get(system_root))
-        .nest(API_RESOURCE_PREFIX, api)
+        .nest("/books", books)
+        .nest("/authors", authors)
+        .nest("/readers", readers)
+        .nest("/keywords", keywords)
+}
+
+fn admin_query_routes<ES>() -> Router<ApplicationInner<ES>>
+where
+    ES: EventStore + Send + Sync + Clone + 'static,
+{
+    Router::new()
+        .route("/consistency", get(admin::consistency))
+        .route("/orphan-authors", get(admin::orphan_authors))
+        .route("/store-stats", get(admin::store_stats))
+        .route("/event-counts", get(admin::event_counts))
+        .route("/durability", get(admin::durability))
+}
+
+fn admin_command_routes<ES>() -> Router<ApplicationInner<ES>>
+where
+    ES: EventStore + Send + Sync + Clone + 'static,
+{
+    Router::new()
+        .route("/books/:id/correct", post(admin::correct_book))
+        .route("/durability", post(admin::set_durability))
+        .route("/flush", post(admin::flush))
+        .route("/reindex", post(admin::reindex))
 }
 
 enum ApiError {
     Internal(Error),
     ServiceStatus(StatusCode),
+    BadRequest(String),
+    // A request body that could not be taken as it was
+    UnusableBody(StatusCode, String),
+    InvalidKeyword(String),
 }
 
 impl ApiError {
@@ -109,19 +344,196 @@ impl ApiError {
 
 impl From<Error> for ApiError {
     fn from(value: Error) -> Self {
-        Self::Internal(value)
+        match value {
+            Error::QueryTimeout => Self::ServiceStatus(StatusCode::SERVICE_UNAVAILABLE),
+            otherwise => Self::Internal(otherwise),
+        }
     }
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        match self {
-            ApiError::Internal(error) => format!("{error}").into_response(),
-            ApiError::ServiceStatus(status) => status.into_response(),
+        let (status, code, error) = match self {
+            ApiError::Internal(error) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                model::ErrorCode::Internal,
+                format!("{error}"),
+            ),
+            ApiError::ServiceStatus(status) => (
+                status,
+                model::ErrorCode::of_status(status),
+                status.canonical_reason().unwrap_or_default().to_owned(),
+            ),
+            ApiError::BadRequest(message) => (
+                StatusCode::BAD_REQUEST,
+                model::ErrorCode::MalformedRequest,
+                message,
+            ),
+            ApiError::UnusableBody(status, message) => {
+                (status, model::ErrorCode::MalformedRequest, message)
+            }
+            ApiError::InvalidKeyword(keyword) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                model::ErrorCode::InvalidKeyword,
+                format!("`{keyword}` is not a valid keyword"),
+            ),
+        };
+
+        (status, Json(model::ErrorBody { code, error })).into_response()
+    }
+}
+
+fn keyword(keyword: String) -> ApiResult<domain::Keyword> {
+    keyword
+        .parse()
+        .map_err(|_| ApiError::InvalidKeyword(keyword))
+}
+
+// axum's Path, except that a segment which does not parse is answered with
+// a JSON error naming it, rather than with axum's plain text rejection.
+struct Path<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> ApiResult<Self> {
+        match extract::Path::from_request_parts(parts, state).await {
+            Ok(extract::Path(value)) => Ok(Self(value)),
+            Err(rejection) => {
+                // Every segment that can fail to parse here is an id
+                let malformed = RawPathParams::from_request_parts(parts, state)
+                    .await
+                    .ok()
+                    .and_then(|params| {
+                        params
+                            .iter()
+                            .find(|(_, value)| Uuid::parse_str(value).is_err())
+                            .map(|(key, value)| {
+                                format!("Malformed {key} `{value}`, expected a UUID")
+                            })
+                    });
+                Err(ApiError::BadRequest(
+                    malformed.unwrap_or_else(|| rejection.body_text()),
+                ))
+            }
+        }
+    }
+}
+
+// axum's Json for request bodies, except that a body which does not
+// parse is answered with a JSON error rather than with plain text.
+struct JsonBody<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> ApiResult<Self> {
+        match Json::from_request(request, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) => Err(ApiError::UnusableBody(
+                rejection.status(),
+                rejection.body_text(),
+            )),
+        }
+    }
+}
+
+// What a response body is encoded in: MessagePack for clients that send
+// Accept: application/msgpack, and JSON for everyone else
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Json,
+    MessagePack,
+}
+
+impl Encoding {
+    const MESSAGE_PACK: &'static str = "application/msgpack";
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Encoding
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> StdResult<Self, Infallible> {
+        let message_pack = parts
+            .headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|media_range| media_range.split(';').next())
+            .any(|media_type| media_type.trim().eq_ignore_ascii_case(Self::MESSAGE_PACK));
+
+        Ok(if message_pack {
+            Self::MessagePack
+        } else {
+            Self::Json
+        })
+    }
+}
+
+// Json, except in the Encoding that was asked for. The MessagePack is
+// human-readable, so that ids and times come out as the same strings.
+struct Encoded<T>(Encoding, T);
+
+impl<T> IntoResponse for Encoded<T>
+where
+    T: Serialize,
+{
+    fn into_response(self) -> Response {
+        let Self(encoding, body) = self;
+        match encoding {
+            Encoding::Json => Json(body).into_response(),
+            Encoding::MessagePack => {
+                let mut bytes = vec![];
+                let mut serializer = rmp_serde::Serializer::new(&mut bytes)
+                    .with_struct_map()
+                    .with_human_readable();
+                match body.serialize(&mut serializer) {
+                    Ok(()) => (
+                        [(
+                            CONTENT_TYPE,
+                            HeaderValue::from_static(Encoding::MESSAGE_PACK),
+                        )],
+                        bytes,
+                    )
+                        .into_response(),
+                    Err(error) => ApiError::Internal(error.into()).into_response(),
+                }
+            }
         }
     }
 }
 
+// A keyword in a JSON body, which is a string before it is a keyword
+struct KeywordBody(domain::Keyword);
+
+#[async_trait]
+impl<S> FromRequest<S> for KeywordBody
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> ApiResult<Self> {
+        let JsonBody(name) = JsonBody::<String>::from_request(request, state).await?;
+        Ok(Self(keyword(name)?))
+    }
+}
+
 #[derive(Serialize)]
 struct Resource {
     id: Uuid,
@@ -164,21 +576,115 @@ fn created_response(resource: Resource) -> ApiResult<(StatusCode, HeaderMap, Jso
     Ok((StatusCode::CREATED, headers, Json(resource)))
 }
 
-// I would like this to have the correct URL
+// Relative to the resource prefix, which the routes put in front; an Api
+// with a public base URL makes it absolute
 fn resource_location(resource_type: &str, id: &str) -> String {
-    format!("{}/{resource_type}/{id}", API_RESOURCE_PREFIX)
+    format!("/{resource_type}/{id}")
+}
+
+fn idempotency_key(headers: &HeaderMap) -> Option<IdempotencyKey> {
+    headers
+        .get("Idempotency-Key")
+        .and_then(|value| value.to_str().ok())
+        .map(|key| IdempotencyKey(key.to_owned()))
+}
+
+// Versions are exchanged as ETags: returned on reads of an aggregate's
+// state and expected back in If-Match on writes to it.
+fn entity_tag(version: AggregateVersion) -> ApiResult<HeaderMap> {
+    let mut headers = HeaderMap::default();
+    headers.insert(
+        "ETag",
+        HeaderValue::from_str(&format!("\"{version}\"")).map_err(Error::from)?,
+    );
+    Ok(headers)
+}
+
+fn expected_version(headers: &HeaderMap) -> ApiResult<Option<AggregateVersion>> {
+    headers
+        .get("If-Match")
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim_matches('"').parse().ok())
+                .map(AggregateVersion)
+                .ok_or(ApiError::ServiceStatus(StatusCode::BAD_REQUEST))
+        })
+        .transpose()
 }
 
 impl From<CommandReceipt> for ApiResult<Response> {
     fn from(value: CommandReceipt) -> Self {
-        Ok(match value {
-            CommandReceipt::Rejected => StatusCode::NOT_ACCEPTABLE.into_response(),
-            CommandReceipt::Accepted => StatusCode::ACCEPTED.into_response(),
-            CommandReceipt::Created(id) => created_response(id.into())?.into_response(),
-        })
+        command_response(value, &[])
     }
 }
 
+// An unknown id that names the resource in the path is not found, while one
+// that comes from the body makes the request unprocessable
+fn command_response(receipt: CommandReceipt, path: &[domain::ResourceId]) -> ApiResult<Response> {
+    let unknown_status = |id: domain::ResourceId| {
+        if path.contains(&id) {
+            StatusCode::NOT_FOUND
+        } else {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+    };
+    Ok(match receipt {
+        CommandReceipt::RejectedBecause(rejection) => (
+            match rejection {
+                Rejection::DuplicateMoniker(..) | Rejection::DuplicateKeyword(..) => {
+                    StatusCode::CONFLICT
+                }
+                Rejection::UnknownAuthor(id) => unknown_status(id.into()),
+                Rejection::UnknownBook(id) => unknown_status(id.into()),
+                Rejection::UnknownReader(id) => unknown_status(id.into()),
+                // Keywords to remove or rename are only ever named in the path
+                Rejection::MissingKeyword(..) | Rejection::UnknownKeyword(..) => {
+                    StatusCode::NOT_FOUND
+                }
+                Rejection::ReadOnly => StatusCode::METHOD_NOT_ALLOWED,
+                Rejection::TooManyKeywords(..)
+                | Rejection::EmptyField(..)
+                | Rejection::IdempotencyKeyReused(..)
+                | Rejection::ProgressOutOfRange(..)
+                | Rejection::SameKeyword(..)
+                | Rejection::SameAuthor(..) => StatusCode::UNPROCESSABLE_ENTITY,
+            },
+            Json(model::RejectionBody::from(rejection)),
+        )
+            .into_response(),
+        CommandReceipt::Failed(error) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(model::ErrorBody {
+                code: model::ErrorCode::Internal,
+                error,
+            }),
+        )
+            .into_response(),
+        CommandReceipt::Conflict => (
+            StatusCode::CONFLICT,
+            Json(model::ErrorBody {
+                code: model::ErrorCode::VersionConflict,
+                error: "The version in If-Match is not the current one".to_owned(),
+            }),
+        )
+            .into_response(),
+        CommandReceipt::Accepted => StatusCode::ACCEPTED.into_response(),
+        // Not an error: a retry, or a read that was recorded before
+        CommandReceipt::AlreadyExists => StatusCode::OK.into_response(),
+        CommandReceipt::Created(id) => created_response(id.into())?.into_response(),
+        CommandReceipt::CreatedWithAuthor(book_id, author_id) => {
+            let (status, headers, _) = created_response(domain::ResourceId::from(book_id).into())?;
+            let book = model::CreatedBook {
+                id: book_id,
+                author_id,
+            };
+            (status, headers, Json(book)).into_response()
+        }
+    })
+}
+
 mod keywords {
     use super::*;
 
@@ -186,36 +692,98 @@ mod keywords {
 
     pub async fn list<ES>(
         State(application): State<ApplicationInner<ES>>,
-    ) -> ApiResult<Json<Vec<String>>>
+        Query(model::WithCounts { with_counts }): Query<model::WithCounts>,
+    ) -> ApiResult<Json<model::Keywords>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        Ok(Json(if with_counts {
+            model::Keywords::Counted(
+                application
+                    .issue_query(query::KeywordsWithCounts)
+                    .await?
+                    .into_iter()
+                    .map(|(keyword, count)| model::KeywordCount { keyword, count })
+                    .collect(),
+            )
+        } else {
+            model::Keywords::Names(
+                application
+                    .issue_query(query::AllKeywords)
+                    .await?
+                    .into_iter()
+                    .collect(),
+            )
+        }))
+    }
+
+    pub async fn targets<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Path(keyword): Path<String>,
+    ) -> ApiResult<Json<Vec<model::KeywordTarget>>>
     where
         ES: EventStore + Clone + 'static,
     {
         Ok(Json(
             application
-                .issue_query(query::AllKeywords)
+                .issue_query(query::KeywordTargets(super::keyword(keyword)?))
                 .await?
                 .into_iter()
+                .map(|b| b.into())
                 .collect(),
         ))
     }
 
-    pub async fn targets<ES>(
+    // The targets with any, or all, of several keywords
+    pub async fn targets_of_several<ES>(
         State(application): State<ApplicationInner<ES>>,
-        Path(keyword): Path<String>,
-    ) -> ApiResult<Json<Vec<model::KeywordTarget>>>
+        Extension(ResourcePrefix(resource_prefix)): Extension<ResourcePrefix>,
+        Query(model::KeywordsQuery { keywords, mode }): Query<model::KeywordsQuery>,
+    ) -> ApiResult<Json<Vec<model::TargetResource>>>
     where
         ES: EventStore + Clone + 'static,
     {
+        let keywords = keywords
+            .split(',')
+            .filter(|keyword| !keyword.is_empty())
+            .map(|keyword| super::keyword(keyword.to_owned()))
+            .collect::<ApiResult<_>>()?;
+
         Ok(Json(
             application
-                .issue_query(query::KeywordTargets(keyword.parse()?))
+                .issue_query(query::TargetsByKeywords {
+                    keywords,
+                    mode: mode.into(),
+                })
                 .await?
                 .into_iter()
-                .map(|b| b.into())
+                .map(|target| model::TargetResource::new(target, &resource_prefix))
                 .collect(),
         ))
     }
 
+    // Renames to a keyword that is already in use merges the two
+    pub async fn rename<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Path(from): Path<String>,
+        headers: HeaderMap,
+        KeywordBody(to): KeywordBody,
+    ) -> ApiResult<Response>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        application
+            .submit_idempotent_command(
+                idempotency_key(&headers),
+                domain::Command::RenameKeyword {
+                    from: super::keyword(from)?,
+                    to,
+                },
+            )
+            .await
+            .into()
+    }
+
     pub async fn by_book<ES>(
         State(application): State<ApplicationInner<ES>>,
         Path(model::BookId(book_id)): Path<model::BookId>,
@@ -235,17 +803,42 @@ mod keywords {
     pub async fn add_to_book<ES>(
         State(application): State<ApplicationInner<ES>>,
         Path(model::BookId(book_id)): Path<model::BookId>,
-        keyword: String,
+        headers: HeaderMap,
+        KeywordBody(keyword): KeywordBody,
     ) -> ApiResult<Response>
     where
         ES: EventStore + Clone + 'static,
     {
-        let keyword = keyword.parse()?;
         application
-            .submit_command(domain::Command::AddKeyword(
-                keyword,
-                domain::KeywordTarget::Book(book_id),
-            ))
+            .submit_idempotent_command(
+                idempotency_key(&headers),
+                domain::Command::AddKeyword(
+                    keyword,
+                    domain::KeywordTarget::Book(book_id),
+                    expected_version(&headers)?,
+                ),
+            )
+            .await
+            .into()
+    }
+
+    pub async fn remove_from_book<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Path((model::BookId(book_id), keyword)): Path<(model::BookId, String)>,
+        headers: HeaderMap,
+    ) -> ApiResult<Response>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        application
+            .submit_idempotent_command(
+                idempotency_key(&headers),
+                domain::Command::RemoveKeyword(
+                    super::keyword(keyword)?,
+                    domain::KeywordTarget::Book(book_id),
+                    expected_version(&headers)?,
+                ),
+            )
             .await
             .into()
     }
@@ -268,23 +861,47 @@ mod keywords {
         ))
     }
 
+    pub async fn remove_from_author<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Path((model::AuthorId(author_id), keyword)): Path<(model::AuthorId, String)>,
+        headers: HeaderMap,
+    ) -> ApiResult<Response>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        application
+            .submit_idempotent_command(
+                idempotency_key(&headers),
+                domain::Command::RemoveKeyword(
+                    super::keyword(keyword)?,
+                    domain::KeywordTarget::Author(author_id),
+                    expected_version(&headers)?,
+                ),
+            )
+            .await
+            .into()
+    }
+
     pub async fn add_to_author<ES>(
         State(application): State<ApplicationInner<ES>>,
         Path(model::AuthorId(author_id)): Path<model::AuthorId>,
-        keyword: String,
+        headers: HeaderMap,
+        KeywordBody(keyword): KeywordBody,
     ) -> ApiResult<Response>
     where
         ES: EventStore + Clone + 'static,
     {
-        let keyword = keyword.parse()?;
-
         println!("keyword: {}", keyword);
 
         application
-            .submit_command(domain::Command::AddKeyword(
-                keyword,
-                domain::KeywordTarget::Author(author_id),
-            ))
+            .submit_idempotent_command(
+                idempotency_key(&headers),
+                domain::Command::AddKeyword(
+                    keyword,
+                    domain::KeywordTarget::Author(author_id),
+                    expected_version(&headers)?,
+                ),
+            )
             .await
             .into()
     }
@@ -297,19 +914,61 @@ mod search {
 
     pub async fn text<ES>(
         State(application): State<ApplicationInner<ES>>,
-        Query(model::SearchTerm { query }): Query<model::SearchTerm>,
-    ) -> ApiResult<Json<Vec<model::SearchResultItem>>>
+        Extension(ResourcePrefix(resource_prefix)): Extension<ResourcePrefix>,
+        Query(model::SearchTerm {
+            query,
+            highlight,
+            fields,
+            fuzzy,
+            infix,
+            limit,
+            offset,
+        }): Query<model::SearchTerm>,
+        encoding: Encoding,
+    ) -> ApiResult<Encoded<model::SearchResults>>
     where
         ES: EventStore + Clone + 'static,
     {
-        let hits = application
-            .issue_query(query::text::SearchQuery(query))
-            .await?
+        let fields = if let Some(fields) = fields {
+            fields
+                .parse()
+                .map_err(|_| ApiError::ServiceStatus(StatusCode::BAD_REQUEST))?
+        } else {
+            query::text::FieldMask::ALL
+        };
+        let search = query::text::SearchQuery {
+            text: query.clone(),
+            fields,
+            fuzzy,
+            infix,
+            limit,
+            offset,
+            highlight,
+            max_hits: application.max_search_hits(),
+        };
+        let query::text::SearchHits { hits, truncated } = application.issue_query(search).await?;
+        let hits = hits
             .into_iter()
-            .map(|hit| model::SearchResultItem::from_search_hit(hit.into(), API_RESOURCE_PREFIX))
-            .collect();
+            .map(|hit| model::SearchResultItem::from_search_hit(hit, &resource_prefix))
+            .collect::<Vec<_>>();
+
+        // Walking the vocabulary is not cheap, so only do it when needed
+        let suggestions = if hits.is_empty() {
+            application
+                .issue_query(query::text::Suggestions(query))
+                .await?
+        } else {
+            vec![]
+        };
 
-        Ok(Json(hits))
+        Ok(Encoded(
+            encoding,
+            model::SearchResults {
+                hits,
+                truncated,
+                suggestions,
+            },
+        ))
     }
 }
 
@@ -325,22 +984,80 @@ mod books {
     where
         ES: EventStore + Clone + 'static,
     {
-        if let Some(book) = application.issue_query(query::BookById(book_id)).await? {
+        if let Some(book) = application
+            .issue_query(query::WithTimestamps(query::BookById(book_id)))
+            .await?
+        {
             Ok(Json(book.into()))
         } else {
             ApiError::not_found()
         }
     }
 
+    // The book as reconstructed from its events, bypassing the read model.
+    pub async fn state<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Path(model::BookId(book_id)): Path<model::BookId>,
+    ) -> ApiResult<(HeaderMap, Json<model::Book>)>
+    where
+        ES: EventStore + Send + Sync + Clone + 'static,
+    {
+        match application.load_aggregate(book_id).await {
+            Err(Error::NoSuchAggregate) => ApiError::not_found(),
+            book => Ok((
+                entity_tag(application.aggregate_version(book_id).await?)?,
+                Json(book?.into()),
+            )),
+        }
+    }
+
+    // All of them, or those added between two times, a page at a time
     pub async fn list<ES>(
         State(application): State<ApplicationInner<ES>>,
+        Query(model::AddedBetween {
+            added_after,
+            added_before,
+        }): Query<model::AddedBetween>,
+        Query(paging): Query<model::Paging>,
+        encoding: Encoding,
+    ) -> ApiResult<Encoded<model::Page<model::Book>>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        let (limit, offset) = (paging.limit(), paging.offset);
+        let books = if added_after.is_some() || added_before.is_some() {
+            application
+                .issue_query(query::WithTimestamps(query::Paged {
+                    query: query::BooksAddedBetween(added_after, added_before),
+                    limit,
+                    offset,
+                }))
+                .await?
+        } else {
+            application
+                .issue_query(query::WithTimestamps(query::Paged {
+                    query: query::AllBooks,
+                    limit,
+                    offset,
+                }))
+                .await?
+        };
+        Ok(Encoded(
+            encoding,
+            model::Page::new(books, model::Book::from),
+        ))
+    }
+
+    pub async fn recent<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Query(limit): Query<model::RecentLimit>,
     ) -> ApiResult<Json<Vec<model::Book>>>
     where
         ES: EventStore + Clone + 'static,
     {
         Ok(Json(
             application
-                .issue_query(query::AllBooks)
+                .issue_query(query::WithTimestamps(query::RecentBooks(limit.get())))
                 .await?
                 .into_iter()
                 .map(|b| b.into())
@@ -348,16 +1065,50 @@ mod books {
         ))
     }
 
+    pub async fn batch_get<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        JsonBody(book_ids): JsonBody<Vec<model::BookId>>,
+    ) -> ApiResult<Json<model::BookBatch>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        Ok(Json(
+            application
+                .issue_query(query::BooksByIds(
+                    book_ids.into_iter().map(|model::BookId(id)| id).collect(),
+                ))
+                .await?
+                .into(),
+        ))
+    }
+
     // return a URI to the created resource
     pub async fn create<ES>(
         State(application): State<ApplicationInner<ES>>,
-        Json(model::NewBook(book)): Json<model::NewBook>,
+        headers: HeaderMap,
+        JsonBody(model::NewBook {
+            isbn,
+            title,
+            author,
+        }): JsonBody<model::NewBook>,
     ) -> ApiResult<Response>
     where
         ES: EventStore + Clone + 'static,
     {
+        let command = match author {
+            model::AuthorRef::Existing(author) => Command::AddBook(domain::BookInfo {
+                isbn,
+                title,
+                author,
+            }),
+            model::AuthorRef::New(author) => Command::AddBookWithAuthor {
+                isbn,
+                title,
+                author,
+            },
+        };
         application
-            .submit_command(Command::AddBook(book))
+            .submit_idempotent_command(idempotency_key(&headers), command)
             .await
             .into()
     }
@@ -371,7 +1122,7 @@ mod books {
     {
         Ok(Json(
             application
-                .issue_query(query::BooksByAuthorId(author_id))
+                .issue_query(query::WithTimestamps(query::BooksByAuthorId(author_id)))
                 .await?
                 .into_iter()
                 .map(|b| b.into())
@@ -382,7 +1133,7 @@ mod books {
     pub async fn by_reader<ES>(
         State(application): State<ApplicationInner<ES>>,
         Path(model::ReaderId(reader_id)): Path<model::ReaderId>,
-    ) -> ApiResult<Json<Vec<model::Book>>>
+    ) -> ApiResult<Json<Vec<model::ReadBook>>>
     where
         ES: EventStore + Clone + 'static,
     {
@@ -396,28 +1147,48 @@ mod books {
         ))
     }
 
-    pub async fn add_reader<ES>(
+    pub async fn unread_by_reader<ES>(
         State(application): State<ApplicationInner<ES>>,
-        Path(model::BookId(book_id)): Path<model::BookId>,
-        Json(model::NewBookRead { reader_id, when }): Json<model::NewBookRead>,
-    ) -> ApiResult<StatusCode>
-    where
+        Path(model::ReaderId(reader_id)): Path<model::ReaderId>,
+        Query(model::UnreadBooksQuery { author }): Query<model::UnreadBooksQuery>,
+    ) -> ApiResult<Json<Vec<model::Book>>>
+    where
         ES: EventStore + Clone + 'static,
     {
-        if application
-            .submit_command(Command::AddReadBook(domain::BookReadInfo {
-                reader_id: reader_id.into(),
-                book_id,
-                when,
-            }))
-            .await
-            .is_success()
+        if let Some(books) = application
+            .issue_query(query::WithTimestamps(query::UnreadBooks(
+                reader_id,
+                author.map(|model::AuthorId(id)| id),
+            )))
+            .await?
         {
-            Ok(StatusCode::ACCEPTED)
+            Ok(Json(books.into_iter().map(|b| b.into()).collect()))
         } else {
-            Ok(StatusCode::NOT_ACCEPTABLE)
+            ApiError::not_found()
         }
     }
+
+    pub async fn add_reader<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Path(model::BookId(book_id)): Path<model::BookId>,
+        headers: HeaderMap,
+        JsonBody(model::NewBookRead { reader_id, when }): JsonBody<model::NewBookRead>,
+    ) -> ApiResult<Response>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        let receipt = application
+            .submit_idempotent_command(
+                idempotency_key(&headers),
+                Command::AddReadBook(domain::BookReadInfo {
+                    reader_id: reader_id.into(),
+                    book_id,
+                    when,
+                }),
+            )
+            .await;
+        command_response(receipt, &[book_id.into()])
+    }
 }
 
 mod authors {
@@ -428,32 +1199,114 @@ mod authors {
     pub async fn get<ES>(
         State(application): State<ApplicationInner<ES>>,
         Path(model::AuthorId(author_id)): Path<model::AuthorId>,
+        Query(model::WithCounts { with_counts }): Query<model::WithCounts>,
     ) -> ApiResult<Json<model::Author>>
     where
         ES: EventStore + Clone + 'static,
     {
-        if let Some(author) = application
-            .issue_query(query::AuthorById(author_id))
-            .await?
-        {
-            Ok(Json(author.into()))
+        let author = if with_counts {
+            application
+                .issue_query(query::WithTimestamps(query::AuthorWithBookCount(author_id)))
+                .await?
+                .map(model::Author::with_book_count)
+        } else {
+            application
+                .issue_query(query::WithTimestamps(query::AuthorById(author_id)))
+                .await?
+                .map(|a| a.into())
+        };
+
+        if let Some(author) = author {
+            Ok(Json(author))
         } else {
             ApiError::not_found()
         }
     }
 
+    // The author as reconstructed from its events, bypassing the read model.
+    pub async fn state<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Path(model::AuthorId(author_id)): Path<model::AuthorId>,
+    ) -> ApiResult<(HeaderMap, Json<model::Author>)>
+    where
+        ES: EventStore + Send + Sync + Clone + 'static,
+    {
+        match application.load_aggregate(author_id).await {
+            Err(Error::NoSuchAggregate) => ApiError::not_found(),
+            author => Ok((
+                entity_tag(application.aggregate_version(author_id).await?)?,
+                Json(author?.into()),
+            )),
+        }
+    }
+
     pub async fn list<ES>(
         State(application): State<ApplicationInner<ES>>,
+        Query(model::WithCounts { with_counts }): Query<model::WithCounts>,
+        Query(paging): Query<model::Paging>,
+        encoding: Encoding,
+    ) -> ApiResult<Encoded<model::Page<model::Author>>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        let (limit, offset) = (paging.limit(), paging.offset);
+        let authors = if with_counts {
+            model::Page::new(
+                application
+                    .issue_query(query::WithTimestamps(query::Paged {
+                        query: query::AllAuthorsWithBookCounts,
+                        limit,
+                        offset,
+                    }))
+                    .await?,
+                model::Author::with_book_count,
+            )
+        } else {
+            model::Page::new(
+                application
+                    .issue_query(query::WithTimestamps(query::Paged {
+                        query: query::AllAuthors,
+                        limit,
+                        offset,
+                    }))
+                    .await?,
+                model::Author::from,
+            )
+        };
+        Ok(Encoded(encoding, authors))
+    }
+
+    pub async fn recent<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Query(limit): Query<model::RecentLimit>,
     ) -> ApiResult<Json<Vec<model::Author>>>
     where
         ES: EventStore + Clone + 'static,
     {
         Ok(Json(
             application
-                .issue_query(query::AllAuthors)
+                .issue_query(query::WithTimestamps(query::RecentAuthors(limit.get())))
                 .await?
                 .into_iter()
-                .map(|b| b.into())
+                .map(|a| a.into())
+                .collect(),
+        ))
+    }
+
+    // Names are not unique, so this is a list
+    pub async fn by_name<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Query(model::AuthorName { name }): Query<model::AuthorName>,
+    ) -> ApiResult<Json<Vec<model::Author>>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        Ok(Json(
+            application
+                .issue_query(query::WithTimestamps(query::AuthorsByName(name)))
+                .await?
+                .into_iter()
+                .map(|a| a.into())
                 .collect(),
         ))
     }
@@ -461,17 +1314,39 @@ mod authors {
     // return a URI to the created resource
     pub async fn create<ES>(
         State(application): State<ApplicationInner<ES>>,
-        Json(model::NewAuthor(author)): Json<model::NewAuthor>,
+        headers: HeaderMap,
+        JsonBody(model::NewAuthor(author)): JsonBody<model::NewAuthor>,
     ) -> ApiResult<Response>
     where
         ES: EventStore + Clone + 'static,
     {
         application
-            .submit_command(Command::AddAuthor(author))
+            .submit_idempotent_command(idempotency_key(&headers), Command::AddAuthor(author))
             .await
             .into()
     }
 
+    // Re-points the books of merge to keep, and removes merge
+    pub async fn merge<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        headers: HeaderMap,
+        Path((model::AuthorId(keep), model::AuthorId(merge))): Path<(
+            model::AuthorId,
+            model::AuthorId,
+        )>,
+    ) -> ApiResult<Response>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        let receipt = application
+            .submit_idempotent_command(
+                idempotency_key(&headers),
+                Command::MergeAuthors { keep, merge },
+            )
+            .await;
+        command_response(receipt, &[keep.into(), merge.into()])
+    }
+
     pub async fn by_book<ES>(
         State(application): State<ApplicationInner<ES>>,
         Path(model::BookId(book_id)): Path<model::BookId>,
@@ -479,13 +1354,16 @@ mod authors {
     where
         ES: EventStore + Clone + 'static,
     {
-        if let Some(author) = application
-            .issue_query(query::AuthorByBookId(book_id))
+        match application
+            .issue_query(query::WithTimestamps(query::AuthorByBookId(book_id)))
             .await?
         {
-            Ok(Json(author.into()))
-        } else {
-            ApiError::not_found()
+            Ok(author) => Ok(Json(author.into())),
+            Err(query::BookAuthorError::NoSuchBook) => ApiError::not_found(),
+            Err(query::BookAuthorError::AuthorMissing(author_id)) => {
+                tracing::warn!("Book {book_id:?} refers to author {author_id:?} which is missing from the read model");
+                Err(ApiError::ServiceStatus(StatusCode::INTERNAL_SERVER_ERROR))
+            }
         }
     }
 }
@@ -503,7 +1381,7 @@ mod readers {
         ES: EventStore + Clone + 'static,
     {
         if let Some(reader) = application
-            .issue_query(query::ReaderById(reader_id))
+            .issue_query(query::WithTimestamps(query::ReaderById(reader_id)))
             .await?
         {
             Ok(Json(reader.into()))
@@ -512,6 +1390,76 @@ mod readers {
         }
     }
 
+    pub async fn leaderboard<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Query(limit): Query<model::RecentLimit>,
+    ) -> ApiResult<Json<Vec<model::Reader>>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        Ok(Json(
+            application
+                .issue_query(query::WithTimestamps(query::TopReaders(limit.get())))
+                .await?
+                .into_iter()
+                .map(model::Reader::with_books_read)
+                .collect(),
+        ))
+    }
+
+    pub async fn reading<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Path(model::ReaderId(reader_id)): Path<model::ReaderId>,
+    ) -> ApiResult<Json<Vec<model::BookBeingRead>>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        if let Some(books) = application
+            .issue_query(query::BooksBeingRead(reader_id))
+            .await?
+        {
+            Ok(Json(books.into_iter().map(|b| b.into()).collect()))
+        } else {
+            ApiError::not_found()
+        }
+    }
+
+    // Reaching 100 percent finishes the book, and records it as read
+    pub async fn update_reading_progress<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Path(model::ReaderId(reader_id)): Path<model::ReaderId>,
+        headers: HeaderMap,
+        JsonBody(model::ReadingProgress { book_id, percent }): JsonBody<model::ReadingProgress>,
+    ) -> ApiResult<Response>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        let receipt = application
+            .submit_idempotent_command(
+                idempotency_key(&headers),
+                Command::UpdateReadingProgress(reader_id, book_id, percent),
+            )
+            .await;
+        command_response(receipt, &[reader_id.into()])
+    }
+
+    pub async fn stats<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Path(model::ReaderId(reader_id)): Path<model::ReaderId>,
+    ) -> ApiResult<Json<model::ReaderStats>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        if let Some(stats) = application
+            .issue_query(query::ReaderStats(reader_id))
+            .await?
+        {
+            Ok(Json(stats.into()))
+        } else {
+            ApiError::not_found()
+        }
+    }
+
     pub async fn list<ES>(
         State(application): State<ApplicationInner<ES>>,
     ) -> ApiResult<Json<Vec<model::Reader>>>
@@ -520,7 +1468,7 @@ mod readers {
     {
         Ok(Json(
             application
-                .issue_query(query::AllReaders)
+                .issue_query(query::WithTimestamps(query::AllReaders))
                 .await?
                 .into_iter()
                 .map(|b| b.into())
@@ -530,13 +1478,14 @@ mod readers {
 
     pub async fn create<ES>(
         State(application): State<ApplicationInner<ES>>,
-        Json(model::NewReader(reader)): Json<model::NewReader>,
+        headers: HeaderMap,
+        JsonBody(model::NewReader(reader)): JsonBody<model::NewReader>,
     ) -> ApiResult<Response>
     where
         ES: EventStore + Clone + 'static,
     {
         application
-            .submit_command(Command::AddReader(reader))
+            .submit_idempotent_command(idempotency_key(&headers), Command::AddReader(reader))
             .await
             .into()
     }
@@ -550,16 +1499,1262 @@ mod readers {
     {
         Ok(Json(
             application
-                .issue_query(query::UniqueReaderByMoniker(moniker))
+                .issue_query(query::WithTimestamps(query::UniqueReaderByMoniker(moniker)))
                 .await?
                 .map(|b| b.into()),
         ))
     }
 }
 
-async fn system_root<ES>(State(_application): State<ApplicationInner<ES>>) -> ApiResult<String>
+mod admin {
+    use super::*;
+
+    use domain::{query, Command};
+
+    // Appends a correction to the book rather than rewriting its history
+    pub async fn correct_book<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        Path(model::BookId(book_id)): Path<model::BookId>,
+        headers: HeaderMap,
+        JsonBody(model::CorrectedBook(info)): JsonBody<model::CorrectedBook>,
+    ) -> ApiResult<Response>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        let receipt = application
+            .submit_idempotent_command(
+                idempotency_key(&headers),
+                Command::CorrectBookInfo(book_id, info, expected_version(&headers)?),
+            )
+            .await;
+        command_response(receipt, &[book_id.into()])
+    }
+
+    pub async fn store_stats<ES>(
+        State(application): State<ApplicationInner<ES>>,
+    ) -> ApiResult<Json<model::StoreStats>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        Ok(Json(application.store_stats().await?.into()))
+    }
+
+    pub async fn durability<ES>(
+        State(application): State<ApplicationInner<ES>>,
+    ) -> ApiResult<Json<model::Durability>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        Ok(Json(application.durability().await.into()))
+    }
+
+    // Buffered writes for the length of a bulk import, say
+    pub async fn set_durability<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        JsonBody(durability): JsonBody<model::Durability>,
+    ) -> ApiResult<Json<model::Durability>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        application.set_durability(durability.into()).await?;
+        Ok(Json(application.durability().await.into()))
+    }
+
+    // Before taking a backup during a buffered import, say
+    pub async fn flush<ES>(State(application): State<ApplicationInner<ES>>) -> ApiResult<StatusCode>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        application.flush().await?;
+        Ok(StatusCode::OK)
+    }
+
+    // After the way terms are indexed changes, so that what was indexed
+    // before is found the same way
+    pub async fn reindex<ES>(
+        State(application): State<ApplicationInner<ES>>,
+        JsonBody(model::Reindex {
+            term_length_threshold,
+        }): JsonBody<model::Reindex>,
+    ) -> ApiResult<Json<model::TextIndex>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        let term_length_threshold = application.reindex_texts(term_length_threshold).await?;
+        Ok(Json(model::TextIndex {
+            term_length_threshold,
+        }))
+    }
+
+    pub async fn event_counts<ES>(
+        State(application): State<ApplicationInner<ES>>,
+    ) -> ApiResult<Json<model::EventCounts>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        Ok(Json(model::EventCounts(
+            application.issue_query(query::EventCounts).await?,
+        )))
+    }
+
+    pub async fn orphan_authors<ES>(
+        State(application): State<ApplicationInner<ES>>,
+    ) -> ApiResult<Json<Vec<model::Author>>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        Ok(Json(
+            application
+                .issue_query(query::WithTimestamps(query::AuthorsWithoutBooks))
+                .await?
+                .into_iter()
+                .map(|a| a.into())
+                .collect(),
+        ))
+    }
+
+    pub async fn consistency<ES>(
+        State(application): State<ApplicationInner<ES>>,
+    ) -> ApiResult<Json<model::ConsistencyReport>>
+    where
+        ES: EventStore + Clone + 'static,
+    {
+        Ok(Json(model::ConsistencyReport {
+            dangling_references: application
+                .issue_query(query::DanglingReferences)
+                .await?
+                .into_iter()
+                .map(|r| r.into())
+                .collect(),
+        }))
+    }
+}
+
+async fn system_root<ES>(
+    State(application): State<ApplicationInner<ES>>,
+) -> ApiResult<Json<model::SystemStatus>>
 where
     ES: EventStore + Send + Sync + Clone + 'static,
 {
-    Ok("Blister 0.1 running.".to_owned())
+    Ok(Json(model::SystemStatus {
+        version: model::ServerVersion::CURRENT,
+        read_model: application.read_model_status().map(|status| status.into()),
+    }))
+}
+
+async fn system_version<ES>(
+    State(_application): State<ApplicationInner<ES>>,
+) -> ApiResult<Json<model::ServerVersion>>
+where
+    ES: EventStore + Send + Sync + Clone + 'static,
+{
+    Ok(Json(model::ServerVersion::CURRENT))
+}
+
+// For the tests of this module, as core::testing is for those of core
+#[cfg(test)]
+pub(crate) mod testing {
+    use http_body_util::BodyExt;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        core::testing::make_application,
+        infrastructure::{persistence::EventArchive, Termination},
+    };
+
+    // The routes in this mode, under the default resource prefix
+    pub fn routes_for<ES>(application: &Arc<Application<ES>>, mode: Mode) -> Router
+    where
+        ES: EventStore + Send + Sync + Clone + 'static,
+    {
+        routing_configuration(mode, API_RESOURCE_PREFIX, BodyLimits::default())
+            .with_state(Arc::clone(application))
+    }
+
+    // Read-write routes on a started application of their own, which stops
+    // when the termination is signalled
+    pub async fn started_routes() -> (Router, Arc<Application<EventArchive>>, Termination, TempDir)
+    {
+        let (application, directory) = make_application();
+        let application = Arc::new(application);
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+        let routes = routes_for(&application, Mode::ReadWrite);
+        (routes, application, termination, directory)
+    }
+
+    pub async fn json_body(response: Response) -> serde_json::Value {
+        let body = response.into_body().collect().await.expect("a body");
+        serde_json::from_slice(&body.to_bytes()).expect("a JSON body")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{json_body, routes_for, started_routes};
+    use super::*;
+    use crate::{
+        core::EventBus,
+        infrastructure::{persistence::EventArchive, Termination},
+    };
+    use axum::body::Body;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn get_author_by_id_returns_the_author() {
+        let (routes, application, termination, _directory) = started_routes().await;
+
+        let author = domain::AuthorInfo {
+            name: "Sun Tzu".to_owned(),
+        };
+        let CommandReceipt::Created(domain::ResourceId::Author(author_id)) = application
+            .submit_command(domain::Command::AddAuthor(author.clone()))
+            .await
+        else {
+            panic!("expected an author")
+        };
+        while application
+            .issue_query(domain::query::AuthorById(author_id))
+            .await
+            .expect("a query result")
+            .is_none()
+        {
+            tokio::task::yield_now().await;
+        }
+
+        let request = axum::http::Request::get(format!(
+            "{API_RESOURCE_PREFIX}/authors/{}",
+            model::AuthorId(author_id)
+        ))
+        .body(Body::empty())
+        .expect("a request");
+        let response = routes.oneshot(request).await.expect("a response");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let payload = json_body(response).await;
+        assert_eq!(payload["id"], serde_json::to_value(author_id).unwrap());
+        assert_eq!(payload["info"]["name"], author.name);
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn an_idempotency_key_reused_for_another_body_is_unprocessable() {
+        let (routes, _, termination, _directory) = started_routes().await;
+
+        let add_author = |name: &str| {
+            axum::http::Request::post(format!("{API_RESOURCE_PREFIX}/authors"))
+                .header("Content-Type", "application/json")
+                .header("Idempotency-Key", "add-an-author")
+                .body(Body::from(serde_json::json!({ "name": name }).to_string()))
+                .expect("a request")
+        };
+
+        let response = routes
+            .clone()
+            .oneshot(add_author("Sun Tzu"))
+            .await
+            .expect("a response");
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = routes
+            .clone()
+            .oneshot(add_author("Sun Tzu"))
+            .await
+            .expect("a response");
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = routes
+            .oneshot(add_author("Lao Tzu"))
+            .await
+            .expect("a response");
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let payload = json_body(response).await;
+        assert_eq!(payload["code"], "IDEMPOTENCY_KEY_REUSED");
+        assert_eq!(payload["key"], "add-an-author");
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn authors_are_found_by_name() {
+        let (routes, application, termination, _directory) = started_routes().await;
+        let CommandReceipt::Created(domain::ResourceId::Author(author_id)) = application
+            .submit_command(domain::Command::AddAuthor(domain::AuthorInfo {
+                name: "Sun Tzu".to_owned(),
+            }))
+            .await
+        else {
+            panic!("expected an author")
+        };
+
+        let payload = loop {
+            let request = axum::http::Request::get(format!(
+                "{API_RESOURCE_PREFIX}/authors/named?name=sun%20tzu"
+            ))
+            .body(Body::empty())
+            .expect("a request");
+            let response = routes.clone().oneshot(request).await.expect("a response");
+            assert_eq!(response.status(), StatusCode::OK);
+            let payload = json_body(response).await;
+            if payload
+                .as_array()
+                .is_some_and(|authors| !authors.is_empty())
+            {
+                break payload;
+            }
+            tokio::task::yield_now().await;
+        };
+        assert_eq!(payload[0]["id"], serde_json::to_value(author_id).unwrap());
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn searches_find_words_with_the_query_in_them_when_asked() {
+        let directory = tempfile::tempdir().expect("a temporary directory");
+        let archive = EventArchive::try_new(directory.path()).expect("a valid event archive");
+        let application = Arc::new(
+            Application::builder(archive)
+                .infix_search()
+                .build()
+                .expect("an application"),
+        );
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+        application
+            .submit_command(domain::Command::AddBookWithAuthor {
+                isbn: domain::Isbn("978-1-61180-697-7".to_owned()),
+                title: "Drinking with Strangers".to_owned(),
+                author: domain::AuthorInfo {
+                    name: "Butch Walker".to_owned(),
+                },
+            })
+            .await;
+
+        let routes = routes_for(&application, Mode::ReadWrite);
+        let search = |query: &str| {
+            let routes = routes.clone();
+            let request = axum::http::Request::get(format!("{API_RESOURCE_PREFIX}/search?{query}"))
+                .body(Body::empty())
+                .expect("a request");
+            async move {
+                let response = routes.oneshot(request).await.expect("a response");
+                assert_eq!(response.status(), StatusCode::OK);
+                let results = json_body(response).await;
+                results["hits"].as_array().expect("hits").len()
+            }
+        };
+
+        while search("query=rink&infix=true").await == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(search("query=rink").await, 0);
+        assert_eq!(search("query=drinking").await, 1);
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn books_can_be_added_with_a_new_author() {
+        let (routes, application, termination, _directory) = started_routes().await;
+
+        let request = axum::http::Request::post(format!("{API_RESOURCE_PREFIX}/books"))
+            .header("Content-Type", "application/json")
+            .body(Body::from(
+                serde_json::json!({
+                    "isbn": "978-1-61180-697-7",
+                    "title": "The Art of War",
+                    "author": { "name": "Sun Tzu" },
+                })
+                .to_string(),
+            ))
+            .expect("a request");
+        let response = routes.oneshot(request).await.expect("a response");
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let payload = json_body(response).await;
+        let book_id: domain::BookId =
+            serde_json::from_value(payload["id"].clone()).expect("a book id");
+        let author_id: domain::AuthorId =
+            serde_json::from_value(payload["author_id"].clone()).expect("an author id");
+
+        let author = loop {
+            match application
+                .issue_query(domain::query::AuthorByBookId(book_id))
+                .await
+                .expect("a query result")
+            {
+                Ok(author) => break author,
+                Err(_) => tokio::task::yield_now().await,
+            }
+        };
+        assert_eq!(author.0, author_id);
+        assert_eq!(author.1.name, "Sun Tzu");
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn authors_can_be_listed_with_book_counts() {
+        let (routes, application, termination, _directory) = started_routes().await;
+
+        let CommandReceipt::CreatedWithAuthor(book_id, author_id) = application
+            .submit_command(domain::Command::AddBookWithAuthor {
+                isbn: domain::Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author: domain::AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            })
+            .await
+        else {
+            panic!("expected a book and its author")
+        };
+        while application
+            .issue_query(domain::query::BookById(book_id))
+            .await
+            .expect("a query result")
+            .is_none()
+        {
+            tokio::task::yield_now().await;
+        }
+
+        let get = |uri: String| {
+            let routes = routes.clone();
+            async move {
+                let request = axum::http::Request::get(format!("{API_RESOURCE_PREFIX}{uri}"))
+                    .body(Body::empty())
+                    .expect("a request");
+                let response = routes.oneshot(request).await.expect("a response");
+                assert_eq!(response.status(), StatusCode::OK, "{uri}");
+                json_body(response).await
+            }
+        };
+
+        let authors = get("/authors?with_counts=true".to_owned()).await;
+        assert_eq!(authors["items"][0]["book_count"], 1);
+        assert_eq!(authors["total"], 1);
+        let books = get("/books?offset=1".to_owned()).await;
+        assert_eq!(books["items"].as_array().map(Vec::len), Some(0));
+        assert_eq!(books["total"], 1);
+        let author = get(format!(
+            "/authors/{}?with_counts=true",
+            model::AuthorId(author_id)
+        ))
+        .await;
+        assert_eq!(author["book_count"], 1);
+        let author = get(format!("/authors/{}", model::AuthorId(author_id))).await;
+        assert!(author.get("book_count").is_none());
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn book_lists_come_in_message_pack_when_asked_for() {
+        let (routes, application, termination, _directory) = started_routes().await;
+
+        let CommandReceipt::CreatedWithAuthor(book_id, _) = application
+            .submit_command(domain::Command::AddBookWithAuthor {
+                isbn: domain::Isbn("978-1-61180-697-7".to_owned()),
+                title: "The Art of War".to_owned(),
+                author: domain::AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            })
+            .await
+        else {
+            panic!("expected a book and its author")
+        };
+        while application
+            .issue_query(domain::query::BookById(book_id))
+            .await
+            .expect("a query result")
+            .is_none()
+        {
+            tokio::task::yield_now().await;
+        }
+
+        let get = |accept: &'static str| {
+            let routes = routes.clone();
+            async move {
+                let request = axum::http::Request::get(format!("{API_RESOURCE_PREFIX}/books"))
+                    .header(ACCEPT, accept)
+                    .body(Body::empty())
+                    .expect("a request");
+                let response = routes.oneshot(request).await.expect("a response");
+                assert_eq!(response.status(), StatusCode::OK);
+                let content_type = response.headers()[CONTENT_TYPE].clone();
+                let body = response.into_body().collect().await.expect("a body");
+                (content_type, body.to_bytes())
+            }
+        };
+
+        let (content_type, json) = get("application/json").await;
+        assert_eq!(content_type, "application/json");
+        let json: serde_json::Value = serde_json::from_slice(&json).expect("a JSON body");
+
+        let (content_type, message_pack) = get("application/msgpack, application/json;q=0.5").await;
+        assert_eq!(content_type, Encoding::MESSAGE_PACK);
+        let mut deserializer =
+            rmp_serde::Deserializer::from_read_ref(&message_pack).with_human_readable();
+        let message_pack =
+            <serde_json::Value as serde::Deserialize>::deserialize(&mut deserializer)
+                .expect("a MessagePack body");
+        assert_eq!(message_pack, json);
+        assert_eq!(json["items"][0]["info"]["title"], "The Art of War");
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn locations_are_absolute_given_a_public_base_url() {
+        let (_, application, termination, _directory) = started_routes().await;
+
+        for (base_url, prefix) in [
+            (None, API_RESOURCE_PREFIX.to_owned()),
+            (
+                Some("https://books.example.com/".to_owned()),
+                format!("https://books.example.com{API_RESOURCE_PREFIX}"),
+            ),
+        ] {
+            let request = axum::http::Request::post(format!("{API_RESOURCE_PREFIX}/authors"))
+                .header("Content-Type", "application/json")
+                .body(Body::from(r#"{"name": "Sun Tzu"}"#))
+                .expect("a request");
+            let response = absolute_locations(
+                routing_configuration(Mode::ReadWrite, API_RESOURCE_PREFIX, BodyLimits::default()),
+                base_url,
+            )
+            .with_state(Arc::clone(&application))
+            .oneshot(request)
+            .await
+            .expect("a response");
+            assert_eq!(response.status(), StatusCode::CREATED);
+
+            let location = response.headers()[LOCATION]
+                .to_str()
+                .expect("a textual location");
+            assert!(
+                location.starts_with(&format!("{prefix}/authors/")),
+                "{location}"
+            );
+        }
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn resources_are_served_under_the_resource_prefix() {
+        let (_, application, termination, _directory) = started_routes().await;
+        let routes = routing_configuration(Mode::ReadWrite, "/library/api", BodyLimits::default())
+            .with_state(Arc::clone(&application));
+
+        let request = axum::http::Request::post("/library/api/authors")
+            .header("Content-Type", "application/json")
+            .body(Body::from(r#"{"name": "Sun Tzu"}"#))
+            .expect("a request");
+        let response = routes.clone().oneshot(request).await.expect("a response");
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()[LOCATION]
+            .to_str()
+            .expect("a textual location")
+            .to_owned();
+        assert!(location.starts_with("/library/api/authors/"), "{location}");
+
+        let get = |uri: String| {
+            let routes = routes.clone();
+            async move {
+                let request = axum::http::Request::get(uri)
+                    .body(Body::empty())
+                    .expect("a request");
+                routes.oneshot(request).await.expect("a response").status()
+            }
+        };
+        // The read model catches up with the new author in a while
+        while get(location.clone()).await != StatusCode::OK {
+            tokio::task::yield_now().await
+        }
+        assert_eq!(get("/library/api/authors".to_owned()).await, StatusCode::OK);
+        assert_eq!(
+            get(format!("{API_RESOURCE_PREFIX}/authors")).await,
+            StatusCode::NOT_FOUND
+        );
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn keywords_are_removed_from_their_target() {
+        let (routes, application, termination, _directory) = started_routes().await;
+
+        let author_id = domain::AuthorId(crate::infrastructure::UniqueId::fresh());
+        let target = domain::KeywordTarget::Author(author_id);
+        for keyword in ["war", "strategy"] {
+            application
+                .submit_command(domain::Command::AddKeyword(
+                    keyword.parse().expect("a valid keyword"),
+                    target,
+                    None,
+                ))
+                .await;
+        }
+
+        let remove = |keyword: &str| {
+            let routes = routes.clone();
+            let uri = format!(
+                "{API_RESOURCE_PREFIX}/authors/{}/keywords/{keyword}",
+                model::AuthorId(author_id)
+            );
+            async move {
+                let request = axum::http::Request::delete(uri)
+                    .body(Body::empty())
+                    .expect("a request");
+                routes.oneshot(request).await.expect("a response").status()
+            }
+        };
+        assert_eq!(
+            remove("not%20a%20keyword").await,
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        // Rejected until the write model has seen the keyword
+        while remove("war").await != StatusCode::ACCEPTED {
+            tokio::task::yield_now().await
+        }
+
+        let keywords = || application.issue_query(domain::query::TargetKeywords(target));
+        while keywords().await.expect("keywords") != vec!["strategy"] {
+            tokio::task::yield_now().await
+        }
+
+        termination.signal();
+    }
+
+    #[test]
+    fn search_hits_are_tagged_by_kind() {
+        let id = model::BookId(domain::BookId(crate::infrastructure::UniqueId::fresh()));
+        let title = serde_json::to_value(model::SearchHit::BookTitle {
+            title: "9781611806977".to_owned(),
+            id,
+        })
+        .expect("a JSON hit");
+        assert_eq!(title["kind"], "book-title");
+        assert!(matches!(
+            serde_json::from_value(title).expect("a search hit"),
+            model::SearchHit::BookTitle { .. }
+        ));
+
+        let isbn = serde_json::to_value(model::SearchHit::BookIsbn {
+            isbn: "978-1-61180-697-7".to_owned(),
+            id,
+        })
+        .expect("a JSON hit");
+        assert_eq!(isbn["kind"], "book-isbn");
+    }
+
+    #[test]
+    fn reads_recorded_before_are_ok_rather_than_rejected() {
+        for (receipt, status) in [
+            (CommandReceipt::Accepted, StatusCode::ACCEPTED),
+            (CommandReceipt::AlreadyExists, StatusCode::OK),
+        ] {
+            let Ok(response) = ApiResult::<Response>::from(receipt) else {
+                panic!("expected a response")
+            };
+            assert_eq!(response.status(), status);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejections_with_a_reason_are_a_conflict_saying_why() {
+        let receipt =
+            CommandReceipt::RejectedBecause(Rejection::DuplicateMoniker("suntzu".to_owned()));
+        let Ok(response) = ApiResult::<Response>::from(receipt) else {
+            panic!("expected a response")
+        };
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let payload = json_body(response).await;
+        assert_eq!(payload["reason"], "duplicate-moniker");
+        assert_eq!(payload["moniker"], "suntzu");
+        assert_eq!(payload["error"], "The moniker `suntzu` is already taken");
+    }
+
+    #[tokio::test]
+    async fn commands_naming_unknown_readers_or_authors_say_which() {
+        let (routes, _, termination, _directory) = started_routes().await;
+
+        let reader_id = Uuid::new_v4();
+        let book_id = Uuid::new_v4();
+        let request =
+            axum::http::Request::post(format!("{API_RESOURCE_PREFIX}/books/{book_id}/readers"))
+                .header("Content-Type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "reader_id": reader_id, "when": null }).to_string(),
+                ))
+                .expect("a request");
+        let response = routes.clone().oneshot(request).await.expect("a response");
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let payload = json_body(response).await;
+        assert_eq!(payload["code"], "UNKNOWN_READER");
+        assert_eq!(payload["reader_id"], reader_id.to_string());
+
+        let (keep, merge) = (Uuid::new_v4(), Uuid::new_v4());
+        let request = axum::http::Request::post(format!(
+            "{API_RESOURCE_PREFIX}/authors/{keep}/merge/{merge}"
+        ))
+        .body(Body::empty())
+        .expect("a request");
+        let response = routes.oneshot(request).await.expect("a response");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let payload = json_body(response).await;
+        assert_eq!(payload["code"], "UNKNOWN_AUTHOR");
+        assert_eq!(payload["author_id"], keep.to_string());
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn writes_with_a_stale_if_match_conflict() {
+        let (routes, _, termination, _directory) = started_routes().await;
+
+        let request = axum::http::Request::post(format!("{API_RESOURCE_PREFIX}/authors"))
+            .header("Content-Type", "application/json")
+            .body(Body::from(r#"{"name": "Sun Tzu"}"#))
+            .expect("a request");
+        let response = routes.clone().oneshot(request).await.expect("a response");
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let author = json_body(response).await;
+        let author_id = author["id"].as_str().expect("an id");
+
+        let request =
+            axum::http::Request::get(format!("{API_RESOURCE_PREFIX}/authors/{author_id}/state"))
+                .body(Body::empty())
+                .expect("a request");
+        let response = routes.clone().oneshot(request).await.expect("a response");
+        assert_eq!(response.status(), StatusCode::OK);
+        let version = response.headers()["ETag"].clone();
+
+        let add_keyword = |keyword: &str| {
+            axum::http::Request::post(format!(
+                "{API_RESOURCE_PREFIX}/authors/{author_id}/keywords"
+            ))
+            .header("Content-Type", "application/json")
+            .header("If-Match", version.clone())
+            .body(Body::from(format!("\"{keyword}\"")))
+            .expect("a request")
+        };
+        let response = routes
+            .clone()
+            .oneshot(add_keyword("strategy"))
+            .await
+            .expect("a response");
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let response = routes
+            .oneshot(add_keyword("warfare"))
+            .await
+            .expect("a response");
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let payload = json_body(response).await;
+        assert_eq!(payload["code"], "VERSION_CONFLICT");
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn failed_writes_are_internal_errors() {
+        let response =
+            command_response(CommandReceipt::Failed("disk full".to_owned()), &[]).into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let payload = json_body(response).await;
+        assert_eq!(payload["code"], "INTERNAL");
+        assert_eq!(payload["error"], "disk full");
+    }
+
+    #[test]
+    fn error_codes_keep_their_names() {
+        use model::ErrorCode;
+
+        for (code, name) in [
+            (ErrorCode::NotFound, "NOT_FOUND"),
+            (ErrorCode::MalformedRequest, "MALFORMED_REQUEST"),
+            (ErrorCode::InvalidKeyword, "INVALID_KEYWORD"),
+            (ErrorCode::TooManyKeywords, "TOO_MANY_KEYWORDS"),
+            (ErrorCode::EmptyField, "EMPTY_FIELD"),
+            (ErrorCode::UnknownAuthor, "UNKNOWN_AUTHOR"),
+            (ErrorCode::UnknownBook, "UNKNOWN_BOOK"),
+            (ErrorCode::UnknownReader, "UNKNOWN_READER"),
+            (ErrorCode::DuplicateMoniker, "DUPLICATE_MONIKER"),
+            (ErrorCode::DuplicateKeyword, "DUPLICATE_KEYWORD"),
+            (ErrorCode::ProgressOutOfRange, "PROGRESS_OUT_OF_RANGE"),
+            (ErrorCode::VersionConflict, "VERSION_CONFLICT"),
+            (ErrorCode::IdempotencyKeyReused, "IDEMPOTENCY_KEY_REUSED"),
+            (ErrorCode::MissingKeyword, "MISSING_KEYWORD"),
+            (ErrorCode::UnknownKeyword, "UNKNOWN_KEYWORD"),
+            (ErrorCode::SameKeyword, "SAME_KEYWORD"),
+            (ErrorCode::SameAuthor, "SAME_AUTHOR"),
+            (ErrorCode::ReadOnly, "READ_ONLY"),
+            (ErrorCode::Unavailable, "UNAVAILABLE"),
+            (ErrorCode::Internal, "INTERNAL"),
+        ] {
+            assert_eq!(serde_json::to_value(code).unwrap(), name);
+        }
+    }
+
+    #[tokio::test]
+    async fn root_and_version_describe_the_server() {
+        let (_, application, termination, _directory) = started_routes().await;
+
+        for uri in ["/", "/version"] {
+            let request = axum::http::Request::get(uri)
+                .body(Body::empty())
+                .expect("a request");
+            let response = routes_for(&application, Mode::ReadOnly)
+                .oneshot(request)
+                .await
+                .expect("a response");
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+            let version = json_body(response).await;
+
+            assert_eq!(version["version"], env!("CARGO_PKG_VERSION"), "{uri}");
+            assert_eq!(
+                version["store_format_version"],
+                crate::infrastructure::STORE_FORMAT_VERSION,
+                "{uri}"
+            );
+            assert!(version["git_sha"].is_string(), "{uri}");
+        }
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_too_large() {
+        let (_, application, termination, _directory) = started_routes().await;
+        // Batch-get above axum's default limit of 2 MiB
+        let body_limits = BodyLimits {
+            commands: 64,
+            batch_get: 3 * 1024 * 1024,
+        };
+        let routes = routing_configuration(Mode::ReadWrite, API_RESOURCE_PREFIX, body_limits)
+            .with_state(Arc::clone(&application));
+
+        let ids = |count| {
+            (0..count)
+                .map(|_| format!("\"{}\"", Uuid::new_v4()))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        for (uri, body, status) in [
+            (
+                "/authors",
+                r#"{"name": "Sun Tzu"}"#.to_owned(),
+                StatusCode::CREATED,
+            ),
+            (
+                "/authors",
+                format!(r#"{{"name": "{}"}}"#, "Sun Tzu ".repeat(10)),
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+            ("/books/batch-get", format!("[{}]", ids(20)), StatusCode::OK),
+            // About 2.4 MiB, then about 3.2 MiB
+            (
+                "/books/batch-get",
+                format!("[{}]", ids(64_000)),
+                StatusCode::OK,
+            ),
+            (
+                "/books/batch-get",
+                format!("[{}]", ids(85_000)),
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+        ] {
+            let request = axum::http::Request::post(format!("{API_RESOURCE_PREFIX}{uri}"))
+                .header("Content-Type", "application/json")
+                .body(Body::from(body))
+                .expect("a request");
+            let response = routes.clone().oneshot(request).await.expect("a response");
+            assert_eq!(response.status(), status, "{uri}");
+        }
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn api_serves_as_a_router_without_a_socket() {
+        let directory = tempfile::tempdir().expect("a temporary directory");
+        let archive = EventArchive::try_new(directory.path()).expect("a valid event archive");
+        let router = Api::new(Application::new(EventBus::new(archive)))
+            .with_resource_prefix("/library/api/")
+            .expect("a valid resource prefix")
+            .into_router();
+
+        for (uri, status) in [
+            ("/version", StatusCode::OK),
+            ("/library/api/authors", StatusCode::OK),
+            ("/api/v1/authors", StatusCode::NOT_FOUND),
+        ] {
+            let request = axum::http::Request::get(uri)
+                .body(Body::empty())
+                .expect("a request");
+            let response = router.clone().oneshot(request).await.expect("a response");
+            assert_eq!(response.status(), status, "{uri}");
+        }
+    }
+
+    #[test]
+    fn resource_prefixes_that_cannot_be_mounted_are_refused() {
+        let directory = tempfile::tempdir().expect("a temporary directory");
+        let archive = EventArchive::try_new(directory.path()).expect("a valid event archive");
+        let application = Arc::new(Application::new(EventBus::new(archive)));
+        let api = || Api {
+            application: Arc::clone(&application),
+            mode: Mode::ReadWrite,
+            public_base_url: None,
+            resource_prefix: API_RESOURCE_PREFIX.to_owned(),
+            body_limits: BodyLimits::default(),
+        };
+
+        for prefix in ["", "/", "//", "library/api", "/books/:id", "/books/*rest"] {
+            assert!(
+                matches!(
+                    api().with_resource_prefix(prefix),
+                    Err(Error::InvalidResourcePrefix(..))
+                ),
+                "{prefix}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn root_reports_whether_the_read_model_is_ready() {
+        let directory = tempfile::tempdir().expect("a temporary directory");
+        let mut archive = EventArchive::try_new(directory.path()).expect("a valid event archive");
+        archive
+            .persist(domain::Event::AuthorAdded(
+                domain::AuthorId(crate::infrastructure::UniqueId::fresh()),
+                domain::AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ))
+            .await
+            .expect("a journaled author");
+        let application = Arc::new(Application::new(EventBus::new(archive)));
+        let read_model = || async {
+            let request = axum::http::Request::get("/")
+                .body(Body::empty())
+                .expect("a request");
+            let response = routes_for(&application, Mode::ReadOnly)
+                .oneshot(request)
+                .await
+                .expect("a response");
+            let status = json_body(response).await;
+            status["read_model"].clone()
+        };
+        assert_eq!(read_model().await["ready"], false);
+
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+        // The replayed author reaches the read model in a while
+        let ready = loop {
+            match read_model().await {
+                status if status["ready"] == true => break status,
+                _ => tokio::task::yield_now().await,
+            }
+        };
+        assert_eq!(ready["applied_events"], 1);
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn reader_stats_have_rfc3339_times_and_unknown_readers_none() {
+        let directory = tempfile::tempdir().expect("a temporary directory");
+        let mut archive = EventArchive::try_new(directory.path()).expect("a valid event archive");
+        let reader_id = domain::ReaderId(crate::infrastructure::UniqueId::fresh());
+        let book_id = domain::BookId(crate::infrastructure::UniqueId::fresh());
+        for event in [
+            domain::Event::ReaderAdded(
+                reader_id,
+                domain::ReaderInfo {
+                    name: "Reader".to_owned(),
+                    unique_moniker: "reader".to_owned(),
+                },
+            ),
+            domain::Event::BookAdded(
+                book_id,
+                domain::BookInfo {
+                    isbn: domain::Isbn("978-1-61180-697-7".to_owned()),
+                    title: "The Art of War".to_owned(),
+                    author: domain::AuthorId(crate::infrastructure::UniqueId::fresh()),
+                },
+            ),
+            domain::Event::BookRead(
+                reader_id,
+                domain::BookReadInfo {
+                    reader_id,
+                    book_id,
+                    when: Some(time::macros::datetime!(2024-01-01 0:00 UTC)),
+                },
+            ),
+        ] {
+            archive.persist(event).await.expect("a journaled event");
+        }
+        let application = Arc::new(Application::new(EventBus::new(archive)));
+        let termination = Termination::default();
+        application
+            .start(&termination)
+            .await
+            .expect("a started application");
+
+        let routes = routes_for(&application, Mode::ReadOnly);
+        let stats = |reader_id: Uuid| {
+            let request = axum::http::Request::get(format!(
+                "{API_RESOURCE_PREFIX}/readers/{reader_id}/stats"
+            ))
+            .body(Body::empty())
+            .expect("a request");
+            routes.clone().oneshot(request)
+        };
+
+        let response = loop {
+            let response = stats(reader_id.into()).await.expect("a response");
+            if response.status() != StatusCode::NOT_FOUND {
+                break response;
+            }
+            tokio::task::yield_now().await;
+        };
+        assert_eq!(response.status(), StatusCode::OK);
+        let payload = json_body(response).await;
+        assert_eq!(payload["books_read"], 1);
+        assert_eq!(payload["first_read"], "2024-01-01T00:00:00Z");
+        assert_eq!(payload["last_read"], "2024-01-01T00:00:00Z");
+
+        let unknown = stats(Uuid::new_v4()).await.expect("a response");
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn error_bodies_carry_a_code() {
+        let (routes, _, termination, _directory) = started_routes().await;
+        let id = Uuid::new_v4();
+
+        for (method, uri, body, status, code) in [
+            (
+                "GET",
+                format!("{API_RESOURCE_PREFIX}/books/{id}"),
+                "",
+                StatusCode::NOT_FOUND,
+                "NOT_FOUND",
+            ),
+            (
+                "POST",
+                format!("{API_RESOURCE_PREFIX}/books/{id}/keywords"),
+                r#""not a keyword""#,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "INVALID_KEYWORD",
+            ),
+            (
+                "POST",
+                format!("{API_RESOURCE_PREFIX}/books"),
+                r#"{"title": 1}"#,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "MALFORMED_REQUEST",
+            ),
+            (
+                "POST",
+                format!("{API_RESOURCE_PREFIX}/books"),
+                &format!(r#"{{"isbn": "9780306406157", "title": "Title", "author": "{id}"}}"#),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "UNKNOWN_AUTHOR",
+            ),
+            (
+                "DELETE",
+                format!("{API_RESOURCE_PREFIX}/books/{id}/keywords/war"),
+                "",
+                StatusCode::NOT_FOUND,
+                "MISSING_KEYWORD",
+            ),
+            (
+                "POST",
+                format!("{API_RESOURCE_PREFIX}/keywords/war/rename"),
+                r#""classic""#,
+                StatusCode::NOT_FOUND,
+                "UNKNOWN_KEYWORD",
+            ),
+            (
+                "POST",
+                format!("{API_RESOURCE_PREFIX}/keywords/war/rename"),
+                r#""war""#,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "SAME_KEYWORD",
+            ),
+            (
+                "POST",
+                format!("{API_RESOURCE_PREFIX}/authors/{id}/merge/{id}"),
+                "",
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "SAME_AUTHOR",
+            ),
+        ] {
+            let request = axum::http::Request::builder()
+                .method(method)
+                .uri(&uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_owned()))
+                .expect("a request");
+            let response = routes.clone().oneshot(request).await.expect("a response");
+            assert_eq!(response.status(), status, "{method} {uri}");
+
+            let payload = json_body(response).await;
+            assert_eq!(payload["code"], code, "{method} {uri}");
+            assert!(payload["error"].is_string(), "{method} {uri}");
+        }
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn malformed_ids_are_a_bad_request_naming_the_segment() {
+        let (routes, _, termination, _directory) = started_routes().await;
+        let id = Uuid::new_v4();
+
+        for (method, uri, segment) in [
+            ("GET", "/books/not-a-uuid".to_owned(), "id"),
+            ("GET", "/books/not-a-uuid/state".to_owned(), "id"),
+            ("GET", "/books/not-a-uuid/keywords".to_owned(), "id"),
+            ("POST", "/books/not-a-uuid/keywords".to_owned(), "id"),
+            ("POST", "/books/not-a-uuid/readers".to_owned(), "id"),
+            ("GET", "/books/not-a-uuid/author".to_owned(), "id"),
+            ("GET", "/authors/not-a-uuid".to_owned(), "id"),
+            ("GET", "/authors/not-a-uuid/state".to_owned(), "id"),
+            ("GET", "/authors/not-a-uuid/keywords".to_owned(), "id"),
+            ("POST", "/authors/not-a-uuid/keywords".to_owned(), "id"),
+            ("GET", "/authors/not-a-uuid/books".to_owned(), "id"),
+            ("POST", format!("/authors/not-a-uuid/merge/{id}"), "keep"),
+            ("POST", format!("/authors/{id}/merge/not-a-uuid"), "merge"),
+            ("GET", "/readers/not-a-uuid".to_owned(), "id"),
+            ("GET", "/readers/not-a-uuid/books".to_owned(), "id"),
+        ] {
+            let request = axum::http::Request::builder()
+                .method(method)
+                .uri(format!("{API_RESOURCE_PREFIX}{uri}"))
+                .body(Body::empty())
+                .expect("a request");
+            let response = routes.clone().oneshot(request).await.expect("a response");
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{method} {uri}");
+
+            let payload = json_body(response).await;
+            assert_eq!(
+                payload["error"],
+                format!("Malformed {segment} `not-a-uuid`, expected a UUID"),
+                "{method} {uri}"
+            );
+        }
+
+        let request = axum::http::Request::post("/admin/books/not-a-uuid/correct")
+            .body(Body::empty())
+            .expect("a request");
+        let response = routes.oneshot(request).await.expect("a response");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn flush_answers_once_buffered_writes_are_synced() {
+        let (routes, application, termination, _directory) = started_routes().await;
+        application
+            .set_durability(crate::infrastructure::Durability::Buffered)
+            .await
+            .expect("buffered writes");
+        application
+            .submit_command(domain::Command::AddAuthor(domain::AuthorInfo {
+                name: "Sun Tzu".to_owned(),
+            }))
+            .await;
+
+        let request = axum::http::Request::post("/admin/flush")
+            .body(Body::empty())
+            .expect("a request");
+        let response = routes.oneshot(request).await.expect("a response");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        termination.signal();
+    }
+
+    #[tokio::test]
+    async fn read_only_api_leaves_out_commands() {
+        let (routes, application, termination, _directory) = started_routes().await;
+        let request = |method: &str, uri: &str| {
+            axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(r#"{"name": "Sun Tzu"}"#))
+                .expect("a request")
+        };
+        let id = Uuid::new_v4();
+
+        for (method, uri, status) in [
+            (
+                "GET",
+                format!("{API_RESOURCE_PREFIX}/authors"),
+                StatusCode::OK,
+            ),
+            (
+                "POST",
+                format!("{API_RESOURCE_PREFIX}/books/batch-get"),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                "POST",
+                format!("{API_RESOURCE_PREFIX}/authors"),
+                StatusCode::METHOD_NOT_ALLOWED,
+            ),
+            (
+                "POST",
+                format!("{API_RESOURCE_PREFIX}/books/{id}/keywords"),
+                StatusCode::METHOD_NOT_ALLOWED,
+            ),
+            (
+                "POST",
+                format!("{API_RESOURCE_PREFIX}/authors/{id}/merge/{id}"),
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                "POST",
+                format!("/admin/books/{id}/correct"),
+                StatusCode::NOT_FOUND,
+            ),
+        ] {
+            let response = routes_for(&application, Mode::ReadOnly)
+                .oneshot(request(method, &uri))
+                .await
+                .expect("a response");
+            assert_eq!(response.status(), status, "{method} {uri}");
+        }
+
+        let response = routes
+            .clone()
+            .oneshot(request("POST", &format!("{API_RESOURCE_PREFIX}/authors")))
+            .await
+            .expect("a response");
+        assert!(response.status().is_success());
+
+        termination.signal();
+    }
 }
@@ -1,8 +1,12 @@
+use axum::http::StatusCode;
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{collections::BTreeMap, fmt, ops::Range};
 use time::OffsetDateTime;
 
-use crate::core::model as domain;
+use crate::{
+    core::{self, model as domain},
+    infrastructure,
+};
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -40,20 +44,76 @@ impl From<domain::AuthorId> for AuthorId {
     }
 }
 
+// When a resource was added, and when it last changed. Resources that are
+// not from the read model go without.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Timestamps {
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+impl From<domain::query::Timestamps> for Timestamps {
+    fn from(
+        domain::query::Timestamps {
+            created_at,
+            updated_at,
+        }: domain::query::Timestamps,
+    ) -> Self {
+        Self {
+            created_at,
+            updated_at,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Author {
     id: domain::AuthorId,
     info: domain::AuthorInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    book_count: Option<usize>,
+    #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
+    timestamps: Option<Timestamps>,
+}
+
+impl Author {
+    pub fn with_book_count(
+        ((author, book_count), timestamps): (
+            (domain::Author, usize),
+            Option<domain::query::Timestamps>,
+        ),
+    ) -> Self {
+        Self {
+            book_count: Some(book_count),
+            ..(author, timestamps).into()
+        }
+    }
 }
 
 impl From<domain::Author> for Author {
     fn from(domain::Author(id, info): domain::Author) -> Self {
-        Self { id, info }
+        Self {
+            id,
+            info,
+            book_count: None,
+            timestamps: None,
+        }
+    }
+}
+
+impl From<(domain::Author, Option<domain::query::Timestamps>)> for Author {
+    fn from((author, timestamps): (domain::Author, Option<domain::query::Timestamps>)) -> Self {
+        Self {
+            timestamps: timestamps.map(Timestamps::from),
+            ..author.into()
+        }
     }
 }
 
 impl From<Author> for domain::Author {
-    fn from(Author { id, info }: Author) -> Self {
+    fn from(Author { id, info, .. }: Author) -> Self {
         Self(id, info)
     }
 }
@@ -78,14 +138,134 @@ impl From<domain::BookId> for BookId {
 pub struct Book {
     id: domain::BookId,
     info: domain::BookInfo,
+    #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
+    timestamps: Option<Timestamps>,
+}
+
+// A Book, with how many times the reader has read it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadBook {
+    #[serde(flatten)]
+    book: Book,
+    times_read: usize,
+}
+
+impl From<domain::query::ReadBook> for ReadBook {
+    fn from(domain::query::ReadBook { book, times_read }: domain::query::ReadBook) -> Self {
+        Self {
+            book: book.into(),
+            times_read,
+        }
+    }
+}
+
+// A Book, with how far into it the reader is
+#[derive(Debug, Serialize)]
+pub struct BookBeingRead {
+    #[serde(flatten)]
+    book: Book,
+    percent: u8,
+}
+
+impl From<domain::query::BookBeingRead> for BookBeingRead {
+    fn from(domain::query::BookBeingRead { book, percent }: domain::query::BookBeingRead) -> Self {
+        Self {
+            book: book.into(),
+            percent,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReadingProgress {
+    pub book_id: domain::BookId,
+    pub percent: u8,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReaderStats {
+    books_read: usize,
+    distinct_authors: usize,
+    #[serde(with = "time::serde::rfc3339::option")]
+    first_read: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    last_read: Option<OffsetDateTime>,
+}
+
+impl From<domain::query::ReaderStatistics> for ReaderStats {
+    fn from(
+        domain::query::ReaderStatistics {
+            books_read,
+            distinct_authors,
+            first_read,
+            last_read,
+        }: domain::query::ReaderStatistics,
+    ) -> Self {
+        Self {
+            books_read,
+            distinct_authors,
+            first_read,
+            last_read,
+        }
+    }
+}
+
+// The author is either an existing one, by id, or one to create along with
+// the book
+#[derive(Debug, Deserialize)]
+pub struct NewBook {
+    pub isbn: domain::Isbn,
+    pub title: String,
+    pub author: AuthorRef,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum AuthorRef {
+    Existing(domain::AuthorId),
+    New(domain::AuthorInfo),
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedBook {
+    pub id: domain::BookId,
+    pub author_id: domain::AuthorId,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct NewBook(pub domain::BookInfo);
+pub struct CorrectedBook(pub domain::BookInfo);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BookBatch {
+    found: Vec<Book>,
+    missing: Vec<BookId>,
+}
+
+impl From<domain::query::BooksFound> for BookBatch {
+    fn from(domain::query::BooksFound { found, missing }: domain::query::BooksFound) -> Self {
+        Self {
+            found: found.into_iter().map(|b| b.into()).collect(),
+            missing: missing.into_iter().map(|id| id.into()).collect(),
+        }
+    }
+}
 
 impl From<domain::Book> for Book {
     fn from(domain::Book(id, info): domain::Book) -> Self {
-        Self { id, info }
+        Self {
+            id,
+            info,
+            timestamps: None,
+        }
+    }
+}
+
+impl From<(domain::Book, Option<domain::query::Timestamps>)> for Book {
+    fn from((book, timestamps): (domain::Book, Option<domain::query::Timestamps>)) -> Self {
+        Self {
+            timestamps: timestamps.map(Timestamps::from),
+            ..book.into()
+        }
     }
 }
 
@@ -96,6 +276,10 @@ pub struct NewAuthor(pub domain::AuthorInfo);
 pub struct Reader {
     id: domain::ReaderId,
     info: domain::ReaderInfo,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    books_read: Option<usize>,
+    #[serde(flatten, default, skip_serializing_if = "Option::is_none")]
+    timestamps: Option<Timestamps>,
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
@@ -119,7 +303,35 @@ impl fmt::Display for ReaderId {
 
 impl From<domain::Reader> for Reader {
     fn from(domain::Reader(id, info): domain::Reader) -> Self {
-        Self { id, info }
+        Self {
+            id,
+            info,
+            books_read: None,
+            timestamps: None,
+        }
+    }
+}
+
+impl Reader {
+    pub fn with_books_read(
+        ((reader, books_read), timestamps): (
+            (domain::Reader, usize),
+            Option<domain::query::Timestamps>,
+        ),
+    ) -> Self {
+        Self {
+            books_read: Some(books_read),
+            ..(reader, timestamps).into()
+        }
+    }
+}
+
+impl From<(domain::Reader, Option<domain::query::Timestamps>)> for Reader {
+    fn from((reader, timestamps): (domain::Reader, Option<domain::query::Timestamps>)) -> Self {
+        Self {
+            timestamps: timestamps.map(Timestamps::from),
+            ..reader.into()
+        }
     }
 }
 
@@ -129,9 +341,170 @@ pub struct NewBookRead {
     pub when: Option<OffsetDateTime>,
 }
 
+// Only books by this author, when given
+// The whole name, not a part of it
+#[derive(Deserialize)]
+pub struct AuthorName {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct UnreadBooksQuery {
+    pub author: Option<AuthorId>,
+}
+
+// keywords is a comma separated list
+#[derive(Deserialize)]
+pub struct KeywordsQuery {
+    pub keywords: String,
+    #[serde(default)]
+    pub mode: KeywordMatch,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeywordMatch {
+    #[default]
+    Any,
+    All,
+}
+
+impl From<KeywordMatch> for domain::query::KeywordMatch {
+    fn from(value: KeywordMatch) -> Self {
+        match value {
+            KeywordMatch::Any => Self::Any,
+            KeywordMatch::All => Self::All,
+        }
+    }
+}
+
+// A KeywordTarget, along with where to find it
+#[derive(Debug, Serialize)]
+pub struct TargetResource {
+    uri: String,
+    #[serde(flatten)]
+    target: KeywordTarget,
+}
+
+impl TargetResource {
+    pub fn new(target: domain::KeywordTarget, resource_prefix: &str) -> Self {
+        let target = KeywordTarget::from(target);
+        let resource = match target {
+            KeywordTarget::Book { book_id } => Resource::Book(book_id),
+            KeywordTarget::Author { author_id } => Resource::Author(author_id),
+        };
+        Self {
+            uri: resource.uri(resource_prefix),
+            target,
+        }
+    }
+}
+
+// Listings of every book or author come a page at a time
+#[derive(Deserialize)]
+pub struct Paging {
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+impl Paging {
+    const DEFAULT_LIMIT: usize = 50;
+
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(Self::DEFAULT_LIMIT)
+    }
+}
+
+// Total is how many there are in all, not just on this page
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+}
+
+impl<T> Page<T> {
+    pub fn new<U>(
+        domain::query::Page { items, total }: domain::query::Page<U>,
+        item: impl FnMut(U) -> T,
+    ) -> Self {
+        Self {
+            items: items.into_iter().map(item).collect(),
+            total,
+        }
+    }
+}
+
+// Either end may be left out. Times are RFC 3339, in which a + has to be
+// sent as %2B.
+#[derive(Deserialize)]
+pub struct AddedBetween {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub added_after: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub added_before: Option<OffsetDateTime>,
+}
+
+// Listings that can say how much each entry is used
+#[derive(Deserialize)]
+pub struct WithCounts {
+    #[serde(default)]
+    pub with_counts: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KeywordCount {
+    pub keyword: String,
+    pub count: usize,
+}
+
+// Either just the keywords, or the keywords with how often they are used
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Keywords {
+    Names(Vec<String>),
+    Counted(Vec<KeywordCount>),
+}
+
+#[derive(Deserialize)]
+pub struct RecentLimit {
+    pub limit: Option<usize>,
+}
+
+impl RecentLimit {
+    const DEFAULT: usize = 10;
+
+    pub fn get(&self) -> usize {
+        self.limit.unwrap_or(Self::DEFAULT)
+    }
+}
+
+// Everything but the query is optional, and by default a search looks for
+// exact matches in every field and returns every hit.
 #[derive(Deserialize)]
 pub struct SearchTerm {
     pub query: String,
+    #[serde(default)]
+    pub highlight: bool,
+    // A comma separated list of title, isbn and author
+    pub fields: Option<String>,
+    #[serde(default)]
+    pub fuzzy: bool,
+    // Also finds words with the query in them, when the server indexes for it
+    #[serde(default)]
+    pub infix: bool,
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub hits: Vec<SearchResultItem>,
+    // More matched than the server ranks; narrow the search to see the rest
+    pub truncated: bool,
+    // Nearby terms to try instead; only offered when there are no hits
+    pub suggestions: Vec<String>,
 }
 
 // This should be in the core model, but then I would
@@ -142,19 +515,42 @@ pub struct SearchResultItem {
     // This can use a peer to text_model::Projection instead
     // because it can also become a Resource.
     hit: SearchHit,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    highlights: Option<Vec<Highlight>>,
 }
 
 impl SearchResultItem {
-    pub fn from_search_hit(hit: SearchHit, resource_prefix: &str) -> Self {
+    pub fn from_search_hit(hit: text_search::SearchHit, resource_prefix: &str) -> Self {
+        let highlights = hit
+            .highlights
+            .clone()
+            .map(|ranges| ranges.into_iter().map(Highlight::from).collect());
+        let hit = SearchHit::from(hit);
+
         Self {
             uri: hit.referenced_resource().uri(resource_prefix),
             hit,
+            highlights,
         }
     }
 }
 
+// Character offsets into the hit's title, isbn or name; end is exclusive
 #[derive(Debug, Serialize, Deserialize)]
-#[serde(untagged)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl From<Range<usize>> for Highlight {
+    fn from(Range { start, end }: Range<usize>) -> Self {
+        Self { start, end }
+    }
+}
+
+// Tagged by kind, since title and isbn hits are otherwise alike
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
 pub enum SearchHit {
     BookTitle { title: String, id: BookId },
     BookIsbn { isbn: String, id: BookId },
@@ -173,7 +569,7 @@ impl SearchHit {
 
 use domain::query::text as text_search;
 impl From<text_search::SearchHit> for SearchHit {
-    fn from(text_search::SearchHit { target, source }: text_search::SearchHit) -> Self {
+    fn from(text_search::SearchHit { target, source, .. }: text_search::SearchHit) -> Self {
         match target {
             text_search::Projection::Books(text_search::BookField::Isbn(id)) => Self::BookIsbn {
                 isbn: source,
@@ -221,3 +617,337 @@ impl From<text_search::Projection> for Resource {
         }
     }
 }
+
+// What went wrong, for clients to branch on. These are part of the API:
+// a code, once added, keeps its name and meaning, whatever the message next
+// to it says.
+//
+// NOT_FOUND           There is no such resource
+// MALFORMED_REQUEST   A path segment, header, query or body did not parse
+// INVALID_KEYWORD     A keyword name that is not letters, `_` and `-`
+// TOO_MANY_KEYWORDS   The book or author has as many keywords as it may
+// EMPTY_FIELD         A name or title that is empty or only whitespace
+// UNKNOWN_AUTHOR      A book was given, or a merge named, an author that
+//                     does not exist
+// UNKNOWN_BOOK        A command names a book that does not exist
+// UNKNOWN_READER      A command names a reader that does not exist
+// DUPLICATE_MONIKER   A reader was given a moniker that is already taken
+// DUPLICATE_KEYWORD   The book or author has that keyword already
+// PROGRESS_OUT_OF_RANGE
+//                     Reading progress of more than 100 percent
+// VERSION_CONFLICT    The aggregate changed since the version in If-Match
+// IDEMPOTENCY_KEY_REUSED
+//                     The Idempotency-Key came with another request before
+// MISSING_KEYWORD     The book or author does not have that keyword
+// UNKNOWN_KEYWORD     No book or author has the keyword to rename
+// SAME_KEYWORD        A keyword was renamed to itself
+// SAME_AUTHOR         An author was merged with itself
+// READ_ONLY           The server only reads the store, and takes no commands
+// UNAVAILABLE         The read model did not catch up in time; try again
+// INTERNAL            Anything else, on the server's part
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    NotFound,
+    MalformedRequest,
+    InvalidKeyword,
+    TooManyKeywords,
+    EmptyField,
+    UnknownAuthor,
+    UnknownBook,
+    UnknownReader,
+    DuplicateMoniker,
+    DuplicateKeyword,
+    ProgressOutOfRange,
+    VersionConflict,
+    IdempotencyKeyReused,
+    MissingKeyword,
+    UnknownKeyword,
+    SameKeyword,
+    SameAuthor,
+    ReadOnly,
+    Unavailable,
+    Internal,
+}
+
+impl ErrorCode {
+    // For errors that are known only by their status
+    pub fn of_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::NOT_FOUND => Self::NotFound,
+            StatusCode::BAD_REQUEST => Self::MalformedRequest,
+            StatusCode::SERVICE_UNAVAILABLE => Self::Unavailable,
+            _ => Self::Internal,
+        }
+    }
+}
+
+// The body of every error response
+#[derive(Debug, Serialize)]
+pub struct ErrorBody {
+    pub code: ErrorCode,
+    pub error: String,
+}
+
+// The body of an error response for a command that was rejected, with the
+// reason in a form that clients can act on
+#[derive(Debug, Serialize)]
+pub struct RejectionBody {
+    pub code: ErrorCode,
+    pub error: String,
+    #[serde(flatten)]
+    pub reason: RejectionReason,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum RejectionReason {
+    DuplicateMoniker { moniker: String },
+    UnknownAuthor { author_id: AuthorId },
+    UnknownBook { book_id: BookId },
+    UnknownReader { reader_id: ReaderId },
+    TooManyKeywords { max: usize },
+    EmptyField { field: &'static str },
+    IdempotencyKeyReused { key: String },
+    DuplicateKeyword { keyword: String },
+    ProgressOutOfRange { percent: u8 },
+    MissingKeyword { keyword: String },
+    UnknownKeyword { keyword: String },
+    SameKeyword { keyword: String },
+    SameAuthor { author_id: AuthorId },
+    ReadOnly,
+}
+
+impl From<core::Rejection> for RejectionBody {
+    fn from(value: core::Rejection) -> Self {
+        match value {
+            core::Rejection::DuplicateMoniker(moniker) => Self {
+                code: ErrorCode::DuplicateMoniker,
+                error: format!("The moniker `{moniker}` is already taken"),
+                reason: RejectionReason::DuplicateMoniker { moniker },
+            },
+            core::Rejection::UnknownAuthor(author_id) => Self {
+                code: ErrorCode::UnknownAuthor,
+                error: format!("There is no author {}", AuthorId(author_id)),
+                reason: RejectionReason::UnknownAuthor {
+                    author_id: AuthorId(author_id),
+                },
+            },
+            core::Rejection::UnknownBook(book_id) => Self {
+                code: ErrorCode::UnknownBook,
+                error: format!("There is no book {}", BookId(book_id)),
+                reason: RejectionReason::UnknownBook {
+                    book_id: BookId(book_id),
+                },
+            },
+            core::Rejection::UnknownReader(reader_id) => Self {
+                code: ErrorCode::UnknownReader,
+                error: format!("There is no reader {}", ReaderId(reader_id)),
+                reason: RejectionReason::UnknownReader {
+                    reader_id: ReaderId(reader_id),
+                },
+            },
+            core::Rejection::TooManyKeywords(max) => Self {
+                code: ErrorCode::TooManyKeywords,
+                error: format!("No more than {max} keywords may be added"),
+                reason: RejectionReason::TooManyKeywords { max },
+            },
+            core::Rejection::EmptyField(field) => Self {
+                code: ErrorCode::EmptyField,
+                error: format!("The {field} must not be empty"),
+                reason: RejectionReason::EmptyField { field },
+            },
+            core::Rejection::IdempotencyKeyReused(key) => Self {
+                code: ErrorCode::IdempotencyKeyReused,
+                error: format!("The key `{key}` was used for another request"),
+                reason: RejectionReason::IdempotencyKeyReused { key },
+            },
+            core::Rejection::DuplicateKeyword(keyword) => Self {
+                code: ErrorCode::DuplicateKeyword,
+                error: format!("The keyword `{keyword}` is there already"),
+                reason: RejectionReason::DuplicateKeyword { keyword },
+            },
+            core::Rejection::ProgressOutOfRange(percent) => Self {
+                code: ErrorCode::ProgressOutOfRange,
+                error: format!("Progress is in percent, {percent} is more than 100"),
+                reason: RejectionReason::ProgressOutOfRange { percent },
+            },
+            core::Rejection::MissingKeyword(keyword) => Self {
+                code: ErrorCode::MissingKeyword,
+                error: format!("The keyword `{keyword}` is not there"),
+                reason: RejectionReason::MissingKeyword { keyword },
+            },
+            core::Rejection::UnknownKeyword(keyword) => Self {
+                code: ErrorCode::UnknownKeyword,
+                error: format!("Nothing has the keyword `{keyword}`"),
+                reason: RejectionReason::UnknownKeyword { keyword },
+            },
+            core::Rejection::SameKeyword(keyword) => Self {
+                code: ErrorCode::SameKeyword,
+                error: format!("The keyword `{keyword}` cannot be renamed to itself"),
+                reason: RejectionReason::SameKeyword { keyword },
+            },
+            core::Rejection::SameAuthor(author_id) => Self {
+                code: ErrorCode::SameAuthor,
+                error: format!(
+                    "The author {} cannot be merged with itself",
+                    AuthorId(author_id)
+                ),
+                reason: RejectionReason::SameAuthor {
+                    author_id: AuthorId(author_id),
+                },
+            },
+            core::Rejection::ReadOnly => Self {
+                code: ErrorCode::ReadOnly,
+                error: "This server takes no commands".to_owned(),
+                reason: RejectionReason::ReadOnly,
+            },
+        }
+    }
+}
+
+// What is running, so that clients can tell whether they fit
+#[derive(Debug, Serialize)]
+pub struct ServerVersion {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub git_sha: &'static str,
+    pub store_format_version: u32,
+}
+
+impl ServerVersion {
+    pub const CURRENT: Self = Self {
+        name: "blister",
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("BLISTER_GIT_SHA"),
+        store_format_version: infrastructure::STORE_FORMAT_VERSION,
+    };
+}
+
+// What `/` answers: the version, and whether the read model is ready,
+// which it is not while the journal is still being replayed into it
+#[derive(Debug, Serialize)]
+pub struct SystemStatus {
+    #[serde(flatten)]
+    pub version: ServerVersion,
+    pub read_model: Option<ReadModelStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadModelStatus {
+    pub ready: bool,
+    pub applied_events: u64,
+}
+
+impl From<core::ReadModelStatus> for ReadModelStatus {
+    fn from(
+        core::ReadModelStatus {
+            ready,
+            applied_events,
+        }: core::ReadModelStatus,
+    ) -> Self {
+        Self {
+            ready,
+            applied_events,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StoreStats {
+    pub event_count: u64,
+    pub aggregate_count: u64,
+    pub approx_bytes: u64,
+}
+
+impl From<infrastructure::StoreStats> for StoreStats {
+    fn from(
+        infrastructure::StoreStats {
+            event_count,
+            aggregate_count,
+            approx_bytes,
+        }: infrastructure::StoreStats,
+    ) -> Self {
+        Self {
+            event_count,
+            aggregate_count,
+            approx_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Durability {
+    pub mode: DurabilityMode,
+}
+
+// See infrastructure::Durability for what buffered risks
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DurabilityMode {
+    SyncAll,
+    Buffered,
+}
+
+impl From<infrastructure::Durability> for Durability {
+    fn from(value: infrastructure::Durability) -> Self {
+        Self {
+            mode: match value {
+                infrastructure::Durability::SyncAll => DurabilityMode::SyncAll,
+                infrastructure::Durability::Buffered => DurabilityMode::Buffered,
+            },
+        }
+    }
+}
+
+impl From<Durability> for infrastructure::Durability {
+    fn from(Durability { mode }: Durability) -> Self {
+        match mode {
+            DurabilityMode::SyncAll => Self::SyncAll,
+            DurabilityMode::Buffered => Self::Buffered,
+        }
+    }
+}
+
+// A threshold, if given, replaces the current one
+#[derive(Debug, Deserialize)]
+pub struct Reindex {
+    #[serde(default)]
+    pub term_length_threshold: Option<usize>,
+}
+
+// Terms no longer than the threshold, in bytes, are not indexed
+#[derive(Debug, Serialize)]
+pub struct TextIndex {
+    pub term_length_threshold: usize,
+}
+
+// Event names, like book-added, to how many of them have been applied
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct EventCounts(pub BTreeMap<String, usize>);
+
+#[derive(Debug, Serialize)]
+pub struct ConsistencyReport {
+    pub dangling_references: Vec<DanglingReference>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum DanglingReference {
+    BookAuthor {
+        book_id: BookId,
+        author_id: AuthorId,
+    },
+}
+
+impl From<domain::query::DanglingReference> for DanglingReference {
+    fn from(value: domain::query::DanglingReference) -> Self {
+        match value {
+            domain::query::DanglingReference::BookAuthor(book_id, author_id) => Self::BookAuthor {
+                book_id: book_id.into(),
+                author_id: author_id.into(),
+            },
+        }
+    }
+}
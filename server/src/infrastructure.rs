@@ -3,15 +3,16 @@ use serde_json::Value as JsonValue;
 use std::{
     fmt::{self, Display},
     future::Future,
+    str::FromStr,
     sync::Arc,
-    time::SystemTime,
 };
-use time::OffsetDateTime;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use tokio::sync::{broadcast, Mutex};
 use uuid::Uuid;
 
 use crate::error::{Error, Result};
 
+pub mod memory;
 pub mod persistence;
 
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
@@ -28,6 +29,32 @@ impl UniqueId {
     }
 }
 
+// Where the ids of new books, authors and readers come from
+pub trait IdGenerator: Send + Sync {
+    fn fresh(&self) -> UniqueId;
+}
+
+pub struct RandomIds;
+
+impl IdGenerator for RandomIds {
+    fn fresh(&self) -> UniqueId {
+        UniqueId::fresh()
+    }
+}
+
+// Where the times that events are journaled at come from
+pub trait Clock: Send + Sync {
+    fn now(&self) -> OffsetDateTime;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
 impl Display for UniqueId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let UniqueId(id) = self;
@@ -50,8 +77,15 @@ impl Termination {
         TerminationWaiter::new(self.signal.subscribe())
     }
 
+    // Nobody is waiting if the application never got started
     pub fn signal(&self) {
-        self.signal.send(()).expect("msg");
+        let _ = self.signal.send(());
+    }
+}
+
+impl Default for Termination {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -70,58 +104,271 @@ impl TerminationWaiter {
 
 pub trait EventStore {
     async fn find_by_event_id(&self, id: UniqueId) -> Result<ExternalRepresentation>;
-    async fn find_by_aggregate_id(&self, id: UniqueId) -> Result<Vec<ExternalRepresentation>>;
 
-    async fn load_aggregate<Aggregate>(&self, aggregate: Aggregate) -> Result<Aggregate::Root>
+    // These are Send, like persist, so that aggregates can be loaded from
+    // request handlers.
+    fn find_by_aggregate_id(
+        &self,
+        id: UniqueId,
+    ) -> impl Future<Output = Result<Vec<ExternalRepresentation>>> + Send;
+
+    // Stores that can read an aggregate's events lazily should override this;
+    // by default it is backed by the eager find_by_aggregate_id.
+    fn find_by_aggregate_id_stream(
+        &self,
+        id: UniqueId,
+    ) -> impl Future<Output = Result<AggregateStream<'_>>> + Send
     where
-        Aggregate: AggregateIdentity,
+        Self: Sync,
     {
-        let stream = self.find_by_aggregate_id(*aggregate.id()).await?;
-        Aggregate::Root::try_load(AggregateStream(stream))
+        async move { Ok(self.find_by_aggregate_id(id).await?.into()) }
+    }
+
+    fn load_aggregate<Aggregate>(
+        &self,
+        aggregate: Aggregate,
+    ) -> impl Future<Output = Result<Aggregate::Root>> + Send
+    where
+        Self: Sync,
+        Aggregate: AggregateIdentity + Send,
+    {
+        async move {
+            let stream = self.find_by_aggregate_id_stream(*aggregate.id()).await?;
+            Aggregate::Root::try_load(stream)
+        }
     }
 
     // Use internal mutability instead?
     // This function has to be this way because the Future has to be Send
     // I wonder if this is something I can solve some other way because this
     // is not pretty. I must be doing something wrong.
-    fn persist<E>(&mut self, event: E) -> impl Future<Output = Result<()>> + Send
+    // Answers the id that the event was stored under, and its time
+    fn persist<E>(&mut self, event: E) -> impl Future<Output = Result<Persisted>> + Send
+    where
+        E: EventDescriptor + Send + Sync + 'static;
+
+    // Persists the event only if its aggregate is still at the expected
+    // version, and fails with a ConcurrencyConflict otherwise.
+    fn persist_expecting<E>(
+        &mut self,
+        event: E,
+        expected: AggregateVersion,
+    ) -> impl Future<Output = Result<Persisted>> + Send
     where
         E: EventDescriptor + Send + Sync + 'static;
 
+    // Persists the events in the order given, and either all of them or
+    // none, for commands that emit more than one event. Answers where and
+    // when each was stored.
+    fn persist_all<E>(
+        &mut self,
+        events: Vec<E>,
+    ) -> impl Future<Output = Result<Vec<Persisted>>> + Send
+    where
+        E: EventDescriptor + Send + Sync + 'static;
+
+    fn aggregate_version(
+        &self,
+        id: UniqueId,
+    ) -> impl Future<Output = Result<AggregateVersion>> + Send;
+
     // This is a pourly thought out solution for journal replays
-    async fn journal(&self) -> Result<Vec<ExternalRepresentation>>;
+    // Send for the same reason as persist: subscribers replay from within
+    // their spawned tasks.
+    fn journal(&self) -> impl Future<Output = Result<Vec<ExternalRepresentation>>> + Send;
+
+    // Events journaled after position, in order, and the position to
+    // continue from.
+    fn journal_after(
+        &self,
+        position: JournalPosition,
+    ) -> impl Future<Output = Result<(Vec<ExternalRepresentation>, JournalPosition)>> + Send;
+
+    // Events numbered after the given sequence, in order; 0 gives the whole
+    // journal. For callers that do not go on from where it ends.
+    fn journal_since(
+        &self,
+        after: u64,
+    ) -> impl Future<Output = Result<Vec<ExternalRepresentation>>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let (events, _) = self.journal_after(JournalPosition(after)).await?;
+            Ok(events)
+        }
+    }
+
+    // Sizes for capacity planning; this should not have to read every event.
+    fn stats(&self) -> impl Future<Output = Result<StoreStats>> + Send;
+
+    // Stores that cannot buffer writes are always synced
+    fn durability(&self) -> Durability {
+        Durability::SyncAll
+    }
+
+    fn set_durability(&self, durability: Durability) -> Result<()> {
+        match durability {
+            Durability::SyncAll => Ok(()),
+            Durability::Buffered => Err(Error::Generic(
+                "This event store cannot buffer writes".to_owned(),
+            )),
+        }
+    }
+
+    // Syncs what was buffered to disk, whatever the durability. Stores that
+    // cannot buffer writes have nothing to flush.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    // Stores that time their events themselves ignore the clock
+    fn set_clock(&mut self, _clock: Arc<dyn Clock>) {}
+}
+
+// When persist returns, a SyncAll store has written the event to disk.
+// A Buffered store has only handed it to the operating system: it survives
+// the process going down, but not the machine, so the last events persisted
+// before a power cut or kernel panic may be lost. Buffered is for bulk
+// imports that can be redone; switching back to SyncAll flushes the buffer.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Durability {
+    #[default]
+    SyncAll,
+    Buffered,
+}
+
+impl FromStr for Durability {
+    type Err = Error;
+
+    fn from_str(durability: &str) -> Result<Self> {
+        match durability {
+            "sync-all" => Ok(Self::SyncAll),
+            "buffered" => Ok(Self::Buffered),
+            otherwise => Err(Error::Generic(format!(
+                "Unknown durability `{otherwise}`, expected sync-all or buffered"
+            ))),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    pub event_count: u64,
+    pub aggregate_count: u64,
+    pub approx_bytes: u64,
+}
+
+// How far a follower has read into the journal: the sequence of the last
+// event it read. The default position is before the first event.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct JournalPosition(pub u64);
+
+// The number of events stored for an aggregate.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregateVersion(pub u64);
+
+impl Display for AggregateVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self(version) = self;
+        write!(f, "{version}")
+    }
 }
 
 pub trait EventDescriptor: Sized {
     fn external_representation(
         &self,
         event_id: UniqueId,
-        event_time: SystemTime,
+        event_time: OffsetDateTime,
     ) -> Result<ExternalRepresentation>;
 
     fn from_external_representation(external: &ExternalRepresentation) -> Result<Self>;
 }
 
+// Where and when an event was journaled
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Persisted {
+    pub id: UniqueId,
+    pub when: OffsetDateTime,
+}
+
+// Bumped whenever events are stored in a shape that an older server cannot
+// read back
+pub const STORE_FORMAT_VERSION: u32 = 1;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ExternalRepresentation {
     pub id: Uuid,
-    pub when: SystemTime,
+    #[serde(with = "event_time")]
+    pub when: OffsetDateTime,
     pub aggregate_id: Uuid,
     pub what: String,
     pub data: JsonValue,
+    // Numbered from 1 by the store, in the order the events were persisted
+    // in. Events stored before they were numbered read as 0.
+    #[serde(default)]
+    pub sequence: u64,
+}
+
+impl ExternalRepresentation {
+    pub fn occurred_at(&self) -> OffsetDateTime {
+        self.when
+    }
+}
+
+// Event times are written as RFC 3339 strings. Events stored before that
+// carry SystemTime's own serde shape, {secs_since_epoch, nanos_since_epoch},
+// which is still accepted when reading. EventArchive::rewrite_legacy_times
+// moves a store over to the new shape once.
+pub(crate) mod event_time {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+    use time::OffsetDateTime;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Representation {
+        Rfc3339(#[serde(with = "time::serde::rfc3339")] OffsetDateTime),
+        Legacy(SystemTime),
+    }
+
+    pub fn serialize<S>(when: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        time::serde::rfc3339::serialize(when, serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match Representation::deserialize(deserializer)? {
+            Representation::Rfc3339(when) => when,
+            Representation::Legacy(when) => when.into(),
+        })
+    }
+
+    // Whether a stored `when` is in the SystemTime shape
+    pub fn is_legacy(when: &serde_json::Value) -> bool {
+        when.is_object()
+    }
 }
 
 impl Display for ExternalRepresentation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let ExternalRepresentation {
             id,
-            when,
             aggregate_id,
             what,
             data,
+            ..
         } = self;
 
-        let when: OffsetDateTime = (*when).into();
+        let when = self
+            .occurred_at()
+            .format(&Rfc3339)
+            .map_err(|_| fmt::Error)?;
         writeln!(f, "[{when}] {aggregate_id}/{id} {what}")?;
 
         let data = serde_json::to_string(data).expect("trust serde");
@@ -133,7 +380,7 @@ impl Display for ExternalRepresentation {
 pub trait AggregateRoot: Sized {
     type Id: AggregateIdentity;
 
-    fn try_load(stream: AggregateStream) -> Result<Self>;
+    fn try_load(stream: AggregateStream<'_>) -> Result<Self>;
 }
 
 pub trait AggregateIdentity {
@@ -142,17 +389,103 @@ pub trait AggregateIdentity {
     fn id(&self) -> &UniqueId;
 }
 
-pub struct AggregateStream(pub Vec<ExternalRepresentation>);
+// The events of an aggregate in the order they were stored, read one
+// at a time.
+pub struct AggregateStream<'a>(Box<dyn Iterator<Item = Result<ExternalRepresentation>> + 'a>);
+
+impl<'a> AggregateStream<'a> {
+    pub fn new<I>(events: I) -> Self
+    where
+        I: IntoIterator<Item = Result<ExternalRepresentation>>,
+        I::IntoIter: 'a,
+    {
+        Self(Box::new(events.into_iter()))
+    }
 
-impl AggregateStream {
-    pub fn peek<E>(&self) -> Result<E>
+    pub fn peek<E>(self) -> Result<E>
     where
         E: EventDescriptor,
     {
+        let Self(mut events) = self;
         E::from_external_representation(
-            self.0
-                .first()
-                .ok_or(Error::Generic("expected an event".to_owned()))?,
+            &events
+                .next()
+                .ok_or(Error::Generic("expected an event".to_owned()))??,
         )
     }
+
+    pub fn fold<E, A, F>(self, init: A, mut f: F) -> Result<A>
+    where
+        E: EventDescriptor,
+        F: FnMut(A, E) -> Result<A>,
+    {
+        let Self(events) = self;
+        events.into_iter().try_fold(init, |state, event| {
+            f(state, E::from_external_representation(&event?)?)
+        })
+    }
+}
+
+impl From<Vec<ExternalRepresentation>> for AggregateStream<'_> {
+    fn from(events: Vec<ExternalRepresentation>) -> Self {
+        Self::new(events.into_iter().map(Ok))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::model::Event;
+
+    // Either an event or one that cannot be written, to fail a batch part way
+    #[derive(Clone, Debug)]
+    pub(super) enum Batched {
+        Event(Event),
+        Unwritable,
+    }
+
+    impl EventDescriptor for Batched {
+        fn external_representation(
+            &self,
+            event_id: UniqueId,
+            event_time: OffsetDateTime,
+        ) -> Result<ExternalRepresentation> {
+            match self {
+                Self::Event(event) => event.external_representation(event_id, event_time),
+                Self::Unwritable => Err(Error::Generic("unwritable".to_owned())),
+            }
+        }
+
+        fn from_external_representation(external: &ExternalRepresentation) -> Result<Self> {
+            Event::from_external_representation(external).map(Self::Event)
+        }
+    }
+
+    #[test]
+    fn event_time_is_written_as_rfc3339_and_read_in_either_shape() {
+        let event = ExternalRepresentation {
+            id: Uuid::new_v4(),
+            when: time::macros::datetime!(2023-11-14 22:13:20.123456789 UTC),
+            aggregate_id: Uuid::new_v4(),
+            what: "author-added".to_owned(),
+            data: serde_json::json!({"name": "Sun Tzu"}),
+            sequence: 7,
+        };
+
+        let mut json = serde_json::to_value(&event).expect("serializable");
+        assert_eq!(json["when"], "2023-11-14T22:13:20.123456789Z");
+        let read: ExternalRepresentation =
+            serde_json::from_value(json.clone()).expect("deserializable");
+        assert_eq!(read.when, event.when);
+        assert_eq!(read.sequence, 7);
+
+        json["when"] = serde_json::json!({
+            "secs_since_epoch": 1_700_000_000,
+            "nanos_since_epoch": 123_456_789
+        });
+        json.as_object_mut().expect("an object").remove("sequence");
+        let legacy: ExternalRepresentation = serde_json::from_value(json).expect("deserializable");
+        assert_eq!(legacy.when, event.when);
+        assert_eq!(legacy.sequence, 0);
+    }
 }
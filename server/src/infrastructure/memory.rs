@@ -0,0 +1,349 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+    error,
+    infrastructure::{
+        AggregateVersion, Clock, EventDescriptor, EventStore, ExternalRepresentation,
+        JournalPosition, Persisted, StoreStats, SystemClock, UniqueId,
+    },
+};
+
+// An EventStore that keeps its events in memory, for tests and for trying
+// things out without a keyspace on disk. Clones share the same events, and
+// the journal is in the order the events were persisted in.
+#[derive(Clone)]
+pub struct MemoryEventStore {
+    events: Arc<Mutex<Vec<ExternalRepresentation>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl MemoryEventStore {
+    pub fn new() -> Self {
+        Self::with_events(vec![])
+    }
+
+    // Starts out with a journal of these events, in this order, numbered
+    // anew from 1
+    pub fn with_events(mut events: Vec<ExternalRepresentation>) -> Self {
+        for (sequence, event) in (1..).zip(events.iter_mut()) {
+            event.sequence = sequence;
+        }
+        Self {
+            events: Arc::new(Mutex::new(events)),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    fn events(&self) -> MutexGuard<'_, Vec<ExternalRepresentation>> {
+        // Nothing panics while holding the lock, so it cannot be poisoned
+        self.events.lock().expect("an unpoisoned lock")
+    }
+
+    fn insert<E>(&self, event: E, expected: Option<AggregateVersion>) -> error::Result<Persisted>
+    where
+        E: EventDescriptor,
+    {
+        let [persisted] = self
+            .insert_all(vec![(event, expected)])?
+            .try_into()
+            .expect("one persisted event");
+        Ok(persisted)
+    }
+
+    // All of the events or none: they are checked before any is appended
+    fn insert_all<E>(
+        &self,
+        events: Vec<(E, Option<AggregateVersion>)>,
+    ) -> error::Result<Vec<Persisted>>
+    where
+        E: EventDescriptor,
+    {
+        let mut journal = self.events();
+        let mut appended: Vec<ExternalRepresentation> = vec![];
+        let mut persisted = vec![];
+        for (event, expected) in events {
+            let event_id = UniqueId::fresh();
+            let event_time = self.clock.now();
+            let mut event = event.external_representation(event_id, event_time)?;
+
+            if let Some(AggregateVersion(expected)) = expected {
+                let aggregate_id = UniqueId(event.aggregate_id);
+                let actual =
+                    version_of(&journal, &aggregate_id) + version_of(&appended, &aggregate_id);
+                if actual != expected {
+                    return Err(error::Error::ConcurrencyConflict { expected, actual });
+                }
+            }
+            event.sequence = (journal.len() + appended.len()) as u64 + 1;
+            appended.push(event);
+            persisted.push(Persisted {
+                id: event_id,
+                when: event_time,
+            });
+        }
+        journal.extend(appended);
+
+        Ok(persisted)
+    }
+}
+
+impl Default for MemoryEventStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn version_of(events: &[ExternalRepresentation], UniqueId(id): &UniqueId) -> u64 {
+    events
+        .iter()
+        .filter(|event| event.aggregate_id == *id)
+        .count() as u64
+}
+
+impl EventStore for MemoryEventStore {
+    async fn find_by_event_id(
+        &self,
+        UniqueId(id): UniqueId,
+    ) -> error::Result<ExternalRepresentation> {
+        self.events()
+            .iter()
+            .find(|event| event.id == id)
+            .cloned()
+            .ok_or_else(|| error::Error::Generic(format!("No such event {id}")))
+    }
+
+    async fn find_by_aggregate_id(
+        &self,
+        UniqueId(id): UniqueId,
+    ) -> error::Result<Vec<ExternalRepresentation>> {
+        Ok(self
+            .events()
+            .iter()
+            .filter(|event| event.aggregate_id == id)
+            .cloned()
+            .collect())
+    }
+
+    async fn persist<E>(&mut self, event: E) -> error::Result<Persisted>
+    where
+        E: EventDescriptor + Send + Sync + 'static,
+    {
+        self.insert(event, None)
+    }
+
+    async fn persist_expecting<E>(
+        &mut self,
+        event: E,
+        expected: AggregateVersion,
+    ) -> error::Result<Persisted>
+    where
+        E: EventDescriptor + Send + Sync + 'static,
+    {
+        self.insert(event, Some(expected))
+    }
+
+    async fn persist_all<E>(&mut self, events: Vec<E>) -> error::Result<Vec<Persisted>>
+    where
+        E: EventDescriptor + Send + Sync + 'static,
+    {
+        self.insert_all(events.into_iter().map(|event| (event, None)).collect())
+    }
+
+    async fn aggregate_version(&self, id: UniqueId) -> error::Result<AggregateVersion> {
+        Ok(AggregateVersion(version_of(&self.events(), &id)))
+    }
+
+    async fn journal(&self) -> error::Result<Vec<ExternalRepresentation>> {
+        Ok(self.events().clone())
+    }
+
+    // The events are in the order of their sequence
+    async fn journal_after(
+        &self,
+        position: JournalPosition,
+    ) -> error::Result<(Vec<ExternalRepresentation>, JournalPosition)> {
+        let JournalPosition(after) = position;
+        let events = self.events();
+        let unread = &events[events.partition_point(|event| event.sequence <= after)..];
+        let position = unread
+            .last()
+            .map_or(position, |event| JournalPosition(event.sequence));
+
+        Ok((unread.to_vec(), position))
+    }
+
+    async fn stats(&self) -> error::Result<StoreStats> {
+        let events = self.events();
+        let aggregates = events
+            .iter()
+            .map(|event| event.aggregate_id)
+            .collect::<HashSet<_>>();
+        Ok(StoreStats {
+            event_count: events.len() as u64,
+            aggregate_count: aggregates.len() as u64,
+            // Nothing is on disk
+            approx_bytes: 0,
+        })
+    }
+
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::model::{AuthorId, AuthorInfo, BookId, BookInfo, Event, Isbn},
+        infrastructure::tests::Batched,
+    };
+
+    fn ids(events: Vec<ExternalRepresentation>) -> Vec<uuid::Uuid> {
+        events.into_iter().map(|event| event.id).collect()
+    }
+
+    #[tokio::test]
+    async fn events_are_found_by_their_id() -> error::Result<()> {
+        let mut store = MemoryEventStore::default();
+        let author_id = AuthorId(UniqueId::fresh());
+        let persisted = store
+            .persist(Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ))
+            .await?;
+
+        let found = store.find_by_event_id(persisted.id).await?;
+        assert_eq!(found.id, *persisted.id.uuid());
+        assert_eq!(found.aggregate_id, *author_id.0.uuid());
+        assert_eq!(found.when, persisted.when);
+        assert!(store.find_by_event_id(UniqueId::fresh()).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn events_are_found_by_their_aggregate_and_journaled_in_order() -> error::Result<()> {
+        let mut store = MemoryEventStore::new();
+        let (sun_tzu, laozi) = (AuthorId(UniqueId::fresh()), AuthorId(UniqueId::fresh()));
+        let book_id = BookId(UniqueId::fresh());
+        let mut persisted = vec![];
+        for event in [
+            Event::AuthorAdded(
+                sun_tzu,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ),
+            Event::AuthorAdded(
+                laozi,
+                AuthorInfo {
+                    name: "Laozi".to_owned(),
+                },
+            ),
+            Event::BookAdded(
+                book_id,
+                BookInfo {
+                    isbn: Isbn("978-1-61180-697-7".to_owned()),
+                    title: "The Art of War".to_owned(),
+                    author: sun_tzu,
+                },
+            ),
+            Event::AuthorAdded(
+                sun_tzu,
+                AuthorInfo {
+                    name: "Sunzi".to_owned(),
+                },
+            ),
+        ] {
+            persisted.push(*store.persist(event).await?.id.uuid());
+        }
+
+        assert_eq!(
+            ids(store.find_by_aggregate_id(sun_tzu.0).await?),
+            vec![persisted[0], persisted[3]]
+        );
+        assert_eq!(
+            ids(store.find_by_aggregate_id(book_id.0).await?),
+            vec![persisted[2]]
+        );
+        assert!(store
+            .find_by_aggregate_id(UniqueId::fresh())
+            .await?
+            .is_empty());
+
+        // Clones share the journal
+        assert_eq!(ids(store.clone().journal().await?), persisted);
+        assert_eq!(ids(store.journal_since(3).await?), persisted[3..]);
+        let (unread, position) = store.journal_after(JournalPosition(3)).await?;
+        assert_eq!(ids(unread), persisted[3..]);
+        assert_eq!(position, JournalPosition(4));
+        let (unread, position) = store.journal_after(position).await?;
+        assert!(unread.is_empty());
+        assert_eq!(position, JournalPosition(4));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn seeded_events_come_first_and_writes_can_conflict() -> error::Result<()> {
+        let mut seed = MemoryEventStore::new();
+        let author_id = AuthorId(UniqueId::fresh());
+        let added = Event::AuthorAdded(
+            author_id,
+            AuthorInfo {
+                name: "Sun Tzu".to_owned(),
+            },
+        );
+        seed.persist(added.clone()).await?;
+
+        let mut store = MemoryEventStore::with_events(seed.journal().await?);
+        assert_eq!(store.journal().await?[0].sequence, 1);
+        assert_eq!(
+            store.aggregate_version(author_id.0).await?,
+            AggregateVersion(1)
+        );
+        assert!(matches!(
+            store
+                .persist_expecting(added.clone(), AggregateVersion(0))
+                .await,
+            Err(error::Error::ConcurrencyConflict {
+                expected: 0,
+                actual: 1
+            })
+        ));
+        store.persist_expecting(added, AggregateVersion(1)).await?;
+        assert_eq!(store.stats().await?.event_count, 2);
+        assert_eq!(store.journal().await?[1].sequence, 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn batches_are_persisted_whole_or_not_at_all() -> error::Result<()> {
+        let mut store = MemoryEventStore::new();
+        let added = Batched::Event(Event::AuthorAdded(
+            AuthorId(UniqueId::fresh()),
+            AuthorInfo {
+                name: "Sun Tzu".to_owned(),
+            },
+        ));
+
+        assert!(store
+            .persist_all(vec![added.clone(), Batched::Unwritable])
+            .await
+            .is_err());
+        assert!(store.journal().await?.is_empty());
+
+        store.persist_all(vec![added.clone(), added]).await?;
+        assert_eq!(store.journal().await?[1].sequence, 2);
+
+        Ok(())
+    }
+}
@@ -1,4 +1,19 @@
-use std::{path::Path, sync::Arc, time::SystemTime};
+#[cfg(test)]
+use std::collections::HashSet;
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    ops::Bound,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
 
 use fjall::{Config, Keyspace, PartitionCreateOptions, PartitionHandle, PersistMode};
 use serde::{Deserialize, Serialize};
@@ -6,11 +21,23 @@ use uuid::Uuid;
 
 use crate::{
     error,
-    infrastructure::{EventDescriptor, ExternalRepresentation, UniqueId},
+    infrastructure::{
+        event_time, AggregateStream, AggregateVersion, Clock, Durability, EventDescriptor,
+        ExternalRepresentation, JournalPosition, Persisted, StoreStats, SystemClock, UniqueId,
+    },
 };
 
 use super::EventStore;
 
+#[cfg(feature = "postgres")]
+pub mod postgres;
+
+// Keys in the metadata partition
+const EVENT_COUNT_KEY: &[u8] = b"event_count";
+const AGGREGATE_COUNT_KEY: &[u8] = b"aggregate_count";
+const LEGACY_TIMES_REWRITTEN_KEY: &[u8] = b"legacy_times_rewritten";
+const AGGREGATES_BY_SEQUENCE_KEY: &[u8] = b"aggregates_by_sequence";
+
 #[derive(Serialize, Deserialize)]
 struct ArchivedRepresentation(ExternalRepresentation);
 
@@ -35,14 +62,34 @@ impl<'a> AsRef<[u8]> for AggregateId<'a> {
 }
 
 impl ArchivedRepresentation {
-    fn event_id(&self) -> EventId {
+    fn aggregate_uuid(&self) -> &Uuid {
+        let Self(ExternalRepresentation { aggregate_id, .. }) = self;
+        aggregate_id
+    }
+
+    fn event_id(&self) -> EventId<'_> {
         let Self(ExternalRepresentation { id, .. }) = self;
         EventId(id)
     }
 
-    fn aggregate_id(&self) -> AggregateId {
-        let Self(ExternalRepresentation { aggregate_id, .. }) = self;
-        AggregateId(aggregate_id)
+    // The aggregate index is keyed on the aggregate id, followed by the
+    // sequence of the event, so that a prefix scan on an aggregate id
+    // yields all of its events in the order they were written, whatever
+    // their times.
+    fn aggregate_key(&self) -> Vec<u8> {
+        let Self(ExternalRepresentation {
+            aggregate_id,
+            sequence,
+            ..
+        }) = self;
+
+        [aggregate_id.as_bytes().as_slice(), &sequence.to_be_bytes()].concat()
+    }
+
+    // The journal is keyed on the sequence, which sorts as a number
+    fn sequence_key(&self) -> [u8; 8] {
+        let Self(ExternalRepresentation { sequence, .. }) = self;
+        sequence.to_be_bytes()
     }
 
     fn as_json(&self) -> error::Result<Vec<u8>> {
@@ -65,29 +112,167 @@ impl From<ExternalRepresentation> for ArchivedRepresentation {
     }
 }
 
+// The clock is the handle's own, the store itself is shared between clones
 #[derive(Clone)]
-pub struct EventArchive(Arc<EventArchiveInner>);
+pub struct EventArchive(Arc<EventArchiveInner>, Arc<dyn Clock>);
 
 impl EventArchive {
+    // Fjall writes this last when creating a keyspace
+    const KEYSPACE_MARKER: &str = "version";
+    const CREATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+    // Blocks while another process is creating the store, so async code
+    // should call it from spawn_blocking
     pub fn try_new<P>(store_path: P) -> error::Result<Self>
     where
         P: AsRef<Path>,
     {
-        Ok(Self(Arc::new(EventArchiveInner::try_open(
-            Keyspace::open(Config::new(store_path))?,
-        )?)))
+        let store_path = store_path.as_ref();
+        let lock_path = Self::lock_path(store_path);
+        Self::wait_for_creation(&lock_path)?;
+        let inner = if store_path.join(Self::KEYSPACE_MARKER).try_exists()? {
+            Self::open(store_path)?
+        } else {
+            Self::create_or_wait(store_path, &lock_path)?
+        };
+
+        Ok(Self(Arc::new(inner), Arc::new(SystemClock)))
+    }
+
+    fn open(store_path: &Path) -> error::Result<EventArchiveInner> {
+        EventArchiveInner::try_open(Keyspace::open(Config::new(store_path))?)
+    }
+
+    fn lock_path(store_path: &Path) -> PathBuf {
+        let mut lock_path = store_path.as_os_str().to_owned();
+        lock_path.push(".creating");
+        PathBuf::from(lock_path)
+    }
+
+    // Creating a store takes several steps, and opening it half way through
+    // fails. Whoever gets to create the lock file next to the store creates
+    // it, and everyone else waits for the lock file to go before opening it.
+    // The lock file holds the id of the process that created it.
+    fn create_or_wait(store_path: &Path, lock_path: &Path) -> error::Result<EventArchiveInner> {
+        if let Some(parent) = store_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(lock_path)
+            {
+                Ok(mut lock) => {
+                    write!(lock, "{}", process::id())?;
+                    let created = Self::open(store_path);
+                    fs::remove_file(lock_path)?;
+                    return created;
+                }
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                    Self::wait_for_creation(lock_path)?;
+                    // Unless the lock was stale, and the store never created
+                    if store_path.join(Self::KEYSPACE_MARKER).try_exists()? {
+                        return Self::open(store_path);
+                    }
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+
+    // Until the lock file is gone, or stale and then removed
+    fn wait_for_creation(lock_path: &Path) -> error::Result<()> {
+        while lock_path.try_exists()? {
+            if Self::is_stale(lock_path)? {
+                tracing::warn!("Removing the stale lock {}", lock_path.display());
+                match fs::remove_file(lock_path) {
+                    Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                        return Err(error.into())
+                    }
+                    _ => break,
+                }
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Ok(())
+    }
+
+    // A lock is stale once the process that took it is gone, or once it is
+    // older than creating a store can take
+    fn is_stale(lock_path: &Path) -> io::Result<bool> {
+        let modified = match fs::metadata(lock_path).and_then(|lock| lock.modified()) {
+            Ok(modified) => modified,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(error) => return Err(error),
+        };
+        if modified.elapsed().unwrap_or_default() > Self::CREATION_TIMEOUT {
+            return Ok(true);
+        }
+
+        // Empty until its creator has written its id
+        let pid = match fs::read_to_string(lock_path) {
+            Ok(pid) => pid.trim().parse::<u32>().ok(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(error) => return Err(error),
+        };
+        Ok(pid.is_some_and(|pid| !Self::is_running(pid)))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn is_running(pid: u32) -> bool {
+        Path::new("/proc").join(pid.to_string()).exists()
+    }
+
+    // Left to the age of the lock
+    #[cfg(not(target_os = "linux"))]
+    fn is_running(_pid: u32) -> bool {
+        true
+    }
+
+    // Rewrites events stored with the legacy SystemTime shape of `when` in
+    // the RFC 3339 shape, and answers how many there were. The store is
+    // marked as rewritten, so that running it again does not scan it.
+    pub fn rewrite_legacy_times(&self) -> error::Result<usize> {
+        self.inner().rewrite_legacy_times()
+    }
+
+    // Where the events, the aggregate index and the versions disagree,
+    // which they should never do, even after a crash
+    #[cfg(test)]
+    fn inconsistencies(&self) -> error::Result<Vec<String>> {
+        self.inner().inconsistencies()
     }
 
     fn inner(&self) -> &EventArchiveInner {
-        let Self(x) = self;
+        let Self(x, _) = self;
         x
     }
+
+    fn clock(&self) -> &dyn Clock {
+        let Self(_, clock) = self;
+        clock.as_ref()
+    }
 }
 
 pub struct EventArchiveInner {
     keyspace: Keyspace,
     events: PartitionHandle,
     aggregates: PartitionHandle,
+    versions: PartitionHandle,
+    // Event ids keyed on the order they were inserted in
+    journal: PartitionHandle,
+    // The counts of events and aggregates, written with each insert
+    metadata: PartitionHandle,
+
+    // Read once on opening, and kept up to date by insert
+    event_count: AtomicU64,
+    aggregate_count: AtomicU64,
+    next_sequence: AtomicU64,
+
+    // A Durability, which can be changed while inserts are going on
+    durability: AtomicU8,
 }
 
 impl EventArchiveInner {
@@ -95,50 +280,382 @@ impl EventArchiveInner {
         let events = keyspace.open_partition("events", PartitionCreateOptions::default())?;
         let aggregates =
             keyspace.open_partition("aggregates", PartitionCreateOptions::default())?;
+        let versions = keyspace.open_partition("versions", PartitionCreateOptions::default())?;
+        let journal = keyspace.open_partition("journal", PartitionCreateOptions::default())?;
+        let metadata = keyspace.open_partition("metadata", PartitionCreateOptions::default())?;
+
+        // The aggregate index follows the sequences, so it is keyed again
+        // whenever they change
+        let mut reindex = !metadata.contains_key(AGGREGATES_BY_SEQUENCE_KEY)?;
+        if journal.is_empty()? && !events.is_empty()? {
+            Self::journal_in_time_order(&keyspace, &events, &journal)?;
+            reindex = true;
+        } else if let Some((key, _)) = journal.first_key_value()? {
+            if Self::sequence_of(&key)? == 0 {
+                Self::renumber_journal(&keyspace, &events, &journal)?;
+                reindex = true;
+            }
+        }
+        if reindex {
+            Self::index_aggregates_by_sequence(&keyspace, &events, &aggregates, &metadata)?;
+        }
+        // Sequences start at 1, so that 0 can stand for before the first
+        let next_sequence = if let Some((key, _)) = journal.last_key_value()? {
+            Self::sequence_of(&key)? + 1
+        } else {
+            1
+        };
+
+        let (event_count, aggregate_count) = match (
+            Self::count_of(&metadata, EVENT_COUNT_KEY)?,
+            Self::count_of(&metadata, AGGREGATE_COUNT_KEY)?,
+        ) {
+            (Some(event_count), Some(aggregate_count)) => (event_count, aggregate_count),
+            _ => Self::count_once(&keyspace, &events, &aggregates, &metadata)?,
+        };
 
         Ok(Self {
             keyspace,
             events,
             aggregates,
+            versions,
+            journal,
+            metadata,
+            event_count: AtomicU64::new(event_count),
+            aggregate_count: AtomicU64::new(aggregate_count),
+            next_sequence: AtomicU64::new(next_sequence),
+            durability: AtomicU8::new(Self::durability_tag(Durability::SyncAll)),
+        })
+    }
+
+    fn count_of(metadata: &PartitionHandle, key: &[u8]) -> error::Result<Option<u64>> {
+        metadata
+            .get(key)?
+            .map(|count| {
+                count
+                    .as_ref()
+                    .try_into()
+                    .map(u64::from_be_bytes)
+                    .map_err(|_| {
+                        error::Error::CorruptStore(format!("The metadata has a malformed {key:?}"))
+                    })
+            })
+            .transpose()
+    }
+
+    // Stores written before the counts were kept have them counted once,
+    // and written for the next time they are opened
+    fn count_once(
+        keyspace: &Keyspace,
+        events: &PartitionHandle,
+        aggregates: &PartitionHandle,
+        metadata: &PartitionHandle,
+    ) -> error::Result<(u64, u64)> {
+        let event_count = events.len()? as u64;
+
+        // The aggregate index is sorted on aggregate id, which prefixes its keys
+        let mut aggregate_count = 0u64;
+        let mut previous_aggregate_id = None;
+        for key in aggregates.keys() {
+            let key = key?;
+            let aggregate_id = key[..16].to_vec();
+            if previous_aggregate_id.as_ref() != Some(&aggregate_id) {
+                aggregate_count += 1;
+                previous_aggregate_id = Some(aggregate_id);
+            }
+        }
+
+        let mut batch = keyspace.batch();
+        batch.insert(metadata, EVENT_COUNT_KEY, event_count.to_be_bytes());
+        batch.insert(metadata, AGGREGATE_COUNT_KEY, aggregate_count.to_be_bytes());
+        batch.commit()?;
+        keyspace.persist(PersistMode::SyncAll)?;
+
+        Ok((event_count, aggregate_count))
+    }
+
+    fn sequence_of(key: &[u8]) -> error::Result<u64> {
+        key.try_into().map(u64::from_be_bytes).map_err(|_| {
+            error::Error::CorruptStore(format!("The journal has a malformed key {key:?}"))
         })
     }
 
-    fn insert(&self, event: ExternalRepresentation) -> error::Result<()> {
+    fn version_of(bytes: &[u8]) -> error::Result<u64> {
+        bytes.try_into().map(u64::from_be_bytes).map_err(|_| {
+            error::Error::CorruptStore(format!("Malformed aggregate version {bytes:?}"))
+        })
+    }
+
+    fn event_id_of(bytes: &[u8]) -> error::Result<Uuid> {
+        Uuid::from_slice(bytes)
+            .map_err(|_| error::Error::CorruptStore(format!("Malformed event id {bytes:?}")))
+    }
+
+    // Stores written before the journal was kept have it built once, in
+    // the order of the times the events were stored with, and the events
+    // are rewritten with their sequence. Events stored at the same time
+    // keep whatever order they end up in.
+    fn journal_in_time_order(
+        keyspace: &Keyspace,
+        events: &PartitionHandle,
+        journal: &PartitionHandle,
+    ) -> error::Result<()> {
+        let mut found = vec![];
+        for pair in events.iter() {
+            let (_, event_bytes) = pair?;
+            let ArchivedRepresentation(event) = ArchivedRepresentation::from_slice(&event_bytes)?;
+            found.push(event);
+        }
+        found.sort_by_key(|event| event.when);
+
+        let mut batch = keyspace.batch();
+        for (sequence, mut event) in (1u64..).zip(found) {
+            event.sequence = sequence;
+            let archived = ArchivedRepresentation::from(event);
+            batch.insert(events, archived.event_id(), archived.as_json()?);
+            batch.insert(journal, archived.sequence_key(), archived.event_id());
+        }
+        batch.commit()?;
+        keyspace.persist(PersistMode::SyncAll)?;
+
+        Ok(())
+    }
+
+    // Stores written while the journal was numbered from 0 have events
+    // without a sequence. Their journal is numbered again from 1, in the
+    // same order, and the events are rewritten with their sequence.
+    fn renumber_journal(
+        keyspace: &Keyspace,
+        events: &PartitionHandle,
+        journal: &PartitionHandle,
+    ) -> error::Result<()> {
+        let mut entries = vec![];
+        for pair in journal.iter() {
+            let (key, value) = pair?;
+            entries.push((Self::sequence_of(&key)?, Self::event_id_of(&value)?));
+        }
+
+        let mut batch = keyspace.batch();
+        // Keys that are not overwritten below
+        for (sequence, _) in &entries {
+            if *sequence == 0 || *sequence > entries.len() as u64 {
+                batch.remove(journal, sequence.to_be_bytes());
+            }
+        }
+        for (sequence, (_, event_id)) in (1u64..).zip(&entries) {
+            let Some(event_bytes) = events.get(event_id)? else {
+                return Err(error::Error::CorruptStore(format!(
+                    "Event {event_id} is journaled, but is missing"
+                )));
+            };
+            let ArchivedRepresentation(mut event) =
+                ArchivedRepresentation::from_slice(&event_bytes)?;
+            event.sequence = sequence;
+            let archived = ArchivedRepresentation::from(event);
+            batch.insert(events, archived.event_id(), archived.as_json()?);
+            batch.insert(journal, archived.sequence_key(), archived.event_id());
+        }
+        batch.commit()?;
+        keyspace.persist(PersistMode::SyncAll)?;
+
+        Ok(())
+    }
+
+    // Stores written while the aggregate index was keyed on the times of
+    // the events have it keyed on their sequences instead. Keys that are
+    // written again are not removed first, as a batch does not order a
+    // removal and an insertion of the same key.
+    fn index_aggregates_by_sequence(
+        keyspace: &Keyspace,
+        events: &PartitionHandle,
+        aggregates: &PartitionHandle,
+        metadata: &PartitionHandle,
+    ) -> error::Result<()> {
+        let mut entries = HashMap::new();
+        for pair in events.iter() {
+            let (primary_key, event_bytes) = pair?;
+            let archived = ArchivedRepresentation::from_slice(&event_bytes)?;
+            entries.insert(archived.aggregate_key(), primary_key);
+        }
+
+        let mut batch = keyspace.batch();
+        for key in aggregates.keys() {
+            let key = key?;
+            if !entries.contains_key(key.as_ref()) {
+                batch.remove(aggregates, key);
+            }
+        }
+        for (key, primary_key) in entries {
+            batch.insert(aggregates, key, primary_key);
+        }
+        batch.insert(metadata, AGGREGATES_BY_SEQUENCE_KEY, []);
+        batch.commit()?;
+        keyspace.persist(PersistMode::SyncAll)?;
+
+        Ok(())
+    }
+
+    fn durability_tag(durability: Durability) -> u8 {
+        match durability {
+            Durability::SyncAll => 0,
+            Durability::Buffered => 1,
+        }
+    }
+
+    fn durability(&self) -> Durability {
+        match self.durability.load(Ordering::Acquire) {
+            0 => Durability::SyncAll,
+            _ => Durability::Buffered,
+        }
+    }
+
+    fn persist_mode(&self) -> PersistMode {
+        match self.durability() {
+            Durability::SyncAll => PersistMode::SyncAll,
+            Durability::Buffered => PersistMode::Buffer,
+        }
+    }
+
+    // What was buffered is synced before this returns, when switching back
+    fn set_durability(&self, durability: Durability) -> error::Result<()> {
+        self.durability
+            .store(Self::durability_tag(durability), Ordering::Release);
+        if durability == Durability::SyncAll {
+            self.keyspace.persist(PersistMode::SyncAll)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> error::Result<()> {
+        Ok(self.keyspace.persist(PersistMode::SyncAll)?)
+    }
+
+    fn insert(
+        &self,
+        event: ExternalRepresentation,
+        expected: Option<AggregateVersion>,
+    ) -> error::Result<()> {
+        self.insert_all(vec![(event, expected)])
+    }
+
+    // Inserts are serialized by the EventBus, which holds the store
+    // exclusively while persisting, so the version checks cannot race.
+    // The events, their aggregate index entries, their aggregates' versions,
+    // their places in the journal and the new counts are written in one
+    // batch, which fjall commits atomically across partitions: after a
+    // crash either all of them are there, or none is. A batch that fails
+    // leaves a gap in the sequence.
+    fn insert_all(
+        &self,
+        events: Vec<(ExternalRepresentation, Option<AggregateVersion>)>,
+    ) -> error::Result<()> {
         let mut batch = self.keyspace.batch();
+        let mut versions = HashMap::<Uuid, u64>::new();
+        let mut new_aggregates = 0;
+        let inserted = events.len() as u64;
+
+        for (mut event, expected) in events {
+            let version = if let Some(version) = versions.get(&event.aggregate_id) {
+                *version
+            } else {
+                let AggregateVersion(version) = self.aggregate_version(&event.aggregate_id)?;
+                version
+            };
+
+            if let Some(AggregateVersion(expected)) = expected {
+                if expected != version {
+                    return Err(error::Error::ConcurrencyConflict {
+                        expected,
+                        actual: version,
+                    });
+                }
+            }
+            if version == 0 {
+                new_aggregates += 1;
+            }
 
-        let archived: ArchivedRepresentation = event.into();
-        let primary_key = archived.event_id();
+            event.sequence = self.next_sequence.fetch_add(1, Ordering::Relaxed);
+            let archived: ArchivedRepresentation = event.into();
+            let primary_key = archived.event_id();
 
-        batch.insert(&self.events, &primary_key, archived.as_json()?);
-        batch.insert(&self.aggregates, archived.aggregate_id(), primary_key);
+            batch.insert(&self.events, &primary_key, archived.as_json()?);
+            batch.insert(&self.aggregates, archived.aggregate_key(), &primary_key);
+            batch.insert(&self.journal, archived.sequence_key(), primary_key);
+            batch.insert(
+                &self.versions,
+                archived.aggregate_uuid().as_bytes(),
+                (version + 1).to_be_bytes(),
+            );
+            versions.insert(*archived.aggregate_uuid(), version + 1);
+        }
 
+        let event_count = self.event_count.load(Ordering::Relaxed) + inserted;
+        let aggregate_count = self.aggregate_count.load(Ordering::Relaxed) + new_aggregates;
+        batch.insert(&self.metadata, EVENT_COUNT_KEY, event_count.to_be_bytes());
+        batch.insert(
+            &self.metadata,
+            AGGREGATE_COUNT_KEY,
+            aggregate_count.to_be_bytes(),
+        );
         batch.commit()?;
 
-        // Yes, no, maybe?
-        self.keyspace.persist(PersistMode::SyncAll)?;
+        self.keyspace.persist(self.persist_mode())?;
+
+        self.event_count.store(event_count, Ordering::Relaxed);
+        self.aggregate_count
+            .store(aggregate_count, Ordering::Relaxed);
 
         Ok(())
     }
 
+    fn stats(&self) -> StoreStats {
+        StoreStats {
+            event_count: self.event_count.load(Ordering::Relaxed),
+            aggregate_count: self.aggregate_count.load(Ordering::Relaxed),
+            // Trails recent writes until fjall has flushed them
+            approx_bytes: self.keyspace.disk_space(),
+        }
+    }
+
     fn find_aggregate_events(
         &self,
         aggregate_id: AggregateId<'_>,
     ) -> error::Result<Vec<ExternalRepresentation>> {
-        let mut events = vec![];
+        let AggregateId(aggregate_id) = aggregate_id;
+        self.stream_aggregate_events(*aggregate_id).collect()
+    }
 
-        for pair in self.aggregates.prefix(aggregate_id) {
-            let (_, value) = pair?;
+    fn stream_aggregate_events(
+        &self,
+        aggregate_id: Uuid,
+    ) -> impl Iterator<Item = error::Result<ExternalRepresentation>> + '_ {
+        self.aggregates
+            .prefix(aggregate_id.into_bytes())
+            .map(move |pair| {
+                let (_, value) = pair?;
 
-            let primary_key = Uuid::from_slice(&value).expect("internal error");
-            let Some(event_bytes) = self.events.get(primary_key)? else {
-                panic!("corrupt index")
-            };
+                let primary_key = Self::event_id_of(&value)?;
+                let Some(event_bytes) = self.events.get(primary_key)? else {
+                    return Err(error::Error::CorruptStore(format!(
+                        "Aggregate {aggregate_id} is indexed with event {primary_key}, \
+                         which is missing"
+                    )));
+                };
 
-            let archived = ArchivedRepresentation::from_slice(&event_bytes)?;
-            events.push(archived.into_external_representation());
-        }
+                let archived = ArchivedRepresentation::from_slice(&event_bytes)?;
+                Ok(archived.into_external_representation())
+            })
+    }
 
-        Ok(events)
+    // Aggregates written before versions were kept are counted instead
+    fn aggregate_version(&self, aggregate_id: &Uuid) -> error::Result<AggregateVersion> {
+        Ok(AggregateVersion(
+            if let Some(version) = self.versions.get(aggregate_id.as_bytes())? {
+                Self::version_of(&version)?
+            } else {
+                self.aggregates.prefix(aggregate_id.into_bytes()).count() as u64
+            },
+        ))
     }
 
     fn find_event(&self, event_id: EventId<'_>) -> error::Result<Option<ExternalRepresentation>> {
@@ -150,11 +667,163 @@ impl EventArchiveInner {
         }
     }
 
+    // The aggregate index keys do not change, as they do not carry the
+    // time; only the events themselves are rewritten. Nothing writes the
+    // legacy shape any more, so a store that was rewritten stays that way.
+    fn rewrite_legacy_times(&self) -> error::Result<usize> {
+        if self.metadata.contains_key(LEGACY_TIMES_REWRITTEN_KEY)? {
+            return Ok(0);
+        }
+
+        let mut batch = self.keyspace.batch();
+        let mut rewritten = 0;
+
+        for pair in self.events.iter() {
+            let (primary_key, event_bytes) = pair?;
+            let stored: serde_json::Value = serde_json::from_slice(&event_bytes)?;
+            if event_time::is_legacy(&stored["when"]) {
+                let archived = ArchivedRepresentation::from_slice(&event_bytes)?;
+                batch.insert(&self.events, primary_key, archived.as_json()?);
+                rewritten += 1;
+            }
+        }
+        batch.insert(&self.metadata, LEGACY_TIMES_REWRITTEN_KEY, []);
+
+        batch.commit()?;
+        self.keyspace.persist(PersistMode::SyncAll)?;
+
+        Ok(rewritten)
+    }
+
+    // Malformed keys and values are reported along with the rest
+    #[cfg(test)]
+    fn inconsistencies(&self) -> error::Result<Vec<String>> {
+        let uuid = |bytes: &[u8]| Uuid::from_slice(bytes).ok();
+        let mut found = vec![];
+
+        let mut indexed = HashSet::new();
+        let mut index_counts = HashMap::<Uuid, u64>::new();
+        for pair in self.aggregates.iter() {
+            let (key, value) = pair?;
+            let (Some(aggregate_id), Ok(sequence), Some(event_id)) = (
+                key.get(..16).and_then(uuid),
+                Self::sequence_of(key.get(16..).unwrap_or_default()),
+                uuid(&value),
+            ) else {
+                found.push(format!(
+                    "The aggregate index has a malformed entry at {key:?}"
+                ));
+                continue;
+            };
+            if let Some(event_bytes) = self.events.get(event_id)? {
+                let ArchivedRepresentation(event) =
+                    ArchivedRepresentation::from_slice(&event_bytes)?;
+                if event.aggregate_id != aggregate_id {
+                    found.push(format!(
+                        "Event {event_id} is indexed under aggregate {aggregate_id}, \
+                         but belongs to {}",
+                        event.aggregate_id
+                    ));
+                }
+                if event.sequence != sequence {
+                    found.push(format!(
+                        "Event {event_id} is indexed at {sequence}, \
+                         but is journaled at {}",
+                        event.sequence
+                    ));
+                }
+            } else {
+                found.push(format!(
+                    "Aggregate {aggregate_id} is indexed with event {event_id}, which is missing"
+                ));
+            }
+            indexed.insert(event_id);
+            *index_counts.entry(aggregate_id).or_default() += 1;
+        }
+
+        let mut journaled = HashSet::new();
+        for pair in self.journal.iter() {
+            let (key, value) = pair?;
+            let (Ok(sequence), Some(event_id)) = (Self::sequence_of(&key), uuid(&value)) else {
+                found.push(format!("The journal has a malformed entry at {key:?}"));
+                continue;
+            };
+            if !self.events.contains_key(event_id)? {
+                found.push(format!(
+                    "Event {event_id} is journaled at {sequence}, but is missing"
+                ));
+            }
+            journaled.insert(event_id);
+        }
+
+        let mut event_count = 0;
+        for key in self.events.keys() {
+            let key = key?;
+            event_count += 1;
+            let Some(event_id) = uuid(&key) else {
+                found.push(format!("An event has a malformed id {key:?}"));
+                continue;
+            };
+            if !indexed.contains(&event_id) {
+                found.push(format!("Event {event_id} is not in the aggregate index"));
+            }
+            if !journaled.contains(&event_id) {
+                found.push(format!("Event {event_id} is not in the journal"));
+            }
+        }
+
+        for pair in self.versions.iter() {
+            let (key, value) = pair?;
+            let (Some(aggregate_id), Ok(version)) = (uuid(&key), Self::version_of(&value)) else {
+                found.push(format!("The versions have a malformed entry at {key:?}"));
+                continue;
+            };
+            let indexed = index_counts.get(&aggregate_id).copied().unwrap_or_default();
+            if version != indexed {
+                found.push(format!(
+                    "Aggregate {aggregate_id} is at version {version}, \
+                     but has {indexed} indexed events"
+                ));
+            }
+        }
+
+        for (key, what, actual) in [
+            (EVENT_COUNT_KEY, "events", event_count),
+            (AGGREGATE_COUNT_KEY, "aggregates", index_counts.len() as u64),
+        ] {
+            if let Some(count) = Self::count_of(&self.metadata, key)? {
+                if count != actual {
+                    found.push(format!("The store counts {count} {what}, but has {actual}"));
+                }
+            }
+        }
+
+        Ok(found)
+    }
+
+    // Events are keyed on their (random) ids, so the journal is read in the
+    // order it was inserted in, whatever the times of its events.
     fn find_all(&self) -> error::Result<Vec<ExternalRepresentation>> {
+        self.find_since(0)
+    }
+
+    // A range scan on the journal, which is keyed on the sequence
+    fn find_since(&self, after: u64) -> error::Result<Vec<ExternalRepresentation>> {
         let mut events = vec![];
 
-        for pair in self.events.iter() {
-            let (_, event_bytes) = pair?;
+        let after = after.to_be_bytes();
+        for pair in self
+            .journal
+            .range::<&[u8], _>((Bound::Excluded(&after[..]), Bound::Unbounded))
+        {
+            let (key, value) = pair?;
+            let primary_key = Self::event_id_of(&value)?;
+            let Some(event_bytes) = self.events.get(primary_key)? else {
+                return Err(error::Error::CorruptStore(format!(
+                    "Event {primary_key} is journaled at {}, but is missing",
+                    Self::sequence_of(&key)?
+                )));
+            };
             let archived = ArchivedRepresentation::from_slice(&event_bytes)?;
             events.push(archived.into_external_representation())
         }
@@ -187,31 +856,934 @@ impl EventStore for EventArchive {
         self.inner().find_aggregate_events(AggregateId(&id))
     }
 
-    async fn persist<E>(&mut self, event: E) -> error::Result<()>
+    async fn find_by_aggregate_id_stream(
+        &self,
+        UniqueId(id): UniqueId,
+    ) -> error::Result<AggregateStream<'_>> {
+        Ok(AggregateStream::new(
+            self.inner().stream_aggregate_events(id),
+        ))
+    }
+
+    async fn persist<E>(&mut self, event: E) -> error::Result<Persisted>
     where
         E: EventDescriptor + Send + Sync + 'static,
     {
         let event_id = UniqueId::fresh();
-        let event_time = SystemTime::now();
+        let event_time = self.clock().now();
         let event = event.external_representation(event_id, event_time)?;
-        self.inner().insert(event)?;
+        self.inner().insert(event, None)?;
 
-        Ok(())
+        Ok(Persisted {
+            id: event_id,
+            when: event_time,
+        })
+    }
+
+    async fn persist_expecting<E>(
+        &mut self,
+        event: E,
+        expected: AggregateVersion,
+    ) -> error::Result<Persisted>
+    where
+        E: EventDescriptor + Send + Sync + 'static,
+    {
+        let event_id = UniqueId::fresh();
+        let event_time = self.clock().now();
+        let event = event.external_representation(event_id, event_time)?;
+        self.inner().insert(event, Some(expected))?;
+
+        Ok(Persisted {
+            id: event_id,
+            when: event_time,
+        })
+    }
+
+    async fn persist_all<E>(&mut self, events: Vec<E>) -> error::Result<Vec<Persisted>>
+    where
+        E: EventDescriptor + Send + Sync + 'static,
+    {
+        let mut persisted = vec![];
+        let mut representations = vec![];
+        for event in events {
+            let event_id = UniqueId::fresh();
+            let event_time = self.clock().now();
+            representations.push((event.external_representation(event_id, event_time)?, None));
+            persisted.push(Persisted {
+                id: event_id,
+                when: event_time,
+            });
+        }
+        self.inner().insert_all(representations)?;
+
+        Ok(persisted)
+    }
+
+    async fn aggregate_version(&self, UniqueId(id): UniqueId) -> error::Result<AggregateVersion> {
+        self.inner().aggregate_version(&id)
     }
 
     async fn journal(&self) -> error::Result<Vec<ExternalRepresentation>> {
         self.inner().find_all()
     }
+
+    async fn journal_after(
+        &self,
+        position: JournalPosition,
+    ) -> error::Result<(Vec<ExternalRepresentation>, JournalPosition)> {
+        let JournalPosition(after) = position;
+        let events = self.inner().find_since(after)?;
+        let position = events
+            .last()
+            .map_or(position, |event| JournalPosition(event.sequence));
+
+        Ok((events, position))
+    }
+
+    async fn stats(&self) -> error::Result<StoreStats> {
+        Ok(self.inner().stats())
+    }
+
+    fn durability(&self) -> Durability {
+        self.inner().durability()
+    }
+
+    fn set_durability(&self, durability: Durability) -> error::Result<()> {
+        self.inner().set_durability(durability)
+    }
+
+    fn flush(&self) -> error::Result<()> {
+        self.inner().flush()
+    }
+
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.1 = clock;
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use fjall::{Config, PartitionCreateOptions, Result};
+    use super::*;
+    use crate::{
+        core::model::{
+            query::{BookById, IndexSet, IndexSetQuery},
+            AuthorId, AuthorInfo, Book, BookId, BookInfo, Event, Isbn, KeywordTarget,
+        },
+        infrastructure::tests::Batched,
+    };
+
+    #[tokio::test]
+    async fn persisted_events_are_found_and_survive_reopening() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+
+        let author_id = AuthorId(UniqueId::fresh());
+        let book_id = BookId(UniqueId::fresh());
+        let mut persisted = vec![];
+        for event in [
+            Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ),
+            Event::BookAdded(
+                book_id,
+                BookInfo {
+                    isbn: Isbn("978-1-61180-697-7".to_owned()),
+                    title: "The Art of War".to_owned(),
+                    author: author_id,
+                },
+            ),
+            Event::KeywordAdded(KeywordTarget::Author(author_id), "strategy".to_owned()),
+        ] {
+            persisted.push(archive.persist(event).await?.id);
+        }
+
+        let found = archive.find_by_event_id(persisted[1]).await?;
+        assert_eq!(found.id, *persisted[1].uuid());
+        assert_eq!(found.aggregate_id, *book_id.0.uuid());
+        let Event::BookAdded(found_id, info) = Event::from_external_representation(&found)? else {
+            panic!("expected the book to be added")
+        };
+        assert_eq!(found_id, book_id);
+        assert_eq!(info.title, "The Art of War");
+        assert!(archive.find_by_event_id(UniqueId::fresh()).await.is_err());
+
+        let ids = |events: Vec<ExternalRepresentation>| {
+            events.into_iter().map(|event| event.id).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids(archive.find_by_aggregate_id(author_id.0).await?),
+            vec![*persisted[0].uuid(), *persisted[2].uuid()]
+        );
+
+        drop(archive);
+        let archive = EventArchive::try_new(directory.path())?;
+        assert_eq!(
+            ids(archive.journal().await?),
+            persisted.iter().map(|id| *id.uuid()).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn second_write_at_the_same_version_conflicts() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        let author_id = AuthorId(UniqueId::fresh());
+        archive
+            .persist(Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ))
+            .await?;
+
+        let version = archive.aggregate_version(author_id.0).await?;
+        assert_eq!(version, AggregateVersion(1));
+
+        let update = |keyword: &str| {
+            Event::KeywordAdded(KeywordTarget::Author(author_id), keyword.to_owned())
+        };
+        archive
+            .persist_expecting(update("strategy"), version)
+            .await?;
+        assert!(matches!(
+            archive.persist_expecting(update("warfare"), version).await,
+            Err(error::Error::ConcurrencyConflict {
+                expected: 1,
+                actual: 2
+            })
+        ));
+        assert_eq!(
+            archive.aggregate_version(author_id.0).await?,
+            AggregateVersion(2)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn batches_are_persisted_whole_or_not_at_all() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        let author_id = AuthorId(UniqueId::fresh());
+        let added = Batched::Event(Event::AuthorAdded(
+            author_id,
+            AuthorInfo {
+                name: "Sun Tzu".to_owned(),
+            },
+        ));
+
+        assert!(archive
+            .persist_all(vec![added.clone(), Batched::Unwritable])
+            .await
+            .is_err());
+        assert!(archive.journal().await?.is_empty());
+        assert_eq!(archive.stats().await?.event_count, 0);
+
+        let persisted = archive.persist_all(vec![added.clone(), added]).await?;
+        assert_eq!(persisted.len(), 2);
+        assert_eq!(
+            archive.aggregate_version(author_id.0).await?,
+            AggregateVersion(2)
+        );
+        assert_eq!(archive.stats().await?.aggregate_count, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn journal_is_in_the_order_events_were_persisted() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        let mut persisted = vec![];
+        for _ in 0..20 {
+            let author_id = AuthorId(UniqueId::fresh());
+            archive
+                .persist(Event::AuthorAdded(
+                    author_id,
+                    AuthorInfo {
+                        name: "Sun Tzu".to_owned(),
+                    },
+                ))
+                .await?;
+            persisted.push(*author_id.0.uuid());
+        }
+
+        let journaled = archive
+            .journal()
+            .await?
+            .into_iter()
+            .map(|event| event.aggregate_id)
+            .collect::<Vec<_>>();
+        assert_eq!(journaled, persisted);
+
+        Ok(())
+    }
+
+    struct BackwardsClock(std::sync::Mutex<time::OffsetDateTime>);
+
+    impl Clock for BackwardsClock {
+        fn now(&self) -> time::OffsetDateTime {
+            let mut now = self.0.lock().expect("an unpoisoned lock");
+            *now -= time::Duration::hours(1);
+            *now
+        }
+    }
+
+    #[tokio::test]
+    async fn journal_replays_in_insertion_order_whatever_the_times() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        archive.set_clock(Arc::new(BackwardsClock(std::sync::Mutex::new(
+            time::macros::datetime!(2024-01-01 0:00 UTC),
+        ))));
+
+        let author_id = AuthorId(UniqueId::fresh());
+        let book_id = BookId(UniqueId::fresh());
+        let mut persisted = vec![];
+        for event in [
+            Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ),
+            Event::BookAdded(
+                book_id,
+                BookInfo {
+                    isbn: Isbn("978-1-61180-697-7".to_owned()),
+                    title: "The Art of War".to_owned(),
+                    author: author_id,
+                },
+            ),
+            Event::KeywordAdded(KeywordTarget::Book(book_id), "strategy".to_owned()),
+        ] {
+            persisted.push(*archive.persist(event).await?.id.uuid());
+        }
+
+        let ids = |events: Vec<ExternalRepresentation>| {
+            events.into_iter().map(|event| event.id).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(archive.journal().await?), persisted);
+
+        drop(archive);
+        let mut archive = EventArchive::try_new(directory.path())?;
+        assert_eq!(ids(archive.journal().await?), persisted);
+        let added = archive
+            .persist(Event::KeywordAdded(
+                KeywordTarget::Author(author_id),
+                "strategy".to_owned(),
+            ))
+            .await?;
+        persisted.push(*added.id.uuid());
+        assert_eq!(ids(archive.journal().await?), persisted);
+        assert_eq!(archive.inconsistencies()?, Vec::<String>::new());
+
+        // Numbered from 1, and on from there after reopening
+        let sequences = archive
+            .journal()
+            .await?
+            .into_iter()
+            .map(|event| event.sequence)
+            .collect::<Vec<_>>();
+        assert_eq!(sequences, vec![1, 2, 3, 4]);
+        assert_eq!(archive.find_by_event_id(added.id).await?.sequence, 4);
+        let after = |position| archive.journal_after(JournalPosition(position));
+        let (unread, position) = after(2).await?;
+        assert_eq!(ids(unread), persisted[2..]);
+        assert_eq!(position, JournalPosition(4));
+        assert_eq!(ids(after(0).await?.0), persisted);
+        for end in [4, u64::MAX] {
+            let (unread, position) = after(end).await?;
+            assert!(unread.is_empty());
+            assert_eq!(position, JournalPosition(end));
+        }
+
+        Ok(())
+    }
+
+    struct FixedClock(time::OffsetDateTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> time::OffsetDateTime {
+            self.0
+        }
+    }
+
+    fn keywords_added(author_id: AuthorId, count: usize) -> Vec<Event> {
+        (0..count)
+            .map(|n| Event::KeywordAdded(KeywordTarget::Author(author_id), format!("keyword-{n}")))
+            .collect()
+    }
+
+    fn keywords_of(events: Vec<ExternalRepresentation>) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match Event::from_external_representation(event) {
+                Ok(Event::KeywordAdded(_, keyword)) => keyword,
+                _ => panic!("expected a keyword to be added"),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn aggregates_fold_in_the_order_their_events_were_written() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        archive.set_clock(Arc::new(FixedClock(time::macros::datetime!(
+            2024-01-01 0:00 UTC
+        ))));
+
+        // All at the same time, and half of them in one batch
+        let author_id = AuthorId(UniqueId::fresh());
+        let mut events = keywords_added(author_id, 16);
+        let later = events.split_off(8);
+        archive.persist_all(events).await?;
+        for event in later {
+            archive.persist(event).await?;
+        }
+
+        let expected = (0..16).map(|n| format!("keyword-{n}")).collect::<Vec<_>>();
+        let found = archive.find_by_aggregate_id(author_id.0).await?;
+        assert_eq!(keywords_of(found), expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn aggregate_indexes_keyed_on_times_are_keyed_on_sequences() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        let author_id = AuthorId(UniqueId::fresh());
+        archive.persist_all(keywords_added(author_id, 4)).await?;
+
+        // As written while the index was keyed on the aggregate id, the time
+        // and the event id, which puts events of the same time in any order
+        let inner = archive.inner();
+        for pair in inner.events.iter() {
+            let (primary_key, event_bytes) = pair?;
+            let ArchivedRepresentation(event) = ArchivedRepresentation::from_slice(&event_bytes)?;
+            let when = u128::try_from(event.when.unix_timestamp_nanos()).unwrap_or_default();
+            let key = [
+                event.aggregate_id.as_bytes().as_slice(),
+                &when.to_be_bytes(),
+                &primary_key,
+            ]
+            .concat();
+            inner.aggregates.insert(key, primary_key)?;
+            inner
+                .aggregates
+                .remove(ArchivedRepresentation(event).aggregate_key())?;
+        }
+        inner.metadata.remove(AGGREGATES_BY_SEQUENCE_KEY)?;
+        drop(archive);
+
+        let archive = EventArchive::try_new(directory.path())?;
+        let expected = (0..4).map(|n| format!("keyword-{n}")).collect::<Vec<_>>();
+        let found = archive.find_by_aggregate_id(author_id.0).await?;
+        assert_eq!(keywords_of(found), expected);
+        assert_eq!(archive.inner().aggregates.len()?, 4);
+        assert_eq!(archive.inconsistencies()?, Vec::<String>::new());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn malformed_entries_are_reported_rather_than_panicked_on() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        let author_id = AuthorId(UniqueId::fresh());
+        archive
+            .persist(Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ))
+            .await?;
+
+        let aggregate_id = author_id.0.uuid().as_bytes();
+        let inner = archive.inner();
+        inner
+            .aggregates
+            .insert([&aggregate_id[..], &[0xff; 8]].concat(), b"not an id")?;
+        inner
+            .aggregates
+            .insert(b"short", Uuid::new_v4().as_bytes())?;
+        inner.versions.insert(aggregate_id, b"not a version")?;
+
+        assert!(matches!(
+            archive.find_by_aggregate_id(author_id.0).await,
+            Err(error::Error::CorruptStore(..))
+        ));
+        assert!(matches!(
+            archive.aggregate_version(author_id.0).await,
+            Err(error::Error::CorruptStore(..))
+        ));
+        let found = archive.inconsistencies()?;
+        assert_eq!(
+            found
+                .iter()
+                .filter(|found| found.contains("malformed"))
+                .count(),
+            3,
+            "{found:?}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stores_without_a_journal_get_one_in_time_order() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        let mut persisted = vec![];
+        for _ in 0..3 {
+            let author_id = AuthorId(UniqueId::fresh());
+            archive
+                .persist(Event::AuthorAdded(
+                    author_id,
+                    AuthorInfo {
+                        name: "Sun Tzu".to_owned(),
+                    },
+                ))
+                .await?;
+            persisted.push(*author_id.0.uuid());
+        }
+
+        // As written before the journal was kept
+        for key in archive.inner().journal.keys() {
+            archive.inner().journal.remove(key?)?;
+        }
+        assert_eq!(archive.inconsistencies()?.len(), 3);
+        drop(archive);
+
+        let archive = EventArchive::try_new(directory.path())?;
+        let journaled = archive
+            .journal()
+            .await?
+            .into_iter()
+            .map(|event| event.aggregate_id)
+            .collect::<Vec<_>>();
+        assert_eq!(journaled, persisted);
+        assert_eq!(archive.inconsistencies()?, Vec::<String>::new());
+        let sequences = archive
+            .find_by_aggregate_id(UniqueId(persisted[1]))
+            .await?
+            .into_iter()
+            .map(|event| event.sequence)
+            .collect::<Vec<_>>();
+        assert_eq!(sequences, vec![2]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn journals_numbered_from_zero_are_renumbered_from_one() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        let mut persisted = vec![];
+        for _ in 0..3 {
+            let author_id = AuthorId(UniqueId::fresh());
+            archive
+                .persist(Event::AuthorAdded(
+                    author_id,
+                    AuthorInfo {
+                        name: "Sun Tzu".to_owned(),
+                    },
+                ))
+                .await?;
+            persisted.push(*author_id.0.uuid());
+        }
+
+        // As written when the journal started at 0 and events had no
+        // sequence
+        let inner = archive.inner();
+        let entries = inner
+            .journal
+            .iter()
+            .map(|pair| Ok(pair?.1))
+            .collect::<error::Result<Vec<_>>>()?;
+        for key in inner.journal.keys() {
+            inner.journal.remove(key?)?;
+        }
+        for (sequence, event_id) in (0u64..).zip(&entries) {
+            let ArchivedRepresentation(mut event) = ArchivedRepresentation::from_slice(
+                &inner.events.get(event_id)?.expect("an event"),
+            )?;
+            event.sequence = 0;
+            let archived = ArchivedRepresentation::from(event);
+            inner.events.insert(event_id, archived.as_json()?)?;
+            inner.journal.insert(sequence.to_be_bytes(), event_id)?;
+        }
+        drop(archive);
+
+        let archive = EventArchive::try_new(directory.path())?;
+        let journal = archive.journal().await?;
+        assert_eq!(
+            journal
+                .iter()
+                .map(|event| event.aggregate_id)
+                .collect::<Vec<_>>(),
+            persisted
+        );
+        assert_eq!(
+            journal
+                .iter()
+                .map(|event| event.sequence)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(archive.inconsistencies()?, Vec::<String>::new());
+        let (unread, _) = archive.journal_after(JournalPosition(2)).await?;
+        assert_eq!(unread.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn durability_can_be_switched_while_open() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        assert_eq!(archive.durability(), Durability::SyncAll);
+
+        archive.set_durability(Durability::Buffered)?;
+        assert_eq!(archive.durability(), Durability::Buffered);
+        let author_id = AuthorId(UniqueId::fresh());
+        archive
+            .persist(Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ))
+            .await?;
+
+        archive.set_durability(Durability::SyncAll)?;
+        assert_eq!(archive.durability(), Durability::SyncAll);
+
+        drop(archive);
+        let archive = EventArchive::try_new(directory.path())?;
+        assert_eq!(archive.find_by_aggregate_id(author_id.0).await?.len(), 1);
+        // Buffering is not remembered across restarts
+        assert_eq!(archive.durability(), Durability::SyncAll);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn store_stats_count_what_was_inserted() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        let author_ids = (0..5)
+            .map(|_| AuthorId(UniqueId::fresh()))
+            .collect::<Vec<_>>();
+        for &author_id in &author_ids {
+            archive
+                .persist(Event::AuthorAdded(
+                    author_id,
+                    AuthorInfo {
+                        name: "Sun Tzu".to_owned(),
+                    },
+                ))
+                .await?;
+        }
+        archive
+            .persist(Event::KeywordAdded(
+                KeywordTarget::Author(author_ids[0]),
+                "strategy".to_owned(),
+            ))
+            .await?;
+
+        let stats = archive.stats().await?;
+        assert_eq!(stats.event_count, 6);
+        assert_eq!(stats.aggregate_count, 5);
+
+        // Read back when the store is opened
+        drop(archive);
+        let archive = EventArchive::try_new(directory.path())?;
+        let reopened = archive.stats().await?;
+        assert_eq!(reopened.event_count, 6);
+        assert_eq!(reopened.aggregate_count, 5);
+
+        // Counted, once, for a store written before the counts were kept
+        let metadata = &archive.inner().metadata;
+        metadata.remove(EVENT_COUNT_KEY)?;
+        metadata.remove(AGGREGATE_COUNT_KEY)?;
+        archive.flush()?;
+        drop(archive);
+        let archive = EventArchive::try_new(directory.path())?;
+        let counted = archive.stats().await?;
+        assert_eq!(counted.event_count, 6);
+        assert_eq!(counted.aggregate_count, 5);
+        assert!(archive.inner().metadata.contains_key(EVENT_COUNT_KEY)?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn loaded_aggregate_agrees_with_read_model() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        let author_id = AuthorId(UniqueId::fresh());
+        let book_id = BookId(UniqueId::fresh());
+        let events = vec![
+            Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ),
+            Event::BookAdded(
+                book_id,
+                BookInfo {
+                    isbn: Isbn("978-1-61180-697-7".to_owned()),
+                    title: "The Art of War".to_owned(),
+                    author: author_id,
+                },
+            ),
+            Event::KeywordAdded(KeywordTarget::Book(book_id), "strategy".to_owned()),
+        ];
+
+        let mut read_model = IndexSet::default();
+        for event in events {
+            archive.persist(event.clone()).await?;
+            read_model.apply(event);
+        }
+
+        let Book(id, info) = archive.load_aggregate(book_id).await?;
+        assert_eq!(Some(Book(id, info)), BookById(book_id).execute(&read_model));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn legacy_event_times_are_rewritten_once() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        let author_id = AuthorId(UniqueId::fresh());
+        archive
+            .persist(Event::AuthorAdded(
+                author_id,
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ))
+            .await?;
+        let [event] = &archive.find_by_aggregate_id(author_id.0).await?[..] else {
+            panic!("expected one event")
+        };
+
+        // As written before event times were RFC 3339
+        let when = std::time::SystemTime::from(event.when);
+        let mut legacy = serde_json::to_value(event)?;
+        legacy["when"] = serde_json::to_value(when)?;
+        let legacy = serde_json::to_vec(&legacy)?;
+        let write_legacy = || archive.inner().events.insert(event.id.as_bytes(), &legacy);
+        write_legacy()?;
+
+        assert_eq!(archive.rewrite_legacy_times()?, 1);
+        assert_eq!(archive.rewrite_legacy_times()?, 0);
+
+        let stored = archive.inner().events.get(event.id.as_bytes())?.unwrap();
+        let stored: serde_json::Value = serde_json::from_slice(&stored)?;
+        assert!(stored["when"].is_string());
+        let rewritten = archive.find_by_event_id(UniqueId(event.id)).await?;
+        assert_eq!(rewritten.when, event.when);
+
+        // Not scanned again once marked, even after reopening
+        write_legacy()?;
+        archive.flush()?;
+        drop(archive);
+        let archive = EventArchive::try_new(directory.path())?;
+        assert_eq!(archive.rewrite_legacy_times()?, 0);
+
+        Ok(())
+    }
 
     #[test]
-    fn xxx() -> Result<()> {
-        let keyspace = Config::new("test-keyspace").open()?;
-        let _events = keyspace.open_partition("events", PartitionCreateOptions::default())?;
+    fn stores_created_concurrently_both_open() -> error::Result<()> {
+        for _ in 0..5 {
+            let directory = tempfile::tempdir()?;
+            let path = directory.path().join("store");
+            let openers = [(); 2].map(|_| {
+                let path = path.clone();
+                thread::spawn(move || EventArchive::try_new(path).map(|_| ()))
+            });
+            for opener in openers {
+                opener.join().expect("an opener that does not panic")?;
+            }
+            let mut lock_path = path.into_os_string();
+            lock_path.push(".creating");
+            assert!(!PathBuf::from(lock_path).try_exists()?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn stale_locks_are_removed_whether_or_not_the_store_exists() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let path = directory.path().join("store");
+        let lock_path = EventArchive::lock_path(&path);
+        // Left behind by a process that is no longer running
+        let leave_stale_lock = || fs::write(&lock_path, u32::MAX.to_string());
+
+        leave_stale_lock()?;
+        drop(EventArchive::try_new(&path)?);
+        assert!(!lock_path.try_exists()?);
+
+        leave_stale_lock()?;
+        drop(EventArchive::try_new(&path)?);
+        assert!(!lock_path.try_exists()?);
+
+        Ok(())
+    }
+
+    // What a crash leaves behind: the files as they are on disk, copied
+    // while the archive is still open and nothing has been flushed on close
+    fn copy_while_open(from: &Path, to: &Path) -> io::Result<()> {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            let target = to.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                copy_while_open(&entry.path(), &target)?;
+            } else {
+                fs::copy(entry.path(), target)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_corrupt_journal_is_an_error_rather_than_a_panic() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let mut archive = EventArchive::try_new(directory.path())?;
+        archive
+            .persist(Event::AuthorAdded(
+                AuthorId(UniqueId::fresh()),
+                AuthorInfo {
+                    name: "Sun Tzu".to_owned(),
+                },
+            ))
+            .await?;
+
+        let journal = &archive.inner().journal;
+        journal.insert(7u64.to_be_bytes(), Uuid::new_v4().as_bytes())?;
+        assert!(matches!(
+            archive.journal().await,
+            Err(error::Error::CorruptStore(..))
+        ));
+
+        journal.insert(7u64.to_be_bytes(), b"not an id")?;
+        assert!(matches!(
+            archive.journal().await,
+            Err(error::Error::CorruptStore(..))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn events_and_their_index_are_consistent_after_a_crash() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let (store_path, crashed_path) = (
+            directory.path().join("store"),
+            directory.path().join("crashed"),
+        );
+        let mut archive = EventArchive::try_new(&store_path)?;
+        archive.set_durability(Durability::Buffered)?;
+
+        let (mut persisted, mut flushed) = (vec![], 0);
+        for n in 0..50 {
+            if n == 25 {
+                archive.flush()?;
+                flushed = persisted.len();
+            }
+            let author_id = AuthorId(UniqueId::fresh());
+            archive
+                .persist(Event::AuthorAdded(
+                    author_id,
+                    AuthorInfo {
+                        name: format!("Author {n}"),
+                    },
+                ))
+                .await?;
+            persisted.push(*author_id.0.uuid());
+            for keyword in 0..n % 3 {
+                archive
+                    .persist(Event::KeywordAdded(
+                        KeywordTarget::Author(author_id),
+                        format!("keyword {keyword}"),
+                    ))
+                    .await?;
+                persisted.push(*author_id.0.uuid());
+            }
+        }
+        assert!(archive.inconsistencies()?.is_empty());
+
+        copy_while_open(&store_path, &crashed_path)?;
+        let crashed = EventArchive::try_new(&crashed_path)?;
+        assert_eq!(crashed.inconsistencies()?, Vec::<String>::new());
+        let recovered = crashed
+            .journal()
+            .await?
+            .into_iter()
+            .map(|event| event.aggregate_id)
+            .collect::<Vec<_>>();
+        // At least what was flushed, which is not nothing
+        assert!(recovered.len() >= flushed && flushed > 0);
+        assert!(persisted.starts_with(&recovered));
+
+        drop(archive);
+        let reopened = EventArchive::try_new(&store_path)?;
+        assert_eq!(reopened.inconsistencies()?, Vec::<String>::new());
+        assert_eq!(reopened.journal().await?.len(), persisted.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn flushed_writes_survive_a_crash_while_buffered() -> error::Result<()> {
+        let directory = tempfile::tempdir()?;
+        let (store_path, crashed_path) = (
+            directory.path().join("store"),
+            directory.path().join("crashed"),
+        );
+        let mut archive = EventArchive::try_new(&store_path)?;
+        archive.set_durability(Durability::Buffered)?;
+
+        let mut persisted = vec![];
+        for n in 0..20 {
+            let author_id = AuthorId(UniqueId::fresh());
+            archive
+                .persist(Event::AuthorAdded(
+                    author_id,
+                    AuthorInfo {
+                        name: format!("Author {n}"),
+                    },
+                ))
+                .await?;
+            persisted.push(*author_id.0.uuid());
+        }
+        archive.flush()?;
+        assert_eq!(archive.durability(), Durability::Buffered);
+
+        copy_while_open(&store_path, &crashed_path)?;
+        let crashed = EventArchive::try_new(&crashed_path)?;
+        let recovered = crashed
+            .journal()
+            .await?
+            .into_iter()
+            .map(|event| event.aggregate_id)
+            .collect::<Vec<_>>();
+        assert_eq!(recovered, persisted);
 
         Ok(())
     }
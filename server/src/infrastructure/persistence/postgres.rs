@@ -0,0 +1,339 @@
+use sqlx::{postgres::PgPoolOptions, PgPool, Row};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{
+    error,
+    infrastructure::{
+        AggregateVersion, Clock, EventDescriptor, EventStore, ExternalRepresentation,
+        JournalPosition, Persisted, StoreStats, SystemClock, UniqueId,
+    },
+};
+
+const CREATE_EVENTS_TABLE: &str = r#"
+    CREATE TABLE IF NOT EXISTS events (
+        id UUID PRIMARY KEY,
+        seq BIGSERIAL UNIQUE NOT NULL,
+        "when" TIMESTAMPTZ NOT NULL,
+        aggregate_id UUID NOT NULL,
+        what TEXT NOT NULL,
+        data JSONB NOT NULL
+    )
+"#;
+
+const CREATE_AGGREGATE_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS events_aggregate_id ON events (aggregate_id)";
+
+const SELECT_EVENTS: &str = r#"SELECT seq, id, "when", aggregate_id, what, data FROM events"#;
+
+// An EventStore on a shared Postgres database, so that several instances
+// can run against the same journal.
+#[derive(Clone)]
+pub struct PostgresEventStore {
+    pool: PgPool,
+    clock: Arc<dyn Clock>,
+}
+
+impl PostgresEventStore {
+    pub async fn try_connect(database_url: &str) -> error::Result<Self> {
+        let pool = PgPoolOptions::new().connect(database_url).await?;
+
+        // Serialize concurrent first-time schema creation
+        let mut transaction = pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('events'))")
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query(CREATE_EVENTS_TABLE)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query(CREATE_AGGREGATE_INDEX)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+
+        Ok(Self {
+            pool,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    async fn select_events(
+        &self,
+        aggregate_id: Option<Uuid>,
+    ) -> error::Result<Vec<ExternalRepresentation>> {
+        let rows = if let Some(aggregate_id) = aggregate_id {
+            sqlx::query(&format!(
+                "{SELECT_EVENTS} WHERE aggregate_id = $1 ORDER BY seq"
+            ))
+            .bind(aggregate_id)
+            .fetch_all(&self.pool)
+            .await?
+        } else {
+            sqlx::query(&format!("{SELECT_EVENTS} ORDER BY seq"))
+                .fetch_all(&self.pool)
+                .await?
+        };
+
+        rows.iter().map(into_external_representation).collect()
+    }
+
+    async fn insert(
+        &self,
+        event: ExternalRepresentation,
+        expected: Option<AggregateVersion>,
+    ) -> error::Result<()> {
+        self.insert_all(vec![(event, expected)]).await
+    }
+
+    // The insert holds a lock on each aggregate for the duration of the
+    // transaction, so that the version checks and the inserts are atomic
+    // across instances. Either every event is inserted, or none is.
+    async fn insert_all(
+        &self,
+        events: Vec<(ExternalRepresentation, Option<AggregateVersion>)>,
+    ) -> error::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        for (event, expected) in events {
+            let ExternalRepresentation {
+                id,
+                when,
+                aggregate_id,
+                what,
+                data,
+                ..
+            } = event;
+
+            sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1::text))")
+                .bind(aggregate_id)
+                .execute(&mut *transaction)
+                .await?;
+
+            if let Some(AggregateVersion(expected)) = expected {
+                let actual: i64 =
+                    sqlx::query_scalar("SELECT count(*) FROM events WHERE aggregate_id = $1")
+                        .bind(aggregate_id)
+                        .fetch_one(&mut *transaction)
+                        .await?;
+                if actual as u64 != expected {
+                    return Err(error::Error::ConcurrencyConflict {
+                        expected,
+                        actual: actual as u64,
+                    });
+                }
+            }
+
+            sqlx::query(
+                r#"INSERT INTO events (id, "when", aggregate_id, what, data)
+                   VALUES ($1, $2, $3, $4, $5)"#,
+            )
+            .bind(id)
+            .bind(when)
+            .bind(aggregate_id)
+            .bind(what)
+            .bind(data)
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        Ok(transaction.commit().await?)
+    }
+}
+
+fn into_external_representation(
+    row: &sqlx::postgres::PgRow,
+) -> error::Result<ExternalRepresentation> {
+    Ok(ExternalRepresentation {
+        id: row.try_get("id")?,
+        when: row.try_get("when")?,
+        aggregate_id: row.try_get("aggregate_id")?,
+        what: row.try_get("what")?,
+        data: row.try_get("data")?,
+        sequence: row.try_get::<i64, _>("seq")? as u64,
+    })
+}
+
+impl EventStore for PostgresEventStore {
+    async fn find_by_event_id(
+        &self,
+        UniqueId(id): UniqueId,
+    ) -> error::Result<ExternalRepresentation> {
+        if let Some(row) = sqlx::query(&format!("{SELECT_EVENTS} WHERE id = $1"))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+        {
+            into_external_representation(&row)
+        } else {
+            Err(error::Error::Generic(format!("No such event {id}")))
+        }
+    }
+
+    async fn find_by_aggregate_id(
+        &self,
+        UniqueId(id): UniqueId,
+    ) -> error::Result<Vec<ExternalRepresentation>> {
+        self.select_events(Some(id)).await
+    }
+
+    async fn persist<E>(&mut self, event: E) -> error::Result<Persisted>
+    where
+        E: EventDescriptor + Send + Sync + 'static,
+    {
+        let event_id = UniqueId::fresh();
+        let event_time = self.clock.now();
+        let event = event.external_representation(event_id, event_time)?;
+        self.insert(event, None).await?;
+
+        Ok(Persisted {
+            id: event_id,
+            when: event_time,
+        })
+    }
+
+    async fn persist_expecting<E>(
+        &mut self,
+        event: E,
+        expected: AggregateVersion,
+    ) -> error::Result<Persisted>
+    where
+        E: EventDescriptor + Send + Sync + 'static,
+    {
+        let event_id = UniqueId::fresh();
+        let event_time = self.clock.now();
+        let event = event.external_representation(event_id, event_time)?;
+        self.insert(event, Some(expected)).await?;
+
+        Ok(Persisted {
+            id: event_id,
+            when: event_time,
+        })
+    }
+
+    async fn persist_all<E>(&mut self, events: Vec<E>) -> error::Result<Vec<Persisted>>
+    where
+        E: EventDescriptor + Send + Sync + 'static,
+    {
+        let mut persisted = vec![];
+        let mut representations = vec![];
+        for event in events {
+            let event_id = UniqueId::fresh();
+            let event_time = self.clock.now();
+            representations.push((event.external_representation(event_id, event_time)?, None));
+            persisted.push(Persisted {
+                id: event_id,
+                when: event_time,
+            });
+        }
+        self.insert_all(representations).await?;
+
+        Ok(persisted)
+    }
+
+    async fn aggregate_version(&self, UniqueId(id): UniqueId) -> error::Result<AggregateVersion> {
+        let version: i64 =
+            sqlx::query_scalar("SELECT count(*) FROM events WHERE aggregate_id = $1")
+                .bind(id)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(AggregateVersion(version as u64))
+    }
+
+    async fn journal(&self) -> error::Result<Vec<ExternalRepresentation>> {
+        self.select_events(None).await
+    }
+
+    // Positions are seq, which other instances append to as well
+    async fn journal_after(
+        &self,
+        position: JournalPosition,
+    ) -> error::Result<(Vec<ExternalRepresentation>, JournalPosition)> {
+        let JournalPosition(after) = position;
+        let rows = sqlx::query(&format!("{SELECT_EVENTS} WHERE seq > $1 ORDER BY seq"))
+            .bind(after as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let position = if let Some(row) = rows.last() {
+            JournalPosition(row.try_get::<i64, _>("seq")? as u64)
+        } else {
+            position
+        };
+        let events = rows
+            .iter()
+            .map(into_external_representation)
+            .collect::<error::Result<_>>()?;
+
+        Ok((events, position))
+    }
+
+    async fn stats(&self) -> error::Result<StoreStats> {
+        let (event_count, aggregate_count, approx_bytes): (i64, i64, i64) = sqlx::query_as(
+            "SELECT count(*), count(DISTINCT aggregate_id), pg_total_relation_size('events')
+             FROM events",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(StoreStats {
+            event_count: event_count as u64,
+            aggregate_count: aggregate_count as u64,
+            approx_bytes: approx_bytes as u64,
+        })
+    }
+
+    fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::model::{AuthorId, AuthorInfo, Event, KeywordTarget};
+
+    // Runs against the database in TEST_DATABASE_URL, and is skipped
+    // when it is not set.
+    #[tokio::test]
+    async fn persisted_events_are_found_and_journaled() -> error::Result<()> {
+        let Ok(database_url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("TEST_DATABASE_URL is not set, skipping");
+            return Ok(());
+        };
+        let mut store = PostgresEventStore::try_connect(&database_url).await?;
+
+        let author_id = AuthorId(UniqueId::fresh());
+        let event = Event::AuthorAdded(
+            author_id,
+            AuthorInfo {
+                name: "Sun Tzu".to_owned(),
+            },
+        );
+        store.persist(event).await?;
+
+        let UniqueId(aggregate_id) = author_id.0;
+        let events = store.find_by_aggregate_id(author_id.0).await?;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].aggregate_id, aggregate_id);
+        assert_eq!(events[0].data["name"], "Sun Tzu");
+
+        let found = store.find_by_event_id(UniqueId(events[0].id)).await?;
+        assert_eq!(found.aggregate_id, aggregate_id);
+
+        let version = store.aggregate_version(author_id.0).await?;
+        assert_eq!(version, AggregateVersion(1));
+        let update = Event::KeywordAdded(KeywordTarget::Author(author_id), "strategy".to_owned());
+        store.persist_expecting(update.clone(), version).await?;
+        assert!(matches!(
+            store.persist_expecting(update, version).await,
+            Err(error::Error::ConcurrencyConflict {
+                expected: 1,
+                actual: 2
+            })
+        ));
+
+        let journal = store.journal().await?;
+        assert!(journal.iter().any(|event| event.id == events[0].id));
+
+        Ok(())
+    }
+}
@@ -1,111 +1,254 @@
-use serde_json::json;
-use std::time::SystemTime;
-use std::{fmt::Debug, path::Path};
-use tokio::net::TcpListener;
-use uuid::Uuid;
+use clap::Parser;
+use std::{path::Path, process::ExitCode, time::Duration};
+use tokio::{net::TcpListener, task};
+use tracing::Subscriber;
+use tracing_subscriber::{fmt, layer::SubscriberExt, EnvFilter};
 
 use server::{
-    core::{Application, EventBus},
-    error::{Error, Result},
-    http,
-    infrastructure::{
-        persistence::EventArchive, EventDescriptor, EventStore, ExternalRepresentation,
-        Termination, UniqueId,
+    core::{
+        model::query::text::DEFAULT_MAX_SEARCH_HITS, Application, DEFAULT_MAX_KEYWORDS_PER_TARGET,
     },
+    error::Result,
+    http,
+    infrastructure::{persistence::EventArchive, Durability, Termination},
 };
 
-#[derive(Clone, Debug, Default)]
-struct _DummyStore {
-    events: Vec<ExternalRepresentation>,
-}
-
-impl _DummyStore {}
-
-impl EventStore for _DummyStore {
-    async fn find_by_event_id(&self, UniqueId(id): UniqueId) -> Result<ExternalRepresentation> {
-        self.events
-            .iter()
-            .find(|e| e.id == id)
-            .ok_or(Error::Generic("No such event".to_owned()))
-            .cloned()
+fn make_application<P>(store_path: P, max_keywords: usize) -> Result<Application<EventArchive>>
+where
+    P: AsRef<Path>,
+{
+    let archive = EventArchive::try_new(store_path)?;
+    let rewritten = archive.rewrite_legacy_times()?;
+    if rewritten > 0 {
+        tracing::info!("Rewrote the time of {rewritten} events in RFC 3339");
     }
 
-    async fn find_by_aggregate_id(
-        &self,
-        UniqueId(id): UniqueId,
-    ) -> Result<Vec<ExternalRepresentation>> {
-        Ok(self
-            .events
-            .iter()
-            .filter(|e| e.aggregate_id == id)
-            .cloned()
-            .collect())
-    }
+    Application::builder(archive)
+        .max_keywords_per_target(max_keywords)
+        .build()
+}
 
-    async fn persist<E>(&mut self, event: E) -> Result<()>
-    where
-        E: EventDescriptor + Send + Sync + 'static,
-    {
-        let event_id = UniqueId::fresh();
+const STORE_PATH: &str = "event-store";
+const LISTEN_ADDRESS: &str = "0.0.0.0:3000";
+
+#[derive(Parser)]
+#[command(name = "server")]
+#[command(about = "The blister API server")]
+struct Options {
+    #[arg(
+        long,
+        help = "Serve queries only, leaving out every route that changes anything"
+    )]
+    read_only: bool,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Poll the event store for events written by other instances sharing it"
+    )]
+    poll_interval: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        default_value_t = DEFAULT_MAX_KEYWORDS_PER_TARGET,
+        help = "Reject keywords added to a book or author that already has this many"
+    )]
+    max_keywords_per_target: usize,
+
+    #[arg(
+        long,
+        value_name = "COUNT",
+        default_value_t = DEFAULT_MAX_SEARCH_HITS,
+        help = "Rank no more than this many search hits, and say that the results are truncated"
+    )]
+    max_search_hits: usize,
+
+    #[arg(
+        long,
+        help = "Let searches find words with the query in them, at the cost of a larger index"
+    )]
+    infix_search: bool,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "The URL that clients reach the server at, for absolute Location headers"
+    )]
+    public_base_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        default_value = http::API_RESOURCE_PREFIX,
+        help = "The path the resources are served under, such as where a proxy mounts them"
+    )]
+    resource_prefix: String,
+
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = http::BodyLimits::default().commands,
+        help = "Answer commands with larger bodies than this with 413"
+    )]
+    max_body_bytes: usize,
+
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value_t = http::BodyLimits::default().batch_get,
+        help = "Answer batch-gets with larger bodies than this with 413"
+    )]
+    max_batch_body_bytes: usize,
+
+    #[arg(
+        long,
+        value_name = "MODE",
+        default_value = "sync-all",
+        help = "sync-all writes every event to disk before answering, buffered leaves it to the \
+                operating system and may lose the latest events if the machine goes down"
+    )]
+    durability: Durability,
+}
 
-        // does this take place here?
-        // Is this the right data type?
-        let timestamp = SystemTime::now();
+async fn run(options: Options, terminator: &Termination) -> std::result::Result<(), String> {
+    let listener = TcpListener::bind(LISTEN_ADDRESS)
+        .await
+        .map_err(|error| format!("Cannot listen on {LISTEN_ADDRESS}: {error}"))?;
 
-        let event_rep = event.external_representation(event_id, timestamp)?;
-        self.events.push(event_rep);
-        Ok(())
+    // Opening waits for whoever else may be creating the store
+    let max_keywords = options.max_keywords_per_target;
+    let mut application = task::spawn_blocking(move || make_application(STORE_PATH, max_keywords))
+        .await
+        .map_err(|error| format!("Cannot open the event store at {STORE_PATH}: {error}"))?
+        .map_err(|error| format!("Cannot open the event store at {STORE_PATH}: {error}"))?
+        .with_max_search_hits(options.max_search_hits);
+    if options.infix_search {
+        application = application.with_infix_search();
     }
-
-    async fn journal(&self) -> Result<Vec<ExternalRepresentation>> {
-        Ok(self.events.clone())
+    application
+        .set_durability(options.durability)
+        .await
+        .map_err(|error| {
+            format!(
+                "Cannot make the event store {:?}: {error}",
+                options.durability
+            )
+        })?;
+
+    // Followed from before the replay, so that nothing written in between
+    // is missed. The handle is not needed, the task stops with the
+    // termination.
+    if let Some(seconds) = options.poll_interval {
+        drop(
+            application
+                .follow_store(Duration::from_secs(seconds), terminator)
+                .await
+                .map_err(|error| format!("Cannot follow the event store: {error}"))?,
+        );
     }
-}
 
-fn _make_application() -> Application<_DummyStore> {
-    let store = _DummyStore {
-        events: vec![ExternalRepresentation {
-            id: Uuid::new_v4(),
-            when: SystemTime::now(),
-            aggregate_id: Uuid::new_v4(),
-            what: "book-added".to_owned(),
-            data: json!({"author":"ba68afbe-83a7-4a5e-9619-8a32a8967b28","isbn":"978-1-61180-697-7","title":"The Art of War"}),
-        }],
+    // threaded because both the QueryHandler and CommandDispatcher
+    // both poll for events
+    // I guess these parts could be re-written to be event driven instead
+    let (started, mode) = if options.read_only {
+        (
+            application.start_read_only(terminator).await,
+            http::Mode::ReadOnly,
+        )
+    } else {
+        (application.start(terminator).await, http::Mode::ReadWrite)
+    };
+    started.map_err(|error| format!("Cannot replay the event store at {STORE_PATH}: {error}"))?;
+
+    let api = http::Api::with_mode(application, mode)
+        .with_resource_prefix(&options.resource_prefix)
+        .map_err(|error| error.to_string())?
+        .with_body_limits(http::BodyLimits {
+            commands: options.max_body_bytes,
+            batch_get: options.max_batch_body_bytes,
+        });
+    let api = if let Some(base_url) = &options.public_base_url {
+        api.with_public_base_url(base_url)
+    } else {
+        api
     };
-    let event_bus = EventBus::new(store);
-    Application::new(event_bus)
+    api.start(listener)
+        .await
+        .map_err(|error| format!("The API stopped: {error}"))
 }
 
-fn make_application<P>(store_path: P) -> Application<EventArchive>
-where
-    P: AsRef<Path>,
-{
-    let archive = EventArchive::try_new(store_path).expect("a valid event archive");
-    let event_bus = EventBus::new(archive);
-
-    Application::new(event_bus)
+type LogSubscriber = Box<dyn Subscriber + Send + Sync>;
+
+// Logs are human-readable unless BLISTER_LOG_FORMAT=json asks for one JSON
+// object per line. RUST_LOG takes tracing's EnvFilter directives, e.g.
+// `warn,server::http=debug`; the default is `info`.
+fn log_subscriber(
+    format: Option<&str>,
+    filter: Option<&str>,
+) -> std::result::Result<LogSubscriber, String> {
+    let filter = EnvFilter::builder()
+        .parse(filter.unwrap_or("info"))
+        .map_err(|error| format!("Bad RUST_LOG: {error}"))?;
+
+    let registry = tracing_subscriber::registry().with(filter);
+    match format {
+        None | Some("pretty") => Ok(Box::new(registry.with(fmt::layer()))),
+        Some("json") => Ok(Box::new(registry.with(fmt::layer().json()))),
+        Some(format) => Err(format!(
+            "Bad BLISTER_LOG_FORMAT `{format}`, expected `pretty` or `json`"
+        )),
+    }
 }
 
 #[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt::init();
-
-    let listener = TcpListener::bind("0.0.0.0:3000")
-        .await
-        .expect("a free port");
-
-    let application = make_application("event-store");
+async fn main() -> ExitCode {
+    let subscriber = log_subscriber(
+        std::env::var("BLISTER_LOG_FORMAT").ok().as_deref(),
+        std::env::var("RUST_LOG").ok().as_deref(),
+    );
+    match subscriber {
+        Ok(subscriber) => {
+            tracing::subscriber::set_global_default(subscriber).expect("a first subscriber")
+        }
+        Err(diagnostic) => {
+            eprintln!("{diagnostic}");
+            return ExitCode::FAILURE;
+        }
+    }
 
+    let options = Options::parse();
     let terminator = Termination::new();
-    // threaded because both the QueryHandler and CommandDispatcher
-    // both poll for events
-    // I guess these parts could be re-written to be event driven instead
-    application.start(&terminator).await;
+    let outcome = run(options, &terminator).await;
+    terminator.signal();
 
-    http::Api::new(application)
-        .start(listener)
-        .await
-        .expect("starting the API to work");
+    if let Err(diagnostic) = outcome {
+        tracing::error!("{diagnostic}");
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
 
-    terminator.signal();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_log_formats_take_a_filter() {
+        for format in [None, Some("pretty"), Some("json")] {
+            let subscriber =
+                log_subscriber(format, Some("warn,server::http=debug")).expect("a subscriber");
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::warn!("{format:?} logs");
+                assert!(tracing::enabled!(target: "server::http", tracing::Level::DEBUG));
+                assert!(!tracing::enabled!(target: "server::core", tracing::Level::INFO));
+            });
+        }
+
+        // Directives on spans need an EnvFilter
+        assert!(log_subscriber(None, Some("warn,[request]=debug")).is_ok());
+        assert!(log_subscriber(Some("xml"), None).is_err());
+        assert!(log_subscriber(None, Some("server=loud")).is_err());
+    }
 }
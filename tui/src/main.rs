@@ -1,4 +1,4 @@
-use api_client::{model as domain, BlockingApiClient};
+use api_client::{model as domain, BlockingApiClient, ClientOptions};
 use cursive::{
     event::Key,
     menu,
@@ -6,6 +6,8 @@ use cursive::{
     views::{Dialog, LinearLayout, SelectView, TextView},
 };
 
+const USER_AGENT: &str = concat!("blister-tui/", env!("CARGO_PKG_VERSION"));
+
 // It does not have to own cursive.
 // run can create cursive, call view on it, then run.
 #[derive(Clone)]
@@ -13,6 +15,7 @@ struct UserInterface {
     api: BlockingApiClient,
 }
 
+#[allow(dead_code)]
 enum ListItem {
     Book(domain::Book),
     Author(domain::Author),
@@ -99,7 +102,9 @@ impl UserInterface {
 }
 
 fn main() {
-    let api_client = BlockingApiClient::new("http://macaroni.local:3000");
+    let options = ClientOptions::default().user_agent(USER_AGENT);
+    let api_client = BlockingApiClient::with_options("http://macaroni.local:3000", &options)
+        .expect("an HTTP client");
     UserInterface::new(api_client).start()
     //    let authors = api_client.get_authors();
     //    println!("{:?}", authors);